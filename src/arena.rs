@@ -0,0 +1,318 @@
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::ops::{Deref, DerefMut};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::raw::{RawAllocation, RawArrayAllocator};
+
+/// An object safe facade over [`RawArrayAllocator`].
+///
+/// Libraries can accept `&dyn ArenaAlloc` rather than being generic over the allocator type and
+/// its size, and allocate typed values through it.
+pub trait ArenaAlloc: RawArrayAllocator {}
+
+impl<A: RawArrayAllocator + ?Sized> ArenaAlloc for A {}
+
+impl<'a> dyn ArenaAlloc + 'a {
+    /// Allocates space for a `T`.
+    ///
+    /// Returns `None` when out of memory or when `T` requires a greater alignment than the
+    /// allocator guarantees.
+    pub fn allocate_value<T>(&self) -> Option<ArenaValue<'_, 'a, T>> {
+        #[cfg(feature = "log")]
+        trace!("ArenaAlloc::allocate_value");
+
+        if align_of::<T>() > self.block_align() {
+            return None;
+        }
+        self.allocate_bytes(size_of::<T>())
+            .map(|allocation| ArenaValue {
+                allocator: self,
+                allocation,
+                __marker: PhantomData,
+            })
+    }
+
+    /// Allocates space for `[T]` of length `len`.
+    ///
+    /// Returns `None` when out of memory or when `T` requires a greater alignment than the
+    /// allocator guarantees.
+    pub fn allocate_slice<T>(&self, len: usize) -> Option<ArenaSlice<'_, 'a, T>> {
+        #[cfg(feature = "log")]
+        trace!("ArenaAlloc::allocate_slice");
+
+        if align_of::<T>() > self.block_align() {
+            return None;
+        }
+        self.allocate_bytes(len.checked_mul(size_of::<T>())?)
+            .map(|allocation| ArenaSlice {
+                allocator: self,
+                allocation,
+                len,
+                __marker: PhantomData,
+            })
+    }
+}
+
+/// Backs `allocator-api2` collections on stable toolchains with any allocator, e.g.
+/// `allocator_api2::vec::Vec::new_in(arena)` for `arena: &dyn ArenaAlloc`.
+///
+/// Allocations requiring a greater alignment than the allocator guarantees fail.
+#[cfg(feature = "allocator-api2")]
+unsafe impl<'a> allocator_api2::alloc::Allocator for dyn ArenaAlloc + 'a {
+    fn allocate(
+        &self,
+        layout: std::alloc::Layout,
+    ) -> Result<std::ptr::NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+        #[cfg(feature = "log")]
+        trace!("ArenaAlloc::allocate");
+
+        if layout.align() > self.block_align() {
+            return Err(allocator_api2::alloc::AllocError);
+        }
+        if layout.size() == 0 {
+            // Zero sized allocations don't need to point into the array.
+            let dangling = unsafe { std::ptr::NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(std::ptr::NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        let allocation = self
+            .allocate_bytes(layout.size())
+            .ok_or(allocator_api2::alloc::AllocError)?;
+        let ptr = unsafe { self.as_ptr(allocation) };
+        Ok(std::ptr::NonNull::slice_from_raw_parts(
+            ptr,
+            allocation.size * self.block_size(),
+        ))
+    }
+
+    unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+        #[cfg(feature = "log")]
+        trace!("ArenaAlloc::deallocate");
+
+        if layout.size() == 0 {
+            return;
+        }
+        // Block/slot pointers are computed from their index.
+        let base = self.as_ptr(RawAllocation { index: 0, size: 0 });
+        let offset = usize::try_from(ptr.as_ptr().offset_from(base.as_ptr())).unwrap();
+        self.free(RawAllocation {
+            index: offset / self.block_size(),
+            size: layout.size().div_ceil(self.block_size()),
+        });
+    }
+}
+
+/// A `T` allocated through a `&dyn ArenaAlloc`.
+///
+/// Like [`crate::linked_list::Value`] the memory is uninitialized on allocation and `T` is not
+/// dropped.
+pub struct ArenaValue<'b, 'a, T> {
+    allocator: &'b (dyn ArenaAlloc + 'a),
+    allocation: RawAllocation,
+    __marker: PhantomData<T>,
+}
+
+impl<'b, 'a, T> ArenaValue<'b, 'a, T> {
+    #[must_use]
+    pub fn allocator(&self) -> &'b (dyn ArenaAlloc + 'a) {
+        self.allocator
+    }
+
+    #[must_use]
+    pub fn allocation(&self) -> RawAllocation {
+        self.allocation
+    }
+}
+
+impl<'b, 'a, T> Deref for ArenaValue<'b, 'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("ArenaValue::deref");
+
+        unsafe { self.allocator.as_ptr(self.allocation).cast().as_ref() }
+    }
+}
+impl<'b, 'a, T> DerefMut for ArenaValue<'b, 'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("ArenaValue::deref_mut");
+
+        unsafe { self.allocator.as_ptr(self.allocation).cast().as_mut() }
+    }
+}
+
+impl<'b, 'a, T> Drop for ArenaValue<'b, 'a, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("ArenaValue::drop");
+
+        unsafe { self.allocator.free(self.allocation) }
+    }
+}
+
+impl<'b, 'a, T> std::fmt::Debug for ArenaValue<'b, 'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArenaValue")
+            .field("allocation", &self.allocation)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A `[T]` allocated through a `&dyn ArenaAlloc`.
+///
+/// Like [`crate::linked_list::Slice`] the memory is uninitialized on allocation and elements are
+/// not dropped.
+pub struct ArenaSlice<'b, 'a, T> {
+    allocator: &'b (dyn ArenaAlloc + 'a),
+    allocation: RawAllocation,
+    len: usize,
+    __marker: PhantomData<T>,
+}
+
+impl<'b, 'a, T> ArenaSlice<'b, 'a, T> {
+    #[must_use]
+    pub fn allocator(&self) -> &'b (dyn ArenaAlloc + 'a) {
+        self.allocator
+    }
+
+    #[must_use]
+    pub fn allocation(&self) -> RawAllocation {
+        self.allocation
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'b, 'a, T> Deref for ArenaSlice<'b, 'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("ArenaSlice::deref");
+
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe {
+            std::slice::from_raw_parts(
+                self.allocator.as_ptr(self.allocation).cast().as_ptr(),
+                self.len,
+            )
+        }
+    }
+}
+impl<'b, 'a, T> DerefMut for ArenaSlice<'b, 'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("ArenaSlice::deref_mut");
+
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.allocator.as_ptr(self.allocation).cast().as_ptr(),
+                self.len,
+            )
+        }
+    }
+}
+
+impl<'b, 'a, T> Drop for ArenaSlice<'b, 'a, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("ArenaSlice::drop");
+
+        unsafe { self.allocator.free(self.allocation) }
+    }
+}
+
+impl<'b, 'a, T> std::fmt::Debug for ArenaSlice<'b, 'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArenaSlice")
+            .field("allocation", &self.allocation)
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::{linked_list, slab};
+
+    fn sum(arena: &dyn ArenaAlloc, values: &[u32]) -> Option<u32> {
+        let mut slice = arena.allocate_slice::<u32>(values.len())?;
+        slice.copy_from_slice(values);
+        Some(slice.iter().sum())
+    }
+
+    #[test]
+    fn arena_slice() {
+        let linked_list = linked_list::ArrayAllocator::<4>::new(None);
+        assert_eq!(sum(&*linked_list, &[1, 2, 3]), Some(6));
+        assert_eq!(sum(&*linked_list, &[]), Some(0));
+
+        let slab = slab::ArrayAllocator::<1, [u32; 4]>::new(None);
+        assert_eq!(sum(&*slab, &[1, 2, 3]), Some(6));
+        assert_eq!(sum(&*slab, &[1, 2, 3, 4, 5, 6, 7]), None);
+    }
+
+    #[test]
+    fn arena_value() {
+        let allocator = linked_list::ArrayAllocator::<1>::new(None);
+        let arena: &dyn ArenaAlloc = &*allocator;
+        let mut value = arena.allocate_value::<u64>().unwrap();
+        *value = 3;
+        assert_eq!(*value, 3);
+        assert!(arena.allocate_value::<u8>().is_none());
+        drop(value);
+        assert!(arena.allocate_value::<u8>().is_some());
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn arena_allocator_api2() {
+        let linked_list = linked_list::ArrayAllocator::<8>::new(None);
+        let arena: &dyn ArenaAlloc = &*linked_list;
+        let mut vec = allocator_api2::vec::Vec::new_in(arena);
+        vec.extend(0..10u32);
+        assert_eq!(vec.iter().sum::<u32>(), 45);
+        drop(vec);
+        assert_eq!(linked_list.stats().free, 8);
+
+        let slab = slab::ArrayAllocator::<2, [u64; 2]>::new(None);
+        let arena: &dyn ArenaAlloc = &*slab;
+        let a = allocator_api2::boxed::Box::new_in(1u64, arena);
+        let b = allocator_api2::boxed::Box::new_in([2u64, 3], arena);
+        assert!(allocator_api2::boxed::Box::try_new_in(4u64, arena).is_err());
+        assert_eq!((*a, *b), (1, [2, 3]));
+        drop(a);
+        assert_eq!(slab.stats().free, 1);
+        assert!(allocator_api2::boxed::Box::try_new_in([0u64; 3], arena).is_err());
+    }
+
+    #[test]
+    fn arena_value_alignment() {
+        #[repr(align(64))]
+        struct Aligned;
+
+        let allocator = linked_list::ArrayAllocator::<4>::new(None);
+        let arena: &dyn ArenaAlloc = &*allocator;
+        assert!(arena.allocate_value::<Aligned>().is_none());
+    }
+}
@@ -0,0 +1,109 @@
+//! A process global registry of named [`crate::linked_list::Allocator`]s, so code deep in a call
+//! stack can reach a shared arena by name rather than having an allocator passed through every
+//! call.
+//!
+//! Arenas are created on first use and live for the rest of the process.
+
+use std::alloc::Layout;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Block};
+
+/// Maps names to arenas.
+static TABLE: Mutex<Option<HashMap<String, &'static Allocator>>> = Mutex::new(None);
+
+fn with_table<R>(f: impl FnOnce(&mut HashMap<String, &'static Allocator>) -> R) -> R {
+    let mut table = TABLE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    f(table.get_or_insert_with(HashMap::new))
+}
+
+/// Returns the arena named `name`, creating it with room for at least `bytes` bytes if it doesn't
+/// exist.
+///
+/// `bytes` is ignored when the arena exists, so every caller attaches to the arena created first.
+///
+/// # Panics
+///
+/// When the memory for the arena cannot be allocated or when failing to initialize its mutex.
+#[must_use]
+pub fn get_or_create(name: &str, bytes: usize) -> &'static Allocator {
+    #[cfg(feature = "log")]
+    trace!("arenas::get_or_create");
+
+    with_table(|table| {
+        if let Some(allocator) = table.get(name) {
+            return *allocator;
+        }
+        let allocator = create(bytes.div_ceil(std::mem::size_of::<Block>()));
+        table.insert(name.to_owned(), allocator);
+        allocator
+    })
+}
+
+/// Returns the arena named `name`, if it was created.
+#[must_use]
+pub fn get(name: &str) -> Option<&'static Allocator> {
+    #[cfg(feature = "log")]
+    trace!("arenas::get");
+
+    with_table(|table| table.get(name).copied())
+}
+
+/// Returns the names of the arenas created, in no particular order.
+#[must_use]
+pub fn names() -> Vec<String> {
+    #[cfg(feature = "log")]
+    trace!("arenas::names");
+
+    with_table(|table| table.keys().cloned().collect())
+}
+
+/// Allocates and initializes an allocator of `n` blocks which is never freed.
+// The layout is aligned for the allocator.
+#[allow(clippy::cast_ptr_alignment)]
+fn create(n: usize) -> &'static Allocator {
+    // The blocks directly follow the allocator, as in `linked_list::ArrayAllocator`.
+    let (layout, offset) = Layout::new::<Allocator>()
+        .extend(Layout::array::<Block>(n).unwrap())
+        .unwrap();
+    assert_eq!(offset, std::mem::size_of::<Allocator>());
+    unsafe {
+        let ptr = std::alloc::alloc(layout.pad_to_align()).cast::<Allocator>();
+        if ptr.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        Allocator::init(ptr, None, n);
+        &*ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn arenas() {
+        assert!(get("arenas").is_none());
+        let a = get_or_create("arenas", 100);
+        let blocks = 100usize.div_ceil(std::mem::size_of::<Block>());
+        assert_eq!(a.stats().total, blocks);
+        let b = get_or_create("arenas", 1000);
+        assert!(std::ptr::eq(a, b));
+        assert!(std::ptr::eq(a, get("arenas").unwrap()));
+        assert!(names().contains(&"arenas".to_owned()));
+
+        let other = get_or_create("arenas_other", 100);
+        assert!(!std::ptr::eq(a, other));
+        let value = a.allocate_value::<u32>().unwrap();
+        assert_eq!(a.stats().free, blocks - 1);
+        drop(value);
+    }
+}
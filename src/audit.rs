@@ -0,0 +1,152 @@
+//! Tracking of the live allocations of a [`crate::linked_list::Allocator`] once enabled by
+//! [`crate::linked_list::Allocator::set_audit`], so audits, compaction and crash recovery can
+//! enumerate the memory in use, see [`crate::linked_list::Allocator::live_allocations`].
+//!
+//! Each allocation is preceded by a header linking it into a doubly linked list of the live
+//! allocations, whose head is held by the allocator. As the list lies within the memory of the
+//! allocator it can be walked by any process sharing it.
+
+use std::mem::size_of;
+
+use crate::Index;
+
+/// The header before each allocation, spanning [`blocks`] blocks.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct Header<I> {
+    prev: Option<I>,
+    next: Option<I>,
+    /// The number of blocks of the allocation including the header.
+    size: I,
+    tag: u32,
+    #[cfg(feature = "ownership")]
+    owner: crate::ownership::Owner,
+}
+
+/// The number of blocks of type `T` a header spans.
+pub(crate) fn blocks<T, I>() -> usize {
+    size_of::<Header<I>>().div_ceil(size_of::<T>())
+}
+
+/// Blocks are only aligned for `I`, so headers are read and written unaligned.
+unsafe fn read<T, I>(data: &[T], index: usize) -> Header<I> {
+    data.as_ptr()
+        .add(index)
+        .cast::<Header<I>>()
+        .read_unaligned()
+}
+
+unsafe fn write<T, I>(data: &mut [T], index: usize, header: Header<I>) {
+    data.as_mut_ptr()
+        .add(index)
+        .cast::<Header<I>>()
+        .write_unaligned(header);
+}
+
+/// Links the allocation of `size` blocks, including the header, at `index` at the front of the
+/// list starting at `head`.
+///
+/// # Safety
+///
+/// The blocks must be allocated and hold no header.
+pub(crate) unsafe fn link<T, I: Index>(
+    data: &mut [T],
+    head: &mut Option<usize>,
+    index: usize,
+    size: usize,
+) {
+    write(
+        data,
+        index,
+        Header {
+            prev: None,
+            next: head.map(I::from_usize),
+            size: I::from_usize(size),
+            tag: 0,
+            #[cfg(feature = "ownership")]
+            owner: crate::ownership::current(),
+        },
+    );
+    if let Some(next) = *head {
+        let mut header = read::<T, I>(data, next);
+        header.prev = Some(I::from_usize(index));
+        write(data, next, header);
+    }
+    *head = Some(index);
+}
+
+/// Unlinks the allocation at `index` from the list starting at `head`.
+///
+/// # Safety
+///
+/// The allocation must be in the list.
+pub(crate) unsafe fn unlink<T, I: Index>(data: &mut [T], head: &mut Option<usize>, index: usize) {
+    let header = read::<T, I>(data, index);
+    let (prev, next) = (header.prev.map(I::to_usize), header.next.map(I::to_usize));
+    match prev {
+        Some(prev) => {
+            let mut before = read::<T, I>(data, prev);
+            before.next = header.next;
+            write(data, prev, before);
+        }
+        None => *head = next,
+    }
+    if let Some(next) = next {
+        let mut after = read::<T, I>(data, next);
+        after.prev = header.prev;
+        write(data, next, after);
+    }
+}
+
+/// Sets the tag of the allocation at `index`.
+///
+/// # Safety
+///
+/// The allocation must be in a list.
+pub(crate) unsafe fn set_tag<T, I: Index>(data: &mut [T], index: usize, tag: u32) {
+    let mut header = read::<T, I>(data, index);
+    header.tag = tag;
+    write(data, index, header);
+}
+
+/// Returns the index, size and tag of each allocation, including the header, in the list starting
+/// at `head`, most recent first.
+///
+/// # Safety
+///
+/// `head` must start a list within `data`.
+#[cfg(feature = "std")]
+pub(crate) unsafe fn live<T, I: Index>(
+    data: &[T],
+    head: Option<usize>,
+) -> Vec<(usize, usize, u32)> {
+    let mut live = Vec::new();
+    let mut next = head;
+    while let Some(index) = next {
+        let header = read::<T, I>(data, index);
+        live.push((index, header.size.to_usize(), header.tag));
+        next = header.next.map(I::to_usize);
+    }
+    live
+}
+
+/// Returns the index, size, including the header, and owner of each allocation in the list
+/// starting at `head`, most recent first.
+///
+/// # Safety
+///
+/// `head` must start a list within `data`.
+#[cfg(feature = "ownership")]
+pub(crate) unsafe fn owners<T, I: Index>(
+    data: &[T],
+    head: Option<usize>,
+) -> Vec<(usize, usize, crate::ownership::Owner)> {
+    let mut owners = Vec::new();
+    let mut next = head;
+    while let Some(index) = next {
+        let header = read::<T, I>(data, index);
+        owners.push((index, header.size.to_usize(), header.owner));
+        next = header.next.map(I::to_usize);
+    }
+    owners
+}
@@ -0,0 +1,452 @@
+//! A fixed length bitset whose words are allocated within a [`crate::linked_list::Allocator`], so
+//! it can be stored in the same shared memory as the data it describes, e.g. as a map of free
+//! slots or set membership.
+//!
+//! Searches for clear bits skip full words a word at a time, or with the `simd` feature several
+//! words at a time.
+
+use std::fmt;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Slice};
+use crate::Index;
+
+/// The number of bits in a word.
+const WORD: usize = u64::BITS as usize;
+
+/// A bitset of a fixed number of bits, all initially clear.
+pub struct Bitset<'a, I: Index = usize> {
+    words: Slice<'a, u64, I>,
+    /// The number of bits.
+    len: usize,
+}
+
+impl<'a, I: Index> Bitset<'a, I> {
+    /// Allocates a bitset of `len` bits within `allocator`, returning `None` when there is no free
+    /// region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn new(allocator: &'a Allocator<I>, len: usize) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("Bitset::new");
+
+        let words = allocator.allocate_slice_default::<u64>(len.div_ceil(WORD))?;
+        Some(Self { words, len })
+    }
+
+    /// The number of bits.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Bitset::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Bitset::is_empty");
+
+        self.len == 0
+    }
+
+    /// The words holding the bits, bit `i` is bit `i % 64` of word `i / 64`.
+    #[must_use]
+    pub fn words(&self) -> &[u64] {
+        #[cfg(feature = "log")]
+        trace!("Bitset::words");
+
+        &self.words
+    }
+
+    /// Returns whether bit `index` is set.
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    #[must_use]
+    pub fn test(&self, index: usize) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Bitset::test");
+
+        self.check(index);
+        self.words[index / WORD] & (1 << (index % WORD)) != 0
+    }
+
+    /// Sets bit `index`, returning whether it was previously set.
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    pub fn set(&mut self, index: usize) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Bitset::set");
+
+        let set = self.test(index);
+        self.words[index / WORD] |= 1 << (index % WORD);
+        set
+    }
+
+    /// Clears bit `index`, returning whether it was previously set.
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    pub fn clear(&mut self, index: usize) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Bitset::clear");
+
+        let set = self.test(index);
+        self.words[index / WORD] &= !(1 << (index % WORD));
+        set
+    }
+
+    /// Clears every bit.
+    pub fn clear_all(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Bitset::clear_all");
+
+        self.words.fill(0);
+    }
+
+    /// The number of set bits.
+    #[must_use]
+    pub fn count_ones(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Bitset::count_ones");
+
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns the index of the first clear bit, e.g. the first free slot.
+    #[must_use]
+    pub fn first_zero(&self) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("Bitset::first_zero");
+
+        let i = full_words(&self.words);
+        self.words
+            .get(i)
+            .map(|word| i * WORD + word.trailing_ones() as usize)
+            .filter(|&index| index < self.len)
+    }
+
+    /// Returns the index of the first of `n` consecutive clear bits, e.g. the first run of `n`
+    /// free slots.
+    #[must_use]
+    pub fn find_zero_run(&self, n: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("Bitset::find_zero_run");
+
+        if n == 0 {
+            return Some(0);
+        }
+        // The start and length of the run of clear bits ending at the current bit.
+        let (mut start, mut run) = (0, 0);
+        let mut i = 0;
+        while i < self.words.len() {
+            if run == 0 {
+                i += full_words(&self.words[i..]);
+                if i == self.words.len() {
+                    break;
+                }
+            }
+            let word = self.word(i);
+            let mut bit = 0;
+            while bit < WORD {
+                let zeros = ((word >> bit).trailing_zeros() as usize).min(WORD - bit);
+                if zeros > 0 {
+                    if run == 0 {
+                        start = i * WORD + bit;
+                    }
+                    run += zeros;
+                    if run >= n {
+                        return Some(start);
+                    }
+                    bit += zeros;
+                    if bit == WORD {
+                        break;
+                    }
+                }
+                run = 0;
+                bit += (word >> bit).trailing_ones() as usize;
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Returns an iterator over the indices of the set bits in ascending order, the same as
+    /// [`Bitset::iter`].
+    #[must_use]
+    pub fn iter_ones(&self) -> Ones<'_> {
+        #[cfg(feature = "log")]
+        trace!("Bitset::iter_ones");
+
+        self.iter()
+    }
+
+    /// Returns an iterator over the indices of the set bits in ascending order.
+    #[must_use]
+    pub fn iter(&self) -> Ones<'_> {
+        #[cfg(feature = "log")]
+        trace!("Bitset::iter");
+
+        Ones {
+            words: &self.words,
+            index: 0,
+            word: self.words.first().copied().unwrap_or(0),
+        }
+    }
+
+    /// Sets every bit set in `other`.
+    ///
+    /// # Panics
+    ///
+    /// When `other` has a different length.
+    pub fn union_with(&mut self, other: &Bitset<I>) {
+        #[cfg(feature = "log")]
+        trace!("Bitset::union_with");
+
+        assert_eq!(self.len, other.len, "bitsets differ in length");
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a |= b;
+        }
+    }
+
+    /// Clears every bit clear in `other`.
+    ///
+    /// # Panics
+    ///
+    /// When `other` has a different length.
+    pub fn intersect_with(&mut self, other: &Bitset<I>) {
+        #[cfg(feature = "log")]
+        trace!("Bitset::intersect_with");
+
+        assert_eq!(self.len, other.len, "bitsets differ in length");
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= b;
+        }
+    }
+
+    /// Clears every bit set in `other`.
+    ///
+    /// # Panics
+    ///
+    /// When `other` has a different length.
+    pub fn difference_with(&mut self, other: &Bitset<I>) {
+        #[cfg(feature = "log")]
+        trace!("Bitset::difference_with");
+
+        assert_eq!(self.len, other.len, "bitsets differ in length");
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= !b;
+        }
+    }
+
+    /// Returns the word at `i` with the bits past the end of the bitset set.
+    fn word(&self, i: usize) -> u64 {
+        let end = self.len - i * WORD;
+        if end < WORD {
+            self.words[i] | (u64::MAX << end)
+        } else {
+            self.words[i]
+        }
+    }
+
+    fn check(&self, index: usize) {
+        assert!(
+            index < self.len,
+            "index {index} out of range for bitset of length {}",
+            self.len
+        );
+    }
+}
+
+impl<'a, I: Index> fmt::Debug for Bitset<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, 'b, I: Index> IntoIterator for &'b Bitset<'a, I> {
+    type Item = usize;
+    type IntoIter = Ones<'b>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Returns the number of leading words with every bit set.
+fn full_words(words: &[u64]) -> usize {
+    #[cfg(feature = "simd")]
+    let chunks = {
+        use std::simd::cmp::SimdPartialEq;
+        use std::simd::u64x8;
+
+        words
+            .chunks_exact(8)
+            .take_while(|chunk| {
+                u64x8::from_slice(chunk)
+                    .simd_eq(u64x8::splat(u64::MAX))
+                    .all()
+            })
+            .count()
+            * 8
+    };
+    #[cfg(not(feature = "simd"))]
+    let chunks = 0;
+    chunks
+        + words[chunks..]
+            .iter()
+            .take_while(|&&word| word == u64::MAX)
+            .count()
+}
+
+/// An iterator over the set bits of a [`Bitset`], see [`Bitset::iter`].
+#[derive(Debug, Clone)]
+pub struct Ones<'b> {
+    words: &'b [u64],
+    /// The index of the current word.
+    index: usize,
+    /// The bits of the current word not yet returned.
+    word: u64,
+}
+
+impl<'b> Iterator for Ones<'b> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.word == 0 {
+            self.index += 1;
+            self.word = *self.words.get(self.index)?;
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+        Some(self.index * WORD + bit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn bitset() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut bitset = Bitset::new(&memory, 100).unwrap();
+        assert_eq!(bitset.len(), 100);
+        assert!(!bitset.set(3));
+        assert!(bitset.set(3));
+        assert!(!bitset.set(64));
+        assert!(!bitset.set(99));
+        assert!(bitset.test(64));
+        assert!(!bitset.test(65));
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), [3, 64, 99]);
+        assert_eq!(bitset.count_ones(), 3);
+        assert_eq!(format!("{bitset:?}"), "{3, 64, 99}");
+        assert!(bitset.clear(64));
+        assert!(!bitset.clear(64));
+        assert_eq!(bitset.words(), [1 << 3, 1 << 35]);
+        assert_eq!(bitset.first_zero(), Some(0));
+        bitset.clear_all();
+        assert_eq!(bitset.iter().next(), None);
+    }
+
+    #[test]
+    fn bitset_first_zero() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut bitset = Bitset::new(&memory, 66).unwrap();
+        for i in 0..65 {
+            bitset.set(i);
+        }
+        assert_eq!(bitset.first_zero(), Some(65));
+        bitset.set(65);
+        assert_eq!(bitset.first_zero(), None);
+    }
+
+    #[test]
+    fn bitset_find_zero_run() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut bitset = Bitset::new(&memory, 200).unwrap();
+        assert_eq!(bitset.find_zero_run(200), Some(0));
+        assert_eq!(bitset.find_zero_run(201), None);
+        for i in (0..130).chain(140..150) {
+            bitset.set(i);
+        }
+        assert_eq!(bitset.find_zero_run(10), Some(130));
+        assert_eq!(bitset.find_zero_run(11), Some(150));
+        // The bits past the end of the bitset are never clear.
+        assert_eq!(bitset.find_zero_run(50), Some(150));
+        assert_eq!(bitset.find_zero_run(51), None);
+    }
+
+    #[test]
+    fn bitset_find_zero_run_scalar() {
+        use rand::{Rng, SeedableRng};
+
+        let memory = ArrayAllocator::<64>::new(None);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut bitset = Bitset::new(&memory, 1000).unwrap();
+        for density in [0.1, 0.5, 0.9, 0.99] {
+            bitset.clear_all();
+            for i in 0..1000 {
+                if rng.gen_bool(density) {
+                    bitset.set(i);
+                }
+            }
+            for n in [1, 2, 3, 7, 64, 65, 100] {
+                // Bit by bit.
+                let expected =
+                    (0..=1000 - n).find(|&start| (start..start + n).all(|i| !bitset.test(i)));
+                assert_eq!(bitset.find_zero_run(n), expected, "{density} {n}");
+            }
+        }
+        // Skips several full words at a time.
+        for i in 0..1000 {
+            bitset.set(i);
+        }
+        bitset.clear(990);
+        assert_eq!(bitset.find_zero_run(1), Some(990));
+        assert_eq!(bitset.first_zero(), Some(990));
+    }
+
+    #[test]
+    fn bitset_bulk() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut a = Bitset::new(&memory, 70).unwrap();
+        let mut b = Bitset::new(&memory, 70).unwrap();
+        a.set(1);
+        a.set(68);
+        b.set(68);
+        b.set(2);
+
+        a.union_with(&b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), [1, 2, 68]);
+        b.clear(2);
+        a.difference_with(&b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), [1, 2]);
+        b.set(1);
+        a.intersect_with(&b);
+        assert_eq!((&a).into_iter().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 8 out of range for bitset of length 8")]
+    fn bitset_out_of_range() {
+        let memory = ArrayAllocator::<1>::new(None);
+        let _ = Bitset::new(&memory, 8).unwrap().test(8);
+    }
+}
@@ -0,0 +1,501 @@
+//! An allocator which tags both ends of every region of blocks with its size and whether it is
+//! free, so freeing coalesces with both neighbours in O(1) by inspecting the tags adjacent to the
+//! region rather than walking an address ordered free list as [`crate::linked_list`] does.
+//!
+//! Free regions are kept in a doubly linked list threaded through their first tags, allocation
+//! takes the first region in the list large enough. The tags are stored apart from the data
+//! blocks, so allocations carry no per-region overhead.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut, Drop};
+use std::ptr::NonNull;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::error::{none_on_oom, AllocError};
+use crate::Index;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ArrayAllocator<const N: usize, I = usize> {
+    allocator: Allocator<I>,
+    data: [Block<I>; N],
+    tags: [Tag<I>; N],
+}
+impl<const N: usize, I: Index> ArrayAllocator<N, I> {
+    #[must_use]
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::new");
+
+        // See `linked_list::ArrayAllocator::new`.
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        let ptr = this.as_mut_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(0, 1);
+            std::ptr::addr_of_mut!((*ptr).tags).write_bytes(0, 1);
+            Allocator::init(std::ptr::addr_of_mut!((*ptr).allocator), attr, N);
+            this.assume_init()
+        }
+    }
+}
+
+impl<const N: usize, I> Deref for ArrayAllocator<N, I> {
+    type Target = Allocator<I>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+impl<const N: usize, I> DerefMut for ArrayAllocator<N, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.allocator
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Allocator<I = usize>(crate::mutex::Mutex<InnerAllocator<I>>);
+
+impl<I: Index> Allocator<I> {
+    /// Initializes `Self` at `ptr`.
+    ///
+    /// `ptr` must be followed by `n` data blocks then `n` tags.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the inner mutex or when `n` is greater than [`Index::MAX`].
+    pub unsafe fn init(ptr: *mut Self, attr: Option<crate::MutexAttr>, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::init");
+
+        std::ptr::addr_of_mut!((*ptr).0.lock).write(crate::mutex::RawMutex::new(attr).unwrap());
+        #[cfg(feature = "watchdog")]
+        std::ptr::addr_of_mut!((*ptr).0.owner).write(crate::watchdog::Owner::new());
+        <InnerAllocator<I>>::init((*ptr).0.get(), n);
+    }
+
+    /// Allocates a given number of blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate(&self, blocks: usize) -> Option<Wrapper<I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        none_on_oom(self.try_allocate(blocks))
+    }
+
+    /// Allocates a given number of blocks.
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough or when locking the mutex fails.
+    pub fn try_allocate(&self, blocks: usize) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate");
+
+        if blocks == 0 {
+            return Ok(Wrapper {
+                allocator: self,
+                index: 0,
+                size: 0,
+            });
+        }
+        let mut inner_allocator = self.0.lock().map_err(AllocError::LockFailed)?;
+        match inner_allocator.allocate(blocks) {
+            Some(index) => Ok(Wrapper {
+                allocator: self,
+                index,
+                size: blocks,
+            }),
+            None => Err(AllocError::OutOfMemory {
+                requested: blocks,
+                largest_free: inner_allocator.largest_free(),
+            }),
+        }
+    }
+
+    /// Returns the number of free blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn free(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free");
+
+        self.0
+            .lock()
+            .unwrap()
+            .free_regions()
+            .map(|(_, size)| size)
+            .sum()
+    }
+
+    /// Returns the number of free regions, as frees coalesce with free neighbours this is the
+    /// number of runs of adjacent free blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn free_regions(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free_regions");
+
+        self.0.lock().unwrap().free_regions().count()
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct InnerAllocator<I = usize> {
+    /// The first region in the free list.
+    head: Option<usize>,
+    size: usize,
+    _marker: PhantomData<I>,
+}
+
+impl<I: Index> InnerAllocator<I> {
+    /// Returns the data blocks.
+    ///
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    ///
+    /// # Panics
+    ///
+    /// When the pointer to the data blocks is null.
+    pub unsafe fn data(&mut self) -> NonNull<[Block<I>]> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::data");
+
+        NonNull::slice_from_raw_parts(
+            NonNull::new((self as *mut Self).add(1).cast::<Block<I>>()).unwrap(),
+            self.size,
+        )
+    }
+
+    /// Returns the tags, which follow the data blocks.
+    ///
+    /// The tags are not borrowed from `self` so the free list head can be updated alongside them.
+    unsafe fn tags<'b>(&mut self) -> &'b mut [Tag<I>] {
+        let tags = self.data().as_ptr().cast::<Block<I>>().add(self.size);
+        std::slice::from_raw_parts_mut(tags.cast::<Tag<I>>(), self.size)
+    }
+
+    unsafe fn init(ptr: *mut Self, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::init");
+
+        assert!(n <= I::MAX, "{n} blocks cannot be indexed by {}", I::MAX);
+
+        (*ptr).size = n;
+        std::ptr::addr_of_mut!((*ptr)._marker).write(PhantomData);
+        if n > 0 {
+            (*ptr).head = Some(0);
+            (*ptr).tag(0, n, true);
+        } else {
+            (*ptr).head = None;
+        }
+    }
+
+    /// Writes the tags at both ends of the region of `size` blocks at `start`, clearing its links.
+    fn tag(&mut self, start: usize, size: usize, free: bool) {
+        let tags = unsafe { self.tags() };
+        let tag = Tag {
+            size: I::from_usize(size),
+            free,
+            prev: None,
+            next: None,
+        };
+        tags[start + size - 1] = tag;
+        tags[start] = tag;
+    }
+
+    /// Removes the free region at `start` from the free list.
+    fn unlink(&mut self, start: usize) {
+        let tags = unsafe { self.tags() };
+        let (prev, next) = (tags[start].prev, tags[start].next);
+        match prev {
+            Some(prev) => tags[prev.to_usize()].next = next,
+            None => self.head = next.map(Index::to_usize),
+        }
+        if let Some(next) = next {
+            tags[next.to_usize()].prev = prev;
+        }
+    }
+
+    /// Puts the free region at `new` in place of the free region at `old` in the free list.
+    fn replace(&mut self, old: usize, new: usize) {
+        let tags = unsafe { self.tags() };
+        let (prev, next) = (tags[old].prev, tags[old].next);
+        tags[new].prev = prev;
+        tags[new].next = next;
+        match prev {
+            Some(prev) => tags[prev.to_usize()].next = Some(I::from_usize(new)),
+            None => self.head = Some(new),
+        }
+        if let Some(next) = next {
+            tags[next.to_usize()].prev = Some(I::from_usize(new));
+        }
+    }
+
+    /// Pushes the free region at `start` to the front of the free list.
+    fn push(&mut self, start: usize) {
+        let head = self.head;
+        let tags = unsafe { self.tags() };
+        tags[start].prev = None;
+        tags[start].next = head.map(I::from_usize);
+        if let Some(head) = head {
+            tags[head].prev = Some(I::from_usize(start));
+        }
+        self.head = Some(start);
+    }
+
+    /// Allocates `blocks` from the first free region large enough, returning its index.
+    fn allocate(&mut self, blocks: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::allocate");
+
+        let (start, size) = self.free_regions().find(|&(_, size)| size >= blocks)?;
+        if size > blocks {
+            // The remainder takes the place of the region so the order of the list is kept.
+            self.tag(start + blocks, size - blocks, true);
+            self.replace(start, start + blocks);
+        } else {
+            self.unlink(start);
+        }
+        self.tag(start, blocks, false);
+        Some(start)
+    }
+
+    /// Frees the `size` blocks at `index`, coalescing with the free regions either side.
+    fn deallocate(&mut self, index: usize, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::deallocate");
+
+        let (mut start, mut end) = (index, index + size);
+        let tags = unsafe { self.tags() };
+        // The tag before the region ends the preceding region and the tag after starts the
+        // following region.
+        let before = (start > 0 && tags[start - 1].free).then(|| tags[start - 1].size());
+        let after = (end < tags.len() && tags[end].free).then(|| tags[end].size());
+        if let Some(before) = before {
+            start -= before;
+            self.unlink(start);
+        }
+        if let Some(after) = after {
+            self.unlink(end);
+            end += after;
+        }
+        self.tag(start, end - start, true);
+        self.push(start);
+    }
+
+    /// Returns the start and size of each free region in the order of the free list.
+    fn free_regions(&mut self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let tags = unsafe { self.tags() };
+        std::iter::successors(self.head, |&start| tags[start].next.map(Index::to_usize))
+            .map(|start| (start, tags[start].size()))
+    }
+
+    fn largest_free(&mut self) -> usize {
+        self.free_regions().map(|(_, size)| size).max().unwrap_or(0)
+    }
+}
+
+/// A data block, sized and aligned as a [`crate::linked_list::Block`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Block<I = usize>([I; 2]);
+
+/// The tag at either end of a region of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Tag<I = usize> {
+    size: I,
+    free: bool,
+    /// The previous free region, only meaningful in the first tag of a free region.
+    prev: Option<I>,
+    /// The next free region, only meaningful in the first tag of a free region.
+    next: Option<I>,
+}
+
+impl<I: Index> Tag<I> {
+    fn size(&self) -> usize {
+        self.size.to_usize()
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Wrapper<'a, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    index: usize,
+    size: usize,
+}
+
+impl<'a, I: Index> Wrapper<'a, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index");
+
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::size");
+
+        self.size
+    }
+}
+
+// See `linked_list::Wrapper`.
+unsafe impl<'a, I: Index> Send for Wrapper<'a, I> {}
+unsafe impl<'a, I: Index> Sync for Wrapper<'a, I> {}
+
+impl<'a, I: Index> Deref for Wrapper<'a, I> {
+    type Target = [Block<I>];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe { &inner_allocator.data().as_ref()[self.index..self.index + self.size] }
+    }
+}
+impl<'a, I: Index> DerefMut for Wrapper<'a, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut");
+
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe { &mut inner_allocator.data().as_mut()[self.index..self.index + self.size] }
+    }
+}
+
+impl<'a, I: Index> Drop for Wrapper<'a, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop");
+
+        if self.size == 0 {
+            return;
+        }
+        self.allocator
+            .0
+            .lock()
+            .unwrap()
+            .deallocate(self.index, self.size);
+    }
+}
+
+impl<I: Index> fmt::Display for Allocator<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut inner_allocator = self.0.lock().map_err(|_| fmt::Error)?;
+        let size = inner_allocator.size;
+        let free = inner_allocator
+            .free_regions()
+            .map(|(_, size)| size)
+            .sum::<usize>();
+        write!(
+            f,
+            "{free}/{size} blocks free ({} bytes per block)",
+            size_of::<Block<I>>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn boundary_tag_coalesce() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(2).unwrap();
+        let b = memory.allocate(3).unwrap();
+        let c = memory.allocate(3).unwrap();
+        assert_eq!((a.index(), b.index(), c.index()), (0, 2, 5));
+        assert_eq!(memory.free(), 0);
+        assert!(memory.allocate(1).is_none());
+
+        // Freeing with allocated neighbours coalesces with neither.
+        drop(b);
+        assert_eq!(memory.free_regions(), 1);
+        // Freeing with a free following neighbour.
+        drop(a);
+        assert_eq!(memory.free_regions(), 1);
+        assert_eq!(memory.free(), 5);
+        // Freeing with a free preceding neighbour.
+        drop(c);
+        assert_eq!(memory.free_regions(), 1);
+        assert_eq!(memory.allocate(8).unwrap().size(), 8);
+    }
+
+    #[test]
+    fn boundary_tag_coalesce_both() {
+        let memory = ArrayAllocator::<6, u16>::new(None);
+        let wrappers = [2, 2, 2].map(|blocks| memory.allocate(blocks).unwrap());
+        let [a, b, c] = wrappers;
+        drop(a);
+        drop(c);
+        assert_eq!(memory.free_regions(), 2);
+        assert_eq!(
+            memory.try_allocate(3).unwrap_err(),
+            AllocError::OutOfMemory {
+                requested: 3,
+                largest_free: 2
+            }
+        );
+        drop(b);
+        assert_eq!(memory.free_regions(), 1);
+        assert_eq!(memory.to_string(), "6/6 blocks free (4 bytes per block)");
+    }
+
+    #[test]
+    fn boundary_tag_split() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut a = memory.allocate(1).unwrap();
+        a[0] = Block([1, 2]);
+        let b = memory.allocate(1).unwrap();
+        drop(a);
+        // The first region in the free list large enough is taken.
+        let c = memory.allocate(2).unwrap();
+        assert_eq!(c.index(), 2);
+        assert_eq!(memory.allocate(1).unwrap().index(), 0);
+        // A freed region is pushed to the front of the free list.
+        drop(c);
+        assert_eq!(memory.allocate(1).unwrap().index(), 2);
+        assert_eq!(memory.allocate(0).unwrap().size(), 0);
+        drop(b);
+    }
+}
@@ -0,0 +1,296 @@
+//! A single writer, multiple reader broadcast ring allocated within a
+//! [`crate::linked_list::Allocator`], for fanning out messages, e.g. telemetry, to readers in
+//! other processes sharing the allocator.
+//!
+//! Each slot holds a sequence number alongside its value. Message `n` is written to slot
+//! `n % capacity` with the sequence number `2n + 1` while it is written and `2n + 2` once
+//! written, so readers never block the writer: each reader tracks its own cursor and detects
+//! when the writer has lapped it. As the ring holds no pointers it can be read from any mapping of
+//! the allocator's memory.
+
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Slice};
+use crate::Index;
+
+/// A slot of a broadcast ring.
+#[repr(C)]
+pub struct Slot<T> {
+    /// `0` when never written, otherwise see the [module](self) documentation.
+    seq: AtomicU64,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Values are only copied out after checking the writer did not overwrite them while they were
+// read.
+unsafe impl<T: Send> Sync for Slot<T> {}
+
+impl<T> fmt::Debug for Slot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slot")
+            .field("seq", &self.seq.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// The writer of a broadcast ring.
+///
+/// As the writer is not [`Sync`] there is only ever one thread sending, while the slots can be
+/// shared with readers on other threads.
+#[derive(Debug)]
+pub struct Broadcast<'a, T: Copy, I: Index = usize> {
+    slots: Slice<'a, Slot<T>, I>,
+    /// The sequence number of the next message.
+    next: Cell<u64>,
+}
+
+impl<'a, T: Copy, I: Index> Broadcast<'a, T, I> {
+    /// Allocates a ring of `capacity` slots within `allocator`.
+    ///
+    /// Returns `None` when `capacity == 0`, when there is no free region large enough or when `T`
+    /// requires a greater alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn new(allocator: &'a Allocator<I>, capacity: usize) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("Broadcast::new");
+
+        if capacity == 0
+            || std::mem::align_of::<Slot<T>>()
+                > std::mem::align_of::<crate::linked_list::Block<I>>()
+        {
+            return None;
+        }
+        let slots = allocator.allocate_slice_from_iter((0..capacity).map(|_| Slot {
+            seq: AtomicU64::new(0),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }))?;
+        Some(Self {
+            slots,
+            next: Cell::new(0),
+        })
+    }
+
+    /// Writes `value` to the ring, overwriting the oldest message once the ring is full, and
+    /// returns its sequence number.
+    pub fn send(&self, value: T) -> u64 {
+        #[cfg(feature = "log")]
+        trace!("Broadcast::send");
+
+        let n = self.next.get();
+        let slot = &self.slots[slot_index(n, self.slots.len())];
+        slot.seq.store(2 * n + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        unsafe { slot.value.get().write_volatile(MaybeUninit::new(value)) };
+        slot.seq.store(2 * n + 2, Ordering::Release);
+        self.next.set(n + 1);
+        n
+    }
+
+    /// Returns a reader starting at the next message sent.
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<'_, T> {
+        #[cfg(feature = "log")]
+        trace!("Broadcast::subscribe");
+
+        Receiver {
+            slots: &self.slots,
+            cursor: self.next.get(),
+        }
+    }
+
+    /// Returns the slots, from which readers in other processes can be constructed with
+    /// [`Receiver::new`].
+    #[must_use]
+    pub fn slots(&self) -> &[Slot<T>] {
+        #[cfg(feature = "log")]
+        trace!("Broadcast::slots");
+
+        &self.slots
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Broadcast::capacity");
+
+        self.slots.len()
+    }
+}
+
+/// The error returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// No message has been sent since the last received.
+    Empty,
+    /// The writer overwrote messages before they were received, the receiver skipped to the
+    /// oldest message still held.
+    Lagged {
+        /// The number of messages skipped.
+        missed: u64,
+    },
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no message available"),
+            Self::Lagged { missed } => write!(f, "receiver lagged, {missed} messages missed"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// A reader of a broadcast ring.
+#[derive(Debug, Clone)]
+pub struct Receiver<'b, T> {
+    slots: &'b [Slot<T>],
+    /// The sequence number of the next message to receive.
+    cursor: u64,
+}
+
+impl<'b, T: Copy> Receiver<'b, T> {
+    /// Constructs a reader of the ring held in `slots`, starting at the next message sent.
+    #[must_use]
+    pub fn new(slots: &'b [Slot<T>]) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Receiver::new");
+
+        // Skips the message being written, if any, as it may have been sent before this reader.
+        let cursor = newest(slots).map_or(0, |n| n + 1);
+        Self { slots, cursor }
+    }
+
+    /// The sequence number of the next message to receive.
+    #[must_use]
+    pub fn cursor(&self) -> u64 {
+        #[cfg(feature = "log")]
+        trace!("Receiver::cursor");
+
+        self.cursor
+    }
+
+    /// Receives the next message.
+    ///
+    /// # Errors
+    ///
+    /// When no message has been sent since the last received or when messages were overwritten
+    /// before they were received.
+    pub fn try_recv(&mut self) -> Result<T, RecvError> {
+        #[cfg(feature = "log")]
+        trace!("Receiver::try_recv");
+
+        let n = self.cursor;
+        let slot = &self.slots[slot_index(n, self.slots.len())];
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq == 2 * n + 2 {
+            let value = unsafe { slot.value.get().read_volatile() };
+            fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) == seq {
+                self.cursor += 1;
+                return Ok(unsafe { value.assume_init() });
+            }
+        } else if seq <= 2 * n + 1 {
+            return Err(RecvError::Empty);
+        }
+        // The slot was overwritten by a later message. Whether or not the newest message has been
+        // completely written, the message before it in its slot was overwritten.
+        let newest = newest(self.slots).unwrap_or(n);
+        let oldest = (newest + 1).saturating_sub(self.slots.len() as u64);
+        self.cursor = oldest.max(n + 1);
+        Err(RecvError::Lagged {
+            missed: self.cursor - n,
+        })
+    }
+}
+
+impl<'b, T: Copy> Iterator for Receiver<'b, T> {
+    type Item = Result<T, RecvError>;
+
+    /// Returns `None` once no message is available, so the iterator can be resumed later.
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.try_recv() {
+            Err(RecvError::Empty) => None,
+            rtn => Some(rtn),
+        }
+    }
+}
+
+/// Returns the sequence number of the newest message written or being written.
+fn newest<T>(slots: &[Slot<T>]) -> Option<u64> {
+    slots
+        .iter()
+        .map(|slot| slot.seq.load(Ordering::Acquire))
+        .filter(|&seq| seq != 0)
+        .map(|seq| (seq - 1) / 2)
+        .max()
+}
+
+fn slot_index(n: u64, capacity: usize) -> usize {
+    (n % capacity as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn broadcast_fan_out() {
+        let memory = ArrayAllocator::<16>::new(None);
+        let broadcast = Broadcast::<u32>::new(&memory, 4).unwrap();
+        assert_eq!(broadcast.send(1), 0);
+
+        let mut a = Receiver::new(broadcast.slots());
+        assert_eq!(a.cursor(), 1);
+        let mut b = Receiver::new(broadcast.slots());
+        assert_eq!(a.try_recv(), Err(RecvError::Empty));
+        let _ = broadcast.send(2);
+        let _ = broadcast.send(3);
+        assert_eq!(a.by_ref().collect::<Vec<_>>(), [Ok(2), Ok(3)]);
+        assert_eq!(b.try_recv(), Ok(2));
+        assert_eq!(a.try_recv(), Err(RecvError::Empty));
+        assert_eq!(b.try_recv(), Ok(3));
+    }
+
+    #[test]
+    fn broadcast_lag() {
+        let memory = ArrayAllocator::<16>::new(None);
+        let broadcast = Broadcast::<u64>::new(&memory, 4).unwrap();
+        let mut receiver = Receiver::new(broadcast.slots());
+        for i in 0..10 {
+            broadcast.send(i);
+        }
+        // Messages 0 to 5 were overwritten.
+        assert_eq!(receiver.try_recv(), Err(RecvError::Lagged { missed: 6 }));
+        assert_eq!(
+            receiver.by_ref().collect::<Vec<_>>(),
+            [Ok(6), Ok(7), Ok(8), Ok(9)]
+        );
+        assert_eq!(
+            RecvError::Lagged { missed: 6 }.to_string(),
+            "receiver lagged, 6 messages missed"
+        );
+    }
+
+    #[test]
+    fn broadcast_capacity() {
+        let memory = ArrayAllocator::<4>::new(None);
+        assert!(Broadcast::<u8>::new(&memory, 0).is_none());
+        assert!(Broadcast::<u64>::new(&memory, 16).is_none());
+        let broadcast = Broadcast::<u8>::new(&memory, 2).unwrap();
+        assert_eq!(broadcast.capacity(), 2);
+        assert_eq!(broadcast.subscribe().cursor(), 0);
+    }
+}
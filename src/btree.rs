@@ -0,0 +1,648 @@
+//! An ordered map whose B-tree nodes are allocated within a [`crate::linked_list::Allocator`] and
+//! link to their children by block index rather than by pointer, see [`BTreeMap`].
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of, MaybeUninit};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Block, Wrapper};
+use crate::Index;
+
+/// The minimum degree of the tree, every node but the root has at least `B - 1` entries.
+const B: usize = 6;
+/// The maximum number of entries in a node.
+const CAPACITY: usize = 2 * B - 1;
+/// A bound on the height of a tree, as every internal node but the root has at least `B` children.
+const MAX_HEIGHT: usize = usize::BITS as usize;
+
+/// The nodes on the path from the root to an entry, with a position in each.
+struct Path {
+    nodes: [(usize, usize); MAX_HEIGHT],
+    len: usize,
+}
+
+impl Path {
+    fn push(&mut self, node: (usize, usize)) {
+        self.nodes[self.len] = node;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(usize, usize)> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.nodes[self.len])
+    }
+}
+
+#[repr(C)]
+struct Node<K, V> {
+    len: usize,
+    leaf: bool,
+    keys: [MaybeUninit<K>; CAPACITY],
+    values: [MaybeUninit<V>; CAPACITY],
+    /// The first block of each child, only meaningful in internal nodes.
+    children: [usize; CAPACITY + 1],
+}
+
+impl<K, V> Node<K, V> {
+    /// # Safety
+    ///
+    /// `i < self.len`.
+    unsafe fn key(&self, i: usize) -> &K {
+        self.keys[i].assume_init_ref()
+    }
+
+    /// Returns the position of `key`, or else of the child which would hold it.
+    fn search<Q: Ord + ?Sized>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+    {
+        for i in 0..self.len {
+            match key.cmp(unsafe { self.key(i) }.borrow()) {
+                Ordering::Less => return Err(i),
+                Ordering::Equal => return Ok(i),
+                Ordering::Greater => {}
+            }
+        }
+        Err(self.len)
+    }
+
+    /// Inserts an entry at `i`, shifting the following entries right.
+    ///
+    /// # Safety
+    ///
+    /// `i <= self.len < CAPACITY`.
+    unsafe fn insert(&mut self, i: usize, key: K, value: V) {
+        shift(&mut self.keys, i, i + 1, self.len - i);
+        shift(&mut self.values, i, i + 1, self.len - i);
+        self.keys[i].write(key);
+        self.values[i].write(value);
+        self.len += 1;
+    }
+
+    /// Removes the entry at `i`, shifting the following entries left.
+    ///
+    /// # Safety
+    ///
+    /// `i < self.len`.
+    unsafe fn remove(&mut self, i: usize) -> (K, V) {
+        let entry = (
+            self.keys[i].assume_init_read(),
+            self.values[i].assume_init_read(),
+        );
+        shift(&mut self.keys, i + 1, i, self.len - i - 1);
+        shift(&mut self.values, i + 1, i, self.len - i - 1);
+        self.len -= 1;
+        entry
+    }
+
+    /// Replaces the entry at `i`, returning the old entry.
+    ///
+    /// # Safety
+    ///
+    /// `i < self.len`.
+    unsafe fn replace(&mut self, i: usize, (key, value): (K, V)) -> (K, V) {
+        (
+            std::mem::replace(&mut self.keys[i], MaybeUninit::new(key)).assume_init(),
+            std::mem::replace(&mut self.values[i], MaybeUninit::new(value)).assume_init(),
+        )
+    }
+}
+
+/// Moves `count` elements of `array` from `from` to `to`.
+///
+/// # Safety
+///
+/// Both ranges must be within `array`.
+unsafe fn shift<T>(array: &mut [T], from: usize, to: usize, count: usize) {
+    let ptr = array.as_mut_ptr();
+    std::ptr::copy(ptr.add(from), ptr.add(to), count);
+}
+
+/// An ordered map within a [`crate::linked_list::Allocator`].
+///
+/// Each node holds up to 11 entries and links to its children by the index of their first block,
+/// so the nodes stay meaningful to every process mapping the allocator.
+pub struct BTreeMap<'a, K, V, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    /// The first block of the root node.
+    root: Option<usize>,
+    len: usize,
+    __marker: PhantomData<(K, V)>,
+}
+
+impl<'a, K: Ord, V, I: Index> BTreeMap<'a, K, V, I> {
+    /// Constructs an empty map whose nodes are allocated within `allocator`.
+    ///
+    /// Returns `None` when a node requires a greater alignment than a block.
+    #[must_use]
+    pub fn new(allocator: &'a Allocator<I>) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::new");
+
+        (align_of::<Node<K, V>>() <= align_of::<Block<I>>()).then_some(Self {
+            allocator,
+            root: None,
+            len: 0,
+            __marker: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::is_empty");
+
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::get");
+
+        let (node, i) = self.find(key)?;
+        Some(unsafe { (*self.node(node)).values[i].assume_init_ref() })
+    }
+
+    pub fn get_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::get_mut");
+
+        let (node, i) = self.find(key)?;
+        Some(unsafe { (*self.node(node)).values[i].assume_init_mut() })
+    }
+
+    #[must_use]
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::contains_key");
+
+        self.find(key).is_some()
+    }
+
+    /// Inserts `value` for `key`, returning the value it replaces.
+    ///
+    /// # Errors
+    ///
+    /// When a node must be allocated and there is no free region large enough, returning the
+    /// entry.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::insert");
+
+        if self.root.is_none() {
+            let Some(root) = self.allocate_node(true) else {
+                return Err((key, value));
+            };
+            self.root = Some(root);
+        }
+        let root = self.root.unwrap();
+        let mut node = root;
+        unsafe {
+            // Full nodes are split on the way down, so there is always room for the entry.
+            if (*self.node(root)).len == CAPACITY {
+                let Some(new_root) = self.allocate_node(false) else {
+                    return Err((key, value));
+                };
+                (*self.node(new_root)).children[0] = root;
+                if !self.split_child(new_root, 0) {
+                    self.free_node(new_root);
+                    return Err((key, value));
+                }
+                self.root = Some(new_root);
+                node = new_root;
+            }
+            loop {
+                let n = &mut *self.node(node);
+                let mut i = match n.search(&key) {
+                    Ok(i) => return Ok(Some(n.replace(i, (key, value)).1)),
+                    Err(i) => i,
+                };
+                if n.leaf {
+                    n.insert(i, key, value);
+                    self.len += 1;
+                    return Ok(None);
+                }
+                if (*self.node(n.children[i])).len == CAPACITY {
+                    if !self.split_child(node, i) {
+                        return Err((key, value));
+                    }
+                    let n = &mut *self.node(node);
+                    match key.cmp(n.key(i)) {
+                        Ordering::Less => {}
+                        Ordering::Equal => return Ok(Some(n.replace(i, (key, value)).1)),
+                        Ordering::Greater => i += 1,
+                    }
+                }
+                node = (*self.node(node)).children[i];
+            }
+        }
+    }
+
+    /// Removes the entry of `key` and returns its value.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::remove");
+
+        let root = self.root?;
+        let entry = unsafe { self.remove_from(root, key) };
+        unsafe {
+            let n = &*self.node(root);
+            if n.len == 0 {
+                self.root = (!n.leaf).then(|| n.children[0]);
+                self.free_node(root);
+            }
+        }
+        let (_, value) = entry?;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Returns an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::iter");
+
+        // The nodes on the path to the next entry, with the position of the next entry in each.
+        let mut stack = Path {
+            nodes: [(0, 0); MAX_HEIGHT],
+            len: 0,
+        };
+        if let Some(root) = self.root {
+            self.descend_first(root, &mut stack);
+        }
+        std::iter::from_fn(move || loop {
+            let (node, i) = stack.pop()?;
+            let n = unsafe { &*self.node(node) };
+            if i < n.len {
+                stack.push((node, i + 1));
+                if !n.leaf {
+                    self.descend_first(n.children[i + 1], &mut stack);
+                }
+                return Some(unsafe { (n.key(i), n.values[i].assume_init_ref()) });
+            }
+        })
+    }
+
+    /// Pushes the path from `node` to its first entry.
+    fn descend_first(&self, mut node: usize, stack: &mut Path) {
+        loop {
+            stack.push((node, 0));
+            let n = unsafe { &*self.node(node) };
+            if n.leaf {
+                return;
+            }
+            node = n.children[0];
+        }
+    }
+
+    fn find<Q: Ord + ?Sized>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+    {
+        let mut node = self.root?;
+        loop {
+            let n = unsafe { &*self.node(node) };
+            match n.search(key) {
+                Ok(i) => return Some((node, i)),
+                Err(_) if n.leaf => return None,
+                Err(i) => node = n.children[i],
+            }
+        }
+    }
+
+    /// Splits the full child `i` of `parent` around its median, which moves into `parent`.
+    ///
+    /// Returns `false` when there is no free region large enough for the new node.
+    ///
+    /// # Safety
+    ///
+    /// `parent` must not be full.
+    unsafe fn split_child(&mut self, parent: usize, i: usize) -> bool {
+        let child = (*self.node(parent)).children[i];
+        let Some(sibling) = self.allocate_node((*self.node(child)).leaf) else {
+            return false;
+        };
+        let (p, c, s) = (
+            &mut *self.node(parent),
+            &mut *self.node(child),
+            &mut *self.node(sibling),
+        );
+        std::ptr::copy_nonoverlapping(c.keys.as_ptr().add(B), s.keys.as_mut_ptr(), B - 1);
+        std::ptr::copy_nonoverlapping(c.values.as_ptr().add(B), s.values.as_mut_ptr(), B - 1);
+        if !c.leaf {
+            s.children[..B].copy_from_slice(&c.children[B..]);
+        }
+        s.len = B - 1;
+        c.len = B;
+        let (key, value) = c.remove(B - 1);
+        p.children.copy_within(i + 1..=p.len, i + 2);
+        p.children[i + 1] = sibling;
+        p.insert(i, key, value);
+        true
+    }
+
+    /// Removes the entry of `key` from the subtree of `node`.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be the root or have at least `B` entries.
+    unsafe fn remove_from<Q: Ord + ?Sized>(&mut self, node: usize, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        let n = &mut *self.node(node);
+        match n.search(key) {
+            Ok(i) if n.leaf => Some(n.remove(i)),
+            Ok(i) => {
+                let (left, right) = (n.children[i], n.children[i + 1]);
+                if (*self.node(left)).len >= B {
+                    let predecessor = self.pop_last(left);
+                    Some(n.replace(i, predecessor))
+                } else if (*self.node(right)).len >= B {
+                    let successor = self.pop_first(right);
+                    Some(n.replace(i, successor))
+                } else {
+                    self.merge(node, i);
+                    self.remove_from(left, key)
+                }
+            }
+            Err(_) if n.leaf => None,
+            Err(i) => {
+                let i = self.fill(node, i);
+                self.remove_from((*self.node(node)).children[i], key)
+            }
+        }
+    }
+
+    /// Removes the last entry of the subtree of `node`.
+    ///
+    /// # Safety
+    ///
+    /// `node` must have at least `B` entries.
+    unsafe fn pop_last(&mut self, node: usize) -> (K, V) {
+        let n = &mut *self.node(node);
+        if n.leaf {
+            return n.remove(n.len - 1);
+        }
+        let i = self.fill(node, n.len);
+        self.pop_last((*self.node(node)).children[i])
+    }
+
+    /// Removes the first entry of the subtree of `node`.
+    ///
+    /// # Safety
+    ///
+    /// `node` must have at least `B` entries.
+    unsafe fn pop_first(&mut self, node: usize) -> (K, V) {
+        let n = &mut *self.node(node);
+        if n.leaf {
+            return n.remove(0);
+        }
+        let i = self.fill(node, 0);
+        self.pop_first((*self.node(node)).children[i])
+    }
+
+    /// Ensures child `i` of `node` has at least `B` entries, by moving an entry from a sibling or
+    /// merging with a sibling, returning the position of the child afterwards.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be internal, and the root or have at least `B` entries.
+    unsafe fn fill(&mut self, node: usize, i: usize) -> usize {
+        let n = &mut *self.node(node);
+        let c = &mut *self.node(n.children[i]);
+        if c.len >= B {
+            return i;
+        }
+        if i > 0 && (*self.node(n.children[i - 1])).len >= B {
+            // Rotate the last entry of the left sibling through the parent.
+            let l = &mut *self.node(n.children[i - 1]);
+            let (key, value) = l.remove(l.len - 1);
+            let (key, value) = n.replace(i - 1, (key, value));
+            c.insert(0, key, value);
+            if !c.leaf {
+                c.children.copy_within(0..c.len, 1);
+                c.children[0] = l.children[l.len + 1];
+            }
+            i
+        } else if i < n.len && (*self.node(n.children[i + 1])).len >= B {
+            // Rotate the first entry of the right sibling through the parent.
+            let r = &mut *self.node(n.children[i + 1]);
+            let (key, value) = r.remove(0);
+            let (key, value) = n.replace(i, (key, value));
+            c.insert(c.len, key, value);
+            if !c.leaf {
+                c.children[c.len] = r.children[0];
+                r.children.copy_within(1..=r.len + 1, 0);
+            }
+            i
+        } else if i < n.len {
+            self.merge(node, i);
+            i
+        } else {
+            self.merge(node, i - 1);
+            i - 1
+        }
+    }
+
+    /// Merges child `i + 1` of `node` and the entry between them into child `i`.
+    ///
+    /// # Safety
+    ///
+    /// Both children must have `B - 1` entries.
+    unsafe fn merge(&mut self, node: usize, i: usize) {
+        let n = &mut *self.node(node);
+        let (left, right) = (n.children[i], n.children[i + 1]);
+        let (l, r) = (&mut *self.node(left), &mut *self.node(right));
+        let (key, value) = n.remove(i);
+        n.children.copy_within(i + 2..=n.len + 1, i + 1);
+        l.keys[B - 1].write(key);
+        l.values[B - 1].write(value);
+        std::ptr::copy_nonoverlapping(r.keys.as_ptr(), l.keys.as_mut_ptr().add(B), B - 1);
+        std::ptr::copy_nonoverlapping(r.values.as_ptr(), l.values.as_mut_ptr().add(B), B - 1);
+        if !l.leaf {
+            l.children[B..].copy_from_slice(&r.children[..B]);
+        }
+        l.len = CAPACITY;
+        self.free_node(right);
+    }
+}
+
+impl<'a, K, V, I: Index> BTreeMap<'a, K, V, I> {
+    fn blocks() -> usize {
+        size_of::<Node<K, V>>().div_ceil(size_of::<Block<I>>())
+    }
+
+    /// Returns a pointer to the node whose first block is `index`.
+    fn node(&self, index: usize) -> *mut Node<K, V> {
+        self.allocator
+            .ptr_at(index * size_of::<Block<I>>())
+            .unwrap()
+            .as_ptr()
+            .cast()
+    }
+
+    fn allocate_node(&self, leaf: bool) -> Option<usize> {
+        let (index, _) = self.allocator.allocate(Self::blocks())?.into_raw_parts();
+        let node = self.node(index);
+        unsafe {
+            std::ptr::addr_of_mut!((*node).len).write(0);
+            std::ptr::addr_of_mut!((*node).leaf).write(leaf);
+        }
+        Some(index)
+    }
+
+    /// Frees the node whose first block is `index` without dropping its entries.
+    ///
+    /// # Safety
+    ///
+    /// The node must not be used afterwards.
+    unsafe fn free_node(&self, index: usize) {
+        drop(Wrapper::from_raw_parts(
+            self.allocator,
+            index,
+            Self::blocks(),
+        ));
+    }
+
+    /// Drops the entries of the subtree of `node` and frees its nodes.
+    ///
+    /// # Safety
+    ///
+    /// The subtree must not be used afterwards.
+    unsafe fn free_subtree(&self, node: usize) {
+        let n = &mut *self.node(node);
+        if !n.leaf {
+            for &child in &n.children[..=n.len] {
+                self.free_subtree(child);
+            }
+        }
+        for i in 0..n.len {
+            n.keys[i].assume_init_drop();
+            n.values[i].assume_init_drop();
+        }
+        self.free_node(node);
+    }
+}
+
+impl<'a, K, V, I: Index> Drop for BTreeMap<'a, K, V, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("BTreeMap::drop");
+
+        if let Some(root) = self.root {
+            unsafe { self.free_subtree(root) };
+        }
+    }
+}
+
+impl<'a, K: Ord + fmt::Debug, V: fmt::Debug, I: Index> fmt::Debug for BTreeMap<'a, K, V, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn btree_map() {
+        let allocator = ArrayAllocator::<8192>::new(None);
+        let mut map = BTreeMap::new(&allocator).unwrap();
+        let mut model = std::collections::BTreeMap::new();
+        let rc = Rc::new(());
+        // Visits every key in 0..1000 in a scattered order.
+        let keys = (0..1000u32).map(|i| i * 7919 % 1000).collect::<Vec<_>>();
+        for &key in &keys {
+            assert!(map.insert(key, rc.clone()).unwrap().is_none());
+            model.insert(key, ());
+        }
+        assert!(map.insert(3, rc.clone()).unwrap().is_some());
+        assert_eq!(map.len(), 1000);
+        assert!(map.iter().map(|(k, _)| *k).eq(model.keys().copied()));
+        assert_eq!(Rc::strong_count(&rc), 1001);
+
+        for &key in keys.iter().rev().step_by(3) {
+            assert!(map.remove(&key).is_some());
+            model.remove(&key);
+        }
+        assert!(map.remove(&keys[keys.len() - 1]).is_none());
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().map(|(k, _)| *k).eq(model.keys().copied()));
+        for key in 0..1000 {
+            assert_eq!(map.contains_key(&key), model.contains_key(&key));
+        }
+        assert_eq!(Rc::strong_count(&rc), 1 + model.len());
+
+        for &key in &keys {
+            map.remove(&key);
+        }
+        assert!(map.is_empty());
+        assert_eq!(allocator.stats().free, 8192);
+        map.insert(1, rc.clone()).unwrap();
+        drop(map);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert_eq!(allocator.stats().free, 8192);
+    }
+
+    #[test]
+    fn btree_map_get() {
+        let allocator = ArrayAllocator::<64>::new(None);
+        let mut map = BTreeMap::new(&allocator).unwrap();
+        map.insert(String::from("b"), 2).unwrap();
+        map.insert(String::from("a"), 1).unwrap();
+        *map.get_mut("b").unwrap() += 1;
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get("c"), None);
+        assert_eq!(format!("{map:?}"), r#"{"a": 1, "b": 3}"#);
+
+        // Out of memory for a second node.
+        let allocator = ArrayAllocator::<16>::new(None);
+        let mut map = BTreeMap::new(&allocator).unwrap();
+        for i in 0..CAPACITY {
+            map.insert(i, ()).unwrap();
+        }
+        assert_eq!(map.insert(CAPACITY, ()), Err((CAPACITY, ())));
+        assert_eq!(map.len(), CAPACITY);
+    }
+}
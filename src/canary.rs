@@ -0,0 +1,123 @@
+//! Guard blocks written before and after each allocation of a
+//! [`crate::linked_list::Allocator`] once enabled by
+//! [`crate::linked_list::Allocator::set_canaries`], so an overflow is reported with the
+//! allocation it overflowed rather than discovered later as a corrupted free list.
+//!
+//! Each allocation is preceded by a block holding its size followed by [`CANARY`] bytes and
+//! followed by a block of [`CANARY`] bytes. The guards are checked when the allocation is freed,
+//! panicking if they were overwritten, and by [`crate::linked_list::Allocator::validate`].
+
+use std::fmt;
+use std::mem::size_of;
+
+use crate::Index;
+
+/// The byte guard blocks are filled with.
+pub const CANARY: u8 = 0xA5;
+
+/// Which guard of an allocation was overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guard {
+    /// The block before the allocation, overwritten by an underflow of this allocation or an
+    /// overflow of the preceding allocation.
+    Before,
+    /// The block after the allocation, overwritten by an overflow of this allocation.
+    After,
+}
+
+/// An allocation whose guard was overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanaryError {
+    /// The index of the first block of the allocation.
+    pub index: usize,
+    /// The number of blocks of the allocation, `None` when it could not be read from the
+    /// overwritten guard.
+    pub size: Option<usize>,
+    pub guard: Guard,
+}
+
+impl fmt::Display for CanaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = match self.guard {
+            Guard::Before => "before",
+            Guard::After => "after",
+        };
+        write!(f, "canary {guard} the allocation at block {}", self.index)?;
+        if let Some(size) = self.size {
+            write!(f, " ({size} blocks)")?;
+        }
+        write!(f, " was overwritten")
+    }
+}
+
+impl std::error::Error for CanaryError {}
+
+/// Writes the guards of an allocation of `size` blocks.
+pub(crate) fn write<T, I: Index>(before: &mut T, after: &mut T, size: usize) {
+    let before = (before as *mut T).cast::<u8>();
+    unsafe {
+        before.write_bytes(CANARY, size_of::<T>());
+        before.cast::<I>().write_unaligned(I::from_usize(size));
+        (after as *mut T)
+            .cast::<u8>()
+            .write_bytes(CANARY, size_of::<T>());
+    }
+}
+
+/// Reads the size stored in the guard before an allocation.
+pub(crate) fn size<T, I: Index>(before: &T) -> usize {
+    unsafe { (before as *const T).cast::<I>().read_unaligned() }.to_usize()
+}
+
+/// Checks the guards of the allocation of `size` blocks at `index`.
+pub(crate) fn check<T, I: Index>(
+    before: &T,
+    after: &T,
+    index: usize,
+    size: usize,
+) -> Result<(), CanaryError> {
+    let intact = |guard: &T, skip: usize| {
+        let bytes =
+            unsafe { std::slice::from_raw_parts((guard as *const T).cast::<u8>(), size_of::<T>()) };
+        bytes[skip..].iter().all(|&byte| byte == CANARY)
+    };
+    let error = |guard| CanaryError {
+        index,
+        size: Some(size),
+        guard,
+    };
+    if self::size::<T, I>(before) != size || !intact(before, size_of::<I>()) {
+        return Err(error(Guard::Before));
+    }
+    if !intact(after, 0) {
+        return Err(error(Guard::After));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn canary_check() {
+        let (mut before, mut after) = ([0u8; 8], [0u8; 8]);
+        write::<_, u16>(&mut before, &mut after, 3);
+        assert_eq!(size::<_, u16>(&before), 3);
+        assert_eq!(check::<_, u16>(&before, &after, 1, 3), Ok(()));
+
+        after[7] = 0;
+        let err = check::<_, u16>(&before, &after, 1, 3).unwrap_err();
+        assert_eq!(err.guard, Guard::After);
+        assert_eq!(
+            err.to_string(),
+            "canary after the allocation at block 1 (3 blocks) was overwritten"
+        );
+
+        before[0] = 4;
+        let err = check::<_, u16>(&before, &after, 1, 3).unwrap_err();
+        assert_eq!(err.guard, Guard::Before);
+    }
+}
@@ -0,0 +1,654 @@
+//! A bounded multi-producer, single-consumer channel whose buffer is allocated within a
+//! [`crate::linked_list::Allocator`], see [`channel`].
+//!
+//! The buffer is a ring of slots each holding a sequence number alongside its value, so senders
+//! claim slots without a lock and the receiver sees a value only once it is completely written.
+//! Sending blocks while the buffer is full and receiving while it is empty, which applies
+//! backpressure to senders outpacing the receiver.
+//!
+//! The cursors, handle counts and the words waited on are held in a control block ahead of the
+//! ring in the same allocation, so handles given up with [`Sender::into_raw`] can be reconstructed
+//! by another process sharing the allocator. Waiting uses `futex` on Linux, so a blocked process
+//! sleeps until another process sends or receives. As values are moved through shared memory,
+//! across processes `T` must not hold pointers into either process.
+
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of, MaybeUninit};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::futex;
+use crate::linked_list::{Allocator, Block, Wrapper};
+use crate::Index;
+
+/// A slot of the buffer of a channel.
+#[repr(C)]
+struct Slot<T> {
+    /// Equal to the position of the next send into the slot while it is free, one greater once the
+    /// value is written.
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// The state shared by the handles of a channel, ahead of its slots.
+#[repr(C)]
+struct Control {
+    capacity: usize,
+    /// The position of the next send.
+    send: AtomicUsize,
+    /// The position of the next receive.
+    recv: AtomicUsize,
+    senders: AtomicUsize,
+    /// The number of handles, the last of which frees the channel.
+    handles: AtomicUsize,
+    /// Whether the receiver is live.
+    receiver: AtomicU32,
+    /// Incremented after each send and when the last sender is dropped, waited on by the receiver.
+    sent: AtomicU32,
+    /// Incremented after each receive and when the receiver is dropped, waited on by senders.
+    received: AtomicU32,
+}
+
+/// The offset of the slots from the start of the allocation.
+fn offset<T>() -> usize {
+    size_of::<Control>().next_multiple_of(align_of::<Slot<T>>())
+}
+
+/// A reference to the allocation of a channel held by each of its handles.
+struct Shared<'a, T, I: Index> {
+    allocator: &'a Allocator<I>,
+    index: usize,
+    size: usize,
+    __marker: PhantomData<T>,
+}
+
+// Values are only accessed by the sender which claimed the slot and then the receiver.
+unsafe impl<'a, T: Send, I: Index> Send for Shared<'a, T, I> {}
+unsafe impl<'a, T: Send, I: Index> Sync for Shared<'a, T, I> {}
+
+impl<'a, T, I: Index> Shared<'a, T, I> {
+    /// Returns a pointer to the control block.
+    // `channel` checks blocks are aligned for the control block.
+    #[allow(clippy::cast_ptr_alignment)]
+    fn ptr(&self) -> *mut Control {
+        self.allocator
+            .ptr_at(self.index * size_of::<Block<I>>())
+            .unwrap()
+            .as_ptr()
+            .cast()
+    }
+
+    fn control(&self) -> &Control {
+        unsafe { &*self.ptr() }
+    }
+
+    fn slots(&self) -> &[Slot<T>] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self.ptr().cast::<u8>().add(offset::<T>()).cast::<Slot<T>>(),
+                self.control().capacity,
+            )
+        }
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let control = self.control();
+        if control.receiver.load(Ordering::Acquire) == 0 {
+            return Err(TrySendError::Disconnected(value));
+        }
+        let slots = self.slots();
+        let mut pos = control.send.load(Ordering::Relaxed);
+        loop {
+            let slot = &slots[pos % slots.len()];
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq == pos {
+                match control.send.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        control.sent.fetch_add(1, Ordering::Release);
+                        futex::wake(&control.sent, 1);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if seq.wrapping_sub(pos) > usize::MAX / 2 {
+                // The slot still holds the value sent a lap ago.
+                return Err(TrySendError::Full(value));
+            } else {
+                pos = control.send.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn try_recv(&self) -> Option<T> {
+        let control = self.control();
+        let slots = self.slots();
+        let pos = control.recv.load(Ordering::Relaxed);
+        let slot = &slots[pos % slots.len()];
+        if slot.seq.load(Ordering::Acquire) != pos.wrapping_add(1) {
+            return None;
+        }
+        let value = unsafe { (*slot.value.get()).assume_init_read() };
+        control.recv.store(pos.wrapping_add(1), Ordering::Relaxed);
+        slot.seq
+            .store(pos.wrapping_add(slots.len()), Ordering::Release);
+        control.received.fetch_add(1, Ordering::Release);
+        futex::wake(&control.received, 1);
+        Some(value)
+    }
+
+    /// Gives up the handle without releasing the channel.
+    fn into_raw(self) -> (usize, usize) {
+        let this = std::mem::ManuallyDrop::new(self);
+        (this.index, this.size)
+    }
+}
+
+impl<'a, T, I: Index> Drop for Shared<'a, T, I> {
+    fn drop(&mut self) {
+        if self.control().handles.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        while self.try_recv().is_some() {}
+        drop(unsafe { Wrapper::from_raw_parts(self.allocator, self.index, self.size) });
+    }
+}
+
+/// Constructs a channel buffering up to `capacity` values within `allocator`.
+///
+/// Returns `None` when `capacity == 0`, when there is no free region large enough or when `T`
+/// requires a greater alignment than a block.
+///
+/// # Panics
+///
+/// When locking the mutex fails.
+pub fn channel<'a, T, I: Index>(
+    allocator: &'a Allocator<I>,
+    capacity: usize,
+) -> Option<(Sender<'a, T, I>, Receiver<'a, T, I>)> {
+    #[cfg(feature = "log")]
+    trace!("channel");
+
+    if capacity == 0
+        || align_of::<Slot<T>>() > align_of::<Block<I>>()
+        || align_of::<Control>() > align_of::<Block<I>>()
+    {
+        return None;
+    }
+    let bytes = capacity
+        .checked_mul(size_of::<Slot<T>>())?
+        .checked_add(offset::<T>())?;
+    let wrapper = allocator.allocate(bytes.div_ceil(size_of::<Block<I>>()))?;
+    let (index, size) = wrapper.into_raw_parts();
+    let shared = Shared {
+        allocator,
+        index,
+        size,
+        __marker: PhantomData,
+    };
+    unsafe {
+        shared.ptr().write(Control {
+            capacity,
+            send: AtomicUsize::new(0),
+            recv: AtomicUsize::new(0),
+            senders: AtomicUsize::new(1),
+            handles: AtomicUsize::new(2),
+            receiver: AtomicU32::new(1),
+            sent: AtomicU32::new(0),
+            received: AtomicU32::new(0),
+        });
+        let slots = shared
+            .ptr()
+            .cast::<u8>()
+            .add(offset::<T>())
+            .cast::<Slot<T>>();
+        for i in 0..capacity {
+            slots.add(i).write(Slot {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            });
+        }
+    }
+    let receiver = Shared {
+        allocator,
+        index,
+        size,
+        __marker: PhantomData,
+    };
+    Some((
+        Sender { shared },
+        Receiver {
+            shared: receiver,
+            __marker: PhantomData,
+        },
+    ))
+}
+
+/// The error returned by [`Sender::send`] when the receiver was dropped, holding the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sending on a disconnected channel")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+/// The error returned by [`Sender::try_send`], holding the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The buffer is full.
+    Full(T),
+    /// The receiver was dropped.
+    Disconnected(T),
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(_) => write!(f, "sending on a full channel"),
+            Self::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for TrySendError<T> {}
+
+/// The error returned by [`Receiver::recv`] when every sender was dropped and the buffer is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "receiving on a closed channel")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// The error returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The buffer is empty.
+    Empty,
+    /// Every sender was dropped and the buffer is empty.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "receiving on an empty channel"),
+            Self::Disconnected => write!(f, "receiving on a closed channel"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+/// The sending half of a [`channel`], which can be cloned to send from several threads or
+/// processes.
+pub struct Sender<'a, T, I: Index = usize> {
+    shared: Shared<'a, T, I>,
+}
+
+impl<'a, T, I: Index> Sender<'a, T, I> {
+    /// Sends `value`, waiting while the buffer is full.
+    ///
+    /// # Errors
+    ///
+    /// When the receiver was dropped.
+    pub fn send(&self, mut value: T) -> Result<(), SendError<T>> {
+        #[cfg(feature = "log")]
+        trace!("Sender::send");
+
+        let received = &self.shared.control().received;
+        loop {
+            // Loaded before trying so a receive in between changes it and the wait returns.
+            let seen = received.load(Ordering::Acquire);
+            match self.shared.try_send(value) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(rtn)) => value = rtn,
+                Err(TrySendError::Disconnected(rtn)) => return Err(SendError(rtn)),
+            }
+            futex::wait(received, seen);
+        }
+    }
+
+    /// Sends `value` without waiting.
+    ///
+    /// # Errors
+    ///
+    /// When the buffer is full or the receiver was dropped.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        #[cfg(feature = "log")]
+        trace!("Sender::try_send");
+
+        self.shared.try_send(value)
+    }
+
+    /// The number of values the buffer holds.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Sender::capacity");
+
+        self.shared.control().capacity
+    }
+
+    /// Gives up the sender without dropping it, returning the index and number of the blocks of
+    /// the channel, e.g. so another process can reconstruct it with [`Sender::from_raw`].
+    #[must_use]
+    pub fn into_raw(self) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("Sender::into_raw");
+
+        let this = std::mem::ManuallyDrop::new(self);
+        unsafe { std::ptr::read(&this.shared) }.into_raw()
+    }
+
+    /// Reconstructs a sender given up with [`Sender::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `index` and `size` must have been returned by [`Sender::into_raw`] for a channel of `T`
+    /// allocated within `allocator`, and the sender must not have been reconstructed already.
+    pub unsafe fn from_raw(allocator: &'a Allocator<I>, index: usize, size: usize) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Sender::from_raw");
+
+        Self {
+            shared: Shared {
+                allocator,
+                index,
+                size,
+                __marker: PhantomData,
+            },
+        }
+    }
+}
+
+impl<'a, T, I: Index> Clone for Sender<'a, T, I> {
+    fn clone(&self) -> Self {
+        let control = self.shared.control();
+        control.senders.fetch_add(1, Ordering::Relaxed);
+        control.handles.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: Shared {
+                allocator: self.shared.allocator,
+                index: self.shared.index,
+                size: self.shared.size,
+                __marker: PhantomData,
+            },
+        }
+    }
+}
+
+impl<'a, T, I: Index> Drop for Sender<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Sender::drop");
+
+        let control = self.shared.control();
+        if control.senders.fetch_sub(1, Ordering::Release) == 1 {
+            control.sent.fetch_add(1, Ordering::Release);
+            futex::wake(&control.sent, u32::MAX);
+        }
+    }
+}
+
+impl<'a, T, I: Index> fmt::Debug for Sender<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a [`channel`].
+///
+/// Receiving reads a slot before releasing it, so only one thread may receive at a time, the
+/// receiver can be sent to another thread but not shared.
+pub struct Receiver<'a, T, I: Index = usize> {
+    shared: Shared<'a, T, I>,
+    __marker: PhantomData<Cell<()>>,
+}
+
+impl<'a, T, I: Index> Receiver<'a, T, I> {
+    /// Receives a value, waiting while the buffer is empty.
+    ///
+    /// # Errors
+    ///
+    /// When every sender was dropped and the buffer is empty.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        #[cfg(feature = "log")]
+        trace!("Receiver::recv");
+
+        let sent = &self.shared.control().sent;
+        loop {
+            // Loaded before trying so a send in between changes it and the wait returns.
+            let seen = sent.load(Ordering::Acquire);
+            match self.try_recv() {
+                Ok(value) => return Ok(value),
+                Err(TryRecvError::Empty) => futex::wait(sent, seen),
+                Err(TryRecvError::Disconnected) => return Err(RecvError),
+            }
+        }
+    }
+
+    /// Receives a value without waiting.
+    ///
+    /// # Errors
+    ///
+    /// When the buffer is empty or every sender was dropped and the buffer is empty.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        #[cfg(feature = "log")]
+        trace!("Receiver::try_recv");
+
+        if let Some(value) = self.shared.try_recv() {
+            return Ok(value);
+        }
+        if self.shared.control().senders.load(Ordering::Acquire) != 0 {
+            return Err(TryRecvError::Empty);
+        }
+        // A value may have been sent before the last sender was dropped.
+        self.shared.try_recv().ok_or(TryRecvError::Disconnected)
+    }
+
+    /// Returns an iterator receiving values until every sender is dropped.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        #[cfg(feature = "log")]
+        trace!("Receiver::iter");
+
+        std::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// The number of values the buffer holds.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Receiver::capacity");
+
+        self.shared.control().capacity
+    }
+
+    /// Disconnects the receiver as if dropped, so sending fails while values already sent can
+    /// still be received.
+    pub(crate) fn close(&self) {
+        #[cfg(feature = "log")]
+        trace!("Receiver::close");
+
+        let control = self.shared.control();
+        control.receiver.store(0, Ordering::Release);
+        control.received.fetch_add(1, Ordering::Release);
+        futex::wake(&control.received, u32::MAX);
+    }
+
+    /// Gives up the receiver without dropping it, see [`Sender::into_raw`].
+    #[must_use]
+    pub fn into_raw(self) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("Receiver::into_raw");
+
+        let this = std::mem::ManuallyDrop::new(self);
+        unsafe { std::ptr::read(&this.shared) }.into_raw()
+    }
+
+    /// Reconstructs a receiver given up with [`Receiver::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `index` and `size` must have been returned by [`Receiver::into_raw`] for a channel of `T`
+    /// allocated within `allocator`, and the receiver must not have been reconstructed already.
+    pub unsafe fn from_raw(allocator: &'a Allocator<I>, index: usize, size: usize) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Receiver::from_raw");
+
+        Self {
+            shared: Shared {
+                allocator,
+                index,
+                size,
+                __marker: PhantomData,
+            },
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, I: Index> Drop for Receiver<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Receiver::drop");
+
+        self.close();
+    }
+}
+
+impl<'a, T, I: Index> fmt::Debug for Receiver<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn channel_try() {
+        let memory = ArrayAllocator::<4>::new(None);
+        assert!(channel::<u8, _>(&memory, 0).is_none());
+        let (sender, receiver) = channel(&memory, 2).unwrap();
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(sender.try_send(1u32), Ok(()));
+        assert_eq!(sender.try_send(2), Ok(()));
+        assert_eq!(sender.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert_eq!(sender.try_send(3), Ok(()));
+        drop(sender);
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(receiver.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn channel_disconnected() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let (sender, receiver) = channel(&memory, 2).unwrap();
+        drop(receiver);
+        assert_eq!(sender.send(1u8), Err(SendError(1)));
+    }
+
+    #[test]
+    fn channel_drop() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let rc = std::rc::Rc::new(());
+        let (sender, receiver) = channel(&memory, 2).unwrap();
+        sender.send(rc.clone()).unwrap();
+        drop((sender, receiver));
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+        assert_eq!(memory.stats().free, 4);
+    }
+
+    #[test]
+    fn channel_raw() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let (sender, receiver) = channel::<u32, _>(&memory, 2).unwrap();
+        let (index, size) = sender.clone().into_raw();
+        drop(sender);
+        std::thread::scope(|s| {
+            // E.g. another process sharing the allocator.
+            s.spawn(|| {
+                let sender = unsafe { Sender::<u32>::from_raw(&memory, index, size) };
+                for i in 0..10 {
+                    sender.send(i).unwrap();
+                }
+            });
+            assert_eq!(
+                receiver.iter().collect::<Vec<_>>(),
+                (0..10).collect::<Vec<_>>()
+            );
+        });
+        drop(receiver);
+        assert_eq!(memory.stats().free, 4);
+    }
+
+    #[test]
+    fn channel_backpressure() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let (sender, receiver) = channel(&memory, 2).unwrap();
+        let received = std::thread::scope(|s| {
+            for t in 0..3 {
+                let sender = sender.clone();
+                s.spawn(move || {
+                    for i in 0..100 {
+                        sender.send(t * 100 + i).unwrap();
+                    }
+                });
+            }
+            drop(sender);
+            receiver.iter().collect::<Vec<u64>>()
+        });
+        let mut sorted = received.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..300).collect::<Vec<_>>());
+        // Values from each sender arrive in order.
+        for t in 0..3 {
+            let from = received.iter().filter(|&&x| x / 100 == t);
+            assert!(from.clone().zip(from.skip(1)).all(|(a, b)| a < b));
+        }
+    }
+
+    #[test]
+    fn channel_receiver_not_sync() {
+        // Resolving `f` is ambiguous when both impls apply, so this only compiles if `Receiver`
+        // is not `Sync`.
+        trait NotSync<A> {
+            fn f() {}
+        }
+        impl<T: ?Sized> NotSync<()> for T {}
+        impl<T: ?Sized + Sync> NotSync<u8> for T {}
+        <Receiver<u8> as NotSync<_>>::f();
+
+        fn send<T: Send>() {}
+        send::<Receiver<u8>>();
+    }
+}
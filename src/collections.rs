@@ -0,0 +1,2108 @@
+//! [`Box`] and [`Vec`] like types allocated within a [`crate::linked_list::Allocator`].
+//!
+//! Unlike [`OwnedValue`] and [`OwnedSlice`] these drop their contents, so they can stand in for
+//! the std types behind a type alias.
+//!
+//! [`Log`] is an append only journal of byte records held in a fixed size buffer, [`SlotMap`] a
+//! map of [`crate::slab::Allocator`] slots addressed by versioned keys, [`HashMap`] a map of
+//! entries in slab slots found through a hash index and [`Deque`] a fixed capacity ring of values.
+
+use std::borrow::{Borrow, BorrowMut};
+use std::fmt;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash};
+use std::mem::{align_of, ManuallyDrop, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Block, OwnedSlice, OwnedValue};
+use crate::Index;
+
+/// A [`Box`] allocated within a [`crate::linked_list::Allocator`].
+pub struct ABox<T, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    value: OwnedValue<T, A, I>,
+}
+
+impl<T, A, I: Index> ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Allocates `x` within `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    pub fn new_in(x: T, allocator: A) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ABox::new_in");
+
+        Self::try_new_in(x, allocator).expect("memory allocation failed")
+    }
+
+    /// Allocates `x` within `allocator`, returning `None` when out of memory or when `T` requires
+    /// a greater alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn try_new_in(x: T, allocator: A) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("ABox::try_new_in");
+
+        if align_of::<T>() > align_of::<Block<I>>() {
+            return None;
+        }
+        let mut value = OwnedValue::<T, A, I>::allocate(allocator)?;
+        unsafe {
+            std::ptr::write(&mut *value, x);
+        }
+        Some(Self { value })
+    }
+
+    /// Moves a heap allocated [`Box`] into `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    #[allow(clippy::boxed_local, clippy::needless_pass_by_value)]
+    #[cfg(feature = "std")]
+    pub fn from_box_in(x: Box<T>, allocator: A) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ABox::from_box_in");
+
+        Self::new_in(*x, allocator)
+    }
+
+    /// Consumes the box, returning the wrapped value.
+    pub fn into_inner(b: Self) -> T {
+        #[cfg(feature = "log")]
+        trace!("ABox::into_inner");
+
+        let b = std::mem::ManuallyDrop::new(b);
+        unsafe {
+            let x = std::ptr::read(&*b.value);
+            // Frees the allocation without dropping `x`.
+            drop(std::ptr::read(&b.value));
+            x
+        }
+    }
+
+    /// Moves the value onto the heap.
+    ///
+    /// `From<ABox<T, A>> for Box<T>` cannot be implemented due to the orphan rule.
+    #[must_use]
+    #[cfg(feature = "std")]
+    pub fn into_box(b: Self) -> Box<T> {
+        #[cfg(feature = "log")]
+        trace!("ABox::into_box");
+
+        Box::new(Self::into_inner(b))
+    }
+
+    #[must_use]
+    pub fn allocator(b: &Self) -> &A {
+        #[cfg(feature = "log")]
+        trace!("ABox::allocator");
+
+        b.value.wrapper().handle()
+    }
+}
+
+impl<T, A, I: Index> Drop for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("ABox::drop");
+
+        unsafe {
+            std::ptr::drop_in_place(&mut *self.value);
+        }
+    }
+}
+
+impl<T, A, I: Index> Deref for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+impl<T, A, I: Index> DerefMut for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T, A, I: Index> AsRef<T> for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+impl<T, A, I: Index> AsMut<T> for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+impl<T, A, I: Index> Borrow<T> for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+impl<T, A, I: Index> BorrowMut<T> for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T: Clone, A: Clone, I: Index> Clone for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn clone(&self) -> Self {
+        Self::new_in((**self).clone(), Self::allocator(self).clone())
+    }
+}
+
+impl<T: PartialEq, A, I: Index> PartialEq for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+impl<T: Eq, A, I: Index> Eq for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+
+impl<T: fmt::Debug, A, I: Index> fmt::Debug for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+impl<T: fmt::Display, A, I: Index> fmt::Display for ABox<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// A [`Vec`] allocated within a [`crate::linked_list::Allocator`].
+///
+/// Growing requires the allocator handle `A` to be [`Clone`], e.g. `&ArrayAllocator<N>` or
+/// `Arc<ArrayAllocator<N>>`.
+pub struct AVec<T, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    buf: OwnedSlice<MaybeUninit<T>, A, I>,
+    len: usize,
+}
+
+impl<T, A, I: Index> AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Constructs an empty vector within `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// When `T` requires a greater alignment than a block or when locking the mutex fails.
+    pub fn new_in(allocator: A) -> Self {
+        #[cfg(feature = "log")]
+        trace!("AVec::new_in");
+
+        Self::with_capacity_in(0, allocator)
+    }
+
+    /// Constructs an empty vector with space for at least `capacity` elements.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory, when `T` requires a greater alignment than a block or when locking the
+    /// mutex fails.
+    pub fn with_capacity_in(capacity: usize, allocator: A) -> Self {
+        #[cfg(feature = "log")]
+        trace!("AVec::with_capacity_in");
+
+        Self::try_with_capacity_in(capacity, allocator).expect("memory allocation failed")
+    }
+
+    /// Constructs an empty vector with space for at least `capacity` elements, returning `None`
+    /// when out of memory or when `T` requires a greater alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn try_with_capacity_in(capacity: usize, allocator: A) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("AVec::try_with_capacity_in");
+
+        if align_of::<T>() > align_of::<Block<I>>() {
+            return None;
+        }
+        let buf = OwnedSlice::allocate(allocator, capacity)?;
+        Some(Self { buf, len: 0 })
+    }
+
+    #[must_use]
+    pub fn allocator(&self) -> &A {
+        #[cfg(feature = "log")]
+        trace!("AVec::allocator");
+
+        self.buf.wrapper().handle()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("AVec::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("AVec::is_empty");
+
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("AVec::capacity");
+
+        self.buf.len()
+    }
+
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self
+    }
+
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+
+    /// Moves the elements onto the heap.
+    ///
+    /// `From<AVec<T, A>> for Vec<T>` cannot be implemented due to the orphan rule.
+    #[must_use]
+    #[cfg(feature = "std")]
+    pub fn into_vec(mut self) -> Vec<T> {
+        #[cfg(feature = "log")]
+        trace!("AVec::into_vec");
+
+        let mut vec = Vec::with_capacity(self.len);
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.as_ptr(), vec.as_mut_ptr(), self.len);
+            vec.set_len(self.len);
+            // The elements have been moved out.
+            self.len = 0;
+        }
+        vec
+    }
+
+    /// Removes the last element and returns it, or `None` if empty.
+    pub fn pop(&mut self) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("AVec::pop");
+
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.buf[self.len].assume_init_read() })
+    }
+
+    /// Shortens the vector to `len` elements, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        #[cfg(feature = "log")]
+        trace!("AVec::truncate");
+
+        if len >= self.len {
+            return;
+        }
+        let tail: *mut [T] = &mut self[len..];
+        self.len = len;
+        unsafe {
+            std::ptr::drop_in_place(tail);
+        }
+    }
+
+    /// Removes all elements.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("AVec::clear");
+
+        self.truncate(0);
+    }
+
+    /// Removes the element at `index`, replacing it with the last element.
+    ///
+    /// # Panics
+    ///
+    /// When `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        #[cfg(feature = "log")]
+        trace!("AVec::swap_remove");
+
+        let len = self.len;
+        assert!(
+            index < len,
+            "swap_remove index (is {index}) should be < len (is {len})"
+        );
+        self.swap(index, len - 1);
+        self.pop().unwrap()
+    }
+
+    /// Removes the element at `index`, shifting all elements after it to the left.
+    ///
+    /// # Panics
+    ///
+    /// When `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        #[cfg(feature = "log")]
+        trace!("AVec::remove");
+
+        let len = self.len;
+        assert!(
+            index < len,
+            "removal index (is {index}) should be < len (is {len})"
+        );
+        unsafe {
+            let ptr = self.buf.as_mut_ptr().add(index);
+            let x = ptr.read().assume_init();
+            std::ptr::copy(ptr.add(1), ptr, len - index - 1);
+            self.len -= 1;
+            x
+        }
+    }
+}
+
+impl<T, A: Clone, I: Index> AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Copies a heap allocated [`Vec`] into `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory, when `T` requires a greater alignment than a block or when locking the
+    /// mutex fails.
+    #[cfg(feature = "std")]
+    pub fn from_vec_in(x: Vec<T>, allocator: A) -> Self {
+        #[cfg(feature = "log")]
+        trace!("AVec::from_vec_in");
+
+        let mut vec = Self::with_capacity_in(x.len(), allocator);
+        vec.extend(x);
+        vec
+    }
+
+    /// Reserves space for at least `additional` more elements, returning `None` when out of
+    /// memory.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn try_reserve(&mut self, additional: usize) -> Option<()> {
+        #[cfg(feature = "log")]
+        trace!("AVec::try_reserve");
+
+        let required = self.len.checked_add(additional)?;
+        if required <= self.capacity() {
+            return Some(());
+        }
+        let capacity = std::cmp::max(required, self.capacity() * 2);
+        self.buf.resize(capacity)
+    }
+
+    /// Reserves space for at least `additional` more elements.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    pub fn reserve(&mut self, additional: usize) {
+        #[cfg(feature = "log")]
+        trace!("AVec::reserve");
+
+        self.try_reserve(additional)
+            .expect("memory allocation failed");
+    }
+
+    /// Shrinks the capacity to the length.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn shrink_to_fit(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("AVec::shrink_to_fit");
+
+        // Shrinking never needs more blocks than the current allocation, but the new allocation
+        // is made before the old is freed so it may still fail, in which case nothing changes.
+        let _ = self.buf.resize(self.len);
+    }
+
+    /// Appends an element.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    pub fn push(&mut self, x: T) {
+        #[cfg(feature = "log")]
+        trace!("AVec::push");
+
+        self.reserve(1);
+        self.buf[self.len].write(x);
+        self.len += 1;
+    }
+
+    /// Inserts an element at `index`, shifting all elements after it to the right.
+    ///
+    /// # Panics
+    ///
+    /// When `index > len`, when out of memory or when locking the mutex fails.
+    pub fn insert(&mut self, index: usize, x: T) {
+        #[cfg(feature = "log")]
+        trace!("AVec::insert");
+
+        let len = self.len;
+        assert!(
+            index <= len,
+            "insertion index (is {index}) should be <= len (is {len})"
+        );
+        self.reserve(1);
+        unsafe {
+            let ptr = self.buf.as_mut_ptr().add(index);
+            std::ptr::copy(ptr, ptr.add(1), len - index);
+            (*ptr).write(x);
+        }
+        self.len += 1;
+    }
+
+    /// Clones and appends all elements of `other`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        #[cfg(feature = "log")]
+        trace!("AVec::extend_from_slice");
+
+        self.extend(other.iter().cloned());
+    }
+}
+
+impl<T, A, I: Index> Drop for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("AVec::drop");
+
+        self.clear();
+    }
+}
+
+impl<T, A, I: Index> Deref for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*std::ptr::from_raw_parts(self.buf.as_ptr().cast::<T>(), self.len) }
+    }
+}
+impl<T, A, I: Index> DerefMut for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *std::ptr::from_raw_parts_mut(self.buf.as_mut_ptr().cast::<T>(), self.len) }
+    }
+}
+
+impl<T, A, I: Index> AsRef<[T]> for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+impl<T, A, I: Index> AsMut<[T]> for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+impl<T, A, I: Index> Borrow<[T]> for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Clone, I: Index> Extend<T> for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl<T, A, I: Index> IntoIterator for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, A, I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        #[cfg(feature = "log")]
+        trace!("AVec::into_iter");
+
+        let this = ManuallyDrop::new(self);
+        // `this` is not dropped so ownership of the buffer moves to the iterator.
+        let buf = unsafe { std::ptr::read(&this.buf) };
+        IntoIter {
+            buf,
+            start: 0,
+            end: this.len,
+        }
+    }
+}
+impl<'b, T, A, I: Index> IntoIterator for &'b AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Item = &'b T;
+    type IntoIter = std::slice::Iter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+impl<'b, T, A, I: Index> IntoIterator for &'b mut AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Item = &'b mut T;
+    type IntoIter = std::slice::IterMut<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator moving the elements out of an [`AVec`], its blocks are freed when it is dropped.
+pub struct IntoIter<T, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    buf: OwnedSlice<MaybeUninit<T>, A, I>,
+    /// The elements in `start..end` have not been moved out.
+    start: usize,
+    end: usize,
+}
+
+impl<T, A, I: Index> IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Returns the remaining elements.
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe {
+            &*std::ptr::from_raw_parts(
+                self.buf.as_ptr().add(self.start).cast::<T>(),
+                self.end - self.start,
+            )
+        }
+    }
+
+    /// Returns the remaining elements.
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe {
+            &mut *std::ptr::from_raw_parts_mut(
+                self.buf.as_mut_ptr().add(self.start).cast::<T>(),
+                self.end - self.start,
+            )
+        }
+    }
+}
+
+impl<T, A, I: Index> Iterator for IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.start += 1;
+        Some(unsafe { self.buf[self.start - 1].assume_init_read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+impl<T, A, I: Index> DoubleEndedIterator for IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { self.buf[self.end].assume_init_read() })
+    }
+}
+impl<T, A, I: Index> ExactSizeIterator for IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+impl<T, A, I: Index> std::iter::FusedIterator for IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+
+impl<T, A, I: Index> Drop for IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("IntoIter::drop");
+
+        unsafe { std::ptr::drop_in_place(self.as_mut_slice()) };
+    }
+}
+
+impl<T: fmt::Debug, A, I: Index> fmt::Debug for IntoIter<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
+
+impl<T: Clone, A: Clone, I: Index> Clone for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn clone(&self) -> Self {
+        let mut vec = Self::with_capacity_in(self.len, self.allocator().clone());
+        vec.extend_from_slice(self);
+        vec
+    }
+}
+
+impl<T: PartialEq, A, I: Index> PartialEq for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+impl<T: Eq, A, I: Index> Eq for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+
+impl<T: fmt::Debug, A, I: Index> fmt::Debug for AVec<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The sequence number of a [`Log`] record, numbered from 0 in the order they were appended.
+pub type SeqNo = u64;
+
+/// What a [`Log`] does when appending a record it has no space for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Retention {
+    /// Discards the oldest records until there is space.
+    #[default]
+    Overwrite,
+    /// Rejects the record with [`LogError::Full`].
+    Reject,
+}
+
+/// The error returned by [`Log`] operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogError {
+    /// The record is larger than the log.
+    TooLarge,
+    /// There is no space for the record and the log rejects records when full.
+    Full,
+    /// The requested record was discarded, `oldest` is the oldest record still held.
+    Truncated { oldest: SeqNo },
+    /// The checksum of the record does not match its contents.
+    Corrupt { seq: SeqNo },
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => write!(f, "record is larger than the log"),
+            Self::Full => write!(f, "log is full"),
+            Self::Truncated { oldest } => {
+                write!(f, "record was discarded, the oldest record is {oldest}")
+            }
+            Self::Corrupt { seq } => write!(f, "checksum of record {seq} does not match"),
+        }
+    }
+}
+
+impl std::error::Error for LogError {}
+
+/// A record read from a [`Log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'b> {
+    pub seq: SeqNo,
+    pub data: &'b [u8],
+}
+
+/// An append only log of byte records within a [`crate::linked_list::Allocator`].
+///
+/// Records are written to a circular buffer, each preceded by a header holding its length,
+/// checksum and sequence number. A record which does not fit before the end of the buffer is
+/// written at its start, the bytes skipped are marked as padding.
+pub struct Log<A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    buf: OwnedSlice<u8, A, I>,
+    retention: Retention,
+    checksums: bool,
+    /// The offset of the oldest record.
+    start: usize,
+    /// The offset the next record is written at.
+    end: usize,
+    /// The bytes held by records and padding.
+    used: usize,
+    /// The number of records held.
+    records: usize,
+    /// The sequence number of the oldest record.
+    first: SeqNo,
+}
+
+impl<A, I: Index> Log<A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// The length of a record header: length, checksum and sequence number.
+    const HEADER: usize = 16;
+    /// The length stored in a header marking padding.
+    const PADDING: u32 = u32::MAX;
+
+    /// Constructs an empty log of `capacity` bytes within `allocator`, returning `None` when out of
+    /// memory.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn try_with_capacity_in(
+        capacity: usize,
+        retention: Retention,
+        allocator: A,
+    ) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("Log::try_with_capacity_in");
+
+        let buf = OwnedSlice::allocate(allocator, capacity)?;
+        Some(Self {
+            buf,
+            retention,
+            checksums: false,
+            start: 0,
+            end: 0,
+            used: 0,
+            records: 0,
+            first: 0,
+        })
+    }
+
+    /// Stores a CRC-32 checksum with each record appended, verified when it is read.
+    #[must_use]
+    pub fn with_checksums(mut self) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Log::with_checksums");
+
+        self.checksums = true;
+        self
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Log::len");
+
+        self.records
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Log::is_empty");
+
+        self.records == 0
+    }
+
+    /// The sequence number of the oldest record held, or of the next record appended when empty.
+    #[must_use]
+    pub fn first_seq(&self) -> SeqNo {
+        #[cfg(feature = "log")]
+        trace!("Log::first_seq");
+
+        self.first
+    }
+
+    /// The sequence number of the next record appended.
+    #[must_use]
+    pub fn next_seq(&self) -> SeqNo {
+        #[cfg(feature = "log")]
+        trace!("Log::next_seq");
+
+        self.first + self.records as SeqNo
+    }
+
+    /// Appends a record, returning its sequence number.
+    ///
+    /// # Errors
+    ///
+    /// When the record is larger than the log, or when there is no space for it and the log
+    /// rejects records when full.
+    pub fn append(&mut self, data: &[u8]) -> Result<SeqNo, LogError> {
+        #[cfg(feature = "log")]
+        trace!("Log::append");
+
+        let capacity = self.buf.len();
+        let need = Self::HEADER + data.len();
+        if need > capacity || u32::try_from(data.len()).map_or(true, |len| len == Self::PADDING) {
+            return Err(LogError::TooLarge);
+        }
+        let (pos, pad) = loop {
+            if self.records == 0 {
+                (self.start, self.end, self.used) = (0, 0, 0);
+            }
+            let (pos, pad) = if capacity - self.end >= need {
+                (self.end, 0)
+            } else {
+                (0, capacity - self.end)
+            };
+            if self.used + pad + need <= capacity {
+                break (pos, pad);
+            }
+            match self.retention {
+                Retention::Overwrite => self.discard(),
+                Retention::Reject => return Err(LogError::Full),
+            }
+        };
+
+        if pad >= Self::HEADER {
+            self.buf[self.end..self.end + 4].copy_from_slice(&Self::PADDING.to_le_bytes());
+        }
+        let seq = self.next_seq();
+        let checksum = if self.checksums { crc32(data) } else { 0 };
+        let header = &mut self.buf[pos..pos + Self::HEADER];
+        header[..4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        header[4..8].copy_from_slice(&checksum.to_le_bytes());
+        header[8..].copy_from_slice(&seq.to_le_bytes());
+        self.buf[pos + Self::HEADER..pos + need].copy_from_slice(data);
+
+        self.end = pos + need;
+        self.used += pad + need;
+        self.records += 1;
+        Ok(seq)
+    }
+
+    /// Returns an iterator over the records from `seq` onwards.
+    ///
+    /// # Errors
+    ///
+    /// When the record `seq` was discarded.
+    pub fn read_from(&self, seq: SeqNo) -> Result<LogIter<'_, A, I>, LogError> {
+        #[cfg(feature = "log")]
+        trace!("Log::read_from");
+
+        if seq < self.first {
+            return Err(LogError::Truncated { oldest: self.first });
+        }
+        let mut iter = LogIter {
+            log: self,
+            offset: self.start,
+            seq: self.first,
+        };
+        while iter.seq < seq.min(self.next_seq()) {
+            let (_, len, _) = iter.header();
+            iter.offset += Self::HEADER + len;
+            iter.seq += 1;
+        }
+        Ok(iter)
+    }
+
+    /// Discards the oldest record.
+    fn discard(&mut self) {
+        let offset = self.skip_padding(self.start);
+        let (_, len, _) = self.header(offset);
+        self.used -= offset
+            .checked_sub(self.start)
+            .unwrap_or(self.buf.len() - self.start);
+        self.used -= Self::HEADER + len;
+        self.start = offset + Self::HEADER + len;
+        self.records -= 1;
+        self.first += 1;
+    }
+
+    /// Returns the offset of the record at `offset`, skipping the padding at the end of the buffer.
+    fn skip_padding(&self, offset: usize) -> usize {
+        let padding = self.buf.len() - offset < Self::HEADER
+            || self.buf[offset..offset + 4] == Self::PADDING.to_le_bytes();
+        if padding {
+            0
+        } else {
+            offset
+        }
+    }
+
+    /// Reads the length, checksum and sequence number of the record at `offset`.
+    fn header(&self, offset: usize) -> (SeqNo, usize, u32) {
+        let header = &self.buf[offset..offset + Self::HEADER];
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let seq = SeqNo::from_le_bytes(header[8..].try_into().unwrap());
+        (seq, len as usize, checksum)
+    }
+}
+
+impl<A, I: Index> fmt::Debug for Log<A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Log")
+            .field("capacity", &self.buf.len())
+            .field("retention", &self.retention)
+            .field("checksums", &self.checksums)
+            .field("first_seq", &self.first)
+            .field("len", &self.records)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the records of a [`Log`], see [`Log::read_from`].
+pub struct LogIter<'b, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    log: &'b Log<A, I>,
+    offset: usize,
+    seq: SeqNo,
+}
+
+impl<'b, A, I: Index> LogIter<'b, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Reads the header of the next record, moving past any padding before it.
+    fn header(&mut self) -> (SeqNo, usize, u32) {
+        self.offset = self.log.skip_padding(self.offset);
+        self.log.header(self.offset)
+    }
+}
+
+impl<'b, A, I: Index> Iterator for LogIter<'b, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Item = Result<Record<'b>, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        #[cfg(feature = "log")]
+        trace!("LogIter::next");
+
+        if self.seq >= self.log.next_seq() {
+            return None;
+        }
+        let (seq, len, checksum) = self.header();
+        let start = self.offset + Log::<A, I>::HEADER;
+        let data = &self.log.buf[start..start + len];
+        self.offset = start + len;
+        self.seq += 1;
+        if seq != self.seq - 1 || (self.log.checksums && crc32(data) != checksum) {
+            return Some(Err(LogError::Corrupt { seq: self.seq - 1 }));
+        }
+        Some(Ok(Record { seq, data }))
+    }
+}
+
+/// The CRC-32 (IEEE) checksum of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(u32::MAX, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+/// A key of a [`SlotMap`], pairing a slot with the generation of the value it was returned for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Key {
+    pub index: usize,
+    pub generation: u32,
+}
+
+/// A map of values held in the slots of a [`crate::slab::Allocator`], addressed by [`Key`]s which
+/// never dangle.
+///
+/// Each slot has a generation counter, odd while the slot holds a value of the map and even
+/// otherwise, incremented on every insert and remove. A key only matches the slot while the
+/// generation is that it was returned with, so a key of a removed value never addresses a value
+/// later inserted in the same slot. The counters are allocated within a
+/// [`crate::linked_list::Allocator`], so like the values they can be held in shared memory.
+pub struct SlotMap<'a, T, I: Index = usize> {
+    slab: &'a crate::slab::Allocator<T, I>,
+    generations: crate::linked_list::Slice<'a, u32, I>,
+    len: usize,
+}
+
+impl<'a, T, I: Index> SlotMap<'a, T, I> {
+    /// Constructs an empty map of values within `slab`, allocating a generation counter for each
+    /// of its slots within `allocator`.
+    ///
+    /// Returns `None` when `allocator` has no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking either mutex fails.
+    pub fn new(
+        slab: &'a crate::slab::Allocator<T, I>,
+        allocator: &'a Allocator<I>,
+    ) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::new");
+
+        let generations = allocator.allocate_slice_default::<u32>(slab.stats().total)?;
+        Some(Self {
+            slab,
+            generations,
+            len: 0,
+        })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::is_empty");
+
+        self.len == 0
+    }
+
+    /// Inserts `value`, returning its key or `None` when the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn insert(&mut self, value: T) -> Option<Key> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::insert");
+
+        let index = self.slab.allocate(value)?.into_index();
+        let generation = &mut self.generations[index];
+        *generation = generation.wrapping_add(1);
+        self.len += 1;
+        Some(Key {
+            index,
+            generation: *generation,
+        })
+    }
+
+    #[must_use]
+    pub fn contains_key(&self, key: Key) -> bool {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::contains_key");
+
+        key.generation % 2 == 1 && self.generations.get(key.index) == Some(&key.generation)
+    }
+
+    #[must_use]
+    pub fn get(&self, key: Key) -> Option<&T> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::get");
+
+        self.contains_key(key)
+            .then(|| unsafe { &*self.slot(key.index) })
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::get_mut");
+
+        self.contains_key(key)
+            .then(|| unsafe { &mut *self.slot(key.index) })
+    }
+
+    /// Removes the value of `key`, freeing its slot.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::remove");
+
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.generations[key.index] = key.generation.wrapping_add(1);
+        self.len -= 1;
+        Some(unsafe { crate::slab::Wrapper::from_index(self.slab, key.index) }.into_inner())
+    }
+
+    /// Returns an iterator over the keys and values in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::iter");
+
+        self.keys()
+            .map(|key| (key, unsafe { &*self.slot(key.index) }))
+    }
+
+    /// Returns an iterator over the keys and mutable values in slot order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::iter_mut");
+
+        let this = &*self;
+        // Each key addresses a different slot.
+        this.keys()
+            .map(|key| (key, unsafe { &mut *this.slot(key.index) }))
+    }
+
+    /// Returns an iterator over the keys in slot order.
+    pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::keys");
+
+        self.generations
+            .iter()
+            .enumerate()
+            .filter(|(_, &generation)| generation % 2 == 1)
+            .map(|(index, &generation)| Key { index, generation })
+    }
+
+    /// Returns a pointer to the value in the slot at `index`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold a value of the map.
+    unsafe fn slot(&self, index: usize) -> *mut T {
+        let mut wrapper = ManuallyDrop::new(crate::slab::Wrapper::from_index(self.slab, index));
+        std::ptr::addr_of_mut!(**wrapper)
+    }
+}
+
+impl<'a, T, I: Index> Drop for SlotMap<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("SlotMap::drop");
+
+        for key in self.keys() {
+            drop(unsafe { crate::slab::Wrapper::from_index(self.slab, key.index) });
+        }
+    }
+}
+
+impl<'a, T: fmt::Debug, I: Index> fmt::Debug for SlotMap<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// A bucket of a [`HashMap`] index which has never held an entry.
+#[cfg(feature = "std")]
+const EMPTY: usize = 0;
+/// A bucket of a [`HashMap`] index whose entry was removed, which lookups probe past.
+#[cfg(feature = "std")]
+const TOMBSTONE: usize = usize::MAX;
+
+/// A hash map whose entries are held in the slots of a [`crate::slab::Allocator`] and found
+/// through an open addressing index allocated within a [`crate::linked_list::Allocator`], so the
+/// whole map can live in shared memory.
+///
+/// The index has at least twice as many buckets as the slab has slots and is probed linearly. The
+/// default hasher has fixed keys, so processes built with the same compiler agree on where entries
+/// are, but it offers no protection against keys chosen to collide.
+#[cfg(feature = "std")]
+pub struct HashMap<'a, K, V, I: Index = usize, S = BuildHasherDefault<DefaultHasher>> {
+    slab: &'a crate::slab::Allocator<(K, V), I>,
+    /// One plus the slot of the entry in each bucket, [`EMPTY`] or [`TOMBSTONE`].
+    index: crate::linked_list::Slice<'a, usize, I>,
+    len: usize,
+    hasher: S,
+}
+
+#[cfg(feature = "std")]
+impl<'a, K: Hash + Eq, V, I: Index, S: BuildHasher + Default> HashMap<'a, K, V, I, S> {
+    /// Constructs an empty map of entries within `slab`, allocating its index within `allocator`.
+    ///
+    /// Returns `None` when `allocator` has no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking either mutex fails.
+    pub fn new(
+        slab: &'a crate::slab::Allocator<(K, V), I>,
+        allocator: &'a Allocator<I>,
+    ) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("HashMap::new");
+
+        Self::with_hasher(slab, allocator, S::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K: Hash + Eq, V, I: Index, S: BuildHasher> HashMap<'a, K, V, I, S> {
+    /// Constructs an empty map of entries within `slab` hashed by `hasher`, allocating its index
+    /// within `allocator`.
+    ///
+    /// Returns `None` when `allocator` has no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking either mutex fails.
+    pub fn with_hasher(
+        slab: &'a crate::slab::Allocator<(K, V), I>,
+        allocator: &'a Allocator<I>,
+        hasher: S,
+    ) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("HashMap::with_hasher");
+
+        let buckets = (2 * slab.stats().total).next_power_of_two();
+        let index = allocator.allocate_slice_from_iter(std::iter::repeat_n(EMPTY, buckets))?;
+        Some(Self {
+            slab,
+            index,
+            len: 0,
+            hasher,
+        })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("HashMap::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("HashMap::is_empty");
+
+        self.len == 0
+    }
+
+    /// Inserts `value` for `key`, returning the value it replaces.
+    ///
+    /// # Errors
+    ///
+    /// When the key is absent and the slab has no free slots, returning the entry.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        #[cfg(feature = "log")]
+        trace!("HashMap::insert");
+
+        let bucket = match self.find(&key) {
+            Ok(bucket) => {
+                let entry = unsafe { &mut *self.entry(self.index[bucket] - 1) };
+                return Ok(Some(std::mem::replace(&mut entry.1, value)));
+            }
+            Err(Some(bucket)) => bucket,
+            Err(None) => return Err((key, value)),
+        };
+        let Some(mut run) = self.slab.reserve_run(1) else {
+            return Err((key, value));
+        };
+        let slot = run.push((key, value))?.into_index();
+        self.index[bucket] = slot + 1;
+        self.len += 1;
+        Ok(None)
+    }
+
+    #[must_use]
+    pub fn contains_key<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("HashMap::contains_key");
+
+        self.find(key).is_ok()
+    }
+
+    #[must_use]
+    pub fn get<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("HashMap::get");
+
+        let bucket = self.find(key).ok()?;
+        Some(unsafe { &(*self.entry(self.index[bucket] - 1)).1 })
+    }
+
+    pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("HashMap::get_mut");
+
+        let bucket = self.find(key).ok()?;
+        Some(unsafe { &mut (*self.entry(self.index[bucket] - 1)).1 })
+    }
+
+    /// Removes the entry of `key`, freeing its slot, and returns its value.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "log")]
+        trace!("HashMap::remove");
+
+        let bucket = self.find(key).ok()?;
+        let slot = std::mem::replace(&mut self.index[bucket], TOMBSTONE) - 1;
+        self.len -= 1;
+        let (_, value) = unsafe { crate::slab::Wrapper::from_index(self.slab, slot) }.into_inner();
+        Some(value)
+    }
+
+    /// Returns an iterator over the entries in index order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        #[cfg(feature = "log")]
+        trace!("HashMap::iter");
+
+        self.slots().map(|slot| {
+            let entry = unsafe { &*self.entry(slot) };
+            (&entry.0, &entry.1)
+        })
+    }
+
+    /// Returns the bucket holding `key`, or else the first bucket it could be inserted in.
+    fn find<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Result<usize, Option<usize>>
+    where
+        K: Borrow<Q>,
+    {
+        let mask = self.index.len() - 1;
+        let mut bucket = self.hasher.hash_one(key) as usize & mask;
+        let mut free = None;
+        for _ in 0..self.index.len() {
+            match self.index[bucket] {
+                EMPTY => return Err(free.or(Some(bucket))),
+                TOMBSTONE => {
+                    free.get_or_insert(bucket);
+                }
+                link => {
+                    if unsafe { (*self.entry(link - 1)).0.borrow() } == key {
+                        return Ok(bucket);
+                    }
+                }
+            }
+            bucket = (bucket + 1) & mask;
+        }
+        Err(free)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V, I: Index, S> HashMap<'a, K, V, I, S> {
+    /// Returns the slots holding entries.
+    fn slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.index
+            .iter()
+            .filter(|&&link| link != EMPTY && link != TOMBSTONE)
+            .map(|&link| link - 1)
+    }
+
+    /// Returns a pointer to the entry in the slot at `slot`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold an entry of the map.
+    unsafe fn entry(&self, slot: usize) -> *mut (K, V) {
+        let mut wrapper = ManuallyDrop::new(crate::slab::Wrapper::from_index(self.slab, slot));
+        std::ptr::addr_of_mut!(**wrapper)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V, I: Index, S> Drop for HashMap<'a, K, V, I, S> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("HashMap::drop");
+
+        for slot in self.slots() {
+            drop(unsafe { crate::slab::Wrapper::from_index(self.slab, slot) });
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K: fmt::Debug, V: fmt::Debug, I: Index, S> fmt::Debug for HashMap<'a, K, V, I, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.slots().map(|slot| {
+                let entry = unsafe { &*self.entry(slot) };
+                (&entry.0, &entry.1)
+            }))
+            .finish()
+    }
+}
+
+/// A double-ended queue of at most a fixed number of values in a ring allocated within a
+/// [`crate::linked_list::Allocator`], so pushing and popping at either end never moves values,
+/// e.g. for work queues.
+pub struct Deque<'a, T, I: Index = usize> {
+    buf: crate::linked_list::Slice<'a, MaybeUninit<T>, I>,
+    /// The position of the front value in `buf`.
+    head: usize,
+    len: usize,
+}
+
+impl<'a, T, I: Index> Deque<'a, T, I> {
+    /// Constructs an empty queue with space for `capacity` values within `allocator`.
+    ///
+    /// Returns `None` when out of memory or when `T` requires a greater alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn new(allocator: &'a Allocator<I>, capacity: usize) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("Deque::new");
+
+        if align_of::<T>() > align_of::<Block<I>>() {
+            return None;
+        }
+        Some(Self {
+            buf: allocator.allocate_slice_uninit(capacity)?,
+            head: 0,
+            len: 0,
+        })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Deque::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Deque::is_empty");
+
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Deque::is_full");
+
+        self.len == self.capacity()
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Deque::capacity");
+
+        self.buf.len()
+    }
+
+    /// Returns the position in the ring of the `i`th value from the front.
+    fn position(&self, i: usize) -> usize {
+        let position = self.head + i;
+        if position >= self.capacity() {
+            position - self.capacity()
+        } else {
+            position
+        }
+    }
+
+    /// Appends `x` at the back, or returns it when full.
+    ///
+    /// # Errors
+    ///
+    /// When the queue is full.
+    pub fn push_back(&mut self, x: T) -> Result<(), T> {
+        #[cfg(feature = "log")]
+        trace!("Deque::push_back");
+
+        if self.is_full() {
+            return Err(x);
+        }
+        let position = self.position(self.len);
+        self.buf[position].write(x);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Prepends `x` at the front, or returns it when full.
+    ///
+    /// # Errors
+    ///
+    /// When the queue is full.
+    pub fn push_front(&mut self, x: T) -> Result<(), T> {
+        #[cfg(feature = "log")]
+        trace!("Deque::push_front");
+
+        if self.is_full() {
+            return Err(x);
+        }
+        self.head = self.position(self.capacity() - 1);
+        self.buf[self.head].write(x);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the front value and returns it, or `None` if empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("Deque::pop_front");
+
+        if self.len == 0 {
+            return None;
+        }
+        let x = unsafe { self.buf[self.head].assume_init_read() };
+        self.head = self.position(1);
+        self.len -= 1;
+        Some(x)
+    }
+
+    /// Removes the back value and returns it, or `None` if empty.
+    pub fn pop_back(&mut self) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("Deque::pop_back");
+
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let position = self.position(self.len);
+        Some(unsafe { self.buf[position].assume_init_read() })
+    }
+
+    /// Returns the `i`th value from the front.
+    #[must_use]
+    pub fn get(&self, i: usize) -> Option<&T> {
+        #[cfg(feature = "log")]
+        trace!("Deque::get");
+
+        (i < self.len).then(|| unsafe { self.buf[self.position(i)].assume_init_ref() })
+    }
+
+    /// Returns the `i`th value from the front.
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        #[cfg(feature = "log")]
+        trace!("Deque::get_mut");
+
+        let position = self.position(i);
+        (i < self.len).then(|| unsafe { self.buf[position].assume_init_mut() })
+    }
+
+    #[must_use]
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    #[must_use]
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len.checked_sub(1)?)
+    }
+
+    /// Returns an iterator over the values from front to back.
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        #[cfg(feature = "log")]
+        trace!("Deque::iter");
+
+        (0..self.len).map(|i| unsafe { self.buf[self.position(i)].assume_init_ref() })
+    }
+
+    /// Removes all values.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Deque::clear");
+
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<'a, T, I: Index> Drop for Deque<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Deque::drop");
+
+        self.clear();
+    }
+}
+
+impl<'a, T: fmt::Debug, I: Index> fmt::Debug for Deque<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn abox() {
+        let allocator = ArrayAllocator::<2>::new(None);
+        let mut b = ABox::new_in(3u32, &*allocator);
+        *b += 1;
+        assert_eq!(*b, 4);
+        assert_eq!(format!("{b:?} {b}"), "4 4");
+        let c = b.clone();
+        assert_eq!(c, b);
+        assert!(ABox::try_new_in(1u8, &*allocator).is_none());
+        drop(c);
+        assert_eq!(ABox::into_inner(b), 4);
+        assert_eq!(allocator.stats().free, 2);
+    }
+
+    #[test]
+    fn abox_conversions() {
+        let allocator = ArrayAllocator::<2>::new(None);
+        let b = ABox::from_box_in(Box::new(String::from("a")), &*allocator);
+        let b: Box<String> = ABox::into_box(b);
+        assert_eq!(*b, "a");
+        assert_eq!(allocator.stats().free, 2);
+    }
+
+    #[test]
+    fn abox_drop() {
+        let allocator = ArrayAllocator::<2>::new(None);
+        let rc = Rc::new(());
+        let b = ABox::new_in(rc.clone(), &*allocator);
+        assert_eq!(Rc::strong_count(&rc), 2);
+        drop(b);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn avec() {
+        let allocator = ArrayAllocator::<16>::new(None);
+        let mut v = AVec::new_in(&*allocator);
+        for i in 0..10u32 {
+            v.push(i);
+        }
+        assert_eq!(v.as_slice(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(v.pop(), Some(9));
+        assert_eq!(v.remove(0), 0);
+        assert_eq!(v.swap_remove(0), 1);
+        v.insert(0, 10);
+        assert_eq!(v.as_slice(), &[10, 8, 2, 3, 4, 5, 6, 7]);
+        v.truncate(2);
+        v.extend_from_slice(&[1, 2]);
+        assert_eq!(v.clone(), v);
+        assert_eq!(format!("{v:?}"), "[10, 8, 1, 2]");
+        v.shrink_to_fit();
+        assert_eq!(v.capacity(), 4);
+        assert!(v.try_reserve(1000).is_none());
+        drop(v);
+        assert_eq!(allocator.stats().free, 16);
+    }
+
+    #[test]
+    fn avec_conversions() {
+        let allocator = ArrayAllocator::<4>::new(None);
+        let v = AVec::from_vec_in(vec![String::from("a"), String::from("b")], &*allocator);
+        let v: Vec<String> = v.into_vec();
+        assert_eq!(v, ["a", "b"]);
+        assert_eq!(allocator.stats().free, 4);
+    }
+
+    #[test]
+    fn avec_into_iter() {
+        let allocator = ArrayAllocator::<4>::new(None);
+        let mut v = AVec::new_in(&*allocator);
+        v.extend([1u32, 2, 3, 4]);
+        for x in &mut v {
+            *x *= 10;
+        }
+        assert_eq!((&v).into_iter().sum::<u32>(), 100);
+
+        let mut iter = v.into_iter();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next(), Some(10));
+        assert_eq!(iter.next_back(), Some(40));
+        assert_eq!(format!("{iter:?}"), "IntoIter([20, 30])");
+        assert_eq!(iter.collect::<Vec<_>>(), [20, 30]);
+        assert_eq!(allocator.stats().free, 4);
+    }
+
+    #[test]
+    fn avec_into_iter_drop() {
+        let allocator = ArrayAllocator::<4>::new(None);
+        let rc = Rc::new(());
+        let mut v = AVec::new_in(&*allocator);
+        v.extend([rc.clone(), rc.clone(), rc.clone()]);
+        let mut iter = v.into_iter();
+        drop(iter.next());
+        assert_eq!(Rc::strong_count(&rc), 3);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert_eq!(allocator.stats().free, 4);
+    }
+
+    #[test]
+    fn avec_drop() {
+        let allocator = ArrayAllocator::<4>::new(None);
+        let rc = Rc::new(());
+        let mut v = AVec::new_in(&*allocator);
+        v.push(rc.clone());
+        v.push(rc.clone());
+        assert_eq!(Rc::strong_count(&rc), 3);
+        drop(v);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    fn records<A, I: Index>(log: &Log<A, I>, seq: SeqNo) -> Vec<(SeqNo, Vec<u8>)>
+    where
+        A: Deref,
+        A::Target: AsRef<Allocator<I>>,
+    {
+        log.read_from(seq)
+            .unwrap()
+            .map(|record| record.map(|r| (r.seq, r.data.to_vec())).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn log() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let mut log = Log::try_with_capacity_in(64, Retention::Overwrite, &*allocator).unwrap();
+        assert!(log.is_empty());
+        assert_eq!(log.append(b"one"), Ok(0));
+        assert_eq!(log.append(b"two"), Ok(1));
+        assert_eq!(log.append(b"three"), Ok(2));
+        assert_eq!(log.len(), 3);
+        assert_eq!(
+            records(&log, 1),
+            [(1, b"two".to_vec()), (2, b"three".to_vec())]
+        );
+        assert_eq!(records(&log, 3), []);
+        assert_eq!(log.append(&[0; 49]), Err(LogError::TooLarge));
+    }
+
+    #[test]
+    fn log_wraparound() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let mut log = Log::try_with_capacity_in(64, Retention::Overwrite, &*allocator).unwrap();
+        // Each record takes 26 bytes, so only 2 fit and the third wraps to the start.
+        for i in 0..5u8 {
+            assert_eq!(log.append(&[i; 10]), Ok(u64::from(i)));
+        }
+        assert_eq!(log.first_seq(), 3);
+        assert_eq!(log.next_seq(), 5);
+        assert_eq!(records(&log, 3), [(3, vec![3; 10]), (4, vec![4; 10])]);
+        assert_eq!(
+            log.read_from(2).err(),
+            Some(LogError::Truncated { oldest: 3 })
+        );
+        // A record needing the whole log discards every other record.
+        assert_eq!(log.append(&[5; 48]), Ok(5));
+        assert_eq!(records(&log, log.first_seq()), [(5, vec![5; 48])]);
+    }
+
+    #[test]
+    fn log_reject() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let mut log = Log::try_with_capacity_in(64, Retention::Reject, &*allocator).unwrap();
+        assert_eq!(log.append(&[0; 10]), Ok(0));
+        assert_eq!(log.append(&[1; 10]), Ok(1));
+        assert_eq!(log.append(&[2; 10]), Err(LogError::Full));
+        assert_eq!(log.len(), 2);
+    }
+
+    #[test]
+    fn slot_map() {
+        let slab = crate::slab::ArrayAllocator::<2, Rc<u8>>::new(None);
+        let allocator = ArrayAllocator::<2>::new(None);
+        let mut map = SlotMap::new(&slab, &allocator).unwrap();
+        let rc = Rc::new(1);
+        let a = map.insert(rc.clone()).unwrap();
+        let b = map.insert(Rc::new(2)).unwrap();
+        assert!(map.insert(Rc::new(3)).is_none());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(a).map(|x| **x), Some(1));
+        *map.get_mut(b).unwrap() = Rc::new(4);
+        assert_eq!(
+            map.iter().map(|(key, x)| (key, **x)).collect::<Vec<_>>(),
+            [(a, 1), (b, 4)]
+        );
+
+        assert_eq!(map.remove(a).map(|x| *x), Some(1));
+        assert_eq!(map.remove(a), None);
+        let c = map.insert(rc.clone()).unwrap();
+        // The slot is reused but the old key doesn't address the new value.
+        assert_eq!(c.index, a.index);
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.get(c).map(|x| **x), Some(1));
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        for (_, x) in map.iter_mut() {
+            *x = Rc::new(**x + 1);
+        }
+        assert_eq!(format!("{map:?}"), format!("{{{c:?}: 2, {b:?}: 5}}"));
+        drop(map);
+        assert_eq!(slab.stats().free, 2);
+    }
+
+    #[test]
+    fn log_checksums() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let mut log = Log::try_with_capacity_in(64, Retention::Overwrite, &*allocator)
+            .unwrap()
+            .with_checksums();
+        assert_eq!(log.append(b"abc"), Ok(0));
+        assert_eq!(log.append(b"def"), Ok(1));
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        log.buf[Log::<&Allocator>::HEADER] = b'x';
+        assert_eq!(
+            log.read_from(0).unwrap().collect::<Vec<_>>(),
+            [
+                Err(LogError::Corrupt { seq: 0 }),
+                Ok(Record {
+                    seq: 1,
+                    data: b"def"
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn deque() {
+        let allocator = ArrayAllocator::<4>::new(None);
+        let rc = Rc::new(());
+        let mut deque = Deque::new(&allocator, 3).unwrap();
+        assert!(deque.push_back(rc.clone()).is_ok());
+        assert!(deque.push_back(rc.clone()).is_ok());
+        assert!(deque.pop_front().is_some());
+        // Wraps around the end of the ring.
+        assert!(deque.push_back(rc.clone()).is_ok());
+        assert!(deque.push_back(rc.clone()).is_ok());
+        assert!(deque.is_full());
+        assert!(deque.push_back(rc.clone()).is_err());
+        assert_eq!(Rc::strong_count(&rc), 4);
+        drop(deque);
+        assert_eq!(Rc::strong_count(&rc), 1);
+
+        let mut deque = Deque::<u32>::new(&allocator, 4).unwrap();
+        assert_eq!(deque.pop_back(), None);
+        deque.push_back(2).unwrap();
+        deque.push_front(1).unwrap();
+        deque.push_front(0).unwrap();
+        deque.push_back(3).unwrap();
+        assert_eq!(deque.push_front(9), Err(9));
+        assert_eq!(format!("{deque:?}"), "[0, 1, 2, 3]");
+        assert_eq!((deque.front(), deque.back()), (Some(&0), Some(&3)));
+        *deque.get_mut(1).unwrap() = 5;
+        assert_eq!(
+            deque.iter().rev().copied().collect::<Vec<_>>(),
+            [3, 2, 5, 0]
+        );
+        assert_eq!(deque.pop_back(), Some(3));
+        assert_eq!(deque.pop_front(), Some(0));
+        assert_eq!(deque.get(2), None);
+        assert_eq!(deque.len(), 2);
+    }
+
+    #[test]
+    fn hash_map() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let slab = crate::slab::ArrayAllocator::<3, (String, Rc<u32>)>::new(None);
+        let mut map = HashMap::<_, _>::new(&slab, &allocator).unwrap();
+        let rc = Rc::new(1);
+        assert_eq!(map.insert(String::from("a"), rc.clone()).unwrap(), None);
+        assert_eq!(map.insert(String::from("b"), rc.clone()).unwrap(), None);
+        assert_eq!(map.insert(String::from("c"), rc.clone()).unwrap(), None);
+        let replaced = map.insert(String::from("b"), Rc::new(2)).unwrap();
+        assert!(Rc::ptr_eq(&replaced.unwrap(), &rc));
+        let (key, _) = map.insert(String::from("d"), rc.clone()).unwrap_err();
+        assert_eq!(key, "d");
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get("b").map(|x| **x), Some(2));
+        assert!(map.get("d").is_none());
+
+        *map.get_mut("a").unwrap() = Rc::new(3);
+        assert_eq!(map.remove("c").map(|x| *x), Some(1));
+        assert_eq!(map.remove("c"), None);
+        assert!(!map.contains_key("c"));
+        // The freed slot and the removed bucket are reused.
+        assert_eq!(map.insert(String::from("d"), rc.clone()).unwrap(), None);
+        assert!(map.contains_key("d"));
+        let mut entries = map
+            .iter()
+            .map(|(k, v)| (k.clone(), **v))
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                (String::from("a"), 3),
+                (String::from("b"), 2),
+                (String::from("d"), 1)
+            ]
+        );
+        assert_eq!(Rc::strong_count(&rc), 2);
+        drop(map);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert_eq!(slab.stats().free, 3);
+        assert_eq!(allocator.stats().free, 8);
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+/// The error returned by the `try_allocate*` functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocError {
+    /// There is no free region large enough.
+    OutOfMemory {
+        /// The number of blocks/slots requested.
+        requested: usize,
+        /// The largest number of contiguous free blocks/slots at the time of the request.
+        largest_free: usize,
+    },
+    /// Locking the allocator failed.
+    LockFailed(crate::mutex::Error),
+    /// The requested type or length cannot be allocated, e.g. its size overflows `usize` or its
+    /// alignment is greater than that of a block.
+    InvalidLayout,
+    /// The allocator is locked by another thread and the request does not wait for it, e.g. when
+    /// polled by an async allocation.
+    WouldBlock,
+    /// The allocator is frozen, so no further allocations can be made.
+    Frozen,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfMemory {
+                requested,
+                largest_free,
+            } => write!(
+                f,
+                "out of memory: requested {requested} blocks but the largest free region is \
+                 {largest_free} blocks"
+            ),
+            Self::LockFailed(err) => write!(f, "failed to lock allocator: {err}"),
+            Self::InvalidLayout => write!(f, "invalid layout"),
+            Self::WouldBlock => write!(f, "allocator is locked"),
+            Self::Frozen => write!(f, "allocator is frozen"),
+        }
+    }
+}
+
+impl std::error::Error for AllocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::LockFailed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Converts the result of a `try_allocate*` function to the result of its `allocate*` counterpart.
+///
+/// # Panics
+///
+/// When `result` is an error other than [`AllocError::OutOfMemory`] or [`AllocError::Frozen`].
+pub(crate) fn none_on_oom<T>(result: Result<T, AllocError>) -> Option<T> {
+    match result {
+        Ok(x) => Some(x),
+        Err(AllocError::OutOfMemory { .. } | AllocError::Frozen) => None,
+        Err(err) => panic!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn alloc_error_display() {
+        let err = AllocError::OutOfMemory {
+            requested: 4,
+            largest_free: 2,
+        };
+        assert_eq!(
+            err.to_string(),
+            "out of memory: requested 4 blocks but the largest free region is 2 blocks"
+        );
+        assert_eq!(AllocError::InvalidLayout.to_string(), "invalid layout");
+    }
+
+    #[test]
+    fn none_on_oom_conversion() {
+        assert_eq!(none_on_oom(Ok(1)), Some(1));
+        let err = AllocError::OutOfMemory {
+            requested: 1,
+            largest_free: 0,
+        };
+        assert_eq!(none_on_oom::<()>(Err(err)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid layout")]
+    fn none_on_oom_panic() {
+        none_on_oom::<()>(Err(AllocError::InvalidLayout));
+    }
+}
@@ -0,0 +1,268 @@
+//! Read-only views of frozen [`crate::linked_list::Allocator`]s, see
+//! [`crate::linked_list::Allocator::freeze`].
+//!
+//! A frozen allocator never allocates or frees again, so its memory can be read without locking,
+//! e.g. by consumer processes which must never mutate it. The view returned by
+//! [`crate::linked_list::Allocator::freeze`] also protects the pages of the blocks read-only on
+//! Linux with the `std` feature, so stray writes fault rather than corrupt what consumers read.
+
+use std::mem::size_of;
+use std::ptr::NonNull;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::Allocator;
+use crate::Index;
+
+/// A view of a frozen allocator, addressing memory by byte offsets from the start of the first
+/// block as given by e.g. [`crate::linked_list::Wrapper::byte_offset`].
+///
+/// Pages protected by [`crate::linked_list::Allocator::freeze`] become writable again when the
+/// view is dropped, so the memory can be released by its owner. Clones don't own the protection.
+#[derive(Debug)]
+pub struct FrozenArena<'a, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    /// The address and length of the pages protected read-only.
+    protected: Option<(usize, usize)>,
+}
+
+impl<'a, I: Index> FrozenArena<'a, I> {
+    pub(crate) fn new(allocator: &'a Allocator<I>) -> Self {
+        Self {
+            allocator,
+            protected: None,
+        }
+    }
+
+    /// Returns a view protecting the pages lying wholly within the `len` bytes of blocks at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be the blocks of `allocator`, which must be frozen.
+    ///
+    /// # Panics
+    ///
+    /// When protecting the pages fails.
+    pub(crate) unsafe fn protect(allocator: &'a Allocator<I>, ptr: *mut u8, len: usize) -> Self {
+        #[cfg(feature = "log")]
+        trace!("FrozenArena::protect");
+
+        Self {
+            allocator,
+            protected: protect(ptr, len),
+        }
+    }
+
+    /// Returns the allocator.
+    #[must_use]
+    pub fn allocator(&self) -> &'a Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("FrozenArena::allocator");
+
+        self.allocator
+    }
+
+    /// Returns the `T` at `offset`, or `None` when it is out of range or misaligned.
+    ///
+    /// # Safety
+    ///
+    /// The bytes at `offset` must be a valid `T` which is not written while the reference lives,
+    /// e.g. through a wrapper allocated before the allocator was frozen.
+    #[must_use]
+    pub unsafe fn get<T>(&self, offset: usize) -> Option<&'a T> {
+        #[cfg(feature = "log")]
+        trace!("FrozenArena::get");
+
+        self.ptr::<T>(offset, 1).map(|ptr| ptr.as_ref())
+    }
+
+    /// Returns the `[T]` of length `len` at `offset`, or `None` when it is out of range or
+    /// misaligned.
+    ///
+    /// # Safety
+    ///
+    /// The bytes at `offset` must be `len` valid `T`s which are not written while the reference
+    /// lives, e.g. through a wrapper allocated before the allocator was frozen.
+    #[must_use]
+    pub unsafe fn slice<T>(&self, offset: usize, len: usize) -> Option<&'a [T]> {
+        #[cfg(feature = "log")]
+        trace!("FrozenArena::slice");
+
+        self.ptr::<T>(offset, len)
+            .map(|ptr| std::slice::from_raw_parts(ptr.as_ptr(), len))
+    }
+
+    /// Returns whether pages of the blocks are protected read-only by this view.
+    #[must_use]
+    pub fn is_protected(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("FrozenArena::is_protected");
+
+        self.protected.is_some()
+    }
+
+    /// Returns a pointer to `len` `T`s at `offset` if they lie within the blocks and are aligned.
+    fn ptr<T>(&self, offset: usize, len: usize) -> Option<NonNull<T>> {
+        let bytes = size_of::<T>().checked_mul(len)?;
+        let ptr = self.allocator.ptr_at(offset)?;
+        if bytes > 0 {
+            self.allocator.ptr_at(offset.checked_add(bytes - 1)?)?;
+        }
+        let ptr = ptr.cast::<T>();
+        ptr.as_ptr().is_aligned().then_some(ptr)
+    }
+}
+
+impl<'a, I: Index> Clone for FrozenArena<'a, I> {
+    fn clone(&self) -> Self {
+        Self::new(self.allocator)
+    }
+}
+
+impl<'a, I: Index> Drop for FrozenArena<'a, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("FrozenArena::drop");
+
+        if let Some((addr, len)) = self.protected.take() {
+            unprotect(addr, len);
+        }
+    }
+}
+
+/// Protects the pages lying wholly within the `len` bytes at `ptr` read-only, returning their
+/// address and length if there are any.
+///
+/// # Panics
+///
+/// When `mprotect` fails.
+#[cfg(all(target_os = "linux", feature = "std"))]
+fn protect(ptr: *mut u8, len: usize) -> Option<(usize, usize)> {
+    let page = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap();
+    let start = ptr.addr().next_multiple_of(page);
+    let end = (ptr.addr() + len) / page * page;
+    if start >= end {
+        return None;
+    }
+    let result =
+        unsafe { libc::mprotect(ptr.with_addr(start).cast(), end - start, libc::PROT_READ) };
+    assert_eq!(
+        result,
+        0,
+        "failed to protect frozen blocks: {}",
+        std::io::Error::last_os_error()
+    );
+    Some((start, end - start))
+}
+
+/// Pages are only protected on Linux with the `std` feature.
+#[cfg(not(all(target_os = "linux", feature = "std")))]
+fn protect(_ptr: *mut u8, _len: usize) -> Option<(usize, usize)> {
+    None
+}
+
+/// Makes the `len` bytes of pages at `addr` protected by [`protect`] writable again.
+///
+/// # Panics
+///
+/// When `mprotect` fails.
+#[cfg(all(target_os = "linux", feature = "std"))]
+fn unprotect(addr: usize, len: usize) {
+    let result = unsafe {
+        libc::mprotect(
+            addr as *mut libc::c_void,
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+        )
+    };
+    assert_eq!(
+        result,
+        0,
+        "failed to unprotect frozen blocks: {}",
+        std::io::Error::last_os_error()
+    );
+}
+
+/// Pages are only protected on Linux with the `std` feature.
+#[cfg(not(all(target_os = "linux", feature = "std")))]
+fn unprotect(_addr: usize, _len: usize) {}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+    use crate::AllocError;
+
+    #[test]
+    fn freeze() {
+        let mut memory = ArrayAllocator::<8>::new(None);
+        assert!(memory.frozen().is_none());
+        let mut value = memory.allocate_value::<u32>().unwrap();
+        *value = 7;
+        let mut slice = memory.allocate_slice::<u16>(3).unwrap();
+        slice.copy_from_slice(&[1, 2, 3]);
+        let (value_offset, slice_offset) = (value.byte_offset(), slice.wrapper().byte_offset());
+        // Allocations are given up to be kept while frozen.
+        std::mem::forget(value);
+        std::mem::forget(slice);
+
+        let frozen = memory.freeze();
+        let allocator = frozen.allocator();
+        assert!(allocator.is_frozen());
+        assert!(allocator.allocate(1).is_none());
+        assert_eq!(allocator.try_allocate(1).unwrap_err(), AllocError::Frozen);
+        // The blocks are too few to span a page.
+        assert!(!frozen.is_protected());
+
+        let frozen_again = allocator.frozen().unwrap();
+        unsafe {
+            assert_eq!(frozen.get::<u32>(value_offset), Some(&7));
+            assert_eq!(
+                frozen_again.slice::<u16>(slice_offset, 3),
+                Some(&[1, 2, 3][..])
+            );
+            assert!(frozen.get::<u32>(value_offset + 1).is_none());
+            assert!(frozen
+                .get::<u64>(8 * size_of::<crate::linked_list::Block>())
+                .is_none());
+            assert!(frozen.slice::<u16>(slice_offset, usize::MAX).is_none());
+        }
+    }
+
+    #[test]
+    fn freeze_free() {
+        let mut memory = ArrayAllocator::<8>::new(None);
+        let (index, size) = memory.allocate(1).unwrap().into_raw_parts();
+        drop(memory.freeze());
+        drop(unsafe { crate::linked_list::Wrapper::from_raw_parts(&memory, index, size) });
+        // The block is leaked rather than freed.
+        assert_eq!(memory.stats().free, 7);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn freeze_protect() {
+        // Enough blocks to span whole pages.
+        let mut memory = Box::new(ArrayAllocator::<1024>::new(None));
+        let wrapper = memory.allocate(1024).unwrap();
+        let offset = wrapper.byte_offset();
+        let (index, size) = wrapper.into_raw_parts();
+
+        let frozen = memory.freeze();
+        assert!(frozen.is_protected());
+        assert!(!frozen.clone().is_protected());
+        unsafe {
+            assert!(frozen.get::<u8>(offset).is_some());
+        }
+        drop(frozen);
+
+        // The pages are writable again once the view is dropped.
+        let mut wrapper =
+            unsafe { crate::linked_list::Wrapper::from_raw_parts(&*memory, index, size) };
+        unsafe { std::ptr::write_bytes(wrapper.as_mut_ptr(), 0, wrapper.len()) };
+        std::mem::forget(wrapper);
+    }
+}
@@ -0,0 +1,75 @@
+//! Waiting for a `u32` in memory, which may be shared between processes, to change, see
+//! [`crate::channel`] and [`crate::rpc`].
+//!
+//! On Linux this uses the `futex` system call without `FUTEX_PRIVATE_FLAG`, so a process can wake
+//! threads of another process waiting on the same physical memory. Elsewhere waiting yields the
+//! thread and waking does nothing, so waiting degrades to spinning.
+
+use std::sync::atomic::AtomicU32;
+
+/// Waits until woken while `word` holds `expected`, returning immediately if it doesn't.
+///
+/// This may return spuriously, so callers check their condition again.
+#[cfg(target_os = "linux")]
+pub(crate) fn wait(word: &AtomicU32, expected: u32) {
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            word.as_ptr(),
+            libc::FUTEX_WAIT,
+            expected,
+            std::ptr::null::<libc::timespec>(),
+        );
+    }
+}
+
+/// Wakes up to `n` threads waiting on `word`.
+#[cfg(target_os = "linux")]
+pub(crate) fn wake(word: &AtomicU32, n: u32) {
+    let n = i32::try_from(n).unwrap_or(i32::MAX);
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAKE, n);
+    }
+}
+
+/// Waits until woken while `word` holds `expected`, returning immediately if it doesn't.
+///
+/// This may return spuriously, so callers check their condition again.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn wait(word: &AtomicU32, expected: u32) {
+    if word.load(std::sync::atomic::Ordering::Acquire) == expected {
+        #[cfg(feature = "std")]
+        std::thread::yield_now();
+        #[cfg(not(feature = "std"))]
+        std::hint::spin_loop();
+    }
+}
+
+/// Wakes up to `n` threads waiting on `word`.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn wake(_word: &AtomicU32, _n: u32) {}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    #[test]
+    fn futex_wake() {
+        let word = AtomicU32::new(0);
+        // A changed word returns immediately.
+        wait(&word, 1);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while word.load(Ordering::Acquire) == 0 {
+                    wait(&word, 0);
+                }
+            });
+            word.store(1, Ordering::Release);
+            wake(&word, u32::MAX);
+        });
+    }
+}
@@ -0,0 +1,68 @@
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// An unsigned integer type used to store indices and sizes within the blocks/slots of an
+/// allocator.
+///
+/// Smaller types reduce the metadata stored per block/slot at the cost of limiting the number
+/// of blocks/slots an allocator can manage to [`Index::MAX`].
+///
+/// For [`crate::linked_list`] a block is `size_of::<linked_list::Block<I>>()` bytes, holding an
+/// `I` and an `Option<I>`, and is aligned to `I`, so smaller types also reduce the alignment of
+/// allocated memory.
+pub trait Index: Copy + Eq + Ord + std::fmt::Debug + sealed::Sealed {
+    /// The largest value representable.
+    const MAX: usize;
+
+    /// Converts from `usize`.
+    ///
+    /// `x` must not be greater than [`Index::MAX`].
+    fn from_usize(x: usize) -> Self;
+
+    /// Converts to `usize`.
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_index {
+    ($($t:ty),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+            impl Index for $t {
+                const MAX: usize = <$t>::MAX as usize;
+
+                fn from_usize(x: usize) -> Self {
+                    debug_assert!(x <= <Self as Index>::MAX);
+                    x as Self
+                }
+
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_index!(u16, u32, usize);
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn index_max() {
+        assert_eq!(<u16 as Index>::MAX, 65535);
+        assert_eq!(<u32 as Index>::MAX, 4294967295);
+        assert_eq!(<usize as Index>::MAX, usize::MAX);
+    }
+
+    #[test]
+    fn index_conversion() {
+        assert_eq!(u16::from_usize(7).to_usize(), 7);
+        assert_eq!(u32::from_usize(7).to_usize(), 7);
+        assert_eq!(usize::from_usize(7).to_usize(), 7);
+    }
+}
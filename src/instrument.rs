@@ -0,0 +1,35 @@
+//! Metrics emitted through the [`metrics`] facade.
+//!
+//! | Name | Type | Description |
+//! | --- | --- | --- |
+//! | `array_allocators_allocations_total` | counter | Successful allocations. |
+//! | `array_allocators_frees_total` | counter | Frees. |
+//! | `array_allocators_failures_total` | counter | Allocations which failed through lack of memory. |
+//! | `array_allocators_bytes_in_use` | gauge | Bytes allocated and not yet freed. |
+//! | `array_allocators_lock_wait_seconds` | histogram | Time spent waiting to lock an allocator. |
+//!
+//! All but the lock wait histogram are labelled with `allocator`, either `linked_list` or `slab`.
+
+#![allow(clippy::cast_precision_loss)]
+
+use std::time::Duration;
+
+pub(crate) fn allocated(allocator: &'static str, bytes: usize) {
+    metrics::counter!("array_allocators_allocations_total", "allocator" => allocator).increment(1);
+    metrics::gauge!("array_allocators_bytes_in_use", "allocator" => allocator)
+        .increment(bytes as f64);
+}
+
+pub(crate) fn freed(allocator: &'static str, bytes: usize) {
+    metrics::counter!("array_allocators_frees_total", "allocator" => allocator).increment(1);
+    metrics::gauge!("array_allocators_bytes_in_use", "allocator" => allocator)
+        .decrement(bytes as f64);
+}
+
+pub(crate) fn failed(allocator: &'static str) {
+    metrics::counter!("array_allocators_failures_total", "allocator" => allocator).increment(1);
+}
+
+pub(crate) fn lock_wait(duration: Duration) {
+    metrics::histogram!("array_allocators_lock_wait_seconds").record(duration);
+}
@@ -0,0 +1,344 @@
+//! A decorator layering debugging features over any [`RawArrayAllocator`], so they compose without
+//! each allocator implementing every one.
+//!
+//! [`Instrumented`] always counts allocations and frees. With the `tracing` feature each
+//! allocation and free is traced, with the `canaries` feature allocations can be surrounded with
+//! guard blocks checked when they are freed and with the `testing` feature failures can be
+//! injected.
+
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::raw::{RawAllocation, RawArrayAllocator, Stats};
+
+/// Counts of the operations made through an [`Instrumented`] allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counts {
+    /// Successful allocations.
+    pub allocations: usize,
+    /// Frees.
+    pub frees: usize,
+    /// Allocations which failed, including injected failures.
+    pub failures: usize,
+    /// Bytes allocated and not yet freed, excluding guard blocks.
+    pub bytes_in_use: usize,
+}
+
+/// Wraps an allocator, counting, tracing, guarding and failing its allocations, see the
+/// [module](self) documentation.
+#[derive(Debug)]
+pub struct Instrumented<A> {
+    inner: A,
+    allocations: AtomicUsize,
+    frees: AtomicUsize,
+    failures: AtomicUsize,
+    bytes_in_use: AtomicUsize,
+    #[cfg(feature = "canaries")]
+    canaries: bool,
+    #[cfg(feature = "testing")]
+    failure: std::sync::Mutex<Option<crate::testing::FailureInjection>>,
+}
+
+impl<A: RawArrayAllocator> Instrumented<A> {
+    #[must_use]
+    pub fn new(inner: A) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::new");
+
+        Self {
+            inner,
+            allocations: AtomicUsize::new(0),
+            frees: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            bytes_in_use: AtomicUsize::new(0),
+            #[cfg(feature = "canaries")]
+            canaries: false,
+            #[cfg(feature = "testing")]
+            failure: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Surrounds each allocation with a guard block either side filled with
+    /// [`crate::canary::CANARY`], checked when the allocation is freed.
+    ///
+    /// The guards are allocated with the allocation, so the inner allocator must support
+    /// allocations of several blocks, e.g. [`crate::linked_list::Allocator`] but not
+    /// [`crate::slab::Allocator`].
+    #[cfg(feature = "canaries")]
+    #[must_use]
+    pub fn with_canaries(mut self) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::with_canaries");
+
+        self.canaries = true;
+        self
+    }
+
+    /// Fails allocations as configured by `failure`.
+    #[cfg(feature = "testing")]
+    #[must_use]
+    pub fn with_failure_injection(self, failure: crate::testing::FailureInjection) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::with_failure_injection");
+
+        *self
+            .failure
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(failure);
+        self
+    }
+
+    #[must_use]
+    pub fn counts(&self) -> Counts {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::counts");
+
+        Counts {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            bytes_in_use: self.bytes_in_use.load(Ordering::Relaxed),
+        }
+    }
+
+    #[must_use]
+    pub fn inner(&self) -> &A {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::inner");
+
+        &self.inner
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> A {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::into_inner");
+
+        self.inner
+    }
+
+    /// The number of guard blocks either side of an allocation.
+    #[allow(clippy::unused_self)]
+    fn guard(&self) -> usize {
+        #[cfg(feature = "canaries")]
+        let guard = usize::from(self.canaries);
+        #[cfg(not(feature = "canaries"))]
+        let guard = 0;
+        guard
+    }
+
+    /// Returns whether an allocation of `bytes` bytes should fail.
+    #[allow(unused_variables, clippy::unused_self)]
+    fn inject(&self, bytes: usize) -> bool {
+        #[cfg(feature = "testing")]
+        if let Some(failure) = self
+            .failure
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_mut()
+        {
+            return failure.inject(bytes);
+        }
+        false
+    }
+
+    /// Checks the guards of an allocation.
+    ///
+    /// # Panics
+    ///
+    /// When a guard was overwritten.
+    #[cfg(feature = "canaries")]
+    unsafe fn check_canaries(&self, allocation: RawAllocation, outer: RawAllocation) {
+        use crate::canary::{CanaryError, Guard, CANARY};
+
+        let block_size = self.inner.block_size();
+        let ptr = self.inner.as_ptr(outer);
+        let intact = |offset: usize| {
+            std::slice::from_raw_parts(ptr.as_ptr().add(offset), block_size)
+                .iter()
+                .all(|&byte| byte == CANARY)
+        };
+        let error = |guard| CanaryError {
+            index: allocation.index,
+            size: Some(allocation.size),
+            guard,
+        };
+        assert!(intact(0), "{}", error(Guard::Before));
+        assert!(
+            intact((outer.size - 1) * block_size),
+            "{}",
+            error(Guard::After)
+        );
+    }
+}
+
+impl<A: RawArrayAllocator> RawArrayAllocator for Instrumented<A> {
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn block_align(&self) -> usize {
+        self.inner.block_align()
+    }
+
+    fn allocate_bytes(&self, bytes: usize) -> Option<RawAllocation> {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::allocate_bytes");
+
+        let guard = self.guard();
+        let outer = if self.inject(bytes) {
+            None
+        } else {
+            let guards = 2 * guard * self.inner.block_size();
+            self.inner.allocate_bytes(bytes + guards)
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(bytes, ?outer, "instrumented::allocate");
+
+        let Some(outer) = outer else {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+
+        #[cfg(feature = "canaries")]
+        if guard == 1 {
+            let block_size = self.inner.block_size();
+            let ptr = unsafe { self.inner.as_ptr(outer) }.as_ptr();
+            unsafe {
+                ptr.write_bytes(crate::canary::CANARY, block_size);
+                ptr.add((outer.size - 1) * block_size)
+                    .write_bytes(crate::canary::CANARY, block_size);
+            }
+        }
+
+        let allocation = RawAllocation {
+            index: outer.index + guard,
+            size: outer.size - 2 * guard,
+        };
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in_use
+            .fetch_add(allocation.size * self.inner.block_size(), Ordering::Relaxed);
+        Some(allocation)
+    }
+
+    unsafe fn free(&self, allocation: RawAllocation) {
+        #[cfg(feature = "log")]
+        trace!("Instrumented::free");
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?allocation, "instrumented::free");
+
+        let guard = self.guard();
+        let outer = RawAllocation {
+            index: allocation.index - guard,
+            size: allocation.size + 2 * guard,
+        };
+
+        #[cfg(feature = "canaries")]
+        if guard == 1 {
+            self.check_canaries(allocation, outer);
+        }
+
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in_use
+            .fetch_sub(allocation.size * self.inner.block_size(), Ordering::Relaxed);
+        self.inner.free(outer);
+    }
+
+    unsafe fn as_ptr(&self, allocation: RawAllocation) -> NonNull<u8> {
+        let guard = self.guard();
+        let outer = RawAllocation {
+            index: allocation.index - guard,
+            size: allocation.size + 2 * guard,
+        };
+        NonNull::new_unchecked(
+            self.inner
+                .as_ptr(outer)
+                .as_ptr()
+                .add(guard * self.inner.block_size()),
+        )
+    }
+
+    fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn instrumented_counts() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let allocator = Instrumented::new(&*memory);
+        let block_size = allocator.block_size();
+
+        let a = allocator.allocate_bytes(block_size + 1).unwrap();
+        assert_eq!(a.size, 2);
+        assert!(allocator.allocate_bytes(3 * block_size).is_none());
+        assert_eq!(
+            allocator.counts(),
+            Counts {
+                allocations: 1,
+                frees: 0,
+                failures: 1,
+                bytes_in_use: 2 * block_size,
+            }
+        );
+        unsafe { allocator.free(a) };
+        assert_eq!(allocator.counts().frees, 1);
+        assert_eq!(allocator.counts().bytes_in_use, 0);
+    }
+
+    #[cfg(feature = "canaries")]
+    #[test]
+    fn instrumented_canaries() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let allocator = Instrumented::new(&*memory).with_canaries();
+        let block_size = allocator.block_size();
+
+        let a = allocator.allocate_bytes(block_size).unwrap();
+        assert_eq!(a, RawAllocation { index: 1, size: 1 });
+        assert_eq!(memory.stats().free, 1);
+        let ptr = unsafe { allocator.as_ptr(a) };
+        unsafe { ptr.as_ptr().write_bytes(0, block_size) };
+        unsafe { allocator.free(a) };
+        assert_eq!(memory.stats().free, 4);
+
+        let a = allocator.allocate_bytes(block_size).unwrap();
+        let ptr = unsafe { allocator.as_ptr(a) };
+        // Overflow into the guard after the allocation.
+        unsafe { ptr.as_ptr().write_bytes(0, block_size + 1) };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+            allocator.free(a)
+        }));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            message,
+            "canary after the allocation at block 1 (1 blocks) was overwritten"
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn instrumented_failure_injection() {
+        use std::num::NonZeroUsize;
+
+        let memory = ArrayAllocator::<4>::new(None);
+        let allocator = Instrumented::new(&*memory).with_failure_injection(
+            crate::testing::FailureInjection::every(NonZeroUsize::new(2).unwrap()),
+        );
+        let a = allocator.allocate_bytes(1).unwrap();
+        assert!(allocator.allocate_bytes(1).is_none());
+        assert_eq!(allocator.counts().failures, 1);
+        unsafe { allocator.free(a) };
+    }
+}
@@ -0,0 +1,96 @@
+//! Validation of the free lists threaded through the memory of
+//! [`crate::linked_list::Allocator`] and [`crate::slab::Allocator`], see
+//! [`crate::linked_list::Allocator::check_integrity`].
+//!
+//! Free lists are ordered by index, so a list is intact when each region starts after the end of
+//! the previous one and ends within the allocator, which also ensures the walk terminates.
+
+use std::fmt;
+
+/// A summary of a free list found intact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IntegrityReport {
+    /// The number of regions in the list, where each free slot of a slab is its own region.
+    pub free_regions: usize,
+    /// The number of free blocks/slots in the list.
+    pub free: usize,
+}
+
+/// The first corruption found walking a free list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// A region extends beyond the end of the allocator, its size is `None` when it starts beyond
+    /// the end so could not be read.
+    OutOfBounds { index: usize, size: Option<usize> },
+    /// A region holds no blocks.
+    Empty { index: usize },
+    /// A region starts within the previous region.
+    Overlap { prev: usize, index: usize },
+    /// A region starts before the previous region, e.g. as the list loops back on itself.
+    Unordered { prev: usize, index: usize },
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { index, size } => {
+                write!(f, "free region at {index}")?;
+                if let Some(size) = size {
+                    write!(f, " of {size}")?;
+                }
+                write!(f, " extends beyond the allocator")
+            }
+            Self::Empty { index } => write!(f, "free region at {index} is empty"),
+            Self::Overlap { prev, index } => write!(
+                f,
+                "free region at {index} overlaps the free region at {prev}"
+            ),
+            Self::Unordered { prev, index } => write!(
+                f,
+                "free region at {index} is linked after the later free region at {prev}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// Walks a free list of `total` blocks/slots from `head`, where `region` returns the size of the
+/// region at an index and the index of the next region.
+pub(crate) fn check(
+    total: usize,
+    head: Option<usize>,
+    region: impl Fn(usize) -> (usize, Option<usize>),
+) -> Result<IntegrityReport, IntegrityError> {
+    let mut report = IntegrityReport::default();
+    let mut prev: Option<(usize, usize)> = None;
+    let mut current = head;
+    while let Some(index) = current {
+        if let Some((prev, prev_end)) = prev {
+            if index <= prev {
+                return Err(IntegrityError::Unordered { prev, index });
+            }
+            if index < prev_end {
+                return Err(IntegrityError::Overlap { prev, index });
+            }
+        }
+        if index >= total {
+            return Err(IntegrityError::OutOfBounds { index, size: None });
+        }
+        let (size, next) = region(index);
+        if size == 0 {
+            return Err(IntegrityError::Empty { index });
+        }
+        if size > total - index {
+            return Err(IntegrityError::OutOfBounds {
+                index,
+                size: Some(size),
+            });
+        }
+        report.free_regions += 1;
+        report.free += size;
+        prev = Some((index, index + size));
+        current = next;
+    }
+    Ok(report)
+}
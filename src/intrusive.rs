@@ -0,0 +1,392 @@
+//! A doubly linked list whose nodes are [`crate::slab::Allocator`] slots linked by index, so
+//! values can be inserted and removed in O(1) given their [`Handle`], e.g. for schedulers and LRU
+//! lists within a fixed number of slots.
+
+use std::fmt;
+use std::mem::ManuallyDrop;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::slab::{Allocator, Wrapper};
+use crate::Index;
+
+/// The link of the end of a list.
+const NONE: usize = 0;
+
+/// A node of a [`List`], allocated in a slot of a [`crate::slab::Allocator`].
+#[derive(Debug)]
+#[repr(C)]
+pub struct Node<T> {
+    /// One plus the slot of the previous node, or [`NONE`].
+    prev: usize,
+    /// One plus the slot of the next node, or [`NONE`].
+    next: usize,
+    value: T,
+}
+
+/// The slot of a node of a [`List`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Handle(usize);
+
+impl Handle {
+    /// Returns the slot of the node.
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    fn link(self) -> usize {
+        self.0 + 1
+    }
+
+    fn from_link(link: usize) -> Option<Self> {
+        link.checked_sub(1).map(Self)
+    }
+}
+
+/// A doubly linked list of values in the slots of a [`crate::slab::Allocator`].
+///
+/// Operations given a [`Handle`] are unsafe as the handle must be of a node in the list, which
+/// is not checked.
+pub struct List<'a, T, I: Index = usize> {
+    slab: &'a Allocator<Node<T>, I>,
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl<'a, T, I: Index> List<'a, T, I> {
+    /// Constructs an empty list of nodes in `slab`.
+    #[must_use]
+    pub fn new(slab: &'a Allocator<Node<T>, I>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("List::new");
+
+        Self {
+            slab,
+            head: NONE,
+            tail: NONE,
+            len: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("List::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("List::is_empty");
+
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn front(&self) -> Option<Handle> {
+        Handle::from_link(self.head)
+    }
+
+    #[must_use]
+    pub fn back(&self) -> Option<Handle> {
+        Handle::from_link(self.tail)
+    }
+
+    /// Prepends `x`, or returns it when the slab has no free slots.
+    ///
+    /// # Errors
+    ///
+    /// When the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn push_front(&mut self, x: T) -> Result<Handle, T> {
+        #[cfg(feature = "log")]
+        trace!("List::push_front");
+
+        let handle = self.allocate(x)?;
+        unsafe { self.link(handle, NONE, self.head) };
+        Ok(handle)
+    }
+
+    /// Appends `x`, or returns it when the slab has no free slots.
+    ///
+    /// # Errors
+    ///
+    /// When the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn push_back(&mut self, x: T) -> Result<Handle, T> {
+        #[cfg(feature = "log")]
+        trace!("List::push_back");
+
+        let handle = self.allocate(x)?;
+        unsafe { self.link(handle, self.tail, NONE) };
+        Ok(handle)
+    }
+
+    /// Inserts `x` after the node of `handle`, or returns it when the slab has no free slots.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    ///
+    /// # Errors
+    ///
+    /// When the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub unsafe fn insert_after(&mut self, handle: Handle, x: T) -> Result<Handle, T> {
+        #[cfg(feature = "log")]
+        trace!("List::insert_after");
+
+        let new = self.allocate(x)?;
+        self.link(new, handle.link(), (*self.node(handle)).next);
+        Ok(new)
+    }
+
+    /// Inserts `x` before the node of `handle`, or returns it when the slab has no free slots.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    ///
+    /// # Errors
+    ///
+    /// When the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub unsafe fn insert_before(&mut self, handle: Handle, x: T) -> Result<Handle, T> {
+        #[cfg(feature = "log")]
+        trace!("List::insert_before");
+
+        let new = self.allocate(x)?;
+        self.link(new, (*self.node(handle)).prev, handle.link());
+        Ok(new)
+    }
+
+    /// Removes the node of `handle`, freeing its slot, and returns its value.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub unsafe fn remove(&mut self, handle: Handle) -> T {
+        #[cfg(feature = "log")]
+        trace!("List::remove");
+
+        self.unlink(handle);
+        Wrapper::from_index(self.slab, handle.index())
+            .into_inner()
+            .value
+    }
+
+    /// Moves the node of `handle` to the front, e.g. when an LRU entry is used.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    pub unsafe fn move_to_front(&mut self, handle: Handle) {
+        #[cfg(feature = "log")]
+        trace!("List::move_to_front");
+
+        self.unlink(handle);
+        self.link(handle, NONE, self.head);
+    }
+
+    /// Removes the front value and returns it, or `None` if empty.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn pop_front(&mut self) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("List::pop_front");
+
+        let handle = self.front()?;
+        Some(unsafe { self.remove(handle) })
+    }
+
+    /// Removes the back value and returns it, or `None` if empty.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn pop_back(&mut self) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("List::pop_back");
+
+        let handle = self.back()?;
+        Some(unsafe { self.remove(handle) })
+    }
+
+    /// Returns the value of the node of `handle`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    #[must_use]
+    pub unsafe fn get(&self, handle: Handle) -> &T {
+        #[cfg(feature = "log")]
+        trace!("List::get");
+
+        &(*self.node(handle)).value
+    }
+
+    /// Returns the value of the node of `handle`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    pub unsafe fn get_mut(&mut self, handle: Handle) -> &mut T {
+        #[cfg(feature = "log")]
+        trace!("List::get_mut");
+
+        &mut (*self.node(handle)).value
+    }
+
+    /// Returns an iterator over the handles and values from front to back.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        #[cfg(feature = "log")]
+        trace!("List::iter");
+
+        let mut next = self.head;
+        std::iter::from_fn(move || {
+            let handle = Handle::from_link(next)?;
+            let node = unsafe { &*self.node(handle) };
+            next = node.next;
+            Some((handle, &node.value))
+        })
+    }
+
+    fn allocate(&self, x: T) -> Result<Handle, T> {
+        let node = Node {
+            prev: NONE,
+            next: NONE,
+            value: x,
+        };
+        let Some(mut run) = self.slab.reserve_run(1) else {
+            return Err(node.value);
+        };
+        let wrapper = run.push(node).map_err(|node| node.value)?;
+        Ok(Handle(wrapper.into_index()))
+    }
+
+    /// Links the node of `handle` between `prev` and `next`.
+    ///
+    /// # Safety
+    ///
+    /// The node must be unlinked, and `prev` and `next` adjacent links of the list.
+    unsafe fn link(&mut self, handle: Handle, prev: usize, next: usize) {
+        let node = &mut *self.node(handle);
+        node.prev = prev;
+        node.next = next;
+        match Handle::from_link(prev) {
+            Some(prev) => (*self.node(prev)).next = handle.link(),
+            None => self.head = handle.link(),
+        }
+        match Handle::from_link(next) {
+            Some(next) => (*self.node(next)).prev = handle.link(),
+            None => self.tail = handle.link(),
+        }
+        self.len += 1;
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must be of a node in the list.
+    unsafe fn unlink(&mut self, handle: Handle) {
+        let (prev, next) = {
+            let node = &*self.node(handle);
+            (node.prev, node.next)
+        };
+        match Handle::from_link(prev) {
+            Some(prev) => (*self.node(prev)).next = next,
+            None => self.head = next,
+        }
+        match Handle::from_link(next) {
+            Some(next) => (*self.node(next)).prev = prev,
+            None => self.tail = prev,
+        }
+        self.len -= 1;
+    }
+
+    /// Returns a pointer to the node of `handle`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold a node.
+    unsafe fn node(&self, handle: Handle) -> *mut Node<T> {
+        let mut wrapper = ManuallyDrop::new(Wrapper::from_index(self.slab, handle.index()));
+        std::ptr::addr_of_mut!(**wrapper)
+    }
+}
+
+impl<'a, T, I: Index> Drop for List<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("List::drop");
+
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<'a, T: fmt::Debug, I: Index> fmt::Debug for List<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|(_, value)| value))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::slab::ArrayAllocator;
+
+    #[test]
+    fn intrusive_list() {
+        let slab = ArrayAllocator::<4, Node<u32>>::new(None);
+        let mut list = List::new(&slab);
+        let b = list.push_back(2).unwrap();
+        let a = list.push_front(1).unwrap();
+        let d = list.push_back(4).unwrap();
+        let c = unsafe { list.insert_before(d, 3) }.unwrap();
+        assert_eq!(list.push_back(5), Err(5));
+        assert_eq!(format!("{list:?}"), "[1, 2, 3, 4]");
+        assert_eq!((list.front(), list.back()), (Some(a), Some(d)));
+
+        unsafe {
+            assert_eq!(list.remove(b), 2);
+            list.move_to_front(d);
+            *list.get_mut(a) += 10;
+            assert_eq!(*list.get(c), 3);
+        }
+        assert_eq!(format!("{list:?}"), "[4, 11, 3]");
+        let e = unsafe { list.insert_after(c, 5) }.unwrap();
+        assert_eq!(list.back(), Some(e));
+        assert_eq!(list.pop_back(), Some(5));
+        assert_eq!(list.pop_front(), Some(4));
+        assert_eq!(list.len(), 2);
+        drop(list);
+        assert_eq!(slab.stats().free, 4);
+    }
+}
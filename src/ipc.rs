@@ -0,0 +1,269 @@
+//! Data structures linking [`crate::slab::Allocator`] slots by index rather than by pointer, so
+//! processes mapping the same region at different addresses can share them.
+//!
+//! [`MpscQueue`] is an unbounded multi-producer, single-consumer queue of nodes, where producers
+//! append without a lock by swapping the tail and the consumer follows the links from a stub node.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::slab::{Allocator, Wrapper};
+use crate::Index;
+
+/// The link of a node without a successor.
+const NONE: usize = 0;
+
+/// A node of an [`MpscQueue`], allocated in a slot of a [`crate::slab::Allocator`].
+#[repr(C)]
+pub struct Node<T> {
+    /// One plus the slot of the next node, or [`NONE`].
+    next: AtomicUsize,
+    /// Uninitialized in the stub node.
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// See `MpscQueue`.
+unsafe impl<T: Send> Sync for Node<T> {}
+
+impl<T> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An unbounded multi-producer, single-consumer queue of values in the slots of a
+/// [`crate::slab::Allocator`].
+///
+/// The queue holds only slot indices, so it can be placed in shared memory alongside the slab,
+/// e.g. with [`crate::linked_list::Allocator::allocate_value`], and used by every process mapping
+/// the region. Each operation is given the slab holding the nodes.
+///
+/// A value is visible to the consumer once the producer has linked its node, so while a push is in
+/// progress the queue may briefly appear to end before it.
+#[repr(C)]
+pub struct MpscQueue<T, I: Index = usize> {
+    /// The slot of the stub node, whose successor holds the front value.
+    head: AtomicUsize,
+    /// The slot of the last node.
+    tail: AtomicUsize,
+    __marker: PhantomData<(T, I)>,
+}
+
+// Values are only accessed by the producer which pushed them and then the consumer.
+unsafe impl<T: Send, I: Index> Send for MpscQueue<T, I> {}
+unsafe impl<T: Send, I: Index> Sync for MpscQueue<T, I> {}
+
+impl<T, I: Index> MpscQueue<T, I> {
+    /// Constructs an empty queue, allocating its stub node within `slab`.
+    ///
+    /// Returns `None` when the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn new(slab: &Allocator<Node<T>, I>) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("MpscQueue::new");
+
+        let stub = slab.allocate(Node {
+            next: AtomicUsize::new(NONE),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        })?;
+        let stub = stub.into_index();
+        Some(Self {
+            head: AtomicUsize::new(stub),
+            tail: AtomicUsize::new(stub),
+            __marker: PhantomData,
+        })
+    }
+
+    /// Appends `value`, or returns it when the slab has no free slots.
+    ///
+    /// # Errors
+    ///
+    /// When the slab has no free slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn push(&self, slab: &Allocator<Node<T>, I>, value: T) -> Result<(), T> {
+        #[cfg(feature = "log")]
+        trace!("MpscQueue::push");
+
+        let node = Node {
+            next: AtomicUsize::new(NONE),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        };
+        let Some(mut run) = slab.reserve_run(1) else {
+            return Err(unsafe { node.value.into_inner().assume_init() });
+        };
+        let index = run
+            .push(node)
+            .map_err(|node| unsafe { node.value.into_inner().assume_init() })?
+            .into_index();
+        let prev = self.tail.swap(index, Ordering::AcqRel);
+        unsafe { Self::node(slab, prev) }
+            .next
+            .store(index + 1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Removes the front value and returns it, or `None` if empty.
+    ///
+    /// # Safety
+    ///
+    /// Only one thread, in any process, may pop at a time, and `slab` must be the slab the queue
+    /// was constructed with.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub unsafe fn pop(&self, slab: &Allocator<Node<T>, I>) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("MpscQueue::pop");
+
+        let head = self.head.load(Ordering::Relaxed);
+        let next = Self::node(slab, head).next.load(Ordering::Acquire);
+        let next = next.checked_sub(1)?;
+        // The successor becomes the stub once its value is moved out.
+        let value = (*Self::node(slab, next).value.get()).assume_init_read();
+        self.head.store(next, Ordering::Relaxed);
+        drop(Wrapper::from_index(slab, head));
+        Some(value)
+    }
+
+    /// Returns whether the queue holds no linked values.
+    ///
+    /// # Safety
+    ///
+    /// `slab` must be the slab the queue was constructed with.
+    #[must_use]
+    pub unsafe fn is_empty(&self, slab: &Allocator<Node<T>, I>) -> bool {
+        #[cfg(feature = "log")]
+        trace!("MpscQueue::is_empty");
+
+        Self::node(slab, self.head.load(Ordering::Relaxed))
+            .next
+            .load(Ordering::Acquire)
+            == NONE
+    }
+
+    /// Drops the values in the queue and frees its nodes.
+    ///
+    /// # Safety
+    ///
+    /// No other thread, in any process, may use the queue, and `slab` must be the slab the queue
+    /// was constructed with.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub unsafe fn destroy(self, slab: &Allocator<Node<T>, I>) {
+        #[cfg(feature = "log")]
+        trace!("MpscQueue::destroy");
+
+        while self.pop(slab).is_some() {}
+        drop(Wrapper::from_index(slab, self.head.load(Ordering::Relaxed)));
+    }
+
+    /// Returns the node in the slot at `index`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold a node of the queue.
+    unsafe fn node(slab: &Allocator<Node<T>, I>, index: usize) -> &Node<T> {
+        let wrapper = ManuallyDrop::new(Wrapper::from_index(slab, index));
+        &*std::ptr::addr_of!(**wrapper)
+    }
+}
+
+impl<T, I: Index> fmt::Debug for MpscQueue<T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MpscQueue")
+            .field("head", &self.head)
+            .field("tail", &self.tail)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::slab::ArrayAllocator;
+
+    #[test]
+    fn mpsc_queue() {
+        let slab = ArrayAllocator::<3, Node<Rc<u32>>>::new(None);
+        let queue = MpscQueue::new(&slab).unwrap();
+        let rc = Rc::new(0);
+        unsafe {
+            assert!(queue.is_empty(&slab));
+            assert!(queue.pop(&slab).is_none());
+            queue.push(&slab, Rc::new(1)).unwrap();
+            queue.push(&slab, rc.clone()).unwrap();
+            // The stub and two values fill the slab.
+            assert_eq!(*queue.push(&slab, Rc::new(3)).unwrap_err(), 3);
+            assert_eq!(queue.pop(&slab).map(|x| *x), Some(1));
+            assert!(!queue.is_empty(&slab));
+            queue.push(&slab, rc.clone()).unwrap();
+            assert_eq!(Rc::strong_count(&rc), 3);
+            queue.destroy(&slab);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert_eq!(slab.stats().free, 3);
+    }
+
+    #[test]
+    fn mpsc_queue_threads() {
+        const PRODUCERS: usize = 4;
+        const VALUES: usize = 1000;
+
+        let slab = ArrayAllocator::<64, Node<usize>>::new(None);
+        let queue = MpscQueue::new(&slab).unwrap();
+        let received = std::thread::scope(|s| {
+            for producer in 0..PRODUCERS {
+                let (slab, queue) = (&slab, &queue);
+                s.spawn(move || {
+                    for i in 0..VALUES {
+                        let mut value = producer * VALUES + i;
+                        while let Err(returned) = queue.push(slab, value) {
+                            value = returned;
+                            std::thread::yield_now();
+                        }
+                    }
+                });
+            }
+            let mut received = Vec::new();
+            while received.len() < PRODUCERS * VALUES {
+                match unsafe { queue.pop(&slab) } {
+                    Some(value) => received.push(value),
+                    None => std::thread::yield_now(),
+                }
+            }
+            received
+        });
+        // Each producer's values arrive in order.
+        for producer in 0..PRODUCERS {
+            let values = received
+                .iter()
+                .filter(|&&value| value / VALUES == producer)
+                .collect::<Vec<_>>();
+            assert_eq!(values.len(), VALUES);
+            assert!(values.windows(2).all(|w| w[0] < w[1]));
+        }
+        unsafe { queue.destroy(&slab) };
+        assert_eq!(slab.stats().free, 64);
+    }
+}
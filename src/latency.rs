@@ -0,0 +1,130 @@
+//! Latency histograms stored within allocators, see
+//! [`crate::linked_list::Allocator::latency_histogram`].
+//!
+//! Lock wait time is recorded separately from the time spent allocating or freeing while holding
+//! the lock, so contention can be told apart from slow free list walks. As the histograms are
+//! stored within the allocator they are shared by all processes using it.
+
+use std::time::{Duration, Instant};
+
+/// The number of buckets in a [`Histogram`].
+pub const BUCKETS: usize = 40;
+
+/// A histogram of durations with logarithmic buckets.
+///
+/// Bucket `i` counts durations of `2^i` to `2^(i + 1)` nanoseconds (bucket 0 includes 0 and the
+/// last bucket includes all longer durations).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+impl Histogram {
+    /// Records a duration.
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = nanos.checked_ilog2().unwrap_or(0) as usize;
+        self.buckets[std::cmp::min(bucket, BUCKETS - 1)] += 1;
+    }
+
+    /// The number of durations recorded in each bucket.
+    #[must_use]
+    pub fn buckets(&self) -> &[u64; BUCKETS] {
+        &self.buckets
+    }
+
+    /// The number of durations recorded.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns an upper bound of the `p`th percentile (`0.0..=1.0`), or `None` if empty.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = std::cmp::max((p.clamp(0.0, 1.0) * count as f64).ceil() as u64, 1);
+        let mut seen = 0;
+        self.buckets
+            .iter()
+            .position(|&n| {
+                seen += n;
+                seen >= rank
+            })
+            .map(|bucket| Duration::from_nanos(1 << (bucket + 1)))
+    }
+}
+
+/// Latencies of an allocator, recorded while its lock is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct Latency {
+    /// Time spent waiting to acquire the lock when allocating or freeing.
+    pub lock_wait: Histogram,
+    /// Time spent allocating while holding the lock.
+    pub allocate: Histogram,
+    /// Time spent freeing while holding the lock.
+    pub free: Histogram,
+}
+
+impl Latency {
+    pub(crate) fn record_allocate(&mut self, start: Instant, locked: Instant) {
+        self.lock_wait.record(locked - start);
+        self.allocate.record(locked.elapsed());
+    }
+
+    pub(crate) fn record_free(&mut self, start: Instant, locked: Instant) {
+        self.lock_wait.record(locked - start);
+        self.free.record(locked.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.record(Duration::ZERO);
+        histogram.record(Duration::from_nanos(1));
+        histogram.record(Duration::from_nanos(1000));
+        histogram.record(Duration::from_secs(1 << 20));
+        assert_eq!(histogram.buckets()[0], 2);
+        assert_eq!(histogram.buckets()[9], 1);
+        assert_eq!(histogram.buckets()[BUCKETS - 1], 1);
+        assert_eq!(histogram.count(), 4);
+    }
+
+    #[test]
+    fn histogram_percentile() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(0.5), None);
+        for _ in 0..99 {
+            histogram.record(Duration::from_nanos(100));
+        }
+        histogram.record(Duration::from_nanos(5000));
+        assert_eq!(histogram.percentile(0.5), Some(Duration::from_nanos(128)));
+        assert_eq!(histogram.percentile(0.99), Some(Duration::from_nanos(128)));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::from_nanos(8192)));
+    }
+}
@@ -1,6 +1,11 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![feature(ptr_metadata)]
 #![feature(int_roundings)]
 #![feature(nonnull_slice_from_raw_parts)]
+#![feature(unsize)]
+#![cfg_attr(feature = "sanitizer", feature(cfg_sanitize))]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 #![warn(clippy::pedantic)]
 #![allow(
     clippy::cast_precision_loss,
@@ -9,18 +14,186 @@
     clippy::let_and_return
 )]
 
+// Modules which only use `core` name it `std`, so they build either way.
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+
+pub mod error;
+
+pub use error::AllocError;
+
+pub mod index;
+
+pub use index::Index;
+
+pub mod raw;
+
+pub use raw::{
+    AllocRequest, OomHook, Priority, RawArrayAllocator, Watermark, WatermarkEvent, WatermarkHook,
+};
+
+pub mod arena;
+
+pub use arena::ArenaAlloc;
+
+#[cfg(feature = "std")]
+pub mod arenas;
+
+pub mod frozen;
+
+pub use frozen::FrozenArena;
+
+#[cfg(feature = "std")]
+pub mod snapshot;
+
+pub mod persist;
+
 pub mod linked_list;
 
-pub type LinkedListArrayAllocator<const N: usize> = linked_list::ArrayAllocator<N>;
-pub type LinkedListAllocator = linked_list::Allocator;
-pub type LinkedListWrapper<'a> = linked_list::Wrapper<'a>;
-pub type LinkedListValue<'a, T> = linked_list::Value<'a, T>;
-pub type LinkedListSlice<'a, T> = linked_list::Slice<'a, T>;
+pub type LinkedListArrayAllocator<const N: usize, I = usize> = linked_list::ArrayAllocator<N, I>;
+pub type LinkedListOutOfBandArrayAllocator<const N: usize, I = usize> =
+    linked_list::OutOfBandArrayAllocator<N, I>;
+pub type LinkedListAllocator<I = usize> = linked_list::Allocator<I>;
+pub type LinkedListWrapper<'a, I = usize> = linked_list::Wrapper<'a, I>;
+pub type LinkedListValue<'a, T, I = usize> = linked_list::Value<'a, T, I>;
+pub type LinkedListSlice<'a, T, I = usize> = linked_list::Slice<'a, T, I>;
+pub type LinkedListSliceView<'s, T, I = usize> = linked_list::SliceView<'s, T, I>;
+pub type LinkedListRawValue<T> = linked_list::RawValue<T>;
+pub type LinkedListRawSlice<T> = linked_list::RawSlice<T>;
+pub type LinkedListSubAllocator<'a, I = usize> = linked_list::SubAllocator<'a, I>;
+pub type LinkedListOwnedWrapper<A, I = usize> = linked_list::OwnedWrapper<A, I>;
+pub type LinkedListOwnedValue<T, A, I = usize> = linked_list::OwnedValue<T, A, I>;
+pub type LinkedListOwnedSlice<T, A, I = usize> = linked_list::OwnedSlice<T, A, I>;
+
+pub mod boundary_tag;
+
+pub type BoundaryTagArrayAllocator<const N: usize, I = usize> = boundary_tag::ArrayAllocator<N, I>;
+pub type BoundaryTagAllocator<I = usize> = boundary_tag::Allocator<I>;
+pub type BoundaryTagWrapper<'a, I = usize> = boundary_tag::Wrapper<'a, I>;
+
+pub mod tlsf;
+
+pub type TlsfArrayAllocator<const N: usize, I = usize> = tlsf::ArrayAllocator<N, I>;
+pub type TlsfAllocator<I = usize> = tlsf::Allocator<I>;
+pub type TlsfWrapper<'a, I = usize> = tlsf::Wrapper<'a, I>;
+
+pub mod stack;
+
+pub type StackArrayAllocator<const N: usize, I = usize> = stack::ArrayAllocator<N, I>;
+pub type StackAllocator<I = usize> = stack::Allocator<I>;
+pub type StackWrapper<'a, I = usize> = stack::Wrapper<'a, I>;
+
+pub mod lock_free;
+
+pub type LockFreeArrayAllocator<const N: usize> = lock_free::ArrayAllocator<N>;
+pub type LockFreeAllocator = lock_free::Allocator;
+pub type LockFreeWrapper<'a> = lock_free::Wrapper<'a>;
+
+pub mod instrumented;
+
+pub use instrumented::Instrumented;
+
+pub mod broadcast;
+
+pub use broadcast::Broadcast;
+
+pub mod bitset;
+
+pub use bitset::Bitset;
+
+pub mod channel;
+
+pub use channel::channel;
+
+pub mod rpc;
+
+pub use rpc::rpc;
+
+pub mod ipc;
+
+pub use ipc::MpscQueue;
+
+pub mod intrusive;
+
+pub mod btree;
+
+pub mod collections;
+
+pub use collections::{ABox, AVec, Deque, Log, SlotMap};
+
+pub mod string;
+
+pub use string::{ArenaCStr, ArenaStr, ArenaString};
+
+pub mod volatile;
+
+pub use volatile::VolatileSlice;
+
+pub mod padded;
+
+pub use padded::PaddedSlice;
 
 pub mod slab;
 
-pub type SlabArrayAllocator<const N: usize, T> = slab::ArrayAllocator<N, T>;
-pub type SlabAllocator<T> = slab::Allocator<T>;
-pub type SlabWrapper<'a, T> = slab::Wrapper<'a, T>;
+pub type SlabArrayAllocator<const N: usize, T, I = usize> = slab::ArrayAllocator<N, T, I>;
+pub type SlabAllocator<T, I = usize> = slab::Allocator<T, I>;
+pub type SlabWrapper<'a, T, I = usize> = slab::Wrapper<'a, T, I>;
+pub type SlabRunReservation<'a, T, I = usize> = slab::RunReservation<'a, T, I>;
+pub type SlabOwnedWrapper<T, A, I = usize> = slab::OwnedWrapper<T, A, I>;
+pub type SlabShared<'a, T, I = usize> = slab::Shared<'a, T, I>;
+
+pub mod slab_gen;
+
+pub type GenSlabArrayAllocator<const N: usize, T, I = usize> = slab_gen::ArrayAllocator<N, T, I>;
+pub type GenSlabAllocator<T, I = usize> = slab_gen::Allocator<T, I>;
+pub type GenSlabWrapper<'a, T, I = usize> = slab_gen::Wrapper<'a, T, I>;
+
+#[cfg(feature = "std")]
+pub mod registry;
+
+#[cfg(feature = "std")]
+pub use registry::SlabRegistry;
+
+pub mod integrity;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "metrics")]
+pub mod instrument;
+
+#[cfg(feature = "latency")]
+pub mod latency;
+
+#[cfg(feature = "sanitizer")]
+mod sanitizer;
+
+#[cfg(feature = "quarantine")]
+pub mod quarantine;
+
+#[cfg(feature = "canaries")]
+pub mod canary;
+
+#[cfg(feature = "audit")]
+pub mod audit;
+
+#[cfg(feature = "ownership")]
+pub mod ownership;
+
+#[cfg(feature = "async")]
+mod waiters;
+
+#[cfg(feature = "profiling")]
+pub mod profiling;
+
+#[cfg(feature = "proptest-support")]
+pub mod proptest_support;
+
+mod futex;
 
 pub(crate) mod mutex;
+
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+
+pub use mutex::MutexAttr;
@@ -1,41 +1,138 @@
+use std::alloc::Layout;
 use std::cmp::Ordering;
+use std::fmt;
 use std::marker::PhantomData;
+use std::marker::Unsize;
 use std::mem::size_of;
 use std::num::NonZeroUsize;
 use std::ops::{Deref, DerefMut, Drop};
-use std::ptr::NonNull;
+use std::ptr::{NonNull, Pointee};
+use std::slice::SliceIndex;
+use std::sync::atomic::AtomicBool;
 
 #[cfg(feature = "log")]
 use log::trace;
 
+use crate::error::{none_on_oom, AllocError};
+use crate::raw::{
+    AllocRequest, OomHook, Priority, RawAllocation, RawArrayAllocator, Stats, WatermarkEvent,
+    WatermarkHook, Watermarks,
+};
+use crate::Index;
+
 #[derive(Debug)]
 #[repr(C)]
-pub struct ArrayAllocator<const N: usize> {
-    allocator: Allocator,
-    data: [Block; N],
+pub struct ArrayAllocator<const N: usize, I = usize> {
+    allocator: Allocator<I>,
+    data: [Block<I>; N],
 }
-impl<const N: usize> ArrayAllocator<N> {
+impl<const N: usize, I: Index> ArrayAllocator<N, I> {
+    /// # Panics
+    ///
+    /// When failing to initialize the mutex, see [`ArrayAllocator::try_new`].
     #[must_use]
-    pub fn new(attr: Option<nix::sys::pthread::MutexAttr>) -> Self {
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
         #[cfg(feature = "log")]
         trace!("ArrayAllocator::new");
 
-        let mut this: Self = unsafe { std::mem::zeroed() };
+        Self::try_new(attr).unwrap()
+    }
+
+    /// # Errors
+    ///
+    /// When failing to initialize the mutex, e.g. when the process lacks permission for `attr`.
+    // Results with an infallible error aren't `must_use`.
+    #[cfg_attr(miri, must_use)]
+    pub fn try_new(attr: Option<crate::MutexAttr>) -> Result<Self, crate::mutex::Error> {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::try_new");
+
+        // Zeroing `Self` would zero the lock, which is not a valid value for every backend, so
+        // only the data is zeroed.
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        let ptr = this.as_mut_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(0, 1);
+            Allocator::try_init(std::ptr::addr_of_mut!((*ptr).allocator), attr, N)?;
+            Ok(this.assume_init())
+        }
+    }
+}
+
+impl<const N: usize, I: Index> fmt::Display for ArrayAllocator<N, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.allocator.fmt(f)
+    }
+}
+
+impl<const N: usize, I> Deref for ArrayAllocator<N, I> {
+    type Target = Allocator<I>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+impl<const N: usize, I> DerefMut for ArrayAllocator<N, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.allocator
+    }
+}
+
+/// An [`ArrayAllocator`] which stores its free list apart from the data blocks, so a buffer
+/// overrun by an allocation cannot corrupt the allocator, at the cost of twice the memory.
+#[derive(Debug)]
+#[repr(C)]
+pub struct OutOfBandArrayAllocator<const N: usize, I = usize> {
+    allocator: Allocator<I>,
+    meta: [Block<I>; N],
+    data: [Block<I>; N],
+}
+impl<const N: usize, I: Index> OutOfBandArrayAllocator<N, I> {
+    /// # Panics
+    ///
+    /// When failing to initialize the mutex, see [`OutOfBandArrayAllocator::try_new`].
+    #[must_use]
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("OutOfBandArrayAllocator::new");
+
+        Self::try_new(attr).unwrap()
+    }
+
+    /// # Errors
+    ///
+    /// When failing to initialize the mutex, e.g. when the process lacks permission for `attr`.
+    // Results with an infallible error aren't `must_use`.
+    #[cfg_attr(miri, must_use)]
+    pub fn try_new(attr: Option<crate::MutexAttr>) -> Result<Self, crate::mutex::Error> {
+        #[cfg(feature = "log")]
+        trace!("OutOfBandArrayAllocator::try_new");
+
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        let ptr = this.as_mut_ptr();
         unsafe {
-            Allocator::init(&mut this.allocator, attr, N);
+            std::ptr::addr_of_mut!((*ptr).meta).write_bytes(0, 1);
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(0, 1);
+            Allocator::try_init_out_of_band(std::ptr::addr_of_mut!((*ptr).allocator), attr, N)?;
+            Ok(this.assume_init())
         }
-        this
     }
 }
 
-impl<const N: usize> Deref for ArrayAllocator<N> {
-    type Target = Allocator;
+impl<const N: usize, I: Index> fmt::Display for OutOfBandArrayAllocator<N, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.allocator.fmt(f)
+    }
+}
+
+impl<const N: usize, I> Deref for OutOfBandArrayAllocator<N, I> {
+    type Target = Allocator<I>;
 
     fn deref(&self) -> &Self::Target {
         &self.allocator
     }
 }
-impl<const N: usize> DerefMut for ArrayAllocator<N> {
+impl<const N: usize, I> DerefMut for OutOfBandArrayAllocator<N, I> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.allocator
     }
@@ -43,9 +140,9 @@ impl<const N: usize> DerefMut for ArrayAllocator<N> {
 
 #[derive(Debug)]
 #[repr(C)]
-pub struct Allocator(super::mutex::Mutex<InnerAllocator>);
+pub struct Allocator<I = usize>(super::mutex::Mutex<InnerAllocator<I>>);
 
-impl Allocator {
+impl<I: Index> Allocator<I> {
     /// Initializes `Self` at `ptr`.
     ///
     /// # Safety
@@ -54,20 +151,131 @@ impl Allocator {
     ///
     /// # Panics
     ///
-    /// When failing to initialize the inner mutex.
-
-    pub unsafe fn init(ptr: *mut Self, attr: Option<nix::sys::pthread::MutexAttr>, n: usize) {
+    /// When failing to initialize the inner mutex or when `n` is greater than [`Index::MAX`].
+    pub unsafe fn init(ptr: *mut Self, attr: Option<crate::MutexAttr>, n: usize) {
         #[cfg(feature = "log")]
         trace!("Allocator::init");
-        (*ptr).0.lock = nix::sys::pthread::Mutex::new(attr).unwrap();
+
+        Self::try_init(ptr, attr, n).unwrap();
+    }
+
+    /// Initializes `Self` at `ptr`, see [`Allocator::init`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Errors
+    ///
+    /// When failing to initialize the inner mutex, e.g. with `EPERM` or `ENOMEM`, in which case
+    /// `ptr` is left uninitialized.
+    ///
+    /// # Panics
+    ///
+    /// When `n` is greater than [`Index::MAX`].
+    pub unsafe fn try_init(
+        ptr: *mut Self,
+        attr: Option<crate::MutexAttr>,
+        n: usize,
+    ) -> Result<(), crate::mutex::Error> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_init");
+
+        Self::init_layout(ptr, attr, n, false)
+    }
+
+    /// Initializes `Self` at `ptr` with the free list stored apart from the data blocks.
+    ///
+    /// `ptr` must be followed by `2 * n` blocks, the first `n` hold the free list and the last `n`
+    /// are allocated. As the allocator never writes to the data blocks, a buffer overrun by an
+    /// allocation cannot corrupt the free list.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the inner mutex or when `n` is greater than [`Index::MAX`].
+    pub unsafe fn init_out_of_band(ptr: *mut Self, attr: Option<crate::MutexAttr>, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::init_out_of_band");
+
+        Self::try_init_out_of_band(ptr, attr, n).unwrap();
+    }
+
+    /// Initializes `Self` at `ptr` with the free list stored apart from the data blocks, see
+    /// [`Allocator::init_out_of_band`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Errors
+    ///
+    /// When failing to initialize the inner mutex, in which case `ptr` is left uninitialized.
+    ///
+    /// # Panics
+    ///
+    /// When `n` is greater than [`Index::MAX`].
+    pub unsafe fn try_init_out_of_band(
+        ptr: *mut Self,
+        attr: Option<crate::MutexAttr>,
+        n: usize,
+    ) -> Result<(), crate::mutex::Error> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_init_out_of_band");
+
+        Self::init_layout(ptr, attr, n, true)
+    }
+
+    unsafe fn init_layout(
+        ptr: *mut Self,
+        attr: Option<crate::MutexAttr>,
+        n: usize,
+        out_of_band: bool,
+    ) -> Result<(), crate::mutex::Error> {
+        std::ptr::addr_of_mut!((*ptr).0.lock).write(crate::mutex::RawMutex::new(attr)?);
+        #[cfg(feature = "watchdog")]
+        std::ptr::addr_of_mut!((*ptr).0.owner).write(crate::watchdog::Owner::new());
 
         #[cfg(feature = "log")]
         trace!("Allocator::init 2");
-        <InnerAllocator>::init((*ptr).0.get(), n);
+        <InnerAllocator<I>>::init((*ptr).0.get(), n, out_of_band);
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::clear(ptr as usize);
+        Ok(())
+    }
+
+    /// Prepares the initialized allocator at `ptr` for use by the current process, e.g. after
+    /// reading it from a file, reinitializing its lock and clearing its hooks, as they belong to
+    /// the process which set them.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be an initialized allocator not in use, e.g. by another process.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the inner mutex.
+    pub(crate) unsafe fn reattach(ptr: *mut Self, attr: Option<crate::MutexAttr>) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::reattach");
+
+        std::ptr::addr_of_mut!((*ptr).0.lock).write(crate::mutex::RawMutex::new(attr).unwrap());
+        #[cfg(feature = "watchdog")]
+        std::ptr::addr_of_mut!((*ptr).0.owner).write(crate::watchdog::Owner::new());
+        let inner_allocator = (*ptr).0.get();
+        std::ptr::addr_of_mut!((*inner_allocator).oom_hook).write(None);
+        std::ptr::addr_of_mut!((*inner_allocator).watermarks).write(None);
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::clear(ptr as usize);
     }
 
     /// Allocates zero blocks.
-    pub fn allocate_zero(&self) -> Wrapper {
+    pub fn allocate_zero(&self) -> Wrapper<I> {
         #[cfg(feature = "log")]
         trace!("Allocator::allocate_zero");
 
@@ -83,81 +291,320 @@ impl Allocator {
     /// # Panics
     ///
     /// When locking the mutex fails.
-    pub fn allocate_nonzero(&self, blocks: NonZeroUsize) -> Option<Wrapper> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_nonzero(&self, blocks: NonZeroUsize) -> Option<Wrapper<I>> {
         #[cfg(feature = "log")]
         trace!("Allocator::allocate_nonzero");
 
+        none_on_oom(self.try_allocate_nonzero(blocks))
+    }
+
+    /// Allocates a non-zero number of blocks.
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate_nonzero(&self, blocks: NonZeroUsize) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate_nonzero");
+
+        self.try_allocate_in(blocks, None, true, Priority::Normal)
+    }
+
+    /// Allocates a given number of blocks starting at an address aligned to `align` bytes, the
+    /// free blocks skipped to reach the alignment remain free.
+    ///
+    /// Zero blocks are allocated as by [`Allocator::allocate_zero`], which are not aligned.
+    ///
+    /// # Panics
+    ///
+    /// When `align` is not a power of two or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_aligned(&self, blocks: usize, align: usize) -> Option<Wrapper<I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_aligned");
+
+        none_on_oom(self.try_allocate_aligned(blocks, align))
+    }
+
+    /// Allocates a given number of blocks starting at an address aligned to `align` bytes, see
+    /// [`Allocator::allocate_aligned`].
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough or when locking the mutex fails.
+    ///
+    /// # Panics
+    ///
+    /// When `align` is not a power of two.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate_aligned(
+        &self,
+        blocks: usize,
+        align: usize,
+    ) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate_aligned");
+
+        assert!(
+            align.is_power_of_two(),
+            "alignment {align} is not a power of two"
+        );
+        if let Ok(nonzero) = NonZeroUsize::try_from(blocks) {
+            self.try_allocate_in(nonzero, Some(align), true, Priority::Normal)
+        } else {
+            Ok(self.allocate_zero())
+        }
+    }
+
+    /// Allocates blocks for `layout`, starting at an address aligned to `layout.align()` bytes,
+    /// e.g. for over-aligned types such as SIMD vectors which [`Allocator::allocate_value`]
+    /// rejects.
+    ///
+    /// A zero sized layout is allocated as by [`Allocator::allocate_zero`], which is not aligned.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_layout(&self, layout: Layout) -> Option<Wrapper<I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_layout");
+
+        none_on_oom(self.try_allocate_layout(layout))
+    }
+
+    /// Allocates blocks for `layout`, see [`Allocator::allocate_layout`].
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate_layout(&self, layout: Layout) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate_layout");
+
+        let blocks = layout.size().div_ceil(size_of::<Block<I>>());
+        if layout.align() > std::mem::align_of::<Block<I>>() {
+            self.try_allocate_aligned(blocks, layout.align())
+        } else {
+            self.try_allocate(blocks)
+        }
+    }
+
+    /// Allocates a given number of blocks starting on a page boundary, e.g. for `mprotect` or
+    /// `O_DIRECT` IO, see [`Allocator::allocate_aligned`].
+    ///
+    /// # Panics
+    ///
+    /// When the page size cannot be queried or when locking the mutex fails.
+    #[cfg(feature = "pthread")]
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_page_aligned(&self, blocks: usize) -> Option<Wrapper<I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_page_aligned");
+
+        let page_size = nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+            .unwrap()
+            .unwrap();
+        self.allocate_aligned(blocks, usize::try_from(page_size).unwrap())
+    }
+
+    /// Allocates a non-zero number of blocks, starting at an address aligned to `align` bytes if
+    /// given.
+    #[allow(clippy::too_many_lines)]
+    #[cfg_attr(feature = "profiling", track_caller)]
+    fn try_allocate_in(
+        &self,
+        blocks: NonZeroUsize,
+        align: Option<usize>,
+        wait: bool,
+        priority: Priority,
+    ) -> Result<Wrapper<I>, AllocError> {
         let blocks = blocks.get();
 
-        let mut allocator_guard = self.0.lock().unwrap();
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "linked_list::allocate",
+            blocks,
+            head = tracing::field::Empty,
+            index = tracing::field::Empty,
+        )
+        .entered();
+
+        #[cfg(feature = "latency")]
+        let start = std::time::Instant::now();
+
+        if self.is_frozen() {
+            return Err(AllocError::Frozen);
+        }
+        let mut allocator_guard = if wait {
+            self.0.lock().map_err(AllocError::LockFailed)?
+        } else {
+            self.0
+                .try_lock()
+                .map_err(AllocError::LockFailed)?
+                .ok_or(AllocError::WouldBlock)?
+        };
+        #[cfg(feature = "latency")]
+        let locked = std::time::Instant::now();
         let allocator = &mut *allocator_guard;
-        let data = unsafe { allocator.data().as_mut() };
+        // The allocator may have been frozen while waiting for the lock.
+        if allocator.frozen.load(std::sync::atomic::Ordering::Acquire) {
+            return Err(AllocError::Frozen);
+        }
 
-        let rtn = if let Some(next) = allocator.head {
-            match blocks.cmp(&data[next].size) {
-                Ordering::Equal => {
-                    allocator.head = data[next].next;
-                    Some(Wrapper {
-                        allocator: self,
-                        index: next,
-                        size: blocks,
-                    })
-                }
-                Ordering::Less => {
-                    let new_index = next + blocks;
-                    data[new_index] = Block {
-                        size: data[next].size - blocks,
-                        next: data[next].next,
-                    };
-                    allocator.head = Some(new_index);
-                    Some(Wrapper {
-                        allocator: self,
-                        index: next,
-                        size: blocks,
-                    })
-                }
-                Ordering::Greater => {
-                    let mut next_opt = data[next].next;
-                    loop {
-                        if let Some(next) = next_opt {
-                            match blocks.cmp(&data[next].size) {
-                                Ordering::Equal => {
-                                    allocator.head = data[next].next;
-                                    break Some(Wrapper {
-                                        allocator: self,
-                                        index: next,
-                                        size: blocks,
-                                    });
-                                }
-                                Ordering::Less => {
-                                    let new_index = next + blocks;
-                                    data[new_index] = Block {
-                                        size: data[next].size - blocks,
-                                        next: data[next].next,
-                                    };
-                                    allocator.head = Some(new_index);
-                                    break Some(Wrapper {
-                                        allocator: self,
-                                        index: next,
-                                        size: blocks,
-                                    });
-                                }
-                                Ordering::Greater => {
-                                    next_opt = data[next].next;
-                                }
-                            }
-                        } else {
-                            break None;
-                        }
-                    }
-                }
-            }
+        // The guards before and after the allocation.
+        #[cfg(feature = "canaries")]
+        let blocks = if allocator.canaries {
+            blocks.checked_add(2).ok_or(AllocError::InvalidLayout)?
         } else {
+            blocks
+        };
+        // The header linking the allocation into the list of live allocations.
+        #[cfg(feature = "audit")]
+        let header = if allocator.audit {
+            crate::audit::blocks::<Block<I>, I>()
+        } else {
+            0
+        };
+        #[cfg(not(feature = "audit"))]
+        let header = 0;
+        let blocks = blocks
+            .checked_add(header)
+            .ok_or(AllocError::InvalidLayout)?;
+
+        #[cfg(feature = "tracing")]
+        span.record("head", tracing::field::debug(allocator.head));
+
+        #[cfg(feature = "testing")]
+        let injected = allocator.failure.inject(blocks * size_of::<Block<I>>());
+        #[cfg(not(feature = "testing"))]
+        let injected = false;
+
+        // Normal allocations may not use the reserve.
+        let reserved = priority == Priority::Normal
+            && allocator.reserve > 0
+            && allocator.size - allocator.used < blocks + allocator.reserve;
+
+        let rtn = if injected || reserved {
             None
+        } else if let Some(align) = align {
+            // The allocation is preceded by a guard when canaries are enabled.
+            #[cfg(feature = "canaries")]
+            let lead = header + usize::from(allocator.canaries);
+            #[cfg(not(feature = "canaries"))]
+            let lead = header;
+            allocator
+                .allocate_aligned(blocks, align, lead)
+                .map(|index| Wrapper {
+                    allocator: self,
+                    index,
+                    size: blocks,
+                })
+        } else {
+            allocator.allocate_fit(blocks).map(|index| Wrapper {
+                allocator: self,
+                index,
+                size: blocks,
+            })
+        };
+
+        let rtn = rtn.ok_or_else(|| AllocError::OutOfMemory {
+            requested: blocks,
+            largest_free: allocator.largest_free(),
+        });
+        let oom_hook = allocator.oom_hook;
+        #[cfg(any(
+            feature = "sanitizer",
+            feature = "debug-fill",
+            feature = "canaries",
+            feature = "audit"
+        ))]
+        let data = unsafe { allocator.data().as_mut() };
+
+        #[cfg(feature = "sanitizer")]
+        if let (Ok(wrapper), true) = (&rtn, allocator.poisoning) {
+            crate::sanitizer::unpoison(&data[wrapper.index..wrapper.index + blocks]);
+        }
+
+        #[cfg(feature = "debug-fill")]
+        if let Ok(wrapper) = &rtn {
+            crate::raw::fill(
+                &mut data[wrapper.index..wrapper.index + blocks],
+                crate::raw::ALLOCATED_FILL,
+            );
+        }
+
+        #[cfg(feature = "audit")]
+        let rtn = rtn.map(|mut wrapper| {
+            if header == 0 {
+                return wrapper;
+            }
+            unsafe { crate::audit::link::<_, I>(data, &mut allocator.live, wrapper.index, blocks) };
+            wrapper.index += header;
+            wrapper.size -= header;
+            wrapper
+        });
+
+        #[cfg(feature = "canaries")]
+        let rtn = rtn.map(|mut wrapper| {
+            if !allocator.canaries {
+                return wrapper;
+            }
+            let size = wrapper.size;
+            let (before, after) = data.split_at_mut(wrapper.index + size - 1);
+            crate::canary::write::<_, I>(&mut before[wrapper.index], &mut after[0], size - 2);
+            wrapper.index += 1;
+            wrapper.size -= 2;
+            wrapper
+        });
+
+        if rtn.is_ok() {
+            allocator.allocations += 1;
+            allocator.used += blocks;
+            allocator.peak = std::cmp::max(allocator.peak, allocator.used);
+        }
+        let crossed = match (&rtn, &mut allocator.watermarks) {
+            (Ok(_), Some(watermarks)) => watermarks.allocated(blocks),
+            _ => None,
         };
 
+        #[cfg(feature = "latency")]
+        allocator.latency.record_allocate(start, locked);
+
         drop(allocator_guard);
 
+        if let Some((hook, event)) = crossed {
+            hook(&event);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Ok(wrapper) = &rtn {
+            span.record("index", wrapper.index);
+        }
+
+        #[cfg(feature = "profiling")]
+        if let Ok(wrapper) = &rtn {
+            crate::profiling::record(
+                self as *const Self as usize,
+                wrapper.index,
+                blocks,
+                std::panic::Location::caller(),
+            );
+        }
+
+        #[cfg(feature = "metrics")]
+        match rtn {
+            Ok(_) => crate::instrument::allocated("linked_list", blocks * size_of::<Block<I>>()),
+            Err(_) => crate::instrument::failed("linked_list"),
+        }
+
+        if rtn.is_err() {
+            self.out_of_memory(oom_hook, blocks);
+        }
+
         rtn
     }
 
@@ -166,7 +613,8 @@ impl Allocator {
     /// # Panics
     ///
     /// When locking the mutex fails.
-    pub fn allocate(&self, blocks: usize) -> Option<Wrapper> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate(&self, blocks: usize) -> Option<Wrapper<I>> {
         #[cfg(feature = "log")]
         trace!("Allocator::allocate");
 
@@ -177,1487 +625,6700 @@ impl Allocator {
         }
     }
 
-    pub fn allocate_value<T>(&self) -> Option<Value<T>> {
-        #[cfg(feature = "log")]
-        trace!("Allocator::allocate_value");
-        let blocks = size_of::<T>().div_ceil(size_of::<Block>());
-        self.allocate(blocks).map(|wrapper| Value {
-            wrapper,
-            __marker: PhantomData,
-        })
-    }
-
-    /// Allocates `[T]` where `length == 0`.
-    pub fn allocate_zero_slice<T>(&self) -> Slice<T> {
+    /// Allocates a given number of blocks.
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate(&self, blocks: usize) -> Result<Wrapper<I>, AllocError> {
         #[cfg(feature = "log")]
-        trace!("Allocator::allocate_zero_slice");
+        trace!("Allocator::try_allocate");
 
-        Slice {
-            wrapper: self.allocate_zero(),
-            len: 0,
-            __marker: PhantomData,
+        if let Ok(nonzero) = NonZeroUsize::try_from(blocks) {
+            self.try_allocate_nonzero(nonzero)
+        } else {
+            Ok(self.allocate_zero())
         }
     }
 
-    /// Allocates `[T]` where `length > 0`.
+    /// Allocates a given number of blocks with a given priority, see [`Allocator::set_reserve`].
     ///
     /// # Panics
     ///
     /// When locking the mutex fails.
-    pub fn allocate_nonzero_slice<T>(&self, len: NonZeroUsize) -> Option<Slice<T>> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_with_priority(&self, blocks: usize, priority: Priority) -> Option<Wrapper<I>> {
         #[cfg(feature = "log")]
-        trace!("Allocator::allocate_nonzero_slice");
-
-        let len = len.get();
+        trace!("Allocator::allocate_with_priority");
 
-        let blocks =
-            NonZeroUsize::try_from((len * size_of::<T>()).div_ceil(size_of::<Block>())).unwrap();
+        none_on_oom(self.try_allocate_with_priority(blocks, priority))
+    }
 
-        debug_assert!(blocks.get() * size_of::<Block>() >= len * size_of::<T>());
+    /// Allocates a given number of blocks with a given priority, see [`Allocator::set_reserve`].
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough, when a normal allocation would use the reserve
+    /// or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate_with_priority(
+        &self,
+        blocks: usize,
+        priority: Priority,
+    ) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate_with_priority");
 
-        self.allocate_nonzero(blocks).map(|wrapper| Slice {
-            wrapper,
-            len,
-            __marker: PhantomData,
-        })
+        if let Ok(nonzero) = NonZeroUsize::try_from(blocks) {
+            self.try_allocate_in(nonzero, None, true, priority)
+        } else {
+            Ok(self.allocate_zero())
+        }
     }
 
-    /// Allocates `[T]`.
+    /// Allocates space for a `T` as a `MaybeUninit<T>`, initialized by [`Value::init`] rather
+    /// than assigned through a reference to uninitialized memory.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
     ///
     /// # Panics
     ///
     /// When locking the mutex fails.
-    pub fn allocate_slice<T>(&self, len: usize) -> Option<Slice<T>> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_value_uninit<T>(&self) -> Option<Value<std::mem::MaybeUninit<T>, I>> {
         #[cfg(feature = "log")]
-        trace!("Allocator::allocate_slice");
+        trace!("Allocator::allocate_value_uninit");
 
-        if let Ok(nonzero) = NonZeroUsize::try_from(len) {
-            self.allocate_nonzero_slice(nonzero)
-        } else {
-            Some(self.allocate_zero_slice())
-        }
+        self.allocate_value()
     }
 
-    /// # Safety
+    /// Allocates space for a `T` then writes the value returned by `f` into it, so a large value
+    /// can be built directly in the blocks rather than on the stack. `f` isn't called when the
+    /// allocation fails.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_value_with<T>(&self, f: impl FnOnce() -> T) -> Option<Value<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_value_with");
 
-    pub unsafe fn inner(&self) -> &super::mutex::Mutex<InnerAllocator> {
-        &self.0
+        Some(self.allocate_value_uninit()?.init(f()))
     }
 
+    /// Allocates space for a `T` and passes it to `f` to initialize in place, e.g. field by field
+    /// through [`std::ptr::addr_of_mut!`], so the value is never built on the stack. `f` isn't
+    /// called when the allocation fails.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
     /// # Safety
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
+    /// `f` must initialize the value.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub unsafe fn allocate_value_in_place<T>(
+        &self,
+        f: impl FnOnce(&mut std::mem::MaybeUninit<T>),
+    ) -> Option<Value<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_value_in_place");
 
-    pub unsafe fn inner_mut(&mut self) -> &mut super::mutex::Mutex<InnerAllocator> {
-        &mut self.0
+        let mut value = self.allocate_value_uninit()?;
+        f(&mut value);
+        Some(value.assume_init())
     }
-}
-
-#[derive(Debug, Eq, PartialEq)]
-#[repr(C)]
-pub struct InnerAllocator {
-    head: Option<usize>,
-    size: usize,
-}
 
-impl InnerAllocator {
-    /// # Safety
+    /// Allocates space for a `T`.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    #[must_use]
-    pub unsafe fn head(&self) -> &Option<usize> {
+    /// The value is uninitialized but is dropped with the [`Value`], so when `T` has a destructor
+    /// it must be written, e.g. with [`std::ptr::write`], before being assigned or dropped. See
+    /// [`Allocator::allocate_value_uninit`].
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block, see [`Allocator::allocate_layout`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_value<T>(&self) -> Option<Value<T, I>> {
         #[cfg(feature = "log")]
-        trace!("InnerAllocator::head");
+        trace!("Allocator::allocate_value");
 
-        &self.head
+        match self.try_allocate_value::<T>() {
+            Err(AllocError::InvalidLayout) => None,
+            rtn => none_on_oom(rtn),
+        }
     }
 
+    /// Constructs a value for the allocation of `size` blocks at `index` given up with
+    /// [`Value::into_raw`], e.g. by another process sharing the allocator.
+    ///
     /// # Safety
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn head_mut(&mut self) -> &mut Option<usize> {
+    /// The allocation must be live, hold an initialized `T` and not be held by another wrapper.
+    pub unsafe fn value_from_raw<T>(&self, index: usize, size: usize) -> Value<T, I> {
         #[cfg(feature = "log")]
-        trace!("InnerAllocator::head_mut");
+        trace!("Allocator::value_from_raw");
 
-        &mut self.head
+        Value {
+            wrapper: Wrapper::from_raw_parts(self, index, size),
+            metadata: (),
+            __marker: PhantomData,
+        }
     }
 
-    /// # Safety
-    ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    ///
-    /// # Panics
-    ///
-    /// When `&self == std::ptr::null()`.
-    #[must_use]
-    pub unsafe fn data(&mut self) -> NonNull<[Block]> {
+    /// Allocates `[T]` where `length == 0`.
+    pub fn allocate_zero_slice<T>(&self) -> Slice<T, I> {
         #[cfg(feature = "log")]
-        trace!("InnerAllocator::data");
+        trace!("Allocator::allocate_zero_slice");
 
-        std::ptr::NonNull::slice_from_raw_parts(
-            NonNull::new((self as *mut Self).add(1).cast()).unwrap(),
-            self.size,
-        )
+        Slice {
+            wrapper: self.allocate_zero(),
+            len: 0,
+            __marker: PhantomData,
+        }
     }
 
-    unsafe fn init(ptr: *mut Self, n: usize) {
+    /// Allocates `[T]` where `length > 0`.
+    ///
+    /// The elements are uninitialized, see [`Allocator::allocate_slice`].
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block, see [`Allocator::allocate_layout`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_nonzero_slice<T>(&self, len: NonZeroUsize) -> Option<Slice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("InnerAllocator::init");
-
-        if n > 0 {
-            #[cfg(feature = "log")]
-            trace!("InnerAllocator::init non-empty");
+        trace!("Allocator::allocate_nonzero_slice");
 
-            (*ptr).head = Some(0);
-            (*ptr).size = n;
+        if std::mem::align_of::<T>() > std::mem::align_of::<Block<I>>() {
+            return None;
+        }
+        let len = len.get();
 
-            #[cfg(feature = "log")]
-            trace!("InnerAllocator::init head written");
+        let blocks =
+            NonZeroUsize::try_from((len * size_of::<T>()).div_ceil(size_of::<Block<I>>())).unwrap();
 
-            std::ptr::write(
-                ptr.add(1).cast(),
-                Block {
-                    size: n,
-                    next: None,
-                },
-            );
-        } else {
-            #[cfg(feature = "log")]
-            trace!("InnerAllocator::init empty");
+        debug_assert!(blocks.get() * size_of::<Block<I>>() >= len * size_of::<T>());
 
-            (*ptr).head = None;
-            (*ptr).size = 0;
-        }
+        self.allocate_nonzero(blocks).map(|wrapper| Slice {
+            wrapper,
+            len,
+            __marker: PhantomData,
+        })
     }
-}
-
-#[derive(Debug, Eq, PartialEq)]
-#[repr(C)]
-pub struct Block {
-    size: usize,
-    next: Option<usize>,
-}
-
-#[derive(Debug)]
-#[repr(C)]
-pub struct Value<'a, T> {
-    pub wrapper: Wrapper<'a>,
-    __marker: PhantomData<T>,
-}
 
-impl<'a, T> Value<'a, T> {
-    #[must_use]
-    pub fn allocator(&self) -> &Allocator {
+    /// Allocates an empty [`Vec`] with space for at least `capacity` elements.
+    ///
+    /// Returns `None` when out of memory or when `T` requires a greater alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate_vec<T>(&self, capacity: usize) -> Option<Vec<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Value::allocator");
+        trace!("Allocator::allocate_vec");
 
-        self.wrapper.allocator
+        Vec::try_with_capacity_in(capacity, self)
     }
 
-    /// # Safety
+    /// Allocates `[MaybeUninit<T>]`, so the elements can be written before
+    /// [`Slice::assume_init`] converts it to a `[T]`.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator {
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_uninit<T>(
+        &self,
+        len: usize,
+    ) -> Option<Slice<std::mem::MaybeUninit<T>, I>> {
         #[cfg(feature = "log")]
-        trace!("Slice::allocator_mut");
+        trace!("Allocator::allocate_slice_uninit");
 
-        &mut self.wrapper.allocator
+        self.allocate_slice(len)
     }
 
-    #[must_use]
-    pub fn index(&self) -> usize {
+    /// Allocates `[T]`.
+    ///
+    /// The elements are uninitialized but are dropped with the [`Slice`], so when `T` has a
+    /// destructor they must be written before the slice is dropped. See
+    /// [`Allocator::allocate_slice_uninit`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice<T>(&self, len: usize) -> Option<Slice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Value::index");
+        trace!("Allocator::allocate_slice");
 
-        self.wrapper.index
+        if let Ok(nonzero) = NonZeroUsize::try_from(len) {
+            self.allocate_nonzero_slice(nonzero)
+        } else {
+            Some(self.allocate_zero_slice())
+        }
     }
 
-    /// # Safety
+    /// Allocates `len` elements spaced `size_of::<T>()` rounded up to `align` bytes apart, with
+    /// the first element aligned to `align`, so e.g. with `align = 64` each element lands on its
+    /// own cache line.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn index_mut(&mut self) -> &mut usize {
+    /// Returns `None` when `align` is not a power of two, when `T` requires a greater alignment
+    /// than `align` or when there is no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_padded<T>(
+        &self,
+        len: usize,
+        align: usize,
+    ) -> Option<crate::padded::PaddedSlice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Value::index_mut");
+        trace!("Allocator::allocate_slice_padded");
 
-        &mut self.wrapper.index
+        if !align.is_power_of_two() || std::mem::align_of::<T>() > align {
+            return None;
+        }
+        let stride = size_of::<T>().next_multiple_of(align);
+        // Space to align the first element.
+        let padding = align.saturating_sub(std::mem::align_of::<Block<I>>());
+        let bytes = len.checked_mul(stride)?.checked_add(padding)?;
+        let wrapper = self.allocate(bytes.div_ceil(size_of::<Block<I>>()))?;
+        let offset = wrapper[..].as_ptr().cast::<u8>().align_offset(align);
+        Some(crate::padded::PaddedSlice {
+            wrapper,
+            offset,
+            stride,
+            len,
+            __marker: PhantomData,
+        })
     }
 
-    #[must_use]
-    pub fn size(&self) -> usize {
+    /// Allocates `value` as an unsized `T`, e.g. a trait object.
+    ///
+    /// Returns `None` when `U` requires a greater alignment than a block or when there is no free
+    /// region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_unsized<T: ?Sized, U: Unsize<T>>(&self, value: U) -> Option<Value<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Value::size");
+        trace!("Allocator::allocate_unsized");
 
-        self.wrapper.size
+        if std::mem::align_of::<U>() > std::mem::align_of::<Block<I>>() {
+            return None;
+        }
+        let metadata = std::ptr::metadata(&value as &T);
+        let mut wrapper = self.allocate(size_of::<U>().div_ceil(size_of::<Block<I>>()))?;
+        unsafe { wrapper[..].as_mut_ptr().cast::<U>().write(value) };
+        Some(Value {
+            wrapper,
+            metadata,
+            __marker: PhantomData,
+        })
     }
 
-    /// # Safety
+    /// Allocates a copy of `values` as a `[T]` behind a single [`Value`].
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn size_mut(&mut self) -> &mut usize {
+    /// Returns `None` when `T` requires a greater alignment than a block or when there is no free
+    /// region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_value<T: Copy>(&self, values: &[T]) -> Option<Value<[T], I>> {
         #[cfg(feature = "log")]
-        trace!("Value::size_mut");
+        trace!("Allocator::allocate_slice_value");
 
-        &mut self.wrapper.size
+        if std::mem::align_of::<T>() > std::mem::align_of::<Block<I>>() {
+            return None;
+        }
+        unsafe { self.allocate_copy(values) }
     }
 
-    #[must_use]
-    pub fn wrapper(&self) -> &Wrapper<'a> {
+    /// Allocates a copy of `s` as a `str` behind a single [`Value`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_str_value(&self, s: &str) -> Option<Value<str, I>> {
         #[cfg(feature = "log")]
-        trace!("Value::wrapper");
+        trace!("Allocator::allocate_str_value");
 
-        &self.wrapper
+        unsafe { self.allocate_copy(s) }
     }
 
+    /// Allocates a bitwise copy of `value`.
+    ///
     /// # Safety
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn wrapper_mut(&mut self) -> &mut Wrapper<'a> {
-        #[cfg(feature = "log")]
-        trace!("Value::wrapper_mut");
-
-        &mut self.wrapper
+    /// `T` must be valid to copy bitwise and must not require a greater alignment than a block.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    unsafe fn allocate_copy<T: ?Sized>(&self, value: &T) -> Option<Value<T, I>> {
+        let bytes = std::mem::size_of_val(value);
+        let mut wrapper = self.allocate(bytes.div_ceil(size_of::<Block<I>>()))?;
+        std::ptr::copy_nonoverlapping(
+            (value as *const T).cast::<u8>(),
+            wrapper[..].as_mut_ptr().cast::<u8>(),
+            bytes,
+        );
+        Some(Value {
+            wrapper,
+            metadata: std::ptr::metadata(value),
+            __marker: PhantomData,
+        })
     }
-}
-
-impl<'a, T> Deref for Value<'a, T> {
-    type Target = T;
 
-    fn deref(&self) -> &Self::Target {
+    /// Allocates a copy of `s` followed by a NUL, e.g. to hand to C APIs.
+    ///
+    /// Returns `None` when `s` contains a NUL or when there is no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_cstr(&self, s: &str) -> Option<crate::string::ArenaCStr<I>> {
         #[cfg(feature = "log")]
-        trace!("Value::deref");
+        trace!("Allocator::allocate_cstr");
 
-        // TODO Test this deref is to the correct ptr
-        unsafe { &*self.wrapper[..].as_ptr().cast() }
+        if s.contains('\0') {
+            return None;
+        }
+        let mut bytes = self.allocate_slice_default::<u8>(s.len() + 1)?;
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        bytes[s.len()] = 0;
+        crate::string::ArenaCStr::from_bytes_with_nul(bytes).ok()
     }
-}
-impl<'a, T> DerefMut for Value<'a, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
+
+    /// Allocates a copy of `s`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_str(&self, s: &str) -> Option<crate::string::ArenaStr<I>> {
         #[cfg(feature = "log")]
-        trace!("Value::deref_mut");
+        trace!("Allocator::allocate_str");
 
-        // TODO Test this deref is to the correct ptr
-        unsafe { &mut *self.wrapper[..].as_mut_ptr().cast() }
+        let bytes = self.allocate_slice_copy_from(s.as_bytes())?;
+        crate::string::ArenaStr::from_utf8(bytes).ok()
     }
-}
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct Wrapper<'a> {
-    allocator: &'a Allocator,
-    index: usize,
-    size: usize,
-}
-
-impl<'a> Wrapper<'a> {
-    #[must_use]
-    pub fn allocator(&self) -> &Allocator {
+    /// Allocates a [`BitSet`] of `len` bits, all clear.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate_bitset(&self, len: usize) -> Option<BitSet<I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::allocator");
+        trace!("Allocator::allocate_bitset");
 
-        self.allocator
+        BitSet::new(self, len)
     }
 
-    /// # Safety
+    /// Allocates a growable copy of `s`.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator {
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate_string(&self, s: &str) -> Option<String<I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::allocator_mut");
+        trace!("Allocator::allocate_string");
 
-        &mut self.allocator
+        String::try_from_str_in(s, self)
     }
 
-    #[must_use]
-    pub fn index(&self) -> usize {
+    /// Allocates space for a `T`.
+    ///
+    /// The value is uninitialized, see [`Allocator::allocate_value`]. Over-aligned types can be
+    /// allocated with [`Allocator::allocate_layout`].
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough, when `T` requires a greater alignment than a
+    /// block or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate_value<T>(&self) -> Result<Value<T, I>, AllocError> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::index");
+        trace!("Allocator::try_allocate_value");
 
-        self.index
+        if std::mem::align_of::<T>() > std::mem::align_of::<Block<I>>() {
+            return Err(AllocError::InvalidLayout);
+        }
+        let blocks = size_of::<T>().div_ceil(size_of::<Block<I>>());
+        self.try_allocate(blocks).map(|wrapper| Value {
+            wrapper,
+            metadata: (),
+            __marker: PhantomData,
+        })
     }
 
-    /// # Safety
+    /// Allocates `[T]`.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn index_mut(&mut self) -> &mut usize {
+    /// The elements are uninitialized, see [`Allocator::allocate_slice`].
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region large enough, when the size of the slice overflows `usize`,
+    /// when `T` requires a greater alignment than a block or when locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate_slice<T>(&self, len: usize) -> Result<Slice<T, I>, AllocError> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::index_mut");
+        trace!("Allocator::try_allocate_slice");
 
-        &mut self.index
+        if std::mem::align_of::<T>() > std::mem::align_of::<Block<I>>() {
+            return Err(AllocError::InvalidLayout);
+        }
+        let bytes = len
+            .checked_mul(size_of::<T>())
+            .ok_or(AllocError::InvalidLayout)?;
+        let blocks = bytes.div_ceil(size_of::<Block<I>>());
+        self.try_allocate(blocks).map(|wrapper| Slice {
+            wrapper,
+            len,
+            __marker: PhantomData,
+        })
     }
 
-    #[must_use]
-    pub fn size(&self) -> usize {
+    /// Allocates a zeroed `T`.
+    ///
+    /// As `T` is [`bytemuck::Pod`] any bytes are a valid `T`, so the value stays valid however
+    /// its memory is written, e.g. by another process sharing the allocator.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "bytemuck")]
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_pod<T: bytemuck::Pod>(&self) -> Option<Value<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::size");
+        trace!("Allocator::allocate_pod");
 
-        self.size
+        let mut value = match self.try_allocate_value::<T>() {
+            Err(AllocError::InvalidLayout) => return None,
+            rtn => none_on_oom(rtn)?,
+        };
+        unsafe {
+            value.wrapper[..]
+                .as_mut_ptr()
+                .cast::<u8>()
+                .write_bytes(0, size_of::<T>());
+        }
+        Some(value)
     }
 
-    /// # Safety
+    /// Allocates a zeroed `[T]`, see [`Allocator::allocate_pod`].
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn size_mut(&mut self) -> &mut usize {
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "bytemuck")]
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_pod_slice<T: bytemuck::Pod>(&self, len: usize) -> Option<Slice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::size_mut");
+        trace!("Allocator::allocate_pod_slice");
 
-        &mut self.size
+        let mut slice = match self.try_allocate_slice::<T>(len) {
+            Err(AllocError::InvalidLayout) => return None,
+            rtn => none_on_oom(rtn)?,
+        };
+        unsafe {
+            slice.wrapper[..]
+                .as_mut_ptr()
+                .cast::<u8>()
+                .write_bytes(0, len * size_of::<T>());
+        }
+        Some(slice)
     }
-}
 
-impl<'a> Deref for Wrapper<'a> {
-    type Target = [Block];
+    /// Allocates the bytes of `value` archived by [`rkyv`], which can be accessed zero-copy with
+    /// [`Slice::access`], including by another process sharing the allocator as an archive holds
+    /// no absolute pointers.
+    ///
+    /// Returns `None` when there is no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When serializing `value` fails or when locking the mutex fails.
+    #[cfg(feature = "rkyv")]
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_archived<T>(&self, value: &T) -> Option<Slice<u8, I>>
+    where
+        T: for<'b> rkyv::Serialize<
+            rkyv::api::high::HighSerializer<
+                rkyv::util::AlignedVec,
+                rkyv::ser::allocator::ArenaHandle<'b>,
+                rkyv::rancor::Error,
+            >,
+        >,
+    {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_archived");
 
-    fn deref(&self) -> &Self::Target {
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(value)
+            .unwrap_or_else(|err| panic!("failed to archive value: {err}"));
+        self.allocate_slice_copy_from(&bytes)
+    }
+
+    /// Allocates `[T]` with every element initialized to `T::default()`.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_default<T: Default>(&self, len: usize) -> Option<Slice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::deref enter");
+        trace!("Allocator::allocate_slice_default");
 
-        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
-        // memory.
+        let mut slice = match self.try_allocate_slice::<T>(len) {
+            Err(AllocError::InvalidLayout) => return None,
+            rtn => none_on_oom(rtn)?,
+        };
+        // The length counts the elements written so a panic drops only those.
+        let ptr = slice.wrapper[..].as_mut_ptr().cast::<T>();
+        slice.len = 0;
+        for i in 0..len {
+            unsafe { ptr.add(i).write(T::default()) };
+            slice.len += 1;
+        }
+        Some(slice)
+    }
 
-        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+    /// Allocates `[T]` holding a copy of `values`.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_copy_from<T: Copy>(&self, values: &[T]) -> Option<Slice<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_slice_copy_from");
 
-        let slice = unsafe { &inner_allocator.data().as_ref()[self.index..self.index + self.size] };
+        let mut slice = match self.try_allocate_slice::<T>(values.len()) {
+            Err(AllocError::InvalidLayout) => return None,
+            rtn => none_on_oom(rtn)?,
+        };
+        unsafe {
+            slice.wrapper[..]
+                .as_mut_ptr()
+                .cast::<T>()
+                .copy_from_nonoverlapping(values.as_ptr(), values.len());
+        }
+        Some(slice)
+    }
 
+    /// Allocates `[T]` holding a clone of each element of `values`.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_clone_from<T: Clone>(&self, values: &[T]) -> Option<Slice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::deref exit");
+        trace!("Allocator::allocate_slice_clone_from");
 
-        slice
+        let mut slice = match self.try_allocate_slice::<T>(values.len()) {
+            Err(AllocError::InvalidLayout) => return None,
+            rtn => none_on_oom(rtn)?,
+        };
+        let ptr = slice.wrapper[..].as_mut_ptr().cast::<T>();
+        slice.len = 0;
+        for (i, value) in values.iter().enumerate() {
+            unsafe { ptr.add(i).write(value.clone()) };
+            slice.len += 1;
+        }
+        Some(slice)
     }
-}
-impl<'a> DerefMut for Wrapper<'a> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
+
+    /// Allocates `[T]` sized by [`ExactSizeIterator::len`] and writes the elements of `iter` in
+    /// place.
+    ///
+    /// Elements beyond the reported length are not consumed, and the slice is truncated to the
+    /// elements written if the iterator ends early.
+    ///
+    /// Returns `None` when there is no free region large enough or when `T` requires a greater
+    /// alignment than a block.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_slice_from_iter<T>(
+        &self,
+        iter: impl ExactSizeIterator<Item = T>,
+    ) -> Option<Slice<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::deref_mut enter");
+        trace!("Allocator::allocate_slice_from_iter");
 
-        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
-        // memory.
+        let len = iter.len();
+        let mut slice = match self.try_allocate_slice::<T>(len) {
+            Err(AllocError::InvalidLayout) => return None,
+            rtn => none_on_oom(rtn)?,
+        };
+        let ptr = slice.wrapper[..].as_mut_ptr().cast::<T>();
+        slice.len = 0;
+        for value in iter.take(len) {
+            unsafe { ptr.add(slice.len).write(value) };
+            slice.len += 1;
+        }
+        Some(slice)
+    }
 
-        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+    /// Returns a [`serde::de::DeserializeSeed`] deserializing a `T` into a [`Value`] allocated
+    /// from this allocator.
+    #[cfg(feature = "serde")]
+    pub fn value_seed<T>(&self) -> ValueSeed<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::value_seed");
 
-        let slice =
-            unsafe { &mut inner_allocator.data().as_mut()[self.index..self.index + self.size] };
+        ValueSeed {
+            allocator: self,
+            __marker: PhantomData,
+        }
+    }
 
+    /// Returns a [`serde::de::DeserializeSeed`] deserializing a sequence of `T` into a [`Slice`]
+    /// allocated from this allocator.
+    #[cfg(feature = "serde")]
+    pub fn slice_seed<T>(&self) -> SliceSeed<T, I> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::deref_mut exit");
+        trace!("Allocator::slice_seed");
 
-        slice
+        SliceSeed {
+            allocator: self,
+            __marker: PhantomData,
+        }
     }
-}
 
-impl<'a> Drop for Wrapper<'a> {
-    fn drop(&mut self) {
+    /// Frees the `size` blocks starting at `index`.
+    ///
+    /// # Safety
+    ///
+    /// The blocks must have been allocated from this allocator and must not be freed again.
+    ///
+    /// The blocks are leaked when the allocator is frozen, see [`Allocator::freeze`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    unsafe fn deallocate(&self, index: usize, size: usize) {
         #[cfg(feature = "log")]
-        trace!("Wrapper::drop enter");
+        trace!("Allocator::deallocate");
 
-        if self.size == 0 {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("linked_list::free", index, size).entered();
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::freed("linked_list", size * size_of::<Block<I>>());
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::remove(self as *const Self as usize, index);
+
+        #[cfg(feature = "latency")]
+        let start = std::time::Instant::now();
+
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        #[cfg(feature = "latency")]
+        let locked = std::time::Instant::now();
+        let inner_allocator = &mut *inner_allocator_guard;
+        if inner_allocator
+            .frozen
+            .load(std::sync::atomic::Ordering::Acquire)
+        {
             return;
         }
 
-        let mut inner_allocator_guard = self.allocator.0.lock().unwrap();
-        // To avoid a massive number of mutex deref calls we deref here.
-        let inner_allocator = &mut *inner_allocator_guard;
-        let data = unsafe { inner_allocator.data().as_mut() };
+        #[cfg(feature = "debug-checks")]
+        inner_allocator.check_free(index, size);
 
-        // ┌───┬─────┬───┐
-        // │...│index│...│
-        // └───┴─────┴───┘
-        // If there is at least 1 free block
-        if let Some(head) = inner_allocator.head {
-            let end = self.index + self.size;
-            match end.cmp(&head) {
-                // ┌───┬────┬────┬───┐
-                // │...│self│head│...│
-                // └───┴────┴────┴───┘
-                Ordering::Equal => {
-                    data[self.index] = Block {
-                        size: self.size + data[head].size,
-                        next: data[head].next,
-                    };
-                    inner_allocator.head = Some(self.index);
-                }
-                // ┌───┬────┬───┬────┬───┐
-                // │...│self│...│head│...│
-                // └───┴────┴───┴────┴───┘
-                Ordering::Less => {
-                    data[self.index] = Block {
-                        size: self.size,
-                        next: inner_allocator.head,
-                    };
-                    inner_allocator.head = Some(self.index);
-                }
-                // ┌───┬────┬───┬────┬───┐
-                // │...│head│...│self│...│
-                // └───┴────┴───┴────┴───┘
-                Ordering::Greater => {
-                    // If `self` was allocated properly
-                    let mut current_index = head;
-                    loop {
-                        let current_end = current_index + data[current_index].size;
+        #[cfg(feature = "zeroize")]
+        inner_allocator.zeroize(index, size);
 
-                        match (current_end == self.index, data[current_index].next) {
-                            // ┌───┬─────┬────┬────┬───┐
-                            // │...│index│self│next│...│
-                            // └───┴─────┴────┴────┴───┘
-                            // The self block starts at the current block and ends at the next
-                            // block.
-                            (true, Some(next_index)) if next_index == end => {
-                                // Update the size and next of the current block and return.
-                                data[current_index].next = data[next_index].next;
-                                data[current_index].size += self.size + data[next_index].size;
-                                // ┌───┬───────────────┬───┐
-                                // │...│index          │...│
-                                // └───┴───────────────┴───┘
-                                break;
-                            }
-                            // ┌───┬─────┬────┬───┬────┬───┐
-                            // │...│index│self│...│next│...│
-                            // └───┴─────┴────┴───┴────┴───┘
-                            // The self block starts at the current block and ends before the next
-                            // block.
-                            (true, Some(next_index)) => {
-                                // Update the size of the current block and return.
-                                debug_assert!(next_index > end);
-                                data[current_index].size += self.size;
-                                // ┌───┬──────────┬───┬────┬───┐
-                                // │...│index     │...│next│...│
-                                // └───┴──────────┴───┴────┴───┘
-                                break;
-                            }
-                            // ┌───┬─────┬────┬───┐
-                            // │...│index│self│...│
-                            // └───┴─────┴────┴───┘
-                            // The self block starts at the current block and there is no next
-                            // block.
-                            (true, None) => {
-                                data[current_index].size += self.size;
-                                // ┌───┬──────────┬───┐
-                                // │...│index     │...│
-                                // └───┴──────────┴───┘
-                                break;
-                            }
-                            // ┌───┬─────┬───┬────┬────┬───┐
-                            // │...│index│...│self│next│...│
-                            // └───┴─────┴───┴────┴────┴───┘
-                            // The self block starts after the current block and ends at the next
-                            // block.
-                            (false, Some(next_index)) if next_index == end => {
-                                // Update the size of the self block and the next of the current
-                                // block.
-                                data[self.index] = Block {
-                                    size: self.size + data[next_index].size,
-                                    next: data[next_index].next,
-                                };
-                                data[current_index].next = Some(self.index);
-                                // ┌───┬─────┬───┬─────────┬───┐
-                                // │...│index│...│self     │...│
-                                // └───┴─────┴───┴─────────┴───┘
-                                break;
-                            }
-                            // ┌───┬─────┬───┬────┬───┬────┬───┐
-                            // │...│index│...│self│...│next│...│
-                            // └───┴─────┴───┴────┴───┴────┴───┘
-                            // The self block starts after the current block and ends before the
-                            // next block.
-                            (false, Some(next_index)) if next_index > end => {
-                                data[self.index] = Block {
-                                    size: self.size,
-                                    next: data[current_index].next,
-                                };
-                                data[current_index].next = Some(self.index);
-                                break;
-                            }
-                            // ┌───┬─────┬───┬────┬───┬────┬───┐
-                            // │...│index│...│next│...│self│...│
-                            // └───┴─────┴───┴────┴───┴────┴───┘
-                            // The self block starts after the next block.
-                            (false, Some(next_index)) => {
-                                debug_assert!(next_index < self.index);
-                                current_index = next_index;
-                                continue;
-                            }
-                            // ┌───┬─────┬───┬────┬───┐
-                            // │...│index│...│self│...│
-                            // └───┴─────┴───┴────┴───┘
-                            // The self block starts after the current block and there is no next
-                            // block.
-                            (false, None) => {
-                                data[self.index] = Block {
-                                    size: self.size,
-                                    next: None,
-                                };
-                                data[current_index].next = Some(self.index);
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        // ┌───┐
-        // │...│
-        // └───┘
-        // If there are no free blocks.
-        else {
-            inner_allocator.head = Some(self.index);
-            data[self.index] = Block {
-                size: self.size,
-                next: None,
-            };
-        }
+        #[cfg(feature = "canaries")]
+        let (index, size) = inner_allocator.check_canaries(index, size);
 
-        drop(inner_allocator_guard);
+        #[cfg(feature = "audit")]
+        let (index, size) = inner_allocator.unlink(index, size);
 
-        #[cfg(feature = "log")]
-        trace!("Wrapper::drop exit");
-    }
-}
+        #[cfg(feature = "quarantine")]
+        let Some((index, size)) = inner_allocator.quarantine(index, size) else {
+            return;
+        };
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct Slice<'a, T> {
-    pub wrapper: Wrapper<'a>,
-    len: usize,
-    __marker: PhantomData<T>,
-}
+        let crossed = inner_allocator.release(index, size);
 
-impl<'a, T> Slice<'a, T> {
-    #[must_use]
-    pub fn allocator(&self) -> &Allocator {
-        #[cfg(feature = "log")]
-        trace!("Slice::allocator");
+        #[cfg(feature = "latency")]
+        inner_allocator.latency.record_free(start, locked);
 
-        self.wrapper.allocator
+        drop(inner_allocator_guard);
+
+        if let Some((hook, event)) = crossed {
+            hook(&event);
+        }
+
+        #[cfg(feature = "async")]
+        crate::waiters::wake(self as *const Self as usize);
     }
 
-    /// # Safety
+    /// Enables or disables tracking live allocations, see [`crate::audit`].
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator {
+    /// # Panics
+    ///
+    /// When there are live allocations or when locking the mutex fails.
+    #[cfg(feature = "audit")]
+    pub fn set_audit(&self, enabled: bool) {
         #[cfg(feature = "log")]
-        trace!("Slice::allocator_mut");
+        trace!("Allocator::set_audit");
 
-        &mut self.wrapper.allocator
+        let mut inner_allocator = self.0.lock().unwrap();
+        let stats = inner_allocator.stats();
+        assert_eq!(
+            stats.free, stats.total,
+            "auditing cannot be toggled while there are allocations"
+        );
+        inner_allocator.audit = enabled;
+    }
+
+    /// Returns the index, size and tag of each live allocation, most recent first, as of the call.
+    ///
+    /// The index and size are those of the wrapper, tags are set with [`Wrapper::set_tag`]. Returns
+    /// nothing when auditing is disabled, see [`Allocator::set_audit`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(all(feature = "audit", feature = "std"))]
+    pub fn live_allocations(&self) -> impl Iterator<Item = (usize, usize, u32)> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::live_allocations");
+
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        let header = crate::audit::blocks::<Block<I>, I>();
+        #[cfg(feature = "canaries")]
+        let guards = usize::from(inner_allocator.canaries);
+        #[cfg(not(feature = "canaries"))]
+        let guards = 0;
+        let data = unsafe { inner_allocator.data().as_ref() };
+        unsafe { crate::audit::live::<_, I>(data, inner_allocator.live) }
+            .into_iter()
+            .map(move |(index, size, tag)| {
+                (index + header + guards, size - header - 2 * guards, tag)
+            })
     }
 
+    /// Groups the live allocations by the process and client which made them, ordered by the
+    /// number of blocks descending, as of the call.
+    ///
+    /// Returns nothing when auditing is disabled, see [`Allocator::set_audit`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "ownership")]
     #[must_use]
-    pub fn index(&self) -> usize {
+    pub fn allocations_by_owner(&self) -> std::vec::Vec<crate::ownership::Usage> {
         #[cfg(feature = "log")]
-        trace!("Slice::index");
+        trace!("Allocator::allocations_by_owner");
 
-        self.wrapper.index
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        let data = unsafe { inner_allocator.data().as_ref() };
+        crate::ownership::usage(
+            unsafe { crate::audit::owners::<_, I>(data, inner_allocator.live) }
+                .into_iter()
+                .map(|(_, size, owner)| (size, owner)),
+        )
     }
 
+    /// Frees every live allocation made by a process `is_alive` reports dead, returning what was
+    /// freed grouped by owner as [`Allocator::allocations_by_owner`] does.
+    ///
+    /// This lets e.g. a broker clean up after crashed clients without reinitializing the
+    /// allocator. Does nothing when auditing is disabled, see [`Allocator::set_audit`].
+    ///
     /// # Safety
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn index_mut(&mut self) -> &mut usize {
+    /// The allocations of dead processes must no longer be accessed, e.g. through wrappers sent
+    /// to other processes, as they may be reallocated.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails or when a guard of a freed allocation was overwritten.
+    #[cfg(feature = "ownership")]
+    pub unsafe fn reclaim_dead_owners(
+        &self,
+        is_alive: impl Fn(u32) -> bool,
+    ) -> std::vec::Vec<crate::ownership::Usage> {
         #[cfg(feature = "log")]
-        trace!("Slice::index_mut");
+        trace!("Allocator::reclaim_dead_owners");
+
+        let header = crate::audit::blocks::<Block<I>, I>();
+        let (dead, guards) = {
+            let mut inner_allocator_guard = self.0.lock().unwrap();
+            let inner_allocator = &mut *inner_allocator_guard;
+            let data = inner_allocator.data().as_ref();
+            let dead = crate::audit::owners::<_, I>(data, inner_allocator.live)
+                .into_iter()
+                .filter(|(_, _, owner)| !is_alive(owner.pid))
+                .collect::<std::vec::Vec<_>>();
+            #[cfg(feature = "canaries")]
+            let guards = usize::from(inner_allocator.canaries);
+            #[cfg(not(feature = "canaries"))]
+            let guards = 0;
+            (dead, guards)
+        };
+        // Dead processes cannot free their allocations, so they stay live once unlocked.
+        for &(index, size, _) in &dead {
+            self.deallocate(index + header + guards, size - header - 2 * guards);
+        }
+        crate::ownership::usage(dead.into_iter().map(|(_, size, owner)| (size, owner)))
+    }
 
-        &mut self.wrapper.index
+    /// Enables or disables writing guard blocks before and after each allocation, see
+    /// [`crate::canary`].
+    ///
+    /// # Panics
+    ///
+    /// When there are live allocations or when locking the mutex fails.
+    #[cfg(feature = "canaries")]
+    pub fn set_canaries(&self, enabled: bool) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_canaries");
+
+        let mut inner_allocator = self.0.lock().unwrap();
+        let stats = inner_allocator.stats();
+        assert_eq!(
+            stats.free, stats.total,
+            "canaries cannot be toggled while there are allocations"
+        );
+        inner_allocator.canaries = enabled;
     }
 
-    #[must_use]
-    pub fn size(&self) -> usize {
+    /// Checks the guards of every live allocation, see [`crate::canary`].
+    ///
+    /// Returns `Ok(())` when canaries are disabled.
+    ///
+    /// # Errors
+    ///
+    /// When a guard was overwritten, returning the first such allocation.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "canaries")]
+    pub fn validate(&self) -> Result<(), crate::canary::CanaryError> {
         #[cfg(feature = "log")]
-        trace!("Slice::size");
+        trace!("Allocator::validate");
 
-        self.wrapper.size
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        if !inner_allocator.canaries {
+            return Ok(());
+        }
+        let meta = unsafe { inner_allocator.meta().as_ref() };
+        let data = unsafe { inner_allocator.data().as_ref() };
+
+        // The used regions lie between the free regions.
+        let mut start = Some(0);
+        let mut next = inner_allocator.head;
+        let used = std::iter::from_fn(|| {
+            let region = start?..next.unwrap_or(inner_allocator.size);
+            start = next.map(|index| index + meta[index].size());
+            next = next.and_then(|index| meta[index].next());
+            Some(region)
+        });
+
+        for region in used {
+            let mut index = region.start;
+            while index < region.end {
+                #[cfg(feature = "quarantine")]
+                if let Some(size) = inner_allocator.quarantine.get(index) {
+                    index += size;
+                    continue;
+                }
+                // Allocations are preceded by their headers when auditing.
+                #[cfg(feature = "audit")]
+                if inner_allocator.audit {
+                    index += crate::audit::blocks::<Block<I>, I>();
+                }
+                let size = crate::canary::size::<_, I>(&data[index]);
+                let Some(end) = size
+                    .checked_add(index + 1)
+                    .filter(|&end| size != 0 && end < region.end)
+                else {
+                    return Err(crate::canary::CanaryError {
+                        index: index + 1,
+                        size: None,
+                        guard: crate::canary::Guard::Before,
+                    });
+                };
+                crate::canary::check::<_, I>(&data[index], &data[end], index + 1, size)?;
+                index = end + 1;
+            }
+        }
+        Ok(())
     }
 
-    /// # Safety
+    /// Allocates a given number of blocks, waiting for blocks to be freed while there is no free
+    /// region large enough.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn size_mut(&mut self) -> &mut usize {
+    /// The allocator is never locked while another thread holds the lock, instead the task waits
+    /// for it to be released so the executor thread is not blocked. Only frees and unlocks made by
+    /// the current process wake the waiting task. Resolves to `None` when
+    /// `blocks` is greater than the number of blocks in the allocator, as the allocation could
+    /// never succeed.
+    #[cfg(feature = "async")]
+    pub fn allocate_async(&self, blocks: usize) -> AllocateFuture<'_, I> {
         #[cfg(feature = "log")]
-        trace!("Slice::size_mut");
+        trace!("Allocator::allocate_async");
 
-        &mut self.wrapper.size
+        AllocateFuture {
+            allocator: self,
+            blocks,
+        }
     }
 
-    pub fn wrapper(&mut self) -> &Wrapper<'a> {
-        &self.wrapper
+    /// Enables or disables marking free blocks as inaccessible to the address sanitizer and
+    /// Valgrind, so use after free of allocations is reported rather than silently reading stale
+    /// data.
+    ///
+    /// While enabled the allocator's memory must not be moved, copied or reused, e.g. an
+    /// [`ArrayAllocator`] on the stack must not be moved or dropped, as the sanitizer would report
+    /// the accesses. Disabling marks all of the allocator's memory as accessible.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "sanitizer")]
+    pub fn set_poisoning(&self, enabled: bool) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_poisoning");
+
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        inner_allocator.poisoning = enabled;
+        if enabled {
+            inner_allocator.poison_free();
+        } else {
+            crate::sanitizer::unpoison(unsafe { inner_allocator.data().as_ref() });
+        }
     }
 
-    /// # Safety
+    /// Sets the number of freed allocations held in quarantine before their blocks rejoin the free
+    /// list, so recently freed memory is not immediately reallocated and use after free is not
+    /// masked by the memory being reused. `0`, the default, disables quarantine and reducing the
+    /// limit releases the oldest quarantined allocations.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn wrapper_mut(&mut self) -> &mut Wrapper<'a> {
-        &mut self.wrapper
+    /// Quarantined blocks are filled and poisoned as if free but are counted as used, e.g. by
+    /// [`Allocator::stats`].
+    ///
+    /// # Panics
+    ///
+    /// When `limit >` [`QUARANTINE_CAPACITY`](crate::quarantine::QUARANTINE_CAPACITY) or when locking the mutex fails.
+    #[cfg(feature = "quarantine")]
+    pub fn set_quarantine(&self, limit: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_quarantine");
+
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        inner_allocator.quarantine.set_limit(limit);
+        // Freeing can only cross the low watermark, so at most once.
+        let mut crossed = None;
+        let frozen = inner_allocator
+            .frozen
+            .load(std::sync::atomic::Ordering::Acquire);
+        while let Some((index, size)) = inner_allocator.quarantine.evict() {
+            // The blocks of a frozen allocator are leaked, see `Allocator::deallocate`.
+            if !frozen {
+                crossed = unsafe { inner_allocator.release(index, size) }.or(crossed);
+            }
+        }
+        drop(inner_allocator_guard);
+
+        if let Some((hook, event)) = crossed {
+            hook(&event);
+        }
+
+        #[cfg(feature = "async")]
+        crate::waiters::wake(self as *const Self as usize);
     }
 
-    #[must_use]
-    pub fn len(&self) -> usize {
+    /// Sets a function called whenever an allocation fails through lack of memory, replacing any
+    /// previous hook.
+    ///
+    /// The hook is called after the allocator is unlocked, so it may use the allocator. As a
+    /// function pointer it is only valid within the process which set it, so when the allocator is
+    /// shared between processes the hook should only be set in one of them.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn set_oom_hook(&self, hook: OomHook) {
         #[cfg(feature = "log")]
-        trace!("Slice::len");
+        trace!("Allocator::set_oom_hook");
 
-        self.len
+        self.0.lock().unwrap().oom_hook = Some(hook);
     }
 
-    /// # Safety
+    /// Removes and returns the out of memory hook.
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn len_mut(&mut self) -> &mut usize {
-        &mut self.len
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn take_oom_hook(&self) -> Option<OomHook> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::take_oom_hook");
+
+        self.0.lock().unwrap().oom_hook.take()
     }
 
-    #[must_use]
-    pub fn is_empty(&self) -> bool {
+    /// Sets a function called when the number of used blocks rises to `high` or falls to `low`,
+    /// replacing any previous watermarks.
+    ///
+    /// Crossings are detected within allocate and free while the lock is held, so each is seen
+    /// exactly once, and the hook is called after the allocator is unlocked, so it may use the
+    /// allocator. After the high watermark is reached the hook is not called again until usage
+    /// falls to the low watermark. Like the out of memory hook, the hook is only valid within the
+    /// process which set it; to notify other processes it may write to e.g. an eventfd.
+    ///
+    /// # Panics
+    ///
+    /// When `low >= high` or when locking the mutex fails.
+    pub fn set_watermarks(&self, low: usize, high: usize, hook: WatermarkHook) {
         #[cfg(feature = "log")]
-        trace!("Slice::is_empty");
+        trace!("Allocator::set_watermarks");
 
-        self.len == 0
+        let mut inner_allocator = self.0.lock().unwrap();
+        let stats = inner_allocator.stats();
+        inner_allocator.watermarks = Some(Watermarks::new(low, high, hook, stats));
     }
 
-    pub fn resize(&mut self, len: usize) -> Option<()> {
+    /// Removes any watermarks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn clear_watermarks(&self) {
         #[cfg(feature = "log")]
-        trace!("Slice::resize enter");
+        trace!("Allocator::clear_watermarks");
 
-        // If resizing to current size, we can do nothing.
-        if self.len() == len {
-            return Some(());
-        }
+        self.0.lock().unwrap().watermarks = None;
+    }
 
-        // Allocate new slice.
-        let mut new = self.wrapper.allocator.allocate_slice(len)?;
+    /// Reserves `blocks` blocks for [`Priority::Critical`] allocations.
+    ///
+    /// Normal allocations fail with out of memory rather than leave fewer than `blocks` blocks
+    /// free, while critical allocations may use every free block. Canaries and audit headers count
+    /// towards an allocation. A reserve of 0, the default, disables the check.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn set_reserve(&self, blocks: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_reserve");
 
-        // Copy data to new allocation
-        let from = self[..].as_ptr();
-        let to = new[..].as_mut_ptr();
-        let n = std::cmp::min(len, self.len);
-        unsafe {
-            std::ptr::copy(from, to, n);
-        }
+        self.0.lock().unwrap().reserve = blocks;
+    }
+
+    /// Returns the number of blocks reserved for [`Priority::Critical`] allocations.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn reserve(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::reserve");
 
-        // Update wrapper
-        let old = std::mem::replace(self, new);
-        drop(old);
+        self.0.lock().unwrap().reserve
+    }
 
+    /// Sets how free regions are picked for allocations, [`Strategy::FirstFit`] by default.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn set_strategy(&self, strategy: Strategy) {
         #[cfg(feature = "log")]
-        trace!("Slice::resize exit");
+        trace!("Allocator::set_strategy");
 
-        Some(())
+        self.0.lock().unwrap().strategy = strategy;
     }
-}
 
-impl<'a, T> Deref for Slice<'a, T> {
-    type Target = [T];
+    /// Returns how free regions are picked for allocations.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn strategy(&self) -> Strategy {
+        #[cfg(feature = "log")]
+        trace!("Allocator::strategy");
 
-    fn deref(&self) -> &Self::Target {
+        self.0.lock().unwrap().strategy
+    }
+
+    /// Returns whether the lock is held, and whether it has been held for longer than `timeout`
+    /// by a process which no longer exists, in which case it will never be released.
+    ///
+    /// A supervisor may poll this and reclaim an abandoned allocator with
+    /// [`Allocator::force_unlock`] rather than deadlock on it.
+    #[cfg(feature = "watchdog")]
+    #[must_use]
+    pub fn check_lock_health(&self, timeout: std::time::Duration) -> crate::watchdog::LockHealth {
         #[cfg(feature = "log")]
-        trace!("Slice::deref enter");
+        trace!("Allocator::check_lock_health");
 
-        // TODO Test this deref is to the correct ptr
-        let slice =
-            unsafe { &*std::ptr::from_raw_parts(self.wrapper[..].as_ptr().cast(), self.len) };
+        self.0.owner.health(timeout)
+    }
 
+    /// Releases a lock held by a process which no longer exists.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by a process which no longer exists, e.g. as reported by
+    /// [`Allocator::check_lock_health`], and no other process may force unlock it concurrently.
+    /// The holder may have died part way through modifying the allocator, so the blocks it was
+    /// allocating or freeing may be lost or still in use.
+    ///
+    /// # Panics
+    ///
+    /// When unlocking the mutex fails.
+    #[cfg(feature = "watchdog")]
+    pub unsafe fn force_unlock(&self) {
         #[cfg(feature = "log")]
-        trace!("Slice::deref exit");
+        trace!("Allocator::force_unlock");
 
-        slice
+        self.0.force_unlock().unwrap();
     }
-}
-impl<'a, T> DerefMut for Slice<'a, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
+
+    /// Sets the failures to inject into allocations, replacing any previous configuration.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "testing")]
+    pub fn set_failure_injection(&self, failure: crate::testing::FailureInjection) {
         #[cfg(feature = "log")]
-        trace!("Slice::deref_mut enter");
+        trace!("Allocator::set_failure_injection");
 
-        // TODO Test this deref is to the correct ptr
-        let slice = unsafe {
-            &mut *std::ptr::from_raw_parts_mut(self.wrapper[..].as_mut_ptr().cast(), self.len)
-        };
+        self.0.lock().unwrap().failure = failure;
+    }
 
+    /// Returns the current failure injection state.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "testing")]
+    #[must_use]
+    pub fn failure_injection(&self) -> crate::testing::FailureInjection {
         #[cfg(feature = "log")]
-        trace!("Slice::deref_mut exit");
+        trace!("Allocator::failure_injection");
 
-        slice
+        self.0.lock().unwrap().failure
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #![allow(clippy::pedantic)]
+    /// Calls the out of memory hook, if set.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    fn out_of_memory(&self, oom_hook: Option<OomHook>, requested: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::out_of_memory");
 
-    use rand::Rng;
+        if let Some(hook) = oom_hook {
+            hook(&AllocRequest {
+                requested,
+                stats: self.stats(),
+            });
+        }
+    }
 
-    use super::*;
+    /// Renders the used (`#`) and free (`.`) blocks, `width` per line with each line prefixed by the
+    /// index of its first block, e.g.
+    ///
+    /// ```text
+    ///  0 [####....]
+    ///  8 [##..]
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// When `width == 0` or when locking the mutex fails.
+    #[must_use]
+    #[cfg(feature = "std")]
+    pub fn render_map(&self, width: usize) -> std::string::String {
+        #[cfg(feature = "log")]
+        trace!("Allocator::render_map");
 
-    #[test]
-    fn slice_debug() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        let (total, free) = self.free_list();
+        crate::raw::render_map(total, free, width)
+    }
 
-        let expected = "Slice { wrapper: Wrapper { allocator: Allocator(Mutex { lock: \
-                        Mutex(UnsafeCell { .. }), data: UnsafeCell { .. } }), index: 0, size: 1 \
-                        }, len: 3, __marker: PhantomData<u8> }";
+    /// Renders the runs of used and free blocks as a row of boxes labelled with their number of
+    /// blocks, in the style of the diagrams in the source, e.g.
+    ///
+    /// ```text
+    /// ┌──────┬──────┬──────┐
+    /// │used:2│free:3│used:1│
+    /// └──────┴──────┴──────┘
+    /// ```
+    ///
+    /// Adjacent allocations form a single used run.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    #[cfg(feature = "std")]
+    pub fn dump(&self) -> std::string::String {
+        #[cfg(feature = "log")]
+        trace!("Allocator::dump");
 
-        assert_eq!(format!("{wrapper:?}"), expected);
+        let (total, free) = self.free_list();
+        crate::raw::render_regions(total, free)
     }
-    #[test]
-    fn slice_allocator() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        let _ = wrapper.allocator();
+
+    /// Returns the number of blocks and the free regions as `(index, size)`.
+    #[cfg(feature = "std")]
+    fn free_list(&self) -> (usize, std::vec::Vec<(usize, usize)>) {
+        let mut inner_allocator = self.0.lock().unwrap();
+        let meta = unsafe { inner_allocator.meta().as_ref() };
+
+        let mut free = std::vec::Vec::new();
+        let mut next = inner_allocator.head;
+        while let Some(index) = next {
+            free.push((index, meta[index].size()));
+            next = meta[index].next();
+        }
+        (inner_allocator.size, free)
     }
-    #[test]
-    fn slice_index() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        assert_eq!(wrapper.index(), 0);
+
+    /// Walks the free list under the lock, checking its regions are ordered, non-empty, don't
+    /// overlap and lie within the allocator, e.g. to diagnose corruption by another process
+    /// sharing the allocator.
+    ///
+    /// # Errors
+    ///
+    /// When the free list is corrupt, returning the first corruption found.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn check_integrity(
+        &self,
+    ) -> Result<crate::integrity::IntegrityReport, crate::integrity::IntegrityError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::check_integrity");
+
+        let mut inner_allocator = self.0.lock().unwrap();
+        let meta = unsafe { inner_allocator.meta().as_ref() };
+        crate::integrity::check(inner_allocator.size, inner_allocator.head, |index| {
+            (meta[index].size(), meta[index].next())
+        })
     }
-    #[test]
-    fn slice_size() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        assert_eq!(wrapper.size(), 1);
+
+    /// Groups the live allocations made by this process by call site, ordered by the number of
+    /// blocks descending.
+    ///
+    /// Call sites are recorded through `#[track_caller]`, so allocations made through wrappers
+    /// which are not `#[track_caller]` are attributed to the wrapper.
+    #[cfg(feature = "profiling")]
+    #[must_use]
+    pub fn dump_live_allocations(&self) -> std::vec::Vec<crate::profiling::CallSite> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::dump_live_allocations");
+
+        crate::profiling::call_sites(self as *const Self as usize)
     }
-    #[test]
-    fn slice_len() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        assert_eq!(wrapper.len(), 3);
+
+    /// Returns the latencies recorded since the allocator was initialized.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "latency")]
+    #[must_use]
+    pub fn latency_histogram(&self) -> crate::latency::Latency {
+        #[cfg(feature = "log")]
+        trace!("Allocator::latency_histogram");
+
+        self.0.lock().unwrap().latency
     }
-    #[test]
-    fn slice_is_empty() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        assert!(!wrapper.is_empty());
+
+    /// Returns usage statistics.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        #[cfg(feature = "log")]
+        trace!("Allocator::stats");
+
+        self.0.lock().unwrap().stats()
     }
-    #[test]
-    fn slice_resize() {
-        let allocator = ArrayAllocator::<5>::new(None);
-        let mut wrapper = allocator.allocate_slice::<u8>(2).unwrap();
-        wrapper[0] = 0;
-        wrapper[1] = 1;
-        wrapper.resize(3).unwrap();
-        wrapper[0] = 0;
-        wrapper[1] = 1;
+
+    /// Returns the total number of blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::capacity");
+
+        self.0.lock().unwrap().size
     }
-    #[test]
-    fn slice_deref() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let mut wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        wrapper[0] = 0;
-        assert_eq!(wrapper[0], 0);
-        wrapper[1] = 1;
-        assert_eq!(wrapper[1], 1);
-        wrapper[2] = 2;
-        assert_eq!(wrapper[2], 2);
+
+    /// Returns the number of free blocks, as of the call.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn free_blocks(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free_blocks");
+
+        let inner_allocator = self.0.lock().unwrap();
+        inner_allocator.size - inner_allocator.used
     }
-    #[test]
-    fn slice_deref_mut() {
-        let allocator = ArrayAllocator::<3>::new(None);
-        let mut wrapper = allocator.allocate_slice::<u8>(3).unwrap();
-        wrapper[0] = 0;
-        assert_eq!(wrapper[0], 0);
-        wrapper[1] = 1;
-        assert_eq!(wrapper[1], 1);
-        wrapper[2] = 2;
-        assert_eq!(wrapper[2], 2);
+
+    /// Returns the number of allocated blocks, including guards, headers and quarantined blocks,
+    /// as of the call.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn used_blocks(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::used_blocks");
+
+        let stats = self.stats();
+        stats.total - stats.free
     }
-    #[test]
-    fn slice_parallel_resize() {
-        const THREADS: usize = 64;
-        const SAMPLES: usize = 256;
 
-        const NUM: std::ops::Range<usize> = 0..5;
-        const SIZE: usize = 2 * THREADS * NUM.end;
-        let allocator = ArrayAllocator::<SIZE>::new(None);
+    /// Returns whether every block is allocated, as of the call.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Allocator::is_full");
 
-        let arc = std::sync::Arc::new(allocator);
-        let handles = (0..THREADS)
-            .map(move |_| {
-                let arc_clone = arc.clone();
-                std::thread::spawn(move || {
-                    let mut rng = rand::thread_rng();
+        self.0.lock().unwrap().head.is_none()
+    }
 
-                    let mut slice = arc_clone.allocate_slice::<u8>(rng.gen_range(NUM)).unwrap();
-                    for _ in 0..SAMPLES {
-                        slice.resize(rng.gen_range(NUM)).unwrap();
-                    }
-                })
-            })
-            .collect::<Vec<_>>();
+    /// Returns whether every block is free, as of the call.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Allocator::is_empty");
+
+        let mut inner_allocator = self.0.lock().unwrap();
+        let size = inner_allocator.size;
+        // Free regions are coalesced, so when every block is free they form one region from 0.
+        size == 0
+            || inner_allocator.head == Some(0)
+                && unsafe { inner_allocator.meta().as_ref() }[0].size() == size
+    }
 
-        for handle in handles {
-            handle.join().unwrap();
+    /// Freezes the allocator, returning a view reading its memory without locking.
+    ///
+    /// The view takes the allocator exclusively for as long as it lives, so no wrapper in this
+    /// process can write or free the blocks; allocations to keep are given up beforehand, e.g. with
+    /// [`Wrapper::into_raw_parts`]. Freezing cannot be undone. Allocations then fail with
+    /// [`AllocError::Frozen`] and freeing leaks the blocks, so every allocation stays readable e.g. by
+    /// consumer processes attaching with [`Allocator::frozen`].
+    ///
+    /// On Linux the pages lying wholly within the blocks, e.g. every block when the allocator was
+    /// initialized in memory from `mmap`, are protected read-only until the view is dropped.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex or protecting the pages fails.
+    pub fn freeze(&mut self) -> crate::frozen::FrozenArena<'_, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::freeze");
+
+        let mut inner_allocator = self.0.lock().unwrap();
+        inner_allocator
+            .frozen
+            .store(true, std::sync::atomic::Ordering::Release);
+        let data = unsafe { inner_allocator.data() };
+        drop(inner_allocator);
+        unsafe {
+            crate::frozen::FrozenArena::protect(
+                self,
+                data.as_ptr().cast::<u8>(),
+                data.len() * size_of::<Block<I>>(),
+            )
         }
     }
 
-    #[test]
-    fn value_debug() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate_value::<u8>().unwrap();
-
-        let expected = "Value { wrapper: Wrapper { allocator: Allocator(Mutex { lock: \
-                        Mutex(UnsafeCell { .. }), data: UnsafeCell { .. } }), index: 0, size: 1 \
-                        }, __marker: PhantomData<u8> }";
+    /// Returns a view reading the memory of the allocator without locking if it is frozen, see
+    /// [`Allocator::freeze`].
+    ///
+    /// Unlike [`Allocator::freeze`] this does not protect the pages.
+    #[must_use]
+    pub fn frozen(&self) -> Option<crate::frozen::FrozenArena<'_, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::frozen");
 
-        assert_eq!(format!("{wrapper:?}"), expected);
-    }
-    #[test]
-    fn value_allocator() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate_value::<u8>().unwrap();
-        let _ = wrapper.allocator();
+        self.is_frozen()
+            .then(|| crate::frozen::FrozenArena::new(self))
     }
-    #[test]
-    fn value_index() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate_value::<u8>().unwrap();
-        assert_eq!(wrapper.index(), 0);
+
+    /// Returns whether the allocator is frozen, see [`Allocator::freeze`].
+    ///
+    /// This does not lock, as the flag is only ever set.
+    #[must_use]
+    pub fn is_frozen(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Allocator::is_frozen");
+
+        unsafe { &*std::ptr::addr_of!((*self.0.get()).frozen) }
+            .load(std::sync::atomic::Ordering::Acquire)
     }
-    #[test]
-    fn value_size() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate_value::<u8>().unwrap();
-        assert_eq!(wrapper.size(), 1);
+
+    /// Returns a copy of the allocator and its blocks, taken while it is locked so the copy is
+    /// consistent.
+    ///
+    /// The copy is an independent allocator owning its memory, so e.g. an analyzer can walk or
+    /// allocate from a point in time view while producers keep allocating from this allocator.
+    /// On Linux the copy is written once to a `memfd` mapped copy-on-write, so further views of
+    /// the same point in time, e.g. in an analyzer process, share its pages until written, see
+    /// [`crate::snapshot`]. Taking the copy is O(n) in the size of the allocator and holds the lock
+    /// throughout.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails, when failing to initialize the mutex of the copy or when
+    /// failing to map the copy.
+    // The layout is aligned for the allocator.
+    #[allow(clippy::cast_ptr_alignment)]
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn snapshot_cow(&self) -> crate::snapshot::Snapshot<I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::snapshot_cow");
+
+        let inner_allocator = self.0.lock().unwrap();
+        let (size, out_of_band) = (inner_allocator.size, inner_allocator.out_of_band);
+        let blocks = if out_of_band { 2 * size } else { size };
+        // The blocks directly follow the allocator, as in `ArrayAllocator`.
+        let (layout, offset) = Layout::new::<Self>()
+            .extend(Layout::array::<Block<I>>(blocks).unwrap())
+            .unwrap();
+        assert_eq!(offset, size_of::<Self>());
+        let layout = layout.pad_to_align();
+        let (ptr, backing) = crate::snapshot::Snapshot::<I>::allocate(layout);
+        let ptr = ptr.as_ptr().cast::<Self>();
+        unsafe {
+            // The copy is only used by the current process, so gets a fresh, unlocked mutex.
+            Self::init_layout(ptr, None, size, out_of_band).unwrap();
+            let source = &*inner_allocator as *const InnerAllocator<I>;
+            let copy = (*ptr).0.get();
+            std::ptr::copy_nonoverlapping(source, copy, 1);
+            std::ptr::copy_nonoverlapping(
+                source.add(1).cast::<Block<I>>(),
+                copy.add(1).cast::<Block<I>>(),
+                blocks,
+            );
+            crate::snapshot::Snapshot::new(NonNull::new_unchecked(ptr), layout, backing)
+        }
     }
-    #[test]
-    fn value_deref() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let mut wrapper = allocator.allocate_value::<u8>().unwrap();
-        *wrapper = 0;
-        assert_eq!(*wrapper, 0);
+
+    /// Returns a pointer to the byte `offset` bytes from the start of the first block, the
+    /// inverse of [`Wrapper::byte_offset`], or `None` when `offset` is past the last block.
+    ///
+    /// Byte offsets don't depend on the size of a block, so suit FFI peers and on-disk formats.
+    /// The pointer may only be dereferenced within memory allocated from this allocator.
+    #[must_use]
+    pub fn ptr_at(&self, offset: usize) -> Option<NonNull<u8>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::ptr_at");
+
+        // Reads through raw pointers rather than locking, as the size and where the blocks lie are
+        // only set on initialization. See `InnerAllocator::data`.
+        let inner = unsafe { self.0.get() };
+        let (size, out_of_band) = unsafe {
+            (
+                std::ptr::addr_of!((*inner).size).read(),
+                std::ptr::addr_of!((*inner).out_of_band).read(),
+            )
+        };
+        let first = if out_of_band { size } else { 0 };
+        (offset < size * size_of::<Block<I>>()).then(|| unsafe {
+            NonNull::new_unchecked(
+                inner
+                    .add(1)
+                    .cast::<Block<I>>()
+                    .add(first)
+                    .cast::<u8>()
+                    .add(offset),
+            )
+        })
     }
-    #[test]
-    fn value_deref_mut() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let mut wrapper = allocator.allocate_value::<u8>().unwrap();
-        *wrapper = 0;
-        assert_eq!(*wrapper, 0);
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn inner(&self) -> &super::mutex::Mutex<InnerAllocator<I>> {
+        &self.0
     }
 
-    #[test]
-    fn block_debug() {
-        assert_eq!(
-            format!(
-                "{:?}",
-                Block {
-                    size: 0,
-                    next: None
-                }
-            ),
-            "Block { size: 0, next: None }"
-        );
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn inner_mut(&mut self) -> &mut super::mutex::Mutex<InnerAllocator<I>> {
+        &mut self.0
     }
-    #[test]
-    fn wrapper_debug() {
-        let allocator = ArrayAllocator::<0>::new(None);
+}
 
-        let expected = "Wrapper { allocator: Allocator(Mutex { lock: Mutex(UnsafeCell { .. }), \
-                        data: UnsafeCell { .. } }), index: 0, size: 0 }";
+/// The future returned by [`Allocator::allocate_async`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct AllocateFuture<'a, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    blocks: usize,
+}
 
-        assert_eq!(
-            format!(
-                "{:?}",
-                Wrapper {
-                    allocator: &allocator,
-                    index: 0,
-                    size: 0
-                }
-            ),
-            expected
-        );
+#[cfg(feature = "async")]
+impl<'a, I: Index> std::future::Future for AllocateFuture<'a, I> {
+    type Output = Option<Wrapper<'a, I>>;
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        use std::task::Poll;
+
+        #[cfg(feature = "log")]
+        trace!("AllocateFuture::poll");
+
+        let allocator = self.allocator;
+        let Ok(blocks) = NonZeroUsize::try_from(self.blocks) else {
+            return Poll::Ready(Some(allocator.allocate_zero()));
+        };
+        let attempt = || match allocator.try_allocate_in(blocks, None, false, Priority::Normal) {
+            Ok(wrapper) => Some(Some(wrapper)),
+            Err(AllocError::OutOfMemory { .. })
+                if blocks.get() > unsafe { (*allocator.0.get()).size } =>
+            {
+                Some(None)
+            }
+            Err(AllocError::Frozen) => Some(None),
+            Err(AllocError::OutOfMemory { .. } | AllocError::WouldBlock) => None,
+            Err(err) => panic!("{err}"),
+        };
+
+        if let Some(rtn) = attempt() {
+            return Poll::Ready(rtn);
+        }
+        crate::waiters::register(allocator as *const Allocator<I> as usize, cx.waker());
+        crate::waiters::register(&allocator.0 as *const _ as usize, cx.waker());
+        // Blocks freed or the lock released before the waker was registered would not have woken
+        // it.
+        match attempt() {
+            Some(rtn) => Poll::Ready(rtn),
+            None => Poll::Pending,
+        }
     }
-    #[test]
-    fn wrapper_allocator() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate(1).unwrap();
-        let _ = wrapper.allocator();
+}
+
+/// How an [`Allocator`] picks the free region to allocate from, see [`Allocator::set_strategy`].
+///
+/// Aligned allocations always use the first free region which fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum Strategy {
+    /// The first free region which fits, the fastest as the search stops early, but small
+    /// remainders accumulate at the start of the array.
+    #[default]
+    FirstFit,
+    /// The smallest free region which fits, keeping large regions intact for large allocations.
+    BestFit,
+    /// The largest free region, leaving remainders large enough to be reused.
+    WorstFit,
+    /// The first free region which fits from where the previous allocation ended, wrapping around,
+    /// spreading allocations across the array.
+    NextFit,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct InnerAllocator<I = usize> {
+    head: Option<usize>,
+    size: usize,
+    oom_hook: Option<OomHook>,
+    watermarks: Option<Watermarks>,
+    /// The number of blocks/slots only critical allocations may use, see [`Priority`].
+    reserve: usize,
+    /// Whether the allocator is frozen, see [`Allocator::freeze`]. This is read without locking.
+    frozen: AtomicBool,
+    out_of_band: bool,
+    strategy: Strategy,
+    /// The index after the most recent allocation, where [`Strategy::NextFit`] resumes searching.
+    rover: usize,
+    /// The number of live allocations, including those in quarantine.
+    allocations: usize,
+    /// The number of blocks allocated, including guards, headers and quarantined blocks.
+    used: usize,
+    /// The greatest value of `used`.
+    peak: usize,
+    #[cfg(feature = "sanitizer")]
+    poisoning: bool,
+    #[cfg(feature = "quarantine")]
+    quarantine: crate::quarantine::Quarantine,
+    #[cfg(feature = "canaries")]
+    canaries: bool,
+    #[cfg(feature = "audit")]
+    audit: bool,
+    /// The index of the header of the most recent live allocation, see [`crate::audit`].
+    #[cfg(feature = "audit")]
+    live: Option<usize>,
+    #[cfg(feature = "testing")]
+    failure: crate::testing::FailureInjection,
+    #[cfg(feature = "latency")]
+    latency: crate::latency::Latency,
+    _marker: PhantomData<I>,
+}
+
+// Function pointers cannot be meaningfully compared, so only whether hooks are set is compared.
+impl<I> PartialEq for InnerAllocator<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.head == other.head
+            && self.size == other.size
+            && self.oom_hook.is_some() == other.oom_hook.is_some()
+            && self.watermarks == other.watermarks
+            && self.reserve == other.reserve
+            && self.frozen.load(std::sync::atomic::Ordering::Acquire)
+                == other.frozen.load(std::sync::atomic::Ordering::Acquire)
+            && self.out_of_band == other.out_of_band
+            && self.strategy == other.strategy
     }
-    #[test]
-    fn wrapper_allocator_mut() {
-        let allocator_one = ArrayAllocator::<1>::new(None);
-        let allocator_two = ArrayAllocator::<1>::new(None);
-        let mut wrapper = allocator_one.allocate(1).unwrap();
-        assert_eq!(
-            wrapper.allocator() as *const Allocator as usize,
-            &allocator_one as *const ArrayAllocator::<1> as usize
-        );
-        unsafe {
-            *wrapper.allocator_mut() = &allocator_two;
+}
+impl<I> Eq for InnerAllocator<I> {}
+
+impl<I: Index> InnerAllocator<I> {
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    #[must_use]
+    pub unsafe fn head(&self) -> &Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::head");
+
+        &self.head
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn head_mut(&mut self) -> &mut Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::head_mut");
+
+        &mut self.head
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    ///
+    /// # Panics
+    ///
+    /// When `&self == std::ptr::null()`.
+    #[must_use]
+    pub unsafe fn data(&mut self) -> NonNull<[Block<I>]> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::data");
+
+        let offset = if self.out_of_band { self.size } else { 0 };
+        std::ptr::NonNull::slice_from_raw_parts(
+            NonNull::new((self as *mut Self).add(1).cast::<Block<I>>().add(offset)).unwrap(),
+            self.size,
+        )
+    }
+
+    /// Returns the blocks holding the free list, these are the data blocks unless the allocator
+    /// was initialized by [`Allocator::init_out_of_band`].
+    ///
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    ///
+    /// # Panics
+    ///
+    /// When `&self == std::ptr::null()`.
+    #[must_use]
+    pub unsafe fn meta(&mut self) -> NonNull<[Block<I>]> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::meta");
+
+        std::ptr::NonNull::slice_from_raw_parts(
+            NonNull::new((self as *mut Self).add(1).cast()).unwrap(),
+            self.size,
+        )
+    }
+
+    /// Returns whether the free list is stored apart from the data blocks.
+    #[must_use]
+    pub fn out_of_band(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::out_of_band");
+
+        self.out_of_band
+    }
+
+    /// Returns usage statistics.
+    fn stats(&mut self) -> Stats {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::stats");
+
+        let meta = unsafe { self.meta().as_ref() };
+
+        let mut stats = Stats {
+            total: self.size,
+            allocations: self.allocations,
+            peak_used: self.peak,
+            ..Stats::default()
+        };
+        let mut next = self.head;
+        while let Some(index) = next {
+            let size = meta[index].size();
+            stats.free += size;
+            stats.free_regions += 1;
+            stats.largest_free = std::cmp::max(stats.largest_free, size);
+            next = meta[index].next();
         }
-        assert_eq!(
-            wrapper.allocator() as *const Allocator as usize,
-            &allocator_two as *const ArrayAllocator::<1> as usize
-        );
+        debug_assert_eq!(stats.free, self.size - self.used);
+        stats
     }
-    #[test]
-    fn wrapper_index() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate(1).unwrap();
-        assert_eq!(wrapper.index(), 0);
+
+    /// Marks every free region as inaccessible, except for the first block holding the free list
+    /// links when they are stored in the data blocks.
+    #[cfg(feature = "sanitizer")]
+    fn poison_free(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::poison_free");
+
+        let skip = usize::from(!self.out_of_band);
+        let meta = unsafe { self.meta().as_ref() };
+        let data = unsafe { self.data().as_ref() };
+        let mut next = self.head;
+        while let Some(index) = next {
+            crate::sanitizer::poison(&data[index + skip..index + meta[index].size()]);
+            next = meta[index].next();
+        }
     }
-    #[test]
-    fn wrapper_size() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        let wrapper = allocator.allocate(1).unwrap();
-        assert_eq!(wrapper.size(), 1);
+
+    /// Marks the free region containing `index` as inaccessible, see
+    /// [`InnerAllocator::poison_free`].
+    #[cfg(feature = "sanitizer")]
+    fn poison_free_region(&mut self, index: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::poison_free_region");
+
+        let skip = usize::from(!self.out_of_band);
+        let meta = unsafe { self.meta().as_ref() };
+        let data = unsafe { self.data().as_ref() };
+        let mut next = self.head;
+        while let Some(start) = next {
+            let end = start + meta[start].size();
+            if (start..end).contains(&index) {
+                crate::sanitizer::poison(&data[start + skip..end]);
+                return;
+            }
+            next = meta[start].next();
+        }
     }
 
-    #[test]
-    fn allocator() {
-        // We hold items in a vec to prevent them being dropped;
-        const SIZE: usize = 5;
-        let memory = ArrayAllocator::<SIZE>::new(None);
-        let mut vec = Vec::new();
+    /// Checks the `size` blocks at `index` are within the allocator and not free.
+    ///
+    /// # Panics
+    ///
+    /// When the blocks are outside the allocator or when any of them is free, e.g. on a double
+    /// free.
+    #[cfg(feature = "debug-checks")]
+    fn check_free(&mut self, index: usize, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::check_free");
 
-        {
-            let mut guard = memory.0.lock().unwrap();
-            assert_eq!(
-                *guard,
-                InnerAllocator {
-                    head: Some(0),
-                    size: SIZE
-                }
-            );
-            assert_eq!(
-                unsafe { guard.data().as_ref() },
-                [
-                    Block {
-                        size: 5,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                ]
+        let end = index + size;
+        assert!(
+            end <= self.size,
+            "freeing blocks {index}..{end} outside the allocator of {} blocks",
+            self.size
+        );
+        let mut current = self.head;
+        let meta = unsafe { self.meta().as_ref() };
+        // The free list is ordered by index so the walk stops after the blocks.
+        while let Some(free) = current.filter(|&free| free < end) {
+            let free_end = free + meta[free].size();
+            assert!(
+                free_end <= index,
+                "freeing blocks {index}..{end} overlapping the free blocks {free}..{free_end}, \
+                 e.g. a double free"
             );
+            current = meta[free].next();
         }
+    }
 
-        vec.push(memory.allocate(1));
+    /// Zeroes the `size` blocks at `index` before they are freed.
+    ///
+    /// # Safety
+    ///
+    /// The blocks must have been allocated from this allocator and must not be freed again.
+    #[cfg(feature = "zeroize")]
+    unsafe fn zeroize(&mut self, index: usize, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::zeroize");
+
+        crate::raw::zeroize(&mut self.data().as_mut()[index..index + size]);
+    }
+
+    /// Checks the guards of the allocation of `size` blocks at `index`, returning the blocks
+    /// including the guards.
+    ///
+    /// # Safety
+    ///
+    /// The blocks must have been allocated from this allocator and must not be freed again.
+    ///
+    /// # Panics
+    ///
+    /// When a guard was overwritten.
+    #[cfg(feature = "canaries")]
+    unsafe fn check_canaries(&mut self, index: usize, size: usize) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::check_canaries");
 
+        if !self.canaries {
+            return (index, size);
+        }
+        let data = self.data().as_ref();
+        if let Err(err) =
+            crate::canary::check::<_, I>(&data[index - 1], &data[index + size], index, size)
         {
-            let mut guard = memory.0.lock().unwrap();
-            assert_eq!(
-                *guard,
-                InnerAllocator {
-                    head: Some(1),
-                    size: SIZE
-                }
-            );
-            assert_eq!(
-                unsafe { guard.data().as_ref() },
-                [
-                    Block {
-                        size: 5,
-                        next: None,
-                    },
-                    Block {
-                        size: 4,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                ]
-            );
+            panic!("{err}");
         }
+        (index - 1, size + 2)
+    }
 
-        vec.push(memory.allocate(2));
+    /// Removes the allocation of `size` blocks at `index` from the list of live allocations,
+    /// returning the blocks including its header.
+    ///
+    /// # Safety
+    ///
+    /// The blocks must have been allocated from this allocator and must not be freed again.
+    #[cfg(feature = "audit")]
+    unsafe fn unlink(&mut self, index: usize, size: usize) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::unlink");
 
-        {
-            let mut guard = memory.0.lock().unwrap();
-            assert_eq!(
-                *guard,
-                InnerAllocator {
-                    head: Some(3),
-                    size: SIZE
-                }
-            );
-            assert_eq!(
-                unsafe { guard.data().as_ref() },
-                [
-                    Block {
-                        size: 5,
-                        next: None,
-                    },
-                    Block {
-                        size: 4,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 2,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                ]
-            );
+        if !self.audit {
+            return (index, size);
+        }
+        let header = crate::audit::blocks::<Block<I>, I>();
+        let data = self.data().as_mut();
+        crate::audit::unlink::<_, I>(data, &mut self.live, index - header);
+        (index - header, size + header)
+    }
+
+    /// Quarantines the `size` blocks starting at `index`, returning the blocks which should rejoin
+    /// the free list, if any.
+    ///
+    /// # Safety
+    ///
+    /// The blocks must have been allocated from this allocator and must not be freed again.
+    #[cfg(feature = "quarantine")]
+    unsafe fn quarantine(&mut self, index: usize, size: usize) -> Option<(usize, usize)> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::quarantine");
+
+        if self.quarantine.limit() == 0 {
+            return Some((index, size));
         }
 
-        vec.pop();
+        #[cfg(feature = "debug-fill")]
+        crate::raw::fill(
+            &mut self.data().as_mut()[index..index + size],
+            crate::raw::FREED_FILL,
+        );
+
+        #[cfg(feature = "sanitizer")]
+        if self.poisoning {
+            crate::sanitizer::poison(&self.data().as_ref()[index..index + size]);
+        }
+
+        self.quarantine.push((index, size))
+    }
+
+    /// Returns the `size` blocks starting at `index` to the free list.
+    ///
+    /// # Safety
+    ///
+    /// The blocks must have been allocated from this allocator and must not be freed again.
+    ///
+    /// Returns the hook to call once unlocked if the low watermark was reached.
+    #[allow(clippy::too_many_lines)]
+    unsafe fn release(
+        &mut self,
+        index: usize,
+        size: usize,
+    ) -> Option<(WatermarkHook, WatermarkEvent)> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::release");
+
+        #[cfg(any(feature = "sanitizer", feature = "debug-fill"))]
+        let data = self.data().as_mut();
+
+        // The blocks may have been poisoned in quarantine.
+        #[cfg(feature = "sanitizer")]
+        if self.poisoning {
+            crate::sanitizer::unpoison(&data[index..index + size]);
+        }
+
+        #[cfg(feature = "debug-fill")]
+        crate::raw::fill(&mut data[index..index + size], crate::raw::FREED_FILL);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(head = ?self.head);
+
+        let meta = self.meta().as_mut();
+
+        // ┌───┬─────┬───┐
+        // │...│index│...│
+        // └───┴─────┴───┘
+        // If there is at least 1 free block
+        if let Some(head) = self.head {
+            let end = index + size;
+            match end.cmp(&head) {
+                // ┌───┬────┬────┬───┐
+                // │...│self│head│...│
+                // └───┴────┴────┴───┘
+                Ordering::Equal => {
+                    meta[index] = Block {
+                        size: I::from_usize(size + meta[head].size()),
+                        next: meta[head].next,
+                    };
+                    self.head = Some(index);
+                }
+                // ┌───┬────┬───┬────┬───┐
+                // │...│self│...│head│...│
+                // └───┴────┴───┴────┴───┘
+                Ordering::Less => {
+                    meta[index] = Block::new(size, self.head);
+                    self.head = Some(index);
+                }
+                // ┌───┬────┬───┬────┬───┐
+                // │...│head│...│self│...│
+                // └───┴────┴───┴────┴───┘
+                Ordering::Greater => {
+                    // If `self` was allocated properly
+                    let mut current_index = head;
+                    loop {
+                        let current_end = current_index + meta[current_index].size();
+
+                        match (current_end == index, meta[current_index].next()) {
+                            // ┌───┬─────┬────┬────┬───┐
+                            // │...│index│self│next│...│
+                            // └───┴─────┴────┴────┴───┘
+                            // The self block starts at the current block and ends at the next
+                            // block.
+                            (true, Some(next_index)) if next_index == end => {
+                                // Update the size and next of the current block and return.
+                                meta[current_index].next = meta[next_index].next;
+                                meta[current_index].size = I::from_usize(
+                                    meta[current_index].size() + size + meta[next_index].size(),
+                                );
+                                // ┌───┬───────────────┬───┐
+                                // │...│index          │...│
+                                // └───┴───────────────┴───┘
+                                break;
+                            }
+                            // ┌───┬─────┬────┬───┬────┬───┐
+                            // │...│index│self│...│next│...│
+                            // └───┴─────┴────┴───┴────┴───┘
+                            // The self block starts at the current block and ends before the next
+                            // block.
+                            (true, Some(next_index)) => {
+                                // Update the size of the current block and return.
+                                debug_assert!(next_index > end);
+                                meta[current_index].size =
+                                    I::from_usize(meta[current_index].size() + size);
+                                // ┌───┬──────────┬───┬────┬───┐
+                                // │...│index     │...│next│...│
+                                // └───┴──────────┴───┴────┴───┘
+                                break;
+                            }
+                            // ┌───┬─────┬────┬───┐
+                            // │...│index│self│...│
+                            // └───┴─────┴────┴───┘
+                            // The self block starts at the current block and there is no next
+                            // block.
+                            (true, None) => {
+                                meta[current_index].size =
+                                    I::from_usize(meta[current_index].size() + size);
+                                // ┌───┬──────────┬───┐
+                                // │...│index     │...│
+                                // └───┴──────────┴───┘
+                                break;
+                            }
+                            // ┌───┬─────┬───┬────┬────┬───┐
+                            // │...│index│...│self│next│...│
+                            // └───┴─────┴───┴────┴────┴───┘
+                            // The self block starts after the current block and ends at the next
+                            // block.
+                            (false, Some(next_index)) if next_index == end => {
+                                // Update the size of the self block and the next of the current
+                                // block.
+                                meta[index] = Block {
+                                    size: I::from_usize(size + meta[next_index].size()),
+                                    next: meta[next_index].next,
+                                };
+                                meta[current_index].next = Some(I::from_usize(index));
+                                // ┌───┬─────┬───┬─────────┬───┐
+                                // │...│index│...│self     │...│
+                                // └───┴─────┴───┴─────────┴───┘
+                                break;
+                            }
+                            // ┌───┬─────┬───┬────┬───┬────┬───┐
+                            // │...│index│...│self│...│next│...│
+                            // └───┴─────┴───┴────┴───┴────┴───┘
+                            // The self block starts after the current block and ends before the
+                            // next block.
+                            (false, Some(next_index)) if next_index > end => {
+                                meta[index] = Block {
+                                    size: I::from_usize(size),
+                                    next: meta[current_index].next,
+                                };
+                                meta[current_index].next = Some(I::from_usize(index));
+                                break;
+                            }
+                            // ┌───┬─────┬───┬────┬───┬────┬───┐
+                            // │...│index│...│next│...│self│...│
+                            // └───┴─────┴───┴────┴───┴────┴───┘
+                            // The self block starts after the next block.
+                            (false, Some(next_index)) => {
+                                debug_assert!(next_index < index);
+                                current_index = next_index;
+                                continue;
+                            }
+                            // ┌───┬─────┬───┬────┬───┐
+                            // │...│index│...│self│...│
+                            // └───┴─────┴───┴────┴───┘
+                            // The self block starts after the current block and there is no next
+                            // block.
+                            (false, None) => {
+                                meta[index] = Block::new(size, None);
+                                meta[current_index].next = Some(I::from_usize(index));
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // ┌───┐
+        // │...│
+        // └───┘
+        // If there are no free blocks.
+        else {
+            self.head = Some(index);
+            meta[index] = Block::new(size, None);
+        }
+
+        #[cfg(feature = "sanitizer")]
+        if self.poisoning {
+            self.poison_free_region(index);
+        }
+
+        // Saturating as wrappers may be moved to another allocator, see `Wrapper::allocator_mut`.
+        self.allocations = self.allocations.saturating_sub(1);
+        self.used = self.used.saturating_sub(size);
+
+        self.watermarks
+            .as_mut()
+            .and_then(|watermarks| watermarks.freed(size))
+    }
+
+    /// Removes `blocks` blocks from the start of the free region picked by the strategy, returning
+    /// the index of the first. The rest of the region remains free.
+    fn allocate_fit(&mut self, blocks: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::allocate_fit");
+
+        let meta = unsafe { self.meta().as_mut() };
+        // The picked region and the free region preceding it.
+        let mut picked: Option<(Option<usize>, usize)> = None;
+        let mut prev = None;
+        let mut next = self.head;
+        while let Some(index) = next {
+            let size = meta[index].size();
+            next = meta[index].next();
+            if size >= blocks {
+                let better = picked.is_none_or(|(_, picked)| match self.strategy {
+                    Strategy::FirstFit => false,
+                    Strategy::BestFit => size < meta[picked].size(),
+                    Strategy::WorstFit => size > meta[picked].size(),
+                    Strategy::NextFit => picked < self.rover && index >= self.rover,
+                });
+                if better {
+                    picked = Some((prev, index));
+                }
+                let done = match self.strategy {
+                    Strategy::FirstFit => true,
+                    Strategy::BestFit => size == blocks,
+                    Strategy::WorstFit => false,
+                    Strategy::NextFit => index >= self.rover,
+                };
+                if done {
+                    break;
+                }
+            }
+            prev = Some(index);
+        }
+
+        let (prev, index) = picked?;
+        let size = meta[index].size();
+        // The free region following the allocation.
+        let after = if blocks < size {
+            #[cfg(feature = "sanitizer")]
+            if self.poisoning {
+                crate::sanitizer::unpoison(&meta[index + blocks..=index + blocks]);
+            }
+            meta[index + blocks] = Block::new(size - blocks, meta[index].next());
+            Some(index + blocks)
+        } else {
+            meta[index].next()
+        };
+        if let Some(prev) = prev {
+            meta[prev].next = after.map(I::from_usize);
+        } else {
+            self.head = after;
+        }
+        self.rover = index + blocks;
+        Some(index)
+    }
+
+    /// Removes `blocks` blocks from the free list such that the block `lead` blocks after the
+    /// first is aligned to `align` bytes, returning the index of the first. The blocks before and
+    /// after remain free.
+    fn allocate_aligned(&mut self, blocks: usize, align: usize, lead: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::allocate_aligned");
+
+        let base = unsafe { self.data() }.as_ptr().cast::<Block<I>>() as usize;
+        let meta = unsafe { self.meta().as_mut() };
+        let mut prev = None;
+        let mut next = self.head;
+        while let Some(start) = next {
+            let end = start + meta[start].size();
+            next = meta[start].next();
+
+            // Block addresses repeat alignments at most every `align` blocks.
+            let aligned = (start..end)
+                .take(align)
+                .find(|i| (base + (i + lead) * size_of::<Block<I>>()) % align == 0)
+                .filter(|index| index + blocks <= end);
+            let Some(index) = aligned else {
+                prev = Some(start);
+                continue;
+            };
+
+            // The free region following the allocation.
+            let after = if index + blocks < end {
+                #[cfg(feature = "sanitizer")]
+                if self.poisoning {
+                    crate::sanitizer::unpoison(&meta[index + blocks..=index + blocks]);
+                }
+                meta[index + blocks] = Block::new(end - index - blocks, next);
+                Some(index + blocks)
+            } else {
+                next
+            };
+            if index > start {
+                meta[start] = Block::new(index - start, after);
+            } else if let Some(prev) = prev {
+                meta[prev].next = after.map(I::from_usize);
+            } else {
+                self.head = after;
+            }
+            return Some(index);
+        }
+        None
+    }
+
+    /// Returns the size of the largest free region in blocks.
+    fn largest_free(&mut self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::largest_free");
+
+        let meta = unsafe { self.meta().as_ref() };
+        let mut largest = 0;
+        let mut next = self.head;
+        while let Some(index) = next {
+            largest = std::cmp::max(largest, meta[index].size());
+            next = meta[index].next();
+        }
+        largest
+    }
+
+    unsafe fn init(ptr: *mut Self, n: usize, out_of_band: bool) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::init");
+
+        assert!(n <= I::MAX, "{n} blocks cannot be indexed by {}", I::MAX);
+
+        if n > 0 {
+            #[cfg(feature = "log")]
+            trace!("InnerAllocator::init non-empty");
+
+            (*ptr).head = Some(0);
+            (*ptr).size = n;
+            (*ptr).oom_hook = None;
+            std::ptr::addr_of_mut!((*ptr).watermarks).write(None);
+            (*ptr).reserve = 0;
+            std::ptr::addr_of_mut!((*ptr).frozen).write(AtomicBool::new(false));
+            std::ptr::addr_of_mut!((*ptr).out_of_band).write(out_of_band);
+            std::ptr::addr_of_mut!((*ptr).strategy).write(Strategy::default());
+            (*ptr).rover = 0;
+            (*ptr).allocations = 0;
+            (*ptr).used = 0;
+            (*ptr).peak = 0;
+            #[cfg(feature = "sanitizer")]
+            std::ptr::addr_of_mut!((*ptr).poisoning).write(false);
+            #[cfg(feature = "quarantine")]
+            std::ptr::addr_of_mut!((*ptr).quarantine)
+                .write(crate::quarantine::Quarantine::default());
+            #[cfg(feature = "canaries")]
+            std::ptr::addr_of_mut!((*ptr).canaries).write(false);
+            #[cfg(feature = "audit")]
+            std::ptr::addr_of_mut!((*ptr).audit).write(false);
+            #[cfg(feature = "audit")]
+            std::ptr::addr_of_mut!((*ptr).live).write(None);
+            #[cfg(feature = "testing")]
+            std::ptr::addr_of_mut!((*ptr).failure)
+                .write(crate::testing::FailureInjection::default());
+            #[cfg(feature = "latency")]
+            std::ptr::addr_of_mut!((*ptr).latency).write(crate::latency::Latency::default());
+
+            #[cfg(feature = "log")]
+            trace!("InnerAllocator::init head written");
+
+            std::ptr::write(ptr.add(1).cast(), Block::<I>::new(n, None));
+        } else {
+            #[cfg(feature = "log")]
+            trace!("InnerAllocator::init empty");
+
+            (*ptr).head = None;
+            (*ptr).size = 0;
+            (*ptr).oom_hook = None;
+            std::ptr::addr_of_mut!((*ptr).watermarks).write(None);
+            (*ptr).reserve = 0;
+            std::ptr::addr_of_mut!((*ptr).frozen).write(AtomicBool::new(false));
+            std::ptr::addr_of_mut!((*ptr).out_of_band).write(out_of_band);
+            std::ptr::addr_of_mut!((*ptr).strategy).write(Strategy::default());
+            (*ptr).rover = 0;
+            (*ptr).allocations = 0;
+            (*ptr).used = 0;
+            (*ptr).peak = 0;
+            #[cfg(feature = "sanitizer")]
+            std::ptr::addr_of_mut!((*ptr).poisoning).write(false);
+            #[cfg(feature = "quarantine")]
+            std::ptr::addr_of_mut!((*ptr).quarantine)
+                .write(crate::quarantine::Quarantine::default());
+            #[cfg(feature = "canaries")]
+            std::ptr::addr_of_mut!((*ptr).canaries).write(false);
+            #[cfg(feature = "audit")]
+            std::ptr::addr_of_mut!((*ptr).audit).write(false);
+            #[cfg(feature = "audit")]
+            std::ptr::addr_of_mut!((*ptr).live).write(None);
+            #[cfg(feature = "testing")]
+            std::ptr::addr_of_mut!((*ptr).failure)
+                .write(crate::testing::FailureInjection::default());
+            #[cfg(feature = "latency")]
+            std::ptr::addr_of_mut!((*ptr).latency).write(crate::latency::Latency::default());
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct Block<I = usize> {
+    size: I,
+    next: Option<I>,
+}
+
+impl<I: Index> Block<I> {
+    fn new(size: usize, next: Option<usize>) -> Self {
+        Self {
+            size: I::from_usize(size),
+            next: next.map(I::from_usize),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size.to_usize()
+    }
+
+    fn next(&self) -> Option<usize> {
+        self.next.map(Index::to_usize)
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Value<'a, T: ?Sized, I: Index = usize> {
+    pub wrapper: Wrapper<'a, I>,
+    /// The pointer metadata of `T`, e.g. the length of a slice or the vtable of a trait object.
+    metadata: <T as Pointee>::Metadata,
+    __marker: PhantomData<T>,
+}
+
+impl<'a, T: ?Sized, I: Index> Value<'a, T, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Value::allocator");
+
+        self.wrapper.allocator
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Slice::allocator_mut");
+
+        &mut self.wrapper.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Value::index");
+
+        self.wrapper.index
+    }
+
+    /// The offset in bytes of the value from the start of the first block, see
+    /// [`Allocator::ptr_at`].
+    #[must_use]
+    pub fn byte_offset(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Value::byte_offset");
+
+        self.wrapper.byte_offset()
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn index_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Value::index_mut");
+
+        &mut self.wrapper.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Value::size");
+
+        self.wrapper.size
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn size_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Value::size_mut");
+
+        &mut self.wrapper.size
+    }
+
+    #[must_use]
+    pub fn wrapper(&self) -> &Wrapper<'a, I> {
+        #[cfg(feature = "log")]
+        trace!("Value::wrapper");
+
+        &self.wrapper
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn wrapper_mut(&mut self) -> &mut Wrapper<'a, I> {
+        #[cfg(feature = "log")]
+        trace!("Value::wrapper_mut");
+
+        &mut self.wrapper
+    }
+
+    /// Gives up the value without running its destructor, returning the wrapper which still frees
+    /// the blocks on drop, e.g. once the value was handed to another process which drops it.
+    #[must_use]
+    pub fn forget_contents(self) -> Wrapper<'a, I> {
+        #[cfg(feature = "log")]
+        trace!("Value::forget_contents");
+
+        let this = std::mem::ManuallyDrop::new(self);
+        unsafe { std::ptr::read(&this.wrapper) }
+    }
+}
+
+impl<'a, T, I: Index> Value<'a, T, I> {
+    /// Gives up the value without freeing its blocks, returning their index and number, see
+    /// [`Allocator::value_from_raw`].
+    #[must_use]
+    pub fn into_raw(self) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("Value::into_raw");
+
+        self.forget_contents().into_raw_parts()
+    }
+}
+
+impl<'a, T, I: Index> Value<'a, std::mem::MaybeUninit<T>, I> {
+    /// Initializes the value with `value`.
+    ///
+    /// Named `init` as `write` is the volatile write of `bytemuck::Pod` values.
+    #[must_use]
+    pub fn init(self, value: T) -> Value<'a, T, I> {
+        #[cfg(feature = "log")]
+        trace!("Value::init");
+
+        let mut wrapper = self.forget_contents();
+        unsafe { wrapper[..].as_mut_ptr().cast::<T>().write(value) };
+        Value {
+            wrapper,
+            metadata: (),
+            __marker: PhantomData,
+        }
+    }
+
+    /// Converts to a `T` once written, e.g. through [`std::mem::MaybeUninit::as_mut_ptr`].
+    ///
+    /// # Safety
+    ///
+    /// The value must be initialized.
+    #[must_use]
+    pub unsafe fn assume_init(self) -> Value<'a, T, I> {
+        #[cfg(feature = "log")]
+        trace!("Value::assume_init");
+
+        Value {
+            wrapper: self.forget_contents(),
+            metadata: (),
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Copy, I: Index> Value<'a, T, I> {
+    /// Reads the value with a volatile read, for memory written by devices or non-Rust peers
+    /// where the compiler must not cache or reorder accesses.
+    ///
+    /// # Safety
+    ///
+    /// The bytes read must be a valid `T`, e.g. they must have been initialized.
+    #[must_use]
+    pub unsafe fn read_volatile(&self) -> T {
+        #[cfg(feature = "log")]
+        trace!("Value::read_volatile");
+
+        self.wrapper[..].as_ptr().cast::<T>().read_volatile()
+    }
+
+    /// Writes the value with a volatile write, see [`Value::read_volatile`].
+    pub fn write_volatile(&mut self, value: T) {
+        #[cfg(feature = "log")]
+        trace!("Value::write_volatile");
+
+        unsafe {
+            self.wrapper[..]
+                .as_mut_ptr()
+                .cast::<T>()
+                .write_volatile(value);
+        }
+    }
+}
+
+#[cfg(feature = "bytemuck")]
+impl<'a, T: bytemuck::Pod, I: Index> Value<'a, T, I> {
+    /// Reads the value with a volatile read, so a value written by another process sharing the
+    /// allocator is not assumed unchanged. As `T` is [`bytemuck::Pod`] any bytes read are valid.
+    #[must_use]
+    pub fn read(&self) -> T {
+        #[cfg(feature = "log")]
+        trace!("Value::read");
+
+        unsafe { self.wrapper[..].as_ptr().cast::<T>().read_volatile() }
+    }
+
+    /// Writes the value with a volatile write, see [`Value::read`].
+    pub fn write(&mut self, value: T) {
+        #[cfg(feature = "log")]
+        trace!("Value::write");
+
+        unsafe {
+            self.wrapper[..]
+                .as_mut_ptr()
+                .cast::<T>()
+                .write_volatile(value);
+        }
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::bytes_of(&**self)
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        bytemuck::bytes_of_mut(&mut **self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: ?Sized + serde::Serialize, I: Index> serde::Serialize for Value<'a, T, I> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[cfg(feature = "log")]
+        trace!("Value::serialize");
+
+        (**self).serialize(serializer)
+    }
+}
+
+/// Deserializes a `T` into a [`Value`], see [`Allocator::value_seed`].
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct ValueSeed<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    __marker: PhantomData<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, 'de, T: serde::Deserialize<'de>, I: Index> serde::de::DeserializeSeed<'de>
+    for ValueSeed<'a, T, I>
+{
+    type Value = Value<'a, T, I>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        #[cfg(feature = "log")]
+        trace!("ValueSeed::deserialize");
+
+        let x = T::deserialize(deserializer)?;
+        let value = self
+            .allocator
+            .try_allocate_value::<std::mem::MaybeUninit<T>>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(value.init(x))
+    }
+}
+
+impl<'a, T: ?Sized, I: Index> Deref for Value<'a, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Value::deref");
+
+        // TODO Test this deref is to the correct ptr
+        unsafe { &*std::ptr::from_raw_parts(self.wrapper[..].as_ptr().cast::<()>(), self.metadata) }
+    }
+}
+impl<'a, T: ?Sized, I: Index> DerefMut for Value<'a, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Value::deref_mut");
+
+        // TODO Test this deref is to the correct ptr
+        unsafe {
+            &mut *std::ptr::from_raw_parts_mut(
+                self.wrapper[..].as_mut_ptr().cast::<()>(),
+                self.metadata,
+            )
+        }
+    }
+}
+
+impl<'a, T: ?Sized, I: Index> Drop for Value<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Value::drop");
+
+        unsafe { std::ptr::drop_in_place::<T>(&mut **self) };
+    }
+}
+
+// Sending a `Value` moves the `T` and sharing it shares `&T`, the blocks themselves are
+// synchronized by the allocator.
+unsafe impl<'a, T: ?Sized + Send, I: Index> Send for Value<'a, T, I> {}
+unsafe impl<'a, T: ?Sized + Sync, I: Index> Sync for Value<'a, T, I> {}
+
+// A `Wrapper` holds untyped blocks and a reference to an allocator which is `Sync`.
+unsafe impl<'a, I: Index> Send for Wrapper<'a, I> {}
+unsafe impl<'a, I: Index> Sync for Wrapper<'a, I> {}
+
+/// Blocks allocated from an [`Allocator`], freed when dropped.
+///
+/// Freeing walks the address ordered free list from its head to the neighbours of the blocks, so
+/// takes time linear in the number of free regions before them. Where frees dominate, e.g. with
+/// thousands of live allocations, [`crate::boundary_tag::Allocator`] coalesces in constant time
+/// by inspecting the tags adjacent to the blocks.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Wrapper<'a, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    index: usize,
+    size: usize,
+}
+
+impl<'a, I: Index> Wrapper<'a, I> {
+    /// Constructs a wrapper for the allocation of `size` blocks at `index`, e.g. one given up with
+    /// [`Wrapper::into_raw_parts`].
+    ///
+    /// # Safety
+    ///
+    /// The allocation must be live and not held by another wrapper.
+    pub unsafe fn from_raw_parts(allocator: &'a Allocator<I>, index: usize, size: usize) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::from_raw_parts");
+
+        Self {
+            allocator,
+            index,
+            size,
+        }
+    }
+
+    /// Gives up the wrapper without freeing its blocks, returning their index and number, e.g. so
+    /// allocations can link to each other by index within shared memory.
+    #[must_use]
+    pub fn into_raw_parts(self) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::into_raw_parts");
+
+        let this = std::mem::ManuallyDrop::new(self);
+        (this.index, this.size)
+    }
+
+    /// Initializes a nested allocator within the blocks, e.g. so a subsystem can allocate from its
+    /// own arena with its own lock within one shared mapping.
+    ///
+    /// The nested allocator manages the blocks following its header. Returns the wrapper when the
+    /// blocks are too few to hold the header or are not aligned for it.
+    ///
+    /// # Errors
+    ///
+    /// When the blocks cannot hold the nested allocator.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the nested mutex.
+    pub fn into_suballocator(
+        mut self,
+        attr: Option<crate::MutexAttr>,
+    ) -> Result<SubAllocator<'a, I>, Self> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::into_suballocator");
+
+        let ptr = self.as_mut_ptr().cast::<Allocator<I>>();
+        let header = size_of::<Allocator<I>>();
+        let bytes = self.size * size_of::<Block<I>>();
+        if bytes < header || ptr as usize % std::mem::align_of::<Allocator<I>>() != 0 {
+            return Err(self);
+        }
+        unsafe {
+            Allocator::init(ptr, attr, (bytes - header) / size_of::<Block<I>>());
+        }
+        Ok(SubAllocator { wrapper: self })
+    }
+
+    /// Sets the tag reported for the allocation by [`Allocator::live_allocations`], e.g. to
+    /// identify its owner or type.
+    ///
+    /// Does nothing when auditing is disabled.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "audit")]
+    pub fn set_tag(&mut self, tag: u32) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::set_tag");
+
+        let mut inner_allocator_guard = self.allocator.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        if !inner_allocator.audit || self.size == 0 {
+            return;
+        }
+        #[cfg(feature = "canaries")]
+        let guards = usize::from(inner_allocator.canaries);
+        #[cfg(not(feature = "canaries"))]
+        let guards = 0;
+        let index = self.index - guards - crate::audit::blocks::<Block<I>, I>();
+        unsafe { crate::audit::set_tag::<_, I>(inner_allocator.data().as_mut(), index, tag) };
+    }
+
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator_mut");
+
+        &mut self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index");
+
+        self.index
+    }
+
+    /// The offset in bytes of the allocation from the start of the first block, see
+    /// [`Allocator::ptr_at`].
+    #[must_use]
+    pub fn byte_offset(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::byte_offset");
+
+        self.index * size_of::<Block<I>>()
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn index_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index_mut");
+
+        &mut self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::size");
+
+        self.size
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn size_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::size_mut");
+
+        &mut self.size
+    }
+}
+
+impl<'a, I: Index> Deref for Wrapper<'a, I> {
+    type Target = [Block<I>];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref enter");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+
+        let slice = unsafe { &inner_allocator.data().as_ref()[self.index..self.index + self.size] };
+
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref exit");
+
+        slice
+    }
+}
+impl<'a, I: Index> DerefMut for Wrapper<'a, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut enter");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+
+        let slice =
+            unsafe { &mut inner_allocator.data().as_mut()[self.index..self.index + self.size] };
+
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut exit");
+
+        slice
+    }
+}
+
+impl<'a, I: Index> Drop for Wrapper<'a, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop enter");
+
+        if self.size == 0 {
+            return;
+        }
+
+        unsafe {
+            self.allocator.deallocate(self.index, self.size);
+        }
+
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop exit");
+    }
+}
+
+/// An [`Allocator`] nested within the blocks of another, see [`Wrapper::into_suballocator`].
+///
+/// The blocks are freed when dropped, after every allocation from the nested allocator.
+#[derive(Debug)]
+pub struct SubAllocator<'a, I: Index = usize> {
+    wrapper: Wrapper<'a, I>,
+}
+
+impl<'a, I: Index> SubAllocator<'a, I> {
+    /// Returns the blocks holding the nested allocator, which becomes invalid.
+    #[must_use]
+    pub fn into_wrapper(self) -> Wrapper<'a, I> {
+        #[cfg(feature = "log")]
+        trace!("SubAllocator::into_wrapper");
+
+        self.wrapper
+    }
+}
+
+impl<'a, I: Index> Deref for SubAllocator<'a, I> {
+    type Target = Allocator<I>;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("SubAllocator::deref");
+
+        unsafe { &*self.wrapper.as_ptr().cast::<Allocator<I>>() }
+    }
+}
+
+/// A growable vector within an [`Allocator`], resizing its allocation to twice its capacity when
+/// full, see [`crate::collections::AVec`].
+pub type Vec<'a, T, I = usize> = crate::collections::AVec<T, &'a Allocator<I>, I>;
+
+/// A growable UTF-8 string within an [`Allocator`], see [`crate::string::ArenaString`].
+pub type String<'a, I = usize> = crate::string::ArenaString<'a, I>;
+
+/// A fixed length bitset within an [`Allocator`], see [`crate::bitset::Bitset`].
+pub type BitSet<'a, I = usize> = crate::bitset::Bitset<'a, I>;
+
+// See `Value`.
+unsafe impl<'a, T: Send, I: Index> Send for Slice<'a, T, I> {}
+unsafe impl<'a, T: Sync, I: Index> Sync for Slice<'a, T, I> {}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Slice<'a, T, I: Index = usize> {
+    pub wrapper: Wrapper<'a, I>,
+    len: usize,
+    __marker: PhantomData<T>,
+}
+
+impl<'a, T, I: Index> Slice<'a, T, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Slice::allocator");
+
+        self.wrapper.allocator
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Slice::allocator_mut");
+
+        &mut self.wrapper.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Slice::index");
+
+        self.wrapper.index
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn index_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Slice::index_mut");
+
+        &mut self.wrapper.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Slice::size");
+
+        self.wrapper.size
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn size_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Slice::size_mut");
+
+        &mut self.wrapper.size
+    }
+
+    pub fn wrapper(&mut self) -> &Wrapper<'a, I> {
+        &self.wrapper
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn wrapper_mut(&mut self) -> &mut Wrapper<'a, I> {
+        &mut self.wrapper
+    }
+
+    /// Gives up the elements without running their destructors, returning the wrapper which still
+    /// frees the blocks on drop, see [`Value::forget_contents`].
+    #[must_use]
+    pub fn forget_contents(self) -> Wrapper<'a, I> {
+        #[cfg(feature = "log")]
+        trace!("Slice::forget_contents");
+
+        let this = std::mem::ManuallyDrop::new(self);
+        unsafe { std::ptr::read(&this.wrapper) }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Slice::len");
+
+        self.len
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn len_mut(&mut self) -> &mut usize {
+        &mut self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Slice::is_empty");
+
+        self.len == 0
+    }
+
+    /// The number of elements the blocks can hold, at least [`Slice::len`] as the blocks are
+    /// rounded up.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Slice::capacity");
+
+        if size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            self.wrapper.size * size_of::<Block<I>>() / size_of::<T>()
+        }
+    }
+
+    /// Appends `x`, doubling the capacity when full, or returns it when there is no free region
+    /// large enough.
+    ///
+    /// # Errors
+    ///
+    /// When the slice is full and there is no free region large enough to grow it.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn push(&mut self, x: T) -> Result<(), T> {
+        #[cfg(feature = "log")]
+        trace!("Slice::push");
+
+        let len = self.len;
+        if len == self.capacity() && self.reallocate(std::cmp::max(2 * len, 1)).is_none() {
+            return Err(x);
+        }
+        unsafe { self.wrapper.as_mut_ptr().cast::<T>().add(len).write(x) };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the last element and returns it, or `None` if empty.
+    pub fn pop(&mut self) -> Option<T> {
+        #[cfg(feature = "log")]
+        trace!("Slice::pop");
+
+        self.len = self.len.checked_sub(1)?;
+        Some(unsafe { self.wrapper.as_ptr().cast::<T>().add(self.len).read() })
+    }
+
+    /// Shortens the slice to `len` elements, dropping the rest and keeping its blocks, or does
+    /// nothing when it is not longer.
+    pub fn truncate(&mut self, len: usize) {
+        #[cfg(feature = "log")]
+        trace!("Slice::truncate");
+
+        if len >= self.len {
+            return;
+        }
+        let tail = std::ptr::addr_of_mut!(self[len..]);
+        // The length is shortened first so a panicking destructor can't drop an element twice.
+        self.len = len;
+        unsafe { std::ptr::drop_in_place(tail) };
+    }
+
+    /// Sets the length to `len`, reallocating when growing beyond [`Slice::capacity`] or
+    /// shrinking.
+    ///
+    /// Added elements are uninitialized, like those of [`Allocator::allocate_slice`], and
+    /// removed elements are dropped. See [`Slice::resize_with`].
+    ///
+    /// Returns `None` when there is no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn resize(&mut self, len: usize) -> Option<()> {
+        #[cfg(feature = "log")]
+        trace!("Slice::resize enter");
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "linked_list::resize",
+            index = self.index(),
+            size = self.size(),
+            from = self.len,
+            to = len,
+        )
+        .entered();
+
+        // If resizing to current size, we can do nothing.
+        if self.len() == len {
+            return Some(());
+        }
+        // Growing within the rounding of the blocks needs no new allocation.
+        if len < self.len || len > self.capacity() {
+            self.reallocate(len)?;
+        }
+        self.len = len;
+
+        #[cfg(feature = "log")]
+        trace!("Slice::resize exit");
+
+        Some(())
+    }
+
+    /// Sets the length to `len` like [`Slice::resize`], writing the values returned by `f` to the
+    /// added elements.
+    ///
+    /// Returns `None` when there is no free region large enough.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn resize_with(&mut self, len: usize, mut f: impl FnMut() -> T) -> Option<()> {
+        #[cfg(feature = "log")]
+        trace!("Slice::resize_with");
+
+        if len <= self.len {
+            return self.resize(len);
+        }
+        if len > self.capacity() {
+            self.reallocate(len)?;
+        }
+
+        // The length counts the elements written so a panic drops only those.
+        let ptr = self.wrapper[..].as_mut_ptr().cast::<T>();
+        while self.len < len {
+            unsafe { ptr.add(self.len).write(f()) };
+            self.len += 1;
+        }
+        Some(())
+    }
+
+    /// Moves the elements to a new allocation with space for `capacity` elements, dropping those
+    /// beyond it.
+    ///
+    /// Returns `None` when there is no free region large enough, leaving the slice unchanged.
+    fn reallocate(&mut self, capacity: usize) -> Option<()> {
+        #[cfg(feature = "log")]
+        trace!("Slice::reallocate");
+
+        let mut new = self.wrapper.allocator.allocate_slice(capacity)?;
+        new.len = 0;
+        self.truncate(capacity);
+
+        // Copy data to new allocation
+        let from = self[..].as_ptr();
+        let to = new.wrapper[..].as_mut_ptr().cast::<T>();
+        unsafe {
+            std::ptr::copy(from, to, self.len);
+        }
+        new.len = self.len;
+
+        // Update wrapper, the elements having been moved.
+        drop(std::mem::replace(self, new).forget_contents());
+
+        Some(())
+    }
+}
+
+impl<'a, T: Copy, I: Index> Slice<'a, T, I> {
+    /// Returns a view where every access is volatile, for memory written by devices or non-Rust
+    /// peers where the compiler must not cache or reorder accesses.
+    pub fn as_volatile(&mut self) -> crate::volatile::VolatileSlice<'_, T> {
+        #[cfg(feature = "log")]
+        trace!("Slice::as_volatile");
+
+        unsafe {
+            crate::volatile::VolatileSlice::from_raw_parts(
+                NonNull::new_unchecked(self.wrapper[..].as_mut_ptr().cast()),
+                self.len,
+            )
+        }
+    }
+}
+
+#[cfg(feature = "bytemuck")]
+impl<'a, T: bytemuck::Pod, I: Index> Slice<'a, T, I> {
+    /// Reads the element at `index` with a volatile read, see [`Value::read`].
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    #[must_use]
+    pub fn read(&self, index: usize) -> T {
+        #[cfg(feature = "log")]
+        trace!("Slice::read");
+
+        assert!(
+            index < self.len,
+            "index {index} out of range for {}",
+            self.len
+        );
+        unsafe {
+            self.wrapper[..]
+                .as_ptr()
+                .cast::<T>()
+                .add(index)
+                .read_volatile()
+        }
+    }
+
+    /// Writes the element at `index` with a volatile write, see [`Value::read`].
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    pub fn write(&mut self, index: usize, value: T) {
+        #[cfg(feature = "log")]
+        trace!("Slice::write");
+
+        assert!(
+            index < self.len,
+            "index {index} out of range for {}",
+            self.len
+        );
+        unsafe {
+            self.wrapper[..]
+                .as_mut_ptr()
+                .cast::<T>()
+                .add(index)
+                .write_volatile(value);
+        }
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self)
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        bytemuck::cast_slice_mut(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: serde::Serialize, I: Index> serde::Serialize for Slice<'a, T, I> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[cfg(feature = "log")]
+        trace!("Slice::serialize");
+
+        (**self).serialize(serializer)
+    }
+}
+
+/// Deserializes a sequence of `T` into a [`Slice`], see [`Allocator::slice_seed`].
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct SliceSeed<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    __marker: PhantomData<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, 'de, T: serde::Deserialize<'de>, I: Index> serde::de::DeserializeSeed<'de>
+    for SliceSeed<'a, T, I>
+{
+    type Value = Slice<'a, T, I>;
+
+    /// The elements are deserialized before allocating, as the length of a sequence may not be
+    /// known upfront.
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        #[cfg(feature = "log")]
+        trace!("SliceSeed::deserialize");
+
+        let values = <std::vec::Vec<T> as serde::Deserialize>::deserialize(deserializer)?;
+        let mut slice = self
+            .allocator
+            .try_allocate_slice::<T>(values.len())
+            .map_err(serde::de::Error::custom)?;
+        let ptr = slice.wrapper[..].as_mut_ptr().cast::<T>();
+        for (i, x) in values.into_iter().enumerate() {
+            unsafe { ptr.add(i).write(x) };
+        }
+        Ok(slice)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<'a, I: Index> Slice<'a, u8, I> {
+    /// Accesses the archived `T` written by [`Allocator::allocate_archived`] without copying,
+    /// checking the bytes are a valid archive first as they may have been written by another
+    /// process.
+    ///
+    /// # Errors
+    ///
+    /// When the bytes are not a valid archived `T`, including when `T::Archived` requires a
+    /// greater alignment than a block.
+    pub fn access<T: rkyv::Archive>(&self) -> Result<&T::Archived, rkyv::rancor::Error>
+    where
+        T::Archived: rkyv::Portable
+            + for<'b> rkyv::bytecheck::CheckBytes<
+                rkyv::api::high::HighValidator<'b, rkyv::rancor::Error>,
+            >,
+    {
+        #[cfg(feature = "log")]
+        trace!("Slice::access");
+
+        rkyv::access::<T::Archived, rkyv::rancor::Error>(self)
+    }
+}
+
+impl<'a, T, I: Index> Deref for Slice<'a, T, I> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Slice::deref enter");
+
+        // TODO Test this deref is to the correct ptr
+        let slice =
+            unsafe { &*std::ptr::from_raw_parts(self.wrapper[..].as_ptr().cast(), self.len) };
+
+        #[cfg(feature = "log")]
+        trace!("Slice::deref exit");
+
+        slice
+    }
+}
+impl<'a, T, I: Index> DerefMut for Slice<'a, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Slice::deref_mut enter");
+
+        // TODO Test this deref is to the correct ptr
+        let slice = unsafe {
+            &mut *std::ptr::from_raw_parts_mut(self.wrapper[..].as_mut_ptr().cast(), self.len)
+        };
+
+        #[cfg(feature = "log")]
+        trace!("Slice::deref_mut exit");
+
+        slice
+    }
+}
+
+impl<'a, T, I: Index> Drop for Slice<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Slice::drop");
+
+        unsafe { std::ptr::drop_in_place::<[T]>(&mut **self) };
+    }
+}
+
+impl<'a, T, I: Index> IntoIterator for Slice<'a, T, I> {
+    type Item = T;
+    type IntoIter = IntoIter<'a, T, I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        #[cfg(feature = "log")]
+        trace!("Slice::into_iter");
+
+        let end = self.len;
+        IntoIter {
+            buf: Slice {
+                len: end,
+                wrapper: self.forget_contents(),
+                __marker: PhantomData,
+            },
+            start: 0,
+            end,
+        }
+    }
+}
+impl<'a, 'b, T, I: Index> IntoIterator for &'b Slice<'a, T, I> {
+    type Item = &'b T;
+    type IntoIter = std::slice::Iter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+impl<'a, 'b, T, I: Index> IntoIterator for &'b mut Slice<'a, T, I> {
+    type Item = &'b mut T;
+    type IntoIter = std::slice::IterMut<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator moving the elements out of a [`Slice`], its blocks are freed when it is dropped.
+pub struct IntoIter<'a, T, I: Index = usize> {
+    buf: Slice<'a, std::mem::MaybeUninit<T>, I>,
+    /// The elements in `start..end` have not been moved out.
+    start: usize,
+    end: usize,
+}
+
+impl<'a, T, I: Index> IntoIter<'a, T, I> {
+    /// Returns the remaining elements.
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe {
+            &*std::ptr::from_raw_parts(
+                self.buf.as_ptr().add(self.start).cast::<T>(),
+                self.end - self.start,
+            )
+        }
+    }
+
+    /// Returns the remaining elements.
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe {
+            &mut *std::ptr::from_raw_parts_mut(
+                self.buf.as_mut_ptr().add(self.start).cast::<T>(),
+                self.end - self.start,
+            )
+        }
+    }
+}
+
+impl<'a, T, I: Index> Iterator for IntoIter<'a, T, I> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.start += 1;
+        Some(unsafe { self.buf[self.start - 1].assume_init_read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+impl<'a, T, I: Index> DoubleEndedIterator for IntoIter<'a, T, I> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { self.buf[self.end].assume_init_read() })
+    }
+}
+impl<'a, T, I: Index> ExactSizeIterator for IntoIter<'a, T, I> {}
+impl<'a, T, I: Index> std::iter::FusedIterator for IntoIter<'a, T, I> {}
+
+impl<'a, T, I: Index> Drop for IntoIter<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("IntoIter::drop");
+
+        unsafe { std::ptr::drop_in_place(self.as_mut_slice()) };
+    }
+}
+
+impl<'a, T: fmt::Debug, I: Index> fmt::Debug for IntoIter<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
+
+impl<'a, T, I: Index> Slice<'a, std::mem::MaybeUninit<T>, I> {
+    /// Converts to a `[T]` once every element is written.
+    ///
+    /// # Safety
+    ///
+    /// Every element must be initialized.
+    #[must_use]
+    pub unsafe fn assume_init(self) -> Slice<'a, T, I> {
+        #[cfg(feature = "log")]
+        trace!("Slice::assume_init");
+
+        Slice {
+            len: self.len,
+            wrapper: self.forget_contents(),
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, I: Index> Slice<'a, T, I> {
+    /// Returns a view of `range`, which can be passed around in place of `&self[range]`.
+    ///
+    /// # Panics
+    ///
+    /// When `range` is out of bounds, as when indexing a slice.
+    pub fn view<R: SliceIndex<[T], Output = [T]>>(&self, range: R) -> SliceView<'_, T, I> {
+        #[cfg(feature = "log")]
+        trace!("Slice::view");
+
+        let sub = &self[range];
+        SliceView {
+            allocator: self.wrapper.allocator,
+            index: self.wrapper.index,
+            start: element_offset(self, sub),
+            len: sub.len(),
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, I: Index> Slice<'a, T, I> {
+    /// Gives up the slice without freeing its blocks, returning a handle which holds no reference
+    /// to the allocator, so it can be stored in shared memory and attached by another process with
+    /// [`Allocator::attach_slice`].
+    #[must_use]
+    pub fn detach(self) -> RawSlice<T> {
+        #[cfg(feature = "log")]
+        trace!("Slice::detach");
+
+        let len = self.len;
+        let (index, size) = self.forget_contents().into_raw_parts();
+        RawSlice {
+            index,
+            size,
+            len,
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, I: Index> Value<'a, T, I> {
+    /// Gives up the value without freeing its blocks, returning a handle which holds no reference
+    /// to the allocator, see [`Allocator::attach_value`].
+    #[must_use]
+    pub fn detach(self) -> RawValue<T> {
+        #[cfg(feature = "log")]
+        trace!("Value::detach");
+
+        let (index, size) = self.into_raw();
+        RawValue {
+            index,
+            size,
+            __marker: PhantomData,
+        }
+    }
+}
+
+impl<I: Index> Allocator<I> {
+    /// Rebinds a handle given up with [`Slice::detach`] to this allocator.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be of a live allocation of this allocator, e.g. as mapped by another
+    /// process, which is not held by another slice.
+    pub unsafe fn attach_slice<T>(&self, raw: RawSlice<T>) -> Slice<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::attach_slice");
+
+        Slice {
+            wrapper: Wrapper::from_raw_parts(self, raw.index, raw.size),
+            len: raw.len,
+            __marker: PhantomData,
+        }
+    }
+
+    /// Rebinds a handle given up with [`Value::detach`] to this allocator.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be of a live allocation of this allocator, e.g. as mapped by another
+    /// process, which is not held by another value.
+    pub unsafe fn attach_value<T>(&self, raw: RawValue<T>) -> Value<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::attach_value");
+
+        self.value_from_raw(raw.index, raw.size)
+    }
+}
+
+/// A [`Slice`] detached from its allocator, holding only the index and number of its blocks and
+/// its length, which are valid in every process mapping the allocator.
+#[repr(C)]
+pub struct RawSlice<T> {
+    index: usize,
+    size: usize,
+    len: usize,
+    __marker: PhantomData<T>,
+}
+
+impl<T> RawSlice<T> {
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Clone for RawSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for RawSlice<T> {}
+
+impl<T> fmt::Debug for RawSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawSlice")
+            .field("index", &self.index)
+            .field("size", &self.size)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// A [`Value`] detached from its allocator, holding only the index and number of its blocks, see
+/// [`RawSlice`].
+#[repr(C)]
+pub struct RawValue<T> {
+    index: usize,
+    size: usize,
+    __marker: PhantomData<T>,
+}
+
+impl<T> RawValue<T> {
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T> Clone for RawValue<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for RawValue<T> {}
+
+impl<T> fmt::Debug for RawValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawValue")
+            .field("index", &self.index)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+/// A non-owning view of part of a [`Slice`], holding the index and length of the elements rather
+/// than a reference so each access re-derives the elements through the allocator.
+pub struct SliceView<'s, T, I: Index = usize> {
+    allocator: &'s Allocator<I>,
+    /// The index of the first block of the allocation.
+    index: usize,
+    /// The offset of the first element of the view from the start of the allocation.
+    start: usize,
+    len: usize,
+    __marker: PhantomData<&'s [T]>,
+}
+
+impl<'s, T, I: Index> SliceView<'s, T, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &'s Allocator<I> {
+        self.allocator
+    }
+
+    /// The index of the first block of the allocation the view is within.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The offset in elements of the view from the start of the allocation.
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a view of `range` within this view.
+    ///
+    /// # Panics
+    ///
+    /// When `range` is out of bounds, as when indexing a slice.
+    #[must_use]
+    pub fn view<R: SliceIndex<[T], Output = [T]>>(&self, range: R) -> Self {
+        #[cfg(feature = "log")]
+        trace!("SliceView::view");
+
+        let sub = &self[range];
+        Self {
+            start: self.start + element_offset(self, sub),
+            len: sub.len(),
+            ..*self
+        }
+    }
+}
+
+/// Returns the offset in elements of `sub` from the start of `slice`, which it must be within.
+fn element_offset<T>(slice: &[T], sub: &[T]) -> usize {
+    (sub.as_ptr() as usize - slice.as_ptr() as usize) / std::cmp::max(size_of::<T>(), 1)
+}
+
+impl<'s, T, I: Index> Clone for SliceView<'s, T, I> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'s, T, I: Index> Copy for SliceView<'s, T, I> {}
+
+impl<'s, T, I: Index> Deref for SliceView<'s, T, I> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("SliceView::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe {
+            let data = inner_allocator
+                .data()
+                .as_ptr()
+                .cast::<Block<I>>()
+                .add(self.index);
+            &*std::ptr::from_raw_parts(data.cast::<T>().add(self.start), self.len)
+        }
+    }
+}
+
+impl<'s, T: fmt::Debug, I: Index> fmt::Debug for SliceView<'s, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// See `Value`.
+unsafe impl<'s, T: Sync, I: Index> Send for SliceView<'s, T, I> {}
+unsafe impl<'s, T: Sync, I: Index> Sync for SliceView<'s, T, I> {}
+
+impl<I: Index> fmt::Display for Allocator<I> {
+    /// Summarizes usage in one line, e.g. `3/8 blocks used (72/192 bytes), 2 free regions,
+    /// largest free region 4 blocks`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.stats().summarize(f, "blocks", size_of::<Block<I>>())
+    }
+}
+
+impl<I: Index> RawArrayAllocator for Allocator<I> {
+    fn block_size(&self) -> usize {
+        size_of::<Block<I>>()
+    }
+
+    fn block_align(&self) -> usize {
+        std::mem::align_of::<Block<I>>()
+    }
+
+    #[cfg_attr(feature = "profiling", track_caller)]
+    fn allocate_bytes(&self, bytes: usize) -> Option<RawAllocation> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_bytes");
+
+        let wrapper = self.allocate(bytes.div_ceil(size_of::<Block<I>>()))?;
+        let allocation = RawAllocation {
+            index: wrapper.index,
+            size: wrapper.size,
+        };
+        std::mem::forget(wrapper);
+        Some(allocation)
+    }
+
+    unsafe fn free(&self, allocation: RawAllocation) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free");
+
+        if allocation.size != 0 {
+            self.deallocate(allocation.index, allocation.size);
+        }
+    }
+
+    unsafe fn as_ptr(&self, allocation: RawAllocation) -> NonNull<u8> {
+        let data = (*self.0.get()).data();
+        NonNull::new_unchecked(
+            data.as_ptr()
+                .cast::<Block<I>>()
+                .add(allocation.index)
+                .cast(),
+        )
+    }
+
+    fn stats(&self) -> Stats {
+        Allocator::stats(self)
+    }
+}
+
+/// Backs std collections, e.g. `Vec::new_in(&allocator)`, aligning over-aligned layouts as
+/// [`Allocator::allocate_layout`] does.
+#[cfg(feature = "allocator-api")]
+unsafe impl<I: Index> std::alloc::Allocator for Allocator<I> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        if layout.size() == 0 {
+            // Zero sized allocations don't need to point into the array.
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        let mut wrapper = self.allocate_layout(layout).ok_or(std::alloc::AllocError)?;
+        let ptr = NonNull::new(wrapper.as_mut_ptr().cast::<u8>()).unwrap();
+        let (_, size) = wrapper.into_raw_parts();
+        Ok(NonNull::slice_from_raw_parts(
+            ptr,
+            size * size_of::<Block<I>>(),
+        ))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::deallocate");
+
+        if layout.size() == 0 {
+            return;
+        }
+        let data = (*self.0.get()).data();
+        let offset = ptr
+            .as_ptr()
+            .offset_from(data.as_ptr().cast::<Block<I>>().cast::<u8>());
+        let index = usize::try_from(offset).unwrap() / size_of::<Block<I>>();
+        Allocator::deallocate(self, index, layout.size().div_ceil(size_of::<Block<I>>()));
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+unsafe impl<I: Index> allocator_api2::alloc::Allocator for Allocator<I> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    fn allocate(
+        &self,
+        layout: std::alloc::Layout,
+    ) -> Result<NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        if layout.align() > std::mem::align_of::<Block<I>>() {
+            return Err(allocator_api2::alloc::AllocError);
+        }
+        if layout.size() == 0 {
+            // Zero sized allocations don't need to point into the array.
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        let allocation = self
+            .allocate_bytes(layout.size())
+            .ok_or(allocator_api2::alloc::AllocError)?;
+        let ptr = unsafe { self.as_ptr(allocation) };
+        Ok(NonNull::slice_from_raw_parts(
+            ptr,
+            allocation.size * size_of::<Block<I>>(),
+        ))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: std::alloc::Layout) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::deallocate");
+
+        if layout.size() == 0 {
+            return;
+        }
+        let data = (*self.0.get()).data();
+        let offset = ptr
+            .as_ptr()
+            .offset_from(data.as_ptr().cast::<Block<I>>().cast::<u8>());
+        let index = usize::try_from(offset).unwrap() / size_of::<Block<I>>();
+        Allocator::deallocate(self, index, layout.size().div_ceil(size_of::<Block<I>>()));
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<I: Index> crate::testing::FreeRegions for Allocator<I> {
+    fn free_regions(&self) -> std::vec::Vec<crate::testing::Region> {
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        let meta = unsafe { inner_allocator.meta().as_ref() };
+
+        let mut regions = std::vec::Vec::new();
+        let mut next = inner_allocator.head;
+        while let Some(index) = next {
+            regions.push(crate::testing::Region {
+                index,
+                size: meta[index].size(),
+            });
+            next = meta[index].next();
+        }
+        regions
+    }
+}
+
+impl<I: Index> AsRef<Allocator<I>> for Allocator<I> {
+    fn as_ref(&self) -> &Allocator<I> {
+        self
+    }
+}
+impl<const N: usize, I: Index> AsRef<Allocator<I>> for ArrayAllocator<N, I> {
+    fn as_ref(&self) -> &Allocator<I> {
+        &self.allocator
+    }
+}
+
+/// A [`Wrapper`] which holds a handle `A` to its allocator rather than a reference, e.g.
+/// `Arc<ArrayAllocator<N>>`, so it can outlive any borrow of the allocator.
+#[derive(Debug)]
+pub struct OwnedWrapper<A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    allocator: A,
+    index: usize,
+    size: usize,
+    __marker: PhantomData<I>,
+}
+
+impl<A, I: Index> OwnedWrapper<A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Allocates a given number of blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate(allocator: A, blocks: usize) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::allocate");
+
+        let wrapper = (*allocator).as_ref().allocate(blocks)?;
+        let (index, size) = (wrapper.index, wrapper.size);
+        std::mem::forget(wrapper);
+        Some(Self {
+            allocator,
+            index,
+            size,
+            __marker: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::allocator");
+
+        (*self.allocator).as_ref()
+    }
+
+    #[must_use]
+    pub fn handle(&self) -> &A {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::handle");
+
+        &self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::index");
+
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::size");
+
+        self.size
+    }
+}
+
+impl<A, I: Index> Deref for OwnedWrapper<A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Target = [Block<I>];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &mut *(self.allocator().0.get()) };
+
+        unsafe { &inner_allocator.data().as_ref()[self.index..self.index + self.size] }
+    }
+}
+impl<A, I: Index> DerefMut for OwnedWrapper<A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::deref_mut");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &mut *(self.allocator().0.get()) };
+
+        unsafe { &mut inner_allocator.data().as_mut()[self.index..self.index + self.size] }
+    }
+}
+
+impl<A, I: Index> Drop for OwnedWrapper<A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::drop");
+
+        if self.size == 0 {
+            return;
+        }
+
+        unsafe {
+            self.allocator().deallocate(self.index, self.size);
+        }
+    }
+}
+
+// The owned variants additionally require the handle to be `Send`/`Sync`.
+unsafe impl<A, I: Index> Send for OwnedWrapper<A, I>
+where
+    A: Deref + Send,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+unsafe impl<A, I: Index> Sync for OwnedWrapper<A, I>
+where
+    A: Deref + Sync,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+
+/// A [`Value`] which holds a handle to its allocator, see [`OwnedWrapper`].
+#[derive(Debug)]
+pub struct OwnedValue<T, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    pub wrapper: OwnedWrapper<A, I>,
+    __marker: PhantomData<T>,
+}
+
+impl<T, A, I: Index> OwnedValue<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Allocates space for a `T`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate(allocator: A) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("OwnedValue::allocate");
+
+        let blocks = size_of::<T>().div_ceil(size_of::<Block<I>>());
+        OwnedWrapper::allocate(allocator, blocks).map(|wrapper| Self {
+            wrapper,
+            __marker: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub fn wrapper(&self) -> &OwnedWrapper<A, I> {
+        #[cfg(feature = "log")]
+        trace!("OwnedValue::wrapper");
+
+        &self.wrapper
+    }
+}
+
+impl<T, A, I: Index> Deref for OwnedValue<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedValue::deref");
+
+        unsafe { &*self.wrapper[..].as_ptr().cast() }
+    }
+}
+impl<T, A, I: Index> DerefMut for OwnedValue<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedValue::deref_mut");
+
+        unsafe { &mut *self.wrapper[..].as_mut_ptr().cast() }
+    }
+}
+
+unsafe impl<T: Send, A, I: Index> Send for OwnedValue<T, A, I>
+where
+    A: Deref + Send,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+unsafe impl<T: Sync, A, I: Index> Sync for OwnedValue<T, A, I>
+where
+    A: Deref + Sync,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+
+/// A [`Slice`] which holds a handle to its allocator, see [`OwnedWrapper`].
+#[derive(Debug)]
+pub struct OwnedSlice<T, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    pub wrapper: OwnedWrapper<A, I>,
+    len: usize,
+    __marker: PhantomData<T>,
+}
+
+impl<T, A, I: Index> OwnedSlice<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    /// Allocates `[T]`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate(allocator: A, len: usize) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::allocate");
+
+        let blocks = (len * size_of::<T>()).div_ceil(size_of::<Block<I>>());
+        OwnedWrapper::allocate(allocator, blocks).map(|wrapper| Self {
+            wrapper,
+            len,
+            __marker: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub fn wrapper(&self) -> &OwnedWrapper<A, I> {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::wrapper");
+
+        &self.wrapper
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::len");
+
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::is_empty");
+
+        self.len == 0
+    }
+
+    /// Resizes the slice, moving it to a new allocation.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn resize(&mut self, len: usize) -> Option<()>
+    where
+        A: Clone,
+    {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::resize");
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "linked_list::resize",
+            index = self.wrapper.index,
+            size = self.wrapper.size,
+            from = self.len,
+            to = len,
+        )
+        .entered();
+
+        if self.len == len {
+            return Some(());
+        }
+
+        let mut new = Self::allocate(self.wrapper.allocator.clone(), len)?;
+
+        let n = std::cmp::min(len, self.len);
+        unsafe {
+            std::ptr::copy(self[..].as_ptr(), new[..].as_mut_ptr(), n);
+        }
+
+        drop(std::mem::replace(self, new));
+
+        Some(())
+    }
+}
+
+impl<T, A, I: Index> Deref for OwnedSlice<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::deref");
+
+        unsafe { &*std::ptr::from_raw_parts(self.wrapper[..].as_ptr().cast(), self.len) }
+    }
+}
+impl<T, A, I: Index> DerefMut for OwnedSlice<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedSlice::deref_mut");
+
+        unsafe {
+            &mut *std::ptr::from_raw_parts_mut(self.wrapper[..].as_mut_ptr().cast(), self.len)
+        }
+    }
+}
+
+unsafe impl<T: Send, A, I: Index> Send for OwnedSlice<T, A, I>
+where
+    A: Deref + Send,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+unsafe impl<T: Sync, A, I: Index> Sync for OwnedSlice<T, A, I>
+where
+    A: Deref + Sync,
+    A::Target: AsRef<Allocator<I>>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn slice_debug() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+
+        let expected = "Slice { wrapper: Wrapper { allocator: Allocator(Mutex { lock: \
+                        Mutex(UnsafeCell { .. }), data: UnsafeCell { .. } }), index: 0, size: 1 \
+                        }, len: 3, __marker: PhantomData<u8> }";
+
+        assert_eq!(format!("{wrapper:?}"), expected);
+    }
+    #[test]
+    fn slice_allocator() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        let _ = wrapper.allocator();
+    }
+    #[test]
+    fn slice_index() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        assert_eq!(wrapper.index(), 0);
+    }
+    #[test]
+    fn slice_size() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        assert_eq!(wrapper.size(), 1);
+    }
+    #[test]
+    fn slice_len() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        assert_eq!(wrapper.len(), 3);
+    }
+    #[test]
+    fn slice_is_empty() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        assert!(!wrapper.is_empty());
+    }
+    #[test]
+    fn slice_resize() {
+        let allocator = ArrayAllocator::<5>::new(None);
+        let mut wrapper = allocator.allocate_slice::<u8>(2).unwrap();
+        wrapper[0] = 0;
+        wrapper[1] = 1;
+        wrapper.resize(3).unwrap();
+        wrapper[0] = 0;
+        wrapper[1] = 1;
+    }
+    #[test]
+    fn slice_push() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let mut slice = allocator.allocate_slice::<u32>(1).unwrap();
+        slice[0] = 0;
+        let capacity = size_of::<Block>() / size_of::<u32>();
+        assert_eq!(slice.capacity(), capacity);
+        for i in 1..capacity as u32 {
+            slice.push(i).unwrap();
+        }
+        // The spare capacity is used before reallocating.
+        assert_eq!((slice.index(), slice.size()), (0, 1));
+        slice.push(capacity as u32).unwrap();
+        assert_eq!(slice.size(), 2);
+        assert_eq!(slice.len(), capacity + 1);
+        assert_eq!(slice.pop(), Some(capacity as u32));
+        slice.truncate(2);
+        assert_eq!(&slice[..], [0, 1]);
+        slice.resize_with(3, || 2).unwrap();
+        assert_eq!(&slice[..], [0, 1, 2]);
+        assert_eq!(slice.size(), 2);
+
+        let mut slice = allocator.allocate_slice::<u32>(0).unwrap();
+        assert_eq!(slice.pop(), None);
+        slice.push(7).unwrap();
+        assert_eq!(&slice[..], [7]);
+        let _rest = allocator.allocate(allocator.stats().largest_free).unwrap();
+        slice.resize_with(slice.capacity(), || 0).unwrap();
+        assert_eq!(slice.push(8), Err(8));
+    }
+
+    #[test]
+    fn slice_uninit() {
+        let allocator = ArrayAllocator::<4>::new(None);
+        let mut slice = allocator.allocate_slice_uninit::<u16>(5).unwrap();
+        for (i, x) in slice.iter_mut().enumerate() {
+            x.write(i as u16);
+        }
+        let slice = unsafe { slice.assume_init() };
+        assert_eq!(&slice[..], [0, 1, 2, 3, 4]);
+        drop(slice);
+        assert_eq!(allocator.stats().free, 4);
+    }
+
+    #[test]
+    fn slice_deref() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let mut wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        wrapper[0] = 0;
+        assert_eq!(wrapper[0], 0);
+        wrapper[1] = 1;
+        assert_eq!(wrapper[1], 1);
+        wrapper[2] = 2;
+        assert_eq!(wrapper[2], 2);
+    }
+    #[test]
+    fn slice_deref_mut() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let mut wrapper = allocator.allocate_slice::<u8>(3).unwrap();
+        wrapper[0] = 0;
+        assert_eq!(wrapper[0], 0);
+        wrapper[1] = 1;
+        assert_eq!(wrapper[1], 1);
+        wrapper[2] = 2;
+        assert_eq!(wrapper[2], 2);
+    }
+    #[test]
+    fn slice_into_iter() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let value = std::rc::Rc::new(());
+        let slice = allocator
+            .allocate_slice_from_iter((0..4).map(|_| value.clone()))
+            .unwrap();
+        let mut iter = slice.into_iter();
+        assert_eq!(iter.len(), 4);
+        drop(iter.next().unwrap());
+        drop(iter.next_back().unwrap());
+        assert_eq!(std::rc::Rc::strong_count(&value), 3);
+        // The remaining elements are dropped with the iterator, which frees the blocks.
+        drop(iter);
+        assert_eq!(std::rc::Rc::strong_count(&value), 1);
+        assert_eq!(allocator.stats().free, 8);
+
+        let slice = allocator.allocate_slice_from_iter(1u32..4).unwrap();
+        assert_eq!(
+            slice.into_iter().rev().collect::<std::vec::Vec<_>>(),
+            [3, 2, 1]
+        );
+    }
+    #[test]
+    fn vec() {
+        let allocator = ArrayAllocator::<8>::new(None);
+        let mut vec: Vec<u32> = allocator.allocate_vec(1).unwrap();
+        vec.push(1);
+        // Growing resizes the allocation.
+        vec.push(3);
+        vec.insert(1, 2);
+        vec.extend([4, 5]);
+        assert_eq!(vec.as_slice(), [1, 2, 3, 4, 5]);
+        assert_eq!(vec.remove(0), 1);
+        assert_eq!(vec.pop(), Some(5));
+        assert_eq!(vec.as_slice(), [2, 3, 4]);
+        assert!(vec.capacity() >= 5);
+        drop(vec);
+        assert_eq!(allocator.stats().free, 8);
+    }
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn slice_parallel_resize() {
+        const THREADS: usize = 64;
+        const SAMPLES: usize = 256;
+
+        const NUM: std::ops::Range<usize> = 0..5;
+        const SIZE: usize = 2 * THREADS * NUM.end;
+        let allocator = ArrayAllocator::<SIZE>::new(None);
+
+        let arc = std::sync::Arc::new(allocator);
+        let handles = (0..THREADS)
+            .map(move |_| {
+                let arc_clone = arc.clone();
+                std::thread::spawn(move || {
+                    let mut rng = rand::thread_rng();
+
+                    let mut slice = arc_clone.allocate_slice::<u8>(rng.gen_range(NUM)).unwrap();
+                    for _ in 0..SAMPLES {
+                        slice.resize(rng.gen_range(NUM)).unwrap();
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn owned_wrapper() {
+        let allocator = std::sync::Arc::new(ArrayAllocator::<3>::new(None));
+        let wrapper = OwnedWrapper::allocate(allocator.clone(), 2).unwrap();
+        assert_eq!(wrapper.index(), 0);
+        assert_eq!(wrapper.size(), 2);
+        assert_eq!(wrapper.len(), 2);
+        assert!(OwnedWrapper::allocate(allocator.clone(), 2).is_none());
+        drop(wrapper);
+        assert!(OwnedWrapper::allocate(allocator, 3).is_some());
+    }
+    #[test]
+    fn owned_value() {
+        let allocator = std::sync::Arc::new(ArrayAllocator::<1>::new(None));
+        let mut value = OwnedValue::<u8, _>::allocate(allocator.clone()).unwrap();
+        *value = 1;
+        let value = std::thread::spawn(move || {
+            assert_eq!(*value, 1);
+            value
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            value.wrapper().allocator() as *const Allocator,
+            &**allocator
+        );
+    }
+    #[test]
+    fn owned_slice() {
+        static ALLOCATOR: std::sync::OnceLock<ArrayAllocator<4>> = std::sync::OnceLock::new();
+        let allocator = ALLOCATOR.get_or_init(|| ArrayAllocator::new(None));
+
+        let mut slice =
+            OwnedSlice::<u8, &'static ArrayAllocator<4>>::allocate(allocator, 2).unwrap();
+        assert_eq!(slice.len(), 2);
+        assert!(!slice.is_empty());
+        slice.copy_from_slice(&[1, 2]);
+        slice.resize(3).unwrap();
+        assert_eq!(slice[..2], [1, 2]);
+    }
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send::<ArrayAllocator<1>>();
+        assert_sync::<ArrayAllocator<1>>();
+        assert_send::<Wrapper>();
+        assert_sync::<Wrapper>();
+        assert_send::<Value<u8>>();
+        assert_sync::<Value<u8>>();
+        assert_send::<Slice<u8>>();
+        assert_sync::<Slice<u8>>();
+        assert_send::<Value<std::cell::Cell<u8>>>();
+        assert_send::<OwnedWrapper<std::sync::Arc<ArrayAllocator<1>>>>();
+        assert_sync::<OwnedWrapper<std::sync::Arc<ArrayAllocator<1>>>>();
+        assert_send::<OwnedValue<u8, std::sync::Arc<ArrayAllocator<1>>>>();
+        assert_sync::<OwnedSlice<u8, std::sync::Arc<ArrayAllocator<1>>>>();
+    }
+
+    #[test]
+    fn value_debug() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate_value::<u8>().unwrap();
+
+        let expected = "Value { wrapper: Wrapper { allocator: Allocator(Mutex { lock: \
+                        Mutex(UnsafeCell { .. }), data: UnsafeCell { .. } }), index: 0, size: 1 \
+                        }, metadata: (), __marker: PhantomData<u8> }";
+
+        assert_eq!(format!("{wrapper:?}"), expected);
+    }
+    #[test]
+    fn value_allocator() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate_value::<u8>().unwrap();
+        let _ = wrapper.allocator();
+    }
+    #[test]
+    fn value_index() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate_value::<u8>().unwrap();
+        assert_eq!(wrapper.index(), 0);
+    }
+    #[test]
+    fn value_size() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate_value::<u8>().unwrap();
+        assert_eq!(wrapper.size(), 1);
+    }
+    #[test]
+    fn value_deref() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let mut wrapper = allocator.allocate_value::<u8>().unwrap();
+        *wrapper = 0;
+        assert_eq!(*wrapper, 0);
+    }
+    #[test]
+    fn value_deref_mut() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let mut wrapper = allocator.allocate_value::<u8>().unwrap();
+        *wrapper = 0;
+        assert_eq!(*wrapper, 0);
+    }
+
+    #[test]
+    fn block_debug() {
+        assert_eq!(
+            format!(
+                "{:?}",
+                Block {
+                    size: 0,
+                    next: None
+                }
+            ),
+            "Block { size: 0, next: None }"
+        );
+    }
+    #[test]
+    fn wrapper_debug() {
+        let allocator = ArrayAllocator::<0>::new(None);
+
+        let expected = "Wrapper { allocator: Allocator(Mutex { lock: Mutex(UnsafeCell { .. }), \
+                        data: UnsafeCell { .. } }), index: 0, size: 0 }";
+
+        assert_eq!(
+            format!(
+                "{:?}",
+                Wrapper {
+                    allocator: &allocator,
+                    index: 0,
+                    size: 0
+                }
+            ),
+            expected
+        );
+    }
+    #[test]
+    fn wrapper_allocator() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate(1).unwrap();
+        let _ = wrapper.allocator();
+    }
+    #[test]
+    fn wrapper_allocator_mut() {
+        let allocator_one = ArrayAllocator::<1>::new(None);
+        let allocator_two = ArrayAllocator::<1>::new(None);
+        let mut wrapper = allocator_one.allocate(1).unwrap();
+        assert_eq!(
+            wrapper.allocator() as *const Allocator as usize,
+            &allocator_one as *const ArrayAllocator::<1> as usize
+        );
+        unsafe {
+            *wrapper.allocator_mut() = &allocator_two;
+        }
+        assert_eq!(
+            wrapper.allocator() as *const Allocator as usize,
+            &allocator_two as *const ArrayAllocator::<1> as usize
+        );
+        // The block is free in `allocator_two`, so freeing it would be caught as a double free.
+        #[cfg(feature = "debug-checks")]
+        std::mem::forget(wrapper);
+    }
+    #[test]
+    fn wrapper_index() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate(1).unwrap();
+        assert_eq!(wrapper.index(), 0);
+    }
+    #[test]
+    fn wrapper_byte_offset() {
+        let allocator = ArrayAllocator::<3>::new(None);
+        let _a = allocator.allocate(1).unwrap();
+        let mut value = allocator.allocate_value::<u32>().unwrap();
+        *value = 7;
+        let offset = value.byte_offset();
+        assert_eq!(offset, size_of::<Block>());
+        let ptr = allocator.ptr_at(offset).unwrap();
+        assert_eq!(unsafe { ptr.cast::<u32>().as_ptr().read() }, 7);
+        assert!(allocator.ptr_at(3 * size_of::<Block>()).is_none());
+    }
+    #[test]
+    fn wrapper_size() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        let wrapper = allocator.allocate(1).unwrap();
+        assert_eq!(wrapper.size(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "debug-fill",
+        ignore = "inspects the contents of allocated blocks"
+    )]
+    fn allocator() {
+        // We hold items in a vec to prevent them being dropped;
+        const SIZE: usize = 5;
+        let memory = ArrayAllocator::<SIZE>::new(None);
+        let mut vec = std::vec::Vec::new();
+
+        {
+            let mut guard = memory.0.lock().unwrap();
+            assert_eq!(
+                *guard,
+                InnerAllocator {
+                    head: Some(0),
+                    size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    frozen: AtomicBool::new(false),
+                    out_of_band: false,
+                    strategy: Strategy::FirstFit,
+                    rover: 0,
+                    allocations: 0,
+                    used: 0,
+                    peak: 0,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "canaries")]
+                    canaries: false,
+                    #[cfg(feature = "audit")]
+                    audit: false,
+                    #[cfg(feature = "audit")]
+                    live: None,
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
+                    _marker: PhantomData
+                }
+            );
+            assert_eq!(
+                unsafe { guard.data().as_ref() },
+                [
+                    Block {
+                        size: 5,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                ]
+            );
+        }
+
+        vec.push(memory.allocate(1));
+
+        {
+            let mut guard = memory.0.lock().unwrap();
+            assert_eq!(
+                *guard,
+                InnerAllocator {
+                    head: Some(1),
+                    size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    frozen: AtomicBool::new(false),
+                    out_of_band: false,
+                    strategy: Strategy::FirstFit,
+                    rover: 0,
+                    allocations: 0,
+                    used: 0,
+                    peak: 0,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "canaries")]
+                    canaries: false,
+                    #[cfg(feature = "audit")]
+                    audit: false,
+                    #[cfg(feature = "audit")]
+                    live: None,
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
+                    _marker: PhantomData
+                }
+            );
+            assert_eq!(
+                unsafe { guard.data().as_ref() },
+                [
+                    Block {
+                        size: 5,
+                        next: None,
+                    },
+                    Block {
+                        size: 4,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                ]
+            );
+        }
+
+        vec.push(memory.allocate(2));
+
+        {
+            let mut guard = memory.0.lock().unwrap();
+            assert_eq!(
+                *guard,
+                InnerAllocator {
+                    head: Some(3),
+                    size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    frozen: AtomicBool::new(false),
+                    out_of_band: false,
+                    strategy: Strategy::FirstFit,
+                    rover: 0,
+                    allocations: 0,
+                    used: 0,
+                    peak: 0,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "canaries")]
+                    canaries: false,
+                    #[cfg(feature = "audit")]
+                    audit: false,
+                    #[cfg(feature = "audit")]
+                    live: None,
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
+                    _marker: PhantomData
+                }
+            );
+            assert_eq!(
+                unsafe { guard.data().as_ref() },
+                [
+                    Block {
+                        size: 5,
+                        next: None,
+                    },
+                    Block {
+                        size: 4,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 2,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                ]
+            );
+        }
+
+        vec.pop();
+
+        {
+            let mut guard = memory.0.lock().unwrap();
+            assert_eq!(
+                *guard,
+                InnerAllocator {
+                    head: Some(1),
+                    size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    frozen: AtomicBool::new(false),
+                    out_of_band: false,
+                    strategy: Strategy::FirstFit,
+                    rover: 0,
+                    allocations: 0,
+                    used: 0,
+                    peak: 0,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "canaries")]
+                    canaries: false,
+                    #[cfg(feature = "audit")]
+                    audit: false,
+                    #[cfg(feature = "audit")]
+                    live: None,
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
+                    _marker: PhantomData
+                }
+            );
+            assert_eq!(
+                unsafe { guard.data().as_ref() },
+                [
+                    Block {
+                        size: 5,
+                        next: None,
+                    },
+                    Block {
+                        size: 4,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 2,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                ]
+            );
+        }
+
+        vec.pop();
+
+        {
+            let mut guard = memory.0.lock().unwrap();
+            assert_eq!(
+                *guard,
+                InnerAllocator {
+                    head: Some(0),
+                    size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    frozen: AtomicBool::new(false),
+                    out_of_band: false,
+                    strategy: Strategy::FirstFit,
+                    rover: 0,
+                    allocations: 0,
+                    used: 0,
+                    peak: 0,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "canaries")]
+                    canaries: false,
+                    #[cfg(feature = "audit")]
+                    audit: false,
+                    #[cfg(feature = "audit")]
+                    live: None,
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
+                    _marker: PhantomData
+                }
+            );
+            assert_eq!(
+                unsafe { guard.data().as_ref() },
+                [
+                    Block {
+                        size: 5,
+                        next: None,
+                    },
+                    Block {
+                        size: 4,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                    Block {
+                        size: 2,
+                        next: None,
+                    },
+                    Block {
+                        size: 0,
+                        next: None,
+                    },
+                ]
+            );
+        }
+
+        drop(vec);
+    }
+
+    #[test]
+    fn allocator_small_index() {
+        assert_eq!(size_of::<Block<u16>>(), 6);
+
+        let memory = ArrayAllocator::<4, u16>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(2).unwrap();
+        assert_eq!(b.index(), 1);
+        drop(a);
+        let mut c = memory.allocate_value::<u16>().unwrap();
+        *c = u16::MAX;
+        assert_eq!(*c, u16::MAX);
+        assert_eq!(c.index(), 0);
+        drop(b);
+        drop(c);
+        assert_eq!(memory.allocate(4).unwrap().size(), 4);
+    }
+
+    #[test]
+    fn allocator_stats() {
+        let memory = ArrayAllocator::<5>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap();
+        drop(a);
+        assert_eq!(
+            memory.stats(),
+            Stats {
+                total: 5,
+                free: 4,
+                largest_free: 3,
+                free_regions: 2,
+                allocations: 1,
+                peak_used: 2,
+            }
+        );
+        drop(b);
+        assert_eq!(memory.stats().largest_free, 5);
+    }
+    #[test]
+    fn raw_array_allocator() {
+        fn roundtrip<A: RawArrayAllocator>(allocator: &A) {
+            let allocation = allocator.allocate_bytes(3).unwrap();
+            unsafe {
+                allocator.as_ptr(allocation).as_ptr().write_bytes(1, 3);
+                allocator.free(allocation);
+            }
+            assert_eq!(allocator.stats().free, allocator.stats().total);
+        }
+        let memory = ArrayAllocator::<2>::new(None);
+        roundtrip(&*memory);
+        let allocation = memory.allocate_bytes(2 * size_of::<Block>()).unwrap();
+        assert_eq!(allocation, RawAllocation { index: 0, size: 2 });
+        assert!(memory.allocate_bytes(1).is_none());
+        unsafe { RawArrayAllocator::free(&*memory, allocation) };
+    }
+
+    #[test]
+    fn array_allocator_debug() {
+        let expected = "ArrayAllocator { allocator: Allocator(Mutex { lock: Mutex(UnsafeCell { .. \
+                        }), data: UnsafeCell { .. } }), data: [] }";
+
+        assert_eq!(format!("{:?}", ArrayAllocator::<0>::new(None)), expected);
+    }
+
+    #[test]
+    fn allocator_init() {
+        let _ = ArrayAllocator::<3>::new(None);
+    }
+
+    #[test]
+    fn allocator_init_zero() {
+        let _ = ArrayAllocator::<0>::new(None);
+    }
+
+    #[test]
+    fn allocate_value() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        allocator.allocate_value::<()>().unwrap();
+    }
+    #[test]
+    fn allocate_slice() {
+        let allocator = ArrayAllocator::<1>::new(None);
+        allocator.allocate_slice::<u8>(size_of::<Block>()).unwrap();
+    }
+
+    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Equal` case.
+    #[test]
+    fn allocate_0() {
+        let memory = ArrayAllocator::<1>::new(None);
+        memory.allocate(1).unwrap();
+    }
+    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Less` case.
+    #[test]
+    fn allocate_1() {
+        let memory = ArrayAllocator::<2>::new(None);
+        memory.allocate(1).unwrap();
+    }
+    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater`
+    // `blocks.cmp(&allocator.data[next].size) == Equal` case.
+    #[test]
+    fn allocate_2() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap();
+
+        drop(a);
+
+        memory.allocate(2).unwrap();
+
+        drop(b);
+    }
+    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater`
+    // `blocks.cmp(&allocator.data[next].size) == Less` case.
+    #[test]
+    fn allocate_3() {
+        let memory = ArrayAllocator::<5>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap();
+
+        drop(a);
+
+        memory.allocate(2).unwrap();
+
+        drop(b);
+    }
+    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater`
+    // `blocks.cmp(&allocator.data[next].size) == Greater` case.
+    #[test]
+    fn allocate_4() {
+        let memory = ArrayAllocator::<7>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap();
+        let c = memory.allocate(2).unwrap();
+
+        drop(b);
+
+        memory.allocate(3).unwrap();
+
+        drop(a);
+        drop(c);
+    }
+    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater` `None` case.
+    #[test]
+    fn allocate_5() {
+        let memory = ArrayAllocator::<6>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap();
+        let c = memory.allocate(2).unwrap();
+
+        drop(b);
+
+        assert!(memory.allocate(3).is_none());
+
+        drop(a);
+        drop(c);
+    }
+    // Tests `Wrapper::allocate` `None` header case.
+    #[test]
+    fn allocate_6() {
+        let memory = ArrayAllocator::<0>::new(None);
+        assert!(memory.allocate(1).is_none());
+    }
+
+    // Tests `Wrapper` drop case of:
+    // ┌───┐
+    // │...│
+    // └───┘
+    #[test]
+    fn drop_1() {
+        let memory = ArrayAllocator::<1>::new(None);
+        let item = memory.allocate(1).unwrap();
+        drop(item);
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬────┬────┬───┐
+    // │...│self│head│...│
+    // └───┴────┴────┴───┘
+    #[test]
+    fn drop_2() {
+        let memory = ArrayAllocator::<1>::new(None);
+        let item = memory.allocate(1).unwrap();
+        drop(item);
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬────┬───┬────┬───┐
+    // │...│self│...│head│...│
+    // └───┴────┴───┴────┴───┘
+    #[test]
+    fn drop_3() {
+        let memory = ArrayAllocator::<2>::new(None);
+
+        let first = memory.allocate(1).unwrap();
+        let second = memory.allocate(1).unwrap(); // self
+
+        drop(second); // This tests our drop case.
+        drop(first);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬────┬────┬───┐
+    // │...│index│self│next│...│
+    // └───┴─────┴────┴────┴───┘
+    #[test]
+    fn drop_4() {
+        let memory = ArrayAllocator::<4>::new(None);
+
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // self
+        let d = memory.allocate(1).unwrap(); // next
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+
+        drop(c); // This tests our drop case.
+
+        // We don't care what order we drop these, only that we drop them after.
+        drop(b);
+        drop(d);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬────┬───┬────┬───┐
+    // │...│index│self│...│next│...│
+    // └───┴─────┴────┴───┴────┴───┘
+    #[test]
+    fn drop_5() {
+        let memory = ArrayAllocator::<5>::new(None);
+
+        let a = memory.allocate(1).unwrap(); // ...
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // self
+        let d = memory.allocate(1).unwrap(); // ...
+        let e = memory.allocate(1).unwrap(); // next
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+        drop(b);
+        drop(e);
+
+        drop(c); // This tests our drop case.
+
+        drop(d);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬────┬───┐
+    // │...│index│self│...│
+    // └───┴─────┴────┴───┘
+    #[test]
+    fn drop_6() {
+        let memory = ArrayAllocator::<3>::new(None);
+
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // self
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+
+        drop(c); // This tests our drop case.
+
+        // We don't care what order we drop these, only that we drop them after.
+        drop(b);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬───┬────┬────┬───┐
+    // │...│index│...│self│next│...│
+    // └───┴─────┴───┴────┴────┴───┘
+    #[test]
+    fn drop_7() {
+        let memory = ArrayAllocator::<5>::new(None);
+
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // ...
+        let d = memory.allocate(1).unwrap(); // self
+        let e = memory.allocate(1).unwrap(); // next
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+
+        drop(c);
+
+        drop(d); // This tests our drop case.
+
+        // We don't care what order we drop these, only that we drop them after.
+        drop(b);
+        drop(e);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬───┬────┬───┬────┬───┐
+    // │...│index│...│self│...│next│...│
+    // └───┴─────┴───┴────┴───┴────┴───┘
+    #[test]
+    fn drop_8() {
+        let memory = ArrayAllocator::<6>::new(None);
+
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // ...
+        let d = memory.allocate(1).unwrap(); // self
+        let e = memory.allocate(1).unwrap(); // ...
+        let f = memory.allocate(1).unwrap(); // next
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+
+        drop(b);
+        drop(f);
+
+        drop(d); // This tests our drop case.
+
+        // We don't care what order we drop these, only that we drop them after.
+        drop(c);
+        drop(e);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬───┬────┬───┬────┬───┐
+    // │...│index│...│next│...│self│...│
+    // └───┴─────┴───┴────┴───┴────┴───┘
+    #[test]
+    fn drop_9() {
+        let memory = ArrayAllocator::<6>::new(None);
+
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // ...
+        let d = memory.allocate(1).unwrap(); // next
+        let e = memory.allocate(1).unwrap(); // ...
+        let f = memory.allocate(1).unwrap(); // self
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+
+        drop(c);
+        drop(e);
+
+        drop(f); // This tests our drop case.
+
+        // We don't care what order we drop these, only that we drop them after.
+        drop(b);
+        drop(d);
+
+        let _ = memory;
+    }
+    // Tests `Wrapper` drop case of:
+    // ┌───┬─────┬───┬────┬───┐
+    // │...│index│...│self│...│
+    // └───┴─────┴───┴────┴───┘
+    #[test]
+    fn drop_10() {
+        let memory = ArrayAllocator::<4>::new(None);
+
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap(); // index
+        let c = memory.allocate(1).unwrap(); // ...
+        let d = memory.allocate(1).unwrap(); // self
+
+        // Dropping this results in the following drop entering the case:
+        // ┌───┬────┬───┬────┬───┐
+        // │...│head│...│self│...│
+        // └───┴────┴───┴────┴───┘
+        drop(a);
+
+        drop(c);
+
+        drop(d); // This tests our drop case.
+
+        // We don't care what order we drop these, only that we drop them after.
+        drop(b);
+
+        let _ = memory;
+    }
+
+    #[test]
+    fn allocate_after_head() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(1).unwrap();
+        drop(a);
+        // The free list is now [0..1, 2..4], this is allocated from the second free block and must
+        // keep the first.
+        let c = memory.allocate(2).unwrap();
+        assert_eq!(c.index(), 2);
+        assert_eq!(memory.stats().free, 1);
+        drop(b);
+        drop(c);
+        assert_eq!(memory.stats().free, 4);
+        assert!(memory.allocate(4).is_some());
+    }
+
+    #[test]
+    fn try_allocate() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let a = memory.try_allocate(1).unwrap();
+        let _b = memory.try_allocate(1).unwrap();
+        drop(a);
+        assert_eq!(
+            memory.try_allocate(3).unwrap_err(),
+            AllocError::OutOfMemory {
+                requested: 3,
+                largest_free: 2
+            }
+        );
+        assert!(memory.try_allocate(0).is_ok());
+    }
+
+    #[test]
+    fn try_allocate_layout() {
+        #[derive(Debug)]
+        #[repr(align(64))]
+        struct Aligned;
+
+        let memory = ArrayAllocator::<4>::new(None);
+        assert_eq!(
+            memory.try_allocate_value::<Aligned>().unwrap_err(),
+            AllocError::InvalidLayout
+        );
+        assert_eq!(
+            memory.try_allocate_slice::<u64>(usize::MAX).unwrap_err(),
+            AllocError::InvalidLayout
+        );
+        let _slice = memory.try_allocate_slice::<u64>(12).unwrap();
+        assert!(matches!(
+            memory.try_allocate_value::<u64>(),
+            Err(AllocError::OutOfMemory {
+                requested: 1,
+                largest_free: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn oom_hook() {
+        static CALLS: std::sync::Mutex<std::vec::Vec<AllocRequest>> =
+            std::sync::Mutex::new(std::vec::Vec::new());
 
-        {
-            let mut guard = memory.0.lock().unwrap();
-            assert_eq!(
-                *guard,
-                InnerAllocator {
-                    head: Some(1),
-                    size: SIZE
+        let memory = ArrayAllocator::<4>::new(None);
+        memory.set_oom_hook(|request| CALLS.lock().unwrap().push(*request));
+        let _a = memory.allocate(3).unwrap();
+        assert!(memory.allocate(2).is_none());
+        assert!(memory.try_allocate_slice::<u8>(1000).is_err());
+        let stats = Stats {
+            total: 4,
+            free: 1,
+            largest_free: 1,
+            free_regions: 1,
+            allocations: 1,
+            peak_used: 3,
+        };
+        assert_eq!(
+            *CALLS.lock().unwrap(),
+            [
+                AllocRequest {
+                    requested: 2,
+                    stats
+                },
+                AllocRequest {
+                    requested: 42,
+                    stats
                 }
-            );
-            assert_eq!(
-                unsafe { guard.data().as_ref() },
-                [
-                    Block {
-                        size: 5,
-                        next: None,
-                    },
-                    Block {
-                        size: 4,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 2,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                ]
-            );
-        }
+            ]
+        );
+        assert!(memory.take_oom_hook().is_some());
+        assert!(memory.allocate(2).is_none());
+        assert_eq!(CALLS.lock().unwrap().len(), 2);
+    }
 
-        vec.pop();
+    #[cfg(feature = "testing")]
+    #[test]
+    fn failure_injection() {
+        use crate::testing::FailureInjection;
+
+        let memory = ArrayAllocator::<8>::new(None);
+        memory.set_failure_injection(FailureInjection::every(NonZeroUsize::new(2).unwrap()));
+        assert!(memory.allocate(1).is_some());
+        assert!(matches!(
+            memory.try_allocate(1),
+            Err(AllocError::OutOfMemory { requested: 1, .. })
+        ));
+        assert!(memory.allocate(1).is_some());
+        assert_eq!(memory.failure_injection().allocations(), 3);
+
+        let block = size_of::<Block>();
+        memory.set_failure_injection(FailureInjection::budget(2 * block));
+        assert!(memory.allocate(2).is_some());
+        assert!(memory.allocate(1).is_none());
+        assert!(memory.allocate(0).is_some());
+    }
 
-        {
-            let mut guard = memory.0.lock().unwrap();
-            assert_eq!(
-                *guard,
-                InnerAllocator {
-                    head: Some(0),
-                    size: SIZE
-                }
-            );
-            assert_eq!(
-                unsafe { guard.data().as_ref() },
-                [
-                    Block {
-                        size: 5,
-                        next: None,
-                    },
-                    Block {
-                        size: 4,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                    Block {
-                        size: 2,
-                        next: None,
-                    },
-                    Block {
-                        size: 0,
-                        next: None,
-                    },
-                ]
-            );
+    #[cfg(feature = "testing")]
+    #[test]
+    fn reference_model() {
+        use crate::testing::{assert_equivalent, LinkedListModel};
+
+        let mut rng = rand::thread_rng();
+        let memory = ArrayAllocator::<16>::new(None);
+        let mut model = LinkedListModel::new(16);
+        let mut live = std::vec::Vec::new();
+        for _ in 0..1000 {
+            if live.is_empty() || rng.gen_bool(0.5) {
+                let blocks = rng.gen_range(1..5);
+                let wrapper = memory.allocate(blocks);
+                assert_eq!(wrapper.as_ref().map(Wrapper::index), model.allocate(blocks));
+                live.extend(wrapper);
+            } else {
+                let wrapper = live.swap_remove(rng.gen_range(0..live.len()));
+                model.free(wrapper.index(), wrapper.size());
+                drop(wrapper);
+            }
+            assert_equivalent(&*memory, &model);
         }
+    }
 
-        drop(vec);
+    #[test]
+    fn reserve() {
+        let memory = ArrayAllocator::<8>::new(None);
+        memory.set_reserve(3);
+        assert_eq!(memory.reserve(), 3);
+        let a = memory.allocate(4).unwrap();
+        assert!(memory.allocate(2).is_none());
+        assert!(matches!(
+            memory.try_allocate(2),
+            Err(AllocError::OutOfMemory { .. })
+        ));
+        let b = memory.allocate(1).unwrap();
+        let c = memory
+            .allocate_with_priority(3, Priority::Critical)
+            .unwrap();
+        assert_eq!(memory.stats().free, 0);
+        drop(c);
+        drop(b);
+        memory.set_reserve(0);
+        let d = memory.allocate_with_priority(4, Priority::Normal).unwrap();
+        drop(d);
+        drop(a);
     }
 
     #[test]
-    fn array_allocator_debug() {
-        let expected = "ArrayAllocator { allocator: Allocator(Mutex { lock: Mutex(UnsafeCell { .. \
-                        }), data: UnsafeCell { .. } }), data: [] }";
+    fn suballocator() {
+        // Features such as `latency` make the nested allocator's header much larger.
+        let memory = ArrayAllocator::<1024>::new(None);
+        let header = size_of::<Allocator>().div_ceil(size_of::<Block>());
+        let small = memory.allocate(header - 1).unwrap();
+        let small = small.into_suballocator(None).unwrap_err();
+        drop(small);
+
+        let nested = memory
+            .allocate(header + 8)
+            .unwrap()
+            .into_suballocator(None)
+            .unwrap();
+        assert_eq!(nested.stats().total, 8);
+        let a = nested.allocate(1).unwrap();
+        let b = nested.allocate(7).unwrap();
+        assert!(nested.allocate(1).is_none());
+        drop((a, b));
+        assert_eq!(nested.stats().free, 8);
+        assert_eq!(memory.stats().free, 1024 - header - 8);
+        drop(nested);
+        assert_eq!(memory.stats().free, 1024);
+    }
 
-        assert_eq!(format!("{:?}", ArrayAllocator::<0>::new(None)), expected);
+    #[test]
+    fn bitset() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut bits = memory.allocate_bitset(100).unwrap();
+        assert!(!bits.set(3));
+        assert!(!bits.set(99));
+        assert!(bits.test(99));
+        assert!(bits.clear(3));
+        assert_eq!(bits.iter_ones().collect::<std::vec::Vec<_>>(), [99]);
     }
 
     #[test]
-    fn allocator_init() {
-        let _ = ArrayAllocator::<3>::new(None);
+    fn allocate_layout() {
+        #[repr(align(64))]
+        struct Aligned(#[allow(dead_code)] [u8; 80]);
+
+        let memory = ArrayAllocator::<32>::new(None);
+        assert!(memory.allocate_value::<Aligned>().is_none());
+        assert!(memory.allocate_slice::<Aligned>(1).is_none());
+        let _a = memory.allocate(1).unwrap();
+        let layout = Layout::new::<Aligned>();
+        let b = memory.allocate_layout(layout).unwrap();
+        assert_eq!(b.as_ptr() as usize % 64, 0);
+        assert!(b.len() * size_of::<Block>() >= 80);
+        let c = memory.allocate_layout(Layout::new::<u32>()).unwrap();
+        assert_eq!(c.len(), 1);
+        assert_eq!(
+            memory.allocate_layout(Layout::new::<()>()).unwrap().len(),
+            0
+        );
+        assert!(memory
+            .allocate_layout(Layout::from_size_align(1, 4096).unwrap())
+            .is_none());
     }
 
     #[test]
-    fn allocator_init_zero() {
-        let _ = ArrayAllocator::<0>::new(None);
+    fn try_new() {
+        let memory = ArrayAllocator::<4>::try_new(None).unwrap();
+        assert_eq!(memory.stats().free, 4);
+        let memory = OutOfBandArrayAllocator::<4>::try_new(None).unwrap();
+        assert!(memory.allocate(4).is_some());
+
+        let mut memory = std::mem::MaybeUninit::<ArrayAllocator<2>>::uninit();
+        unsafe {
+            Allocator::<usize>::try_init(memory.as_mut_ptr().cast(), None, 2).unwrap();
+            assert_eq!(memory.assume_init_ref().stats().free, 2);
+        }
     }
 
     #[test]
-    fn allocate_value() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        allocator.allocate_value::<()>().unwrap();
+    fn strategy() {
+        let picks = |strategy| {
+            let memory = ArrayAllocator::<12>::new(None);
+            memory.set_strategy(strategy);
+            assert_eq!(memory.strategy(), strategy);
+            // Leaves free regions of 2, 4 and 3 blocks at 0, 3 and 8.
+            let sizes = [2, 1, 4, 1, 3, 1];
+            let mut wrappers = sizes.map(|size| Some(memory.allocate(size).unwrap()));
+            for i in [0, 2, 4] {
+                wrappers[i] = None;
+            }
+            let a = memory.allocate(1).unwrap();
+            let first = a.index;
+            drop(a);
+            let b = memory.allocate(1).unwrap();
+            (first, b.index)
+        };
+        assert_eq!(picks(Strategy::FirstFit), (0, 0));
+        assert_eq!(picks(Strategy::BestFit), (0, 0));
+        assert_eq!(picks(Strategy::WorstFit), (3, 3));
+        // Resumes after the previous allocation, wrapping around from the end of the array.
+        assert_eq!(picks(Strategy::NextFit), (0, 3));
     }
+
+    #[cfg(feature = "watchdog")]
     #[test]
-    fn allocate_slice() {
-        let allocator = ArrayAllocator::<1>::new(None);
-        allocator.allocate_slice::<u8>(size_of::<Block>()).unwrap();
+    fn lock_health() {
+        use crate::watchdog::LockHealth;
+        use std::time::Duration;
+
+        let memory = ArrayAllocator::<8>::new(None);
+        assert_eq!(
+            memory.check_lock_health(Duration::ZERO),
+            LockHealth::Unlocked
+        );
+        // Leaks the lock as a process exiting while holding it would.
+        std::mem::forget(memory.allocator.0.lock().unwrap());
+        assert!(matches!(
+            memory.check_lock_health(Duration::ZERO),
+            LockHealth::Held { pid, .. } if pid == std::process::id()
+        ));
+        unsafe {
+            memory.force_unlock();
+        }
+        assert_eq!(
+            memory.check_lock_health(Duration::ZERO),
+            LockHealth::Unlocked
+        );
+        drop(memory.allocate(2).unwrap());
     }
 
-    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Equal` case.
     #[test]
-    fn allocate_0() {
-        let memory = ArrayAllocator::<1>::new(None);
-        memory.allocate(1).unwrap();
+    fn watermarks() {
+        use crate::raw::{Watermark, WatermarkEvent};
+
+        static EVENTS: std::sync::Mutex<std::vec::Vec<WatermarkEvent>> =
+            std::sync::Mutex::new(std::vec::Vec::new());
+
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(2).unwrap();
+        memory.set_watermarks(2, 6, |event| EVENTS.lock().unwrap().push(*event));
+        let b = memory.allocate(3).unwrap();
+        assert!(EVENTS.lock().unwrap().is_empty());
+        let c = memory.allocate(2).unwrap();
+        drop(b);
+        let d = memory.allocate(3).unwrap();
+        drop(d);
+        drop(c);
+        drop(a);
+        assert_eq!(
+            *EVENTS.lock().unwrap(),
+            [
+                WatermarkEvent {
+                    watermark: Watermark::High,
+                    used: 7,
+                    total: 8
+                },
+                WatermarkEvent {
+                    watermark: Watermark::Low,
+                    used: 2,
+                    total: 8
+                }
+            ]
+        );
+        memory.clear_watermarks();
+        let _e = memory.allocate(8).unwrap();
+        assert_eq!(EVENTS.lock().unwrap().len(), 2);
     }
-    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Less` case.
+
+    #[cfg(feature = "sanitizer")]
     #[test]
-    fn allocate_1() {
-        let memory = ArrayAllocator::<2>::new(None);
-        memory.allocate(1).unwrap();
+    fn poisoning() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(2).unwrap();
+        memory.set_poisoning(true);
+        let b = memory.allocate(3).unwrap();
+        drop(a);
+        let c = memory.allocate(1).unwrap();
+        drop(b);
+        drop(c);
+        assert_eq!(memory.allocate(8).unwrap().size(), 8);
+        memory.set_poisoning(false);
     }
-    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater`
-    // `blocks.cmp(&allocator.data[next].size) == Equal` case.
+
+    #[cfg(feature = "debug-fill")]
     #[test]
-    fn allocate_2() {
+    fn debug_fill() {
+        use crate::raw::{RawArrayAllocator, ALLOCATED_FILL, FREED_FILL};
+
         let memory = ArrayAllocator::<4>::new(None);
-        let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap();
+        let len = 3 * size_of::<Block>();
+        let a = memory.allocate_bytes(len).unwrap();
+        let ptr = unsafe { memory.as_ptr(a) }.as_ptr();
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&byte| byte == ALLOCATED_FILL));
+        unsafe { memory.free(a) };
+        // The first block holds the free list links.
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+        assert!(bytes[size_of::<Block>()..]
+            .iter()
+            .all(|&byte| byte == FREED_FILL));
+    }
 
-        drop(a);
+    // Freed memory is then filled with `FREED_FILL`.
+    #[cfg(all(feature = "zeroize", not(feature = "debug-fill")))]
+    #[test]
+    fn zeroize() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut slice = memory.allocate_slice::<u8>(3 * size_of::<Block>()).unwrap();
+        slice.fill(0xAB);
+        let ptr = slice.as_ptr();
+        let len = slice.len();
+        drop(slice);
+        // The first block holds the free list links.
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+        assert!(bytes[size_of::<Block>()..].iter().all(|&byte| byte == 0));
+    }
 
-        memory.allocate(2).unwrap();
+    #[test]
+    fn check_integrity() {
+        use crate::integrity::{IntegrityError, IntegrityReport};
 
-        drop(b);
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(2).unwrap();
+        let b = memory.allocate(1).unwrap();
+        drop(a);
+        let stats = memory.stats();
+        assert_eq!(
+            memory.check_integrity(),
+            Ok(IntegrityReport {
+                free_regions: stats.free_regions,
+                free: stats.free
+            })
+        );
+
+        // Corrupt the free list as a misbehaving process might.
+        let mut guard = memory.0.lock().unwrap();
+        let meta = unsafe { guard.meta().as_mut() };
+        meta[3].next = Some(0);
+        drop(guard);
+        assert_eq!(
+            memory.check_integrity(),
+            Err(IntegrityError::Unordered { prev: 3, index: 0 })
+        );
+        let mut guard = memory.0.lock().unwrap();
+        let meta = unsafe { guard.meta().as_mut() };
+        meta[3].next = None;
+        meta[0].size = 4;
+        drop(guard);
+        assert_eq!(
+            memory.check_integrity(),
+            Err(IntegrityError::Overlap { prev: 0, index: 3 })
+        );
+        let mut guard = memory.0.lock().unwrap();
+        let meta = unsafe { guard.meta().as_mut() };
+        meta[0].size = 2;
+        meta[3].size = 6;
+        drop(guard);
+        assert_eq!(
+            memory.check_integrity(),
+            Err(IntegrityError::OutOfBounds {
+                index: 3,
+                size: Some(6)
+            })
+        );
+        std::mem::forget(b);
     }
-    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater`
-    // `blocks.cmp(&allocator.data[next].size) == Less` case.
+
+    #[cfg(feature = "debug-checks")]
     #[test]
-    fn allocate_3() {
-        let memory = ArrayAllocator::<5>::new(None);
-        let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap();
+    #[should_panic(expected = "double free")]
+    fn double_free() {
+        use crate::raw::RawArrayAllocator;
 
-        drop(a);
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate_bytes(size_of::<Block>()).unwrap();
+        let b = memory.allocate_bytes(2 * size_of::<Block>()).unwrap();
+        unsafe {
+            memory.free(a);
+            memory.free(b);
+            memory.free(a);
+        }
+    }
 
-        memory.allocate(2).unwrap();
+    #[cfg(feature = "debug-checks")]
+    #[test]
+    #[should_panic(expected = "outside the allocator")]
+    fn free_outside() {
+        use crate::raw::{RawAllocation, RawArrayAllocator};
 
-        drop(b);
+        let memory = ArrayAllocator::<8>::new(None);
+        unsafe { memory.free(RawAllocation { index: 6, size: 4 }) };
     }
-    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater`
-    // `blocks.cmp(&allocator.data[next].size) == Greater` case.
+
+    #[cfg(feature = "quarantine")]
     #[test]
-    fn allocate_4() {
-        let memory = ArrayAllocator::<7>::new(None);
+    fn quarantine() {
+        let memory = ArrayAllocator::<4>::new(None);
+        memory.set_quarantine(2);
         let a = memory.allocate(1).unwrap();
         let b = memory.allocate(1).unwrap();
-        let c = memory.allocate(2).unwrap();
-
-        drop(b);
-
-        memory.allocate(3).unwrap();
-
         drop(a);
+        drop(b);
+        assert_eq!(memory.stats().free, 2);
+        let c = memory.allocate(1).unwrap();
+        assert_eq!(c.index(), 2);
+        // Evicts `a`.
         drop(c);
+        assert_eq!(memory.allocate(1).unwrap().index(), 0);
+        memory.set_quarantine(0);
+        assert_eq!(memory.stats().free, 4);
+        assert_eq!(memory.allocate(4).unwrap().size(), 4);
     }
-    // Tests `Wrapper::allocate` `blocks.cmp(&allocator.data[next].size) == Greater` `None` case.
+
+    #[cfg(feature = "serde")]
     #[test]
-    fn allocate_5() {
-        let memory = ArrayAllocator::<6>::new(None);
-        let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap();
-        let c = memory.allocate(2).unwrap();
+    fn serde_seeds() {
+        use serde::de::value::{Error, SeqDeserializer};
+        use serde::de::{DeserializeSeed, IntoDeserializer};
 
-        drop(b);
+        let memory = ArrayAllocator::<4>::new(None);
+        let value = memory
+            .value_seed::<u32>()
+            .deserialize(IntoDeserializer::<Error>::into_deserializer(7u32))
+            .unwrap();
+        assert_eq!(*value, 7);
+
+        let slice = memory
+            .slice_seed::<u16>()
+            .deserialize(SeqDeserializer::<_, Error>::new([1u16, 2, 3].into_iter()))
+            .unwrap();
+        assert_eq!(&slice[..], [1, 2, 3]);
+
+        let err = memory
+            .slice_seed::<u64>()
+            .deserialize(SeqDeserializer::<_, Error>::new(0..16u64))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("out of memory"));
+    }
 
-        assert!(memory.allocate(3).is_none());
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn allocate_archived() {
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct Message {
+            id: u32,
+            body: std::string::String,
+            tags: std::vec::Vec<u16>,
+        }
 
-        drop(a);
-        drop(c);
+        let memory = ArrayAllocator::<32>::new(None);
+        let message = Message {
+            id: 7,
+            body: std::string::String::from("hello"),
+            tags: vec![1, 2, 3],
+        };
+        let mut slice = memory.allocate_archived(&message).unwrap();
+        let archived = slice.access::<Message>().unwrap();
+        assert_eq!(archived.id, 7);
+        assert_eq!(archived.body, "hello");
+        assert_eq!(archived.tags.as_slice(), [1, 2, 3]);
+
+        // An overwritten archive is rejected rather than read.
+        let len = slice.len();
+        slice[len - 1] ^= 0xFF;
+        assert!(slice.access::<Message>().is_err());
     }
-    // Tests `Wrapper::allocate` `None` header case.
+
+    #[cfg(feature = "bytemuck")]
     #[test]
-    fn allocate_6() {
-        let memory = ArrayAllocator::<0>::new(None);
-        assert!(memory.allocate(1).is_none());
+    fn allocate_pod() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut value = memory.allocate_pod::<[u32; 3]>().unwrap();
+        assert_eq!(value.read(), [0; 3]);
+        value.write([1, 2, 3]);
+        assert_eq!(*value, [1, 2, 3]);
+        value.as_bytes_mut()[0] = 4;
+        assert_eq!(value.as_bytes()[..4], 4u32.to_ne_bytes());
+
+        let mut slice = memory.allocate_pod_slice::<u16>(5).unwrap();
+        assert_eq!(&slice[..], &[0; 5]);
+        slice.write(4, 7);
+        assert_eq!(slice.read(4), 7);
+        assert_eq!(slice.as_bytes().len(), 10);
+        assert_eq!(memory.allocate_pod_slice::<u16>(0).unwrap().len(), 0);
+
+        assert!(std::mem::align_of::<u128>() > std::mem::align_of::<Block>());
+        assert!(memory.allocate_pod::<u128>().is_none());
     }
 
-    // Tests `Wrapper` drop case of:
-    // ┌───┐
-    // │...│
-    // └───┘
     #[test]
-    fn drop_1() {
-        let memory = ArrayAllocator::<1>::new(None);
-        let item = memory.allocate(1).unwrap();
-        drop(item);
-        drop(memory);
+    fn drop_contents() {
+        let rc = std::rc::Rc::new(0);
+        let memory = ArrayAllocator::<16>::new(None);
+        let value = memory.allocate_value_with(|| rc.clone()).unwrap();
+        let mut slice = memory
+            .allocate_slice_clone_from(&[rc.clone(), rc.clone(), rc.clone(), rc.clone()])
+            .unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&rc), 6);
+
+        drop(value);
+        slice.truncate(3);
+        assert_eq!(std::rc::Rc::strong_count(&rc), 4);
+        slice.resize(2).unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&rc), 3);
+        assert_eq!(slice.len(), 2);
+        slice.resize_with(4, || rc.clone()).unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&rc), 5);
+        slice.truncate(2);
+
+        // Forgotten contents keep their references while the blocks are freed.
+        drop(slice.forget_contents());
+        assert_eq!(std::rc::Rc::strong_count(&rc), 3);
+        assert_eq!(memory.stats().free, 16);
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬────┬────┬───┐
-    // │...│self│head│...│
-    // └───┴────┴────┴───┘
+
     #[test]
-    fn drop_2() {
-        let memory = ArrayAllocator::<1>::new(None);
-        let item = memory.allocate(1).unwrap();
-        drop(item);
-        drop(memory);
+    fn value_uninit() {
+        let allocator = ArrayAllocator::<2>::new(None);
+        let value = allocator.allocate_value_uninit::<u64>().unwrap();
+        let value = value.init(3);
+        assert_eq!(*value, 3);
+
+        let mut value = allocator.allocate_value_uninit::<[u8; 2]>().unwrap();
+        unsafe { value.as_mut_ptr().cast::<u8>().write_bytes(1, 2) };
+        assert_eq!(*unsafe { value.assume_init() }, [1, 1]);
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬────┬───┬────┬───┐
-    // │...│self│...│head│...│
-    // └───┴────┴───┴────┴───┘
+
     #[test]
-    fn drop_3() {
-        let memory = ArrayAllocator::<2>::new(None);
+    fn value_raw() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut value = memory.allocate_value::<u64>().unwrap();
+        *value = 7;
+        let (index, size) = value.into_raw();
+        assert_eq!(memory.stats().free, 3);
+
+        let value = unsafe { memory.value_from_raw::<u64>(index, size) };
+        assert_eq!(*value, 7);
+        drop(value);
+        assert_eq!(memory.stats().free, 4);
+    }
 
-        let first = memory.allocate(1).unwrap();
-        let second = memory.allocate(1).unwrap(); // self
+    #[test]
+    fn detach() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut slice = memory.allocate_slice::<u8>(3).unwrap();
+        slice.copy_from_slice(&[1, 2, 3]);
+        let raw_slice = slice.detach();
+        let mut value = memory.allocate_value::<u32>().unwrap();
+        *value = 4;
+        let raw_value = value.detach();
+        assert_eq!((raw_slice.len(), raw_value.size()), (3, 1));
+        assert_eq!(memory.stats().free, 2);
+
+        // Handles hold no reference so may outlive the borrow they were detached from.
+        let (slice, value) = unsafe {
+            (
+                memory.attach_slice(raw_slice),
+                memory.attach_value(raw_value),
+            )
+        };
+        assert_eq!((&*slice, *value), (&[1, 2, 3][..], 4));
+        drop((slice, value));
+        assert_eq!(memory.stats().free, 4);
+    }
+
+    #[test]
+    fn value_with() {
+        let allocator = ArrayAllocator::<64>::new(None);
+        let value = allocator.allocate_value_with(|| [7u8; 512]).unwrap();
+        assert!(value.iter().all(|&x| x == 7));
+        assert!(allocator
+            .allocate_value_with::<[u8; 4096]>(|| unreachable!())
+            .is_none());
+
+        let value = unsafe {
+            allocator.allocate_value_in_place::<[u64; 16]>(|value| {
+                let ptr = value.as_mut_ptr().cast::<u64>();
+                for i in 0..16 {
+                    ptr.add(i).write(i as u64);
+                }
+            })
+        }
+        .unwrap();
+        assert_eq!(value[15], 15);
+    }
 
-        drop(second); // This tests our drop case.
-        drop(first);
+    #[test]
+    fn value_volatile() {
+        let memory = ArrayAllocator::<2>::new(None);
+        let mut value = memory.allocate_value::<u64>().unwrap();
+        value.write_volatile(3);
+        assert_eq!(unsafe { value.read_volatile() }, 3);
+        assert_eq!(*value, 3);
+    }
 
-        drop(memory);
+    #[test]
+    fn allocate_unsized() {
+        use std::fmt::Display;
+
+        let memory = ArrayAllocator::<8>::new(None);
+        let values = [
+            memory.allocate_unsized::<dyn Display, _>(1u8).unwrap(),
+            memory.allocate_unsized::<dyn Display, _>("two").unwrap(),
+            memory.allocate_unsized::<dyn Display, _>(3.5f64).unwrap(),
+        ];
+        let strings = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(strings, ["1", "two", "3.5"]);
+
+        let mut slice = memory.allocate_unsized::<[u16], _>([1u16, 2, 3]).unwrap();
+        slice[1] = 4;
+        assert_eq!(&*slice, &[1, 4, 3]);
+        assert!(memory.allocate_unsized::<dyn Display, _>(1u128).is_none());
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬────┬────┬───┐
-    // │...│index│self│next│...│
-    // └───┴─────┴────┴────┴───┘
+
     #[test]
-    fn drop_4() {
+    fn allocate_slice_and_str_value() {
         let memory = ArrayAllocator::<4>::new(None);
+        let mut slice = memory.allocate_slice_value(&[1u32, 2, 3]).unwrap();
+        slice[0] = 5;
+        assert_eq!(&*slice, &[5, 2, 3]);
+        assert_eq!(slice.size(), 1);
+        let s = memory.allocate_str_value("hello").unwrap();
+        assert_eq!(&*s, "hello");
+        assert_eq!(memory.allocate_slice_value::<u8>(&[]).unwrap().len(), 0);
+    }
 
+    #[cfg(feature = "canaries")]
+    #[test]
+    fn canaries() {
+        let memory = ArrayAllocator::<8>::new(None);
+        memory.set_canaries(true);
         let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // self
-        let d = memory.allocate(1).unwrap(); // next
+        let mut b = memory.allocate(2).unwrap();
+        assert_eq!((a.index(), b.index()), (1, 4));
+        assert_eq!(memory.validate(), Ok(()));
 
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
+        // Overflow `b` by a byte.
+        unsafe { b.as_mut_ptr().add(2).cast::<u8>().write(0) };
+        assert_eq!(
+            memory.validate(),
+            Err(crate::canary::CanaryError {
+                index: 4,
+                size: Some(2),
+                guard: crate::canary::Guard::After,
+            })
+        );
         drop(a);
+        assert!(memory.validate().is_err());
 
-        drop(c); // This tests our drop case.
-
-        // We don't care what order we drop these, only that we drop them after.
-        drop(b);
-        drop(d);
-
-        drop(memory);
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(b))).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<std::string::String>().unwrap(),
+            "canary after the allocation at block 4 (2 blocks) was overwritten"
+        );
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬────┬───┬────┬───┐
-    // │...│index│self│...│next│...│
-    // └───┴─────┴────┴───┴────┴───┘
+
+    #[cfg(feature = "canaries")]
     #[test]
-    fn drop_5() {
-        let memory = ArrayAllocator::<5>::new(None);
+    fn canaries_overflow() {
+        let memory = ArrayAllocator::<8>::new(None);
+        memory.set_canaries(true);
+        assert_eq!(
+            memory.try_allocate(usize::MAX - 1).map(|_| ()),
+            Err(AllocError::InvalidLayout)
+        );
+        assert_eq!(memory.stats().free, 8);
+    }
 
-        let a = memory.allocate(1).unwrap(); // ...
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // self
-        let d = memory.allocate(1).unwrap(); // ...
-        let e = memory.allocate(1).unwrap(); // next
+    #[cfg(feature = "canaries")]
+    #[test]
+    #[should_panic(expected = "canaries cannot be toggled while there are allocations")]
+    fn canaries_toggle() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let _wrapper = memory.allocate(1).unwrap();
+        memory.set_canaries(true);
+    }
 
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
-        drop(a);
+    #[cfg(feature = "audit")]
+    #[test]
+    fn audit() {
+        let memory = ArrayAllocator::<16>::new(None);
+        memory.set_audit(true);
+        let header = crate::audit::blocks::<Block, usize>();
+        let mut a = memory.allocate(2).unwrap();
+        let b = memory.allocate_value::<u64>().unwrap();
+        let c = memory.allocate(1).unwrap();
+        a.set_tag(7);
+        assert_eq!(a.index(), header);
+        assert_eq!(memory.stats().free, 16 - 4 - 3 * header);
+        assert_eq!(
+            memory.live_allocations().collect::<std::vec::Vec<_>>(),
+            [(c.index(), 1, 0), (b.index(), 1, 0), (a.index(), 2, 7)]
+        );
         drop(b);
-        drop(e);
+        assert_eq!(
+            memory.live_allocations().collect::<std::vec::Vec<_>>(),
+            [(c.index(), 1, 0), (a.index(), 2, 7)]
+        );
+        drop((a, c));
+        assert_eq!(memory.live_allocations().count(), 0);
+        assert_eq!(memory.stats().free, 16);
+    }
 
-        drop(c); // This tests our drop case.
+    #[cfg(feature = "audit")]
+    #[test]
+    fn audit_overflow() {
+        let memory = ArrayAllocator::<8>::new(None);
+        memory.set_audit(true);
+        assert_eq!(
+            memory.try_allocate(usize::MAX).map(|_| ()),
+            Err(AllocError::InvalidLayout)
+        );
+        assert_eq!(memory.stats().free, 8);
+    }
 
-        drop(d);
+    #[cfg(all(feature = "audit", feature = "canaries"))]
+    #[test]
+    fn audit_canaries() {
+        let memory = ArrayAllocator::<32>::new(None);
+        memory.set_audit(true);
+        memory.set_canaries(true);
+        let mut a = memory.allocate(2).unwrap();
+        let b = memory.allocate_aligned(1, 64).unwrap();
+        a.set_tag(1);
+        assert_eq!(b.as_ptr() as usize % 64, 0);
+        assert_eq!(
+            memory.live_allocations().collect::<std::vec::Vec<_>>(),
+            [(b.index(), 1, 0), (a.index(), 2, 1)]
+        );
+        assert_eq!(memory.validate(), Ok(()));
+        drop((a, b));
+        assert_eq!(memory.stats().free, 32);
+    }
 
-        drop(memory);
+    #[cfg(feature = "audit")]
+    #[test]
+    #[should_panic(expected = "auditing cannot be toggled while there are allocations")]
+    fn audit_toggle() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let _wrapper = memory.allocate(1).unwrap();
+        memory.set_audit(true);
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬────┬───┐
-    // │...│index│self│...│
-    // └───┴─────┴────┴───┘
+
+    #[cfg(feature = "ownership")]
     #[test]
-    fn drop_6() {
-        let memory = ArrayAllocator::<3>::new(None);
+    fn reclaim_dead_owners() {
+        let memory = ArrayAllocator::<64>::new(None);
+        memory.set_audit(true);
+        let header = crate::audit::blocks::<Block, usize>();
+        let pid = std::process::id();
 
         let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // self
+        let b = memory.allocate(3).unwrap();
+        assert!(unsafe { memory.reclaim_dead_owners(|_| true) }.is_empty());
+        assert_eq!(memory.allocations_by_owner()[0].allocations, 2);
+
+        // Treats the current process as dead, so its wrappers must not be dropped.
+        let reclaimed = unsafe { memory.reclaim_dead_owners(|alive| alive != pid) };
+        std::mem::forget(a);
+        std::mem::forget(b);
+        assert_eq!(reclaimed.len(), 1);
+        assert_eq!(reclaimed[0].owner.pid, pid);
+        assert_eq!(reclaimed[0].allocations, 2);
+        assert_eq!(reclaimed[0].blocks, 4 + 2 * header);
+        assert!(memory.allocations_by_owner().is_empty());
+        assert_eq!(memory.stats().free, 64);
+    }
 
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
-        drop(a);
+    #[cfg(feature = "ownership")]
+    #[test]
+    fn allocations_by_owner() {
+        use crate::ownership::{set_client, Owner, Usage};
 
-        drop(c); // This tests our drop case.
+        let memory = ArrayAllocator::<64>::new(None);
+        assert!(memory.allocations_by_owner().is_empty());
+        memory.set_audit(true);
+        let header = crate::audit::blocks::<Block, usize>();
+        let pid = std::process::id();
 
-        // We don't care what order we drop these, only that we drop them after.
+        let a = memory.allocate(1).unwrap();
+        set_client(Some(7));
+        let b = memory.allocate(3).unwrap();
+        let c = memory.allocate(2).unwrap();
+        set_client(None);
+        assert_eq!(
+            memory.allocations_by_owner(),
+            [
+                Usage {
+                    owner: Owner {
+                        pid,
+                        client: Some(7)
+                    },
+                    allocations: 2,
+                    blocks: 5 + 2 * header,
+                },
+                Usage {
+                    owner: Owner { pid, client: None },
+                    allocations: 1,
+                    blocks: 1 + header,
+                },
+            ]
+        );
         drop(b);
-
-        drop(memory);
+        drop(c);
+        assert_eq!(memory.allocations_by_owner().len(), 1);
+        drop(a);
+        assert!(memory.allocations_by_owner().is_empty());
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬───┬────┬────┬───┐
-    // │...│index│...│self│next│...│
-    // └───┴─────┴───┴────┴────┴───┘
+
     #[test]
-    fn drop_7() {
-        let memory = ArrayAllocator::<5>::new(None);
+    fn allocate_slice_default() {
+        #[derive(Debug, PartialEq)]
+        struct Five(u16);
+        impl Default for Five {
+            fn default() -> Self {
+                Self(5)
+            }
+        }
 
-        let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // ...
-        let d = memory.allocate(1).unwrap(); // self
-        let e = memory.allocate(1).unwrap(); // next
+        let memory = ArrayAllocator::<4>::new(None);
+        memory.allocate_slice::<u32>(8).unwrap().fill(7);
+        assert_eq!(&*memory.allocate_slice_default::<u32>(8).unwrap(), &[0; 8]);
+        assert_eq!(
+            &*memory.allocate_slice_default::<Five>(3).unwrap(),
+            &[Five(5), Five(5), Five(5)]
+        );
+        assert_eq!(memory.allocate_slice_default::<u16>(0).unwrap().len(), 0);
+        assert!(memory.allocate_slice_default::<u128>(1).is_none());
+    }
 
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
-        drop(a);
+    #[test]
+    fn allocate_slice_from() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let copy = memory.allocate_slice_copy_from(&[1u32, 2, 3]).unwrap();
+        assert_eq!(&*copy, &[1, 2, 3]);
+        let names = [
+            std::string::String::from("a"),
+            std::string::String::from("b"),
+        ];
+        let clone = memory.allocate_slice_clone_from(&names).unwrap();
+        assert_eq!(&*clone, &names);
+        assert_eq!(memory.allocate_slice_copy_from::<u8>(&[]).unwrap().len(), 0);
+        assert!(memory.allocate_slice_copy_from(&[0u128]).is_none());
+        assert!(memory.allocate_slice_copy_from(&[0u8; 1024]).is_none());
+    }
 
-        drop(c);
+    #[test]
+    fn allocate_slice_from_iter() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let slice = memory
+            .allocate_slice_from_iter((1..4).map(|x| x * 10))
+            .unwrap();
+        assert_eq!(&*slice, &[10u32, 20, 30]);
+        assert_eq!(
+            memory
+                .allocate_slice_from_iter(std::iter::empty::<u8>())
+                .unwrap()
+                .len(),
+            0
+        );
+        assert!(memory
+            .allocate_slice_from_iter(std::iter::repeat_n(0u8, 1024))
+            .is_none());
+    }
 
-        drop(d); // This tests our drop case.
+    #[test]
+    fn slice_view() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut slice = memory.allocate_slice::<u16>(8).unwrap();
+        for (i, x) in slice.iter_mut().enumerate() {
+            *x = i as u16;
+        }
+        let view = slice.view(2..6);
+        assert_eq!((view.index(), view.start(), view.len()), (0, 2, 4));
+        assert_eq!(&*view, &[2, 3, 4, 5]);
+        let sub = view.view(1..);
+        assert_eq!(sub.start(), 3);
+        assert_eq!(format!("{sub:?}"), "[3, 4, 5]");
+        assert!(slice.view(8..).is_empty());
+    }
 
-        // We don't care what order we drop these, only that we drop them after.
-        drop(b);
-        drop(e);
+    #[test]
+    #[should_panic]
+    fn slice_view_out_of_bounds() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let slice = memory.allocate_slice::<u16>(8).unwrap();
+        let _ = slice.view(4..9);
+    }
 
-        drop(memory);
+    #[test]
+    fn allocate_aligned() {
+        let memory = ArrayAllocator::<64, u32>::new(None);
+        let block = size_of::<Block<u32>>();
+        let _a = memory.allocate(1).unwrap();
+        let b = memory.allocate_aligned(2, 64).unwrap();
+        assert_eq!(b.as_ptr() as usize % 64, 0);
+        assert_eq!(b.size(), 2);
+
+        // The blocks skipped to reach the alignment remain free.
+        assert_eq!(memory.stats().free, 64 - 3);
+        let c = memory.allocate_aligned(64 / block * 4, 32).unwrap();
+        assert_eq!(c.as_ptr() as usize % 32, 0);
+        drop((b, c));
+        assert_eq!(
+            memory.stats(),
+            Stats {
+                total: 64,
+                free: 63,
+                largest_free: 63,
+                free_regions: 1,
+                allocations: 1,
+                peak_used: 23,
+            }
+        );
+        assert!(memory.allocate_aligned(64, 64).is_none());
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬───┬────┬───┬────┬───┐
-    // │...│index│...│self│...│next│...│
-    // └───┴─────┴───┴────┴───┴────┴───┘
+
+    #[cfg(feature = "pthread")]
     #[test]
-    fn drop_8() {
-        let memory = ArrayAllocator::<6>::new(None);
+    fn allocate_page_aligned() {
+        let memory = Box::new(ArrayAllocator::<1024>::new(None));
+        let wrapper = memory.allocate_page_aligned(2).unwrap();
+        assert_eq!(wrapper.as_ptr() as usize % 4096, 0);
+    }
 
-        let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // ...
-        let d = memory.allocate(1).unwrap(); // self
-        let e = memory.allocate(1).unwrap(); // ...
-        let f = memory.allocate(1).unwrap(); // next
+    #[cfg(feature = "async")]
+    #[test]
+    fn allocate_async() {
+        use std::future::Future;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Counter(AtomicUsize);
+        impl Wake for Counter {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
 
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
+        let memory = ArrayAllocator::<4>::new(None);
+        let a = memory.allocate(3).unwrap();
+        let mut future = Box::pin(memory.allocate_async(2));
+        assert!(future.as_mut().poll(&mut cx).is_pending());
         drop(a);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        let Poll::Ready(Some(wrapper)) = future.as_mut().poll(&mut cx) else {
+            panic!("allocation did not complete");
+        };
+        assert_eq!(wrapper.size(), 2);
+        drop(wrapper);
 
-        drop(b);
-        drop(f);
+        // Waits for the lock rather than blocking, a critical section is never held by another
+        // thread.
+        #[cfg(not(feature = "critical-section"))]
+        {
+            let guard = memory.0.lock().unwrap();
+            let mut future = Box::pin(memory.allocate_async(1));
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            drop(guard);
+            assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+            assert!(matches!(
+                future.as_mut().poll(&mut cx),
+                Poll::Ready(Some(_))
+            ));
+        }
 
-        drop(d); // This tests our drop case.
+        let mut future = Box::pin(memory.allocate_async(5));
+        assert!(matches!(future.as_mut().poll(&mut cx), Poll::Ready(None)));
+    }
 
-        // We don't care what order we drop these, only that we drop them after.
-        drop(c);
-        drop(e);
+    #[test]
+    fn out_of_band() {
+        let memory = OutOfBandArrayAllocator::<8, u32>::new(None);
+        assert!(memory.0.lock().unwrap().out_of_band());
+        let mut a = memory.allocate(2).unwrap();
+        let b = memory.allocate(3).unwrap();
+        drop(b);
+        #[cfg(not(feature = "debug-fill"))]
+        assert!(unsafe { memory.0.lock().unwrap().data().as_ref() }
+            .iter()
+            .all(|block| *block == Block::new(0, None)));
+
+        // Overrun `a` into the following free blocks.
+        for i in 0..8 {
+            unsafe { a.as_mut_ptr().add(i).write(Block::new(7, Some(3))) };
+        }
+        assert_eq!(
+            memory.stats(),
+            Stats {
+                total: 8,
+                free: 6,
+                largest_free: 6,
+                free_regions: 1,
+                allocations: 1,
+                peak_used: 5,
+            }
+        );
 
-        drop(memory);
+        drop(a);
+        let c = memory.allocate(8).unwrap();
+        assert_eq!(c.index(), 0);
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬───┬────┬───┬────┬───┐
-    // │...│index│...│next│...│self│...│
-    // └───┴─────┴───┴────┴───┴────┴───┘
-    #[test]
-    fn drop_9() {
-        let memory = ArrayAllocator::<6>::new(None);
 
-        let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // ...
-        let d = memory.allocate(1).unwrap(); // next
-        let e = memory.allocate(1).unwrap(); // ...
-        let f = memory.allocate(1).unwrap(); // self
+    #[test]
+    fn display() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(2).unwrap();
+        let _b = memory.allocate(3).unwrap();
+        drop(a);
+        assert_eq!(
+            memory.to_string(),
+            "3/8 blocks used (72/192 bytes), 2 free regions, largest free region 3 blocks"
+        );
+    }
 
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
+    #[test]
+    fn render_map() {
+        let memory = ArrayAllocator::<12>::new(None);
+        let a = memory.allocate(4).unwrap();
+        let _b = memory.allocate(6).unwrap();
         drop(a);
+        assert_eq!(memory.render_map(8), " 0 [....####]\n 8 [##..]\n");
+    }
 
-        drop(c);
-        drop(e);
+    #[test]
+    fn usage_accessors() {
+        let memory = ArrayAllocator::<4>::new(None);
+        assert_eq!(memory.capacity(), 4);
+        assert!(memory.is_empty() && !memory.is_full());
 
-        drop(f); // This tests our drop case.
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(3).unwrap();
+        assert_eq!((memory.free_blocks(), memory.used_blocks()), (0, 4));
+        assert!(!memory.is_empty() && memory.is_full());
 
-        // We don't care what order we drop these, only that we drop them after.
+        drop(a);
+        assert_eq!((memory.free_blocks(), memory.used_blocks()), (1, 3));
+        assert!(!memory.is_empty() && !memory.is_full());
         drop(b);
-        drop(d);
+        assert!(memory.is_empty());
+    }
 
-        drop(memory);
+    #[test]
+    fn dump() {
+        let memory = ArrayAllocator::<12>::new(None);
+        let a = memory.allocate(4).unwrap();
+        let _b = memory.allocate(6).unwrap();
+        drop(a);
+        assert_eq!(
+            memory.dump(),
+            "┌──────┬──────┬──────┐\n│free:4│used:6│free:2│\n└──────┴──────┴──────┘\n"
+        );
     }
-    // Tests `Wrapper` drop case of:
-    // ┌───┬─────┬───┬────┬───┐
-    // │...│index│...│self│...│
-    // └───┴─────┴───┴────┴───┘
+
+    #[cfg(feature = "latency")]
     #[test]
-    fn drop_10() {
+    fn latency_histogram() {
         let memory = ArrayAllocator::<4>::new(None);
+        let a = memory.allocate(2).unwrap();
+        let _b = memory.allocate(2).unwrap();
+        assert!(memory.allocate(1).is_none());
+        drop(a);
+        let latency = memory.latency_histogram();
+        assert_eq!(latency.allocate.count(), 3);
+        assert_eq!(latency.free.count(), 1);
+        assert_eq!(latency.lock_wait.count(), 4);
+        assert!(latency.allocate.percentile(1.0).is_some());
+    }
 
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn dump_live_allocations() {
+        let memory = ArrayAllocator::<8>::new(None);
         let a = memory.allocate(1).unwrap();
-        let b = memory.allocate(1).unwrap(); // index
-        let c = memory.allocate(1).unwrap(); // ...
-        let d = memory.allocate(1).unwrap(); // self
-
-        // Dropping this results in the following drop entering the case:
-        // ┌───┬────┬───┬────┬───┐
-        // │...│head│...│self│...│
-        // └───┴────┴───┴────┴───┘
+        let wrappers = (2..4)
+            .map(|n| (line!(), memory.allocate(n).unwrap()))
+            .collect::<std::vec::Vec<_>>();
+        let sites = memory.dump_live_allocations();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].location.line(), wrappers[0].0);
+        assert_eq!((sites[0].allocations, sites[0].blocks), (2, 5));
+        assert_eq!((sites[1].allocations, sites[1].blocks), (1, 1));
+        drop(wrappers);
         drop(a);
+        assert!(memory.dump_live_allocations().is_empty());
+    }
 
-        drop(c);
-
-        drop(d); // This tests our drop case.
+    #[cfg(feature = "allocator-api")]
+    #[test]
+    fn allocator_api_vec() {
+        let memory = ArrayAllocator::<16>::new(None);
+        let mut vec = std::vec::Vec::new_in(&*memory);
+        for i in 0..10u8 {
+            vec.push(i);
+        }
+        assert_eq!(vec, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(memory.stats().free < 16);
+        drop(vec);
+        assert_eq!(memory.stats().free, 16);
+
+        #[repr(align(64))]
+        struct Aligned(u8);
+        let boxed = Box::new_in(Aligned(1), &*memory);
+        assert_eq!(std::ptr::addr_of!(*boxed) as usize % 64, 0);
+        assert_eq!(boxed.0, 1);
+        drop(boxed);
+        assert_eq!(memory.stats().free, 16);
+
+        let mut vec = std::vec::Vec::<u64, _>::new_in(&*memory);
+        assert!(vec.try_reserve(1000).is_err());
+    }
 
-        // We don't care what order we drop these, only that we drop them after.
-        drop(b);
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn allocator_api2_vec() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut vec = allocator_api2::vec::Vec::new_in(&*memory);
+        for i in 0..10u8 {
+            vec.push(i);
+        }
+        assert_eq!(vec.as_slice(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(memory.stats().free < 8);
+        drop(vec);
+        assert_eq!(memory.stats().free, 8);
 
-        drop(memory);
+        let mut vec = allocator_api2::vec::Vec::<u64, _>::new_in(&*memory);
+        assert!(vec.try_reserve(1000).is_err());
+        vec.try_reserve(1).unwrap();
     }
 }
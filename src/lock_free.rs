@@ -0,0 +1,504 @@
+//! An experimental allocator which never locks, so threads allocating and freeing disjoint regions
+//! don't serialize on one mutex as they do with [`crate::linked_list`].
+//!
+//! Free regions are kept in a stack linked through an array of links beside the blocks, whose head
+//! is updated with compare-and-swap and tagged with a counter so a head popped and pushed back
+//! between a load and a swap is detected. The links are kept out of the blocks so reading the link
+//! of a region popped meanwhile never races with its new owner writing to it. Freeing pushes the
+//! region without coalescing, coalescing is deferred to [`Allocator::maintain`]. Allocation pops
+//! regions until one is large enough, then pushes the skipped regions back, so a concurrent
+//! allocation may fail while they are held.
+//!
+//! Blocks are addressed by `u32`s, so an allocator has fewer than [`u32::MAX`] blocks.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut, Drop};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::error::{none_on_oom, AllocError};
+#[cfg(feature = "std")]
+use crate::raw::Stats;
+
+/// The link of the last region in the stack.
+const EMPTY: u32 = 0;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ArrayAllocator<const N: usize> {
+    allocator: Allocator,
+    links: [Link; N],
+    data: [Block; N],
+}
+impl<const N: usize> ArrayAllocator<N> {
+    #[must_use]
+    pub fn new() -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::new");
+
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        let ptr = this.as_mut_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).links).write_bytes(0, 1);
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(0, 1);
+            Allocator::init(std::ptr::addr_of_mut!((*ptr).allocator), N);
+            this.assume_init()
+        }
+    }
+}
+
+impl<const N: usize> Default for ArrayAllocator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for ArrayAllocator<N> {
+    type Target = Allocator;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+impl<const N: usize> DerefMut for ArrayAllocator<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.allocator
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Allocator {
+    /// The tag in the high half and the link of the first free region in the low half, where a
+    /// link is the index of a block plus one, or [`EMPTY`].
+    head: AtomicU64,
+    free: AtomicUsize,
+    size: usize,
+}
+
+fn pack(tag: u32, link: u32) -> u64 {
+    (u64::from(tag) << 32) | u64::from(link)
+}
+
+fn unpack(head: u64) -> (u32, u32) {
+    ((head >> 32) as u32, head as u32)
+}
+
+fn link(index: usize) -> u32 {
+    index as u32 + 1
+}
+
+impl Allocator {
+    /// Initializes `Self` at `ptr`.
+    ///
+    /// `ptr` must be followed by `n` links then `n` data blocks, as in [`ArrayAllocator`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Panics
+    ///
+    /// When `n` is not less than [`u32::MAX`].
+    pub unsafe fn init(ptr: *mut Self, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::init");
+
+        assert!(n < u32::MAX as usize);
+        ptr.write(Self {
+            head: AtomicU64::new(if n == 0 { pack(0, EMPTY) } else { pack(0, 1) }),
+            free: AtomicUsize::new(n),
+            size: n,
+        });
+        if n > 0 {
+            let first = &(*ptr).links()[0];
+            first.size.store(n as u32, Ordering::Relaxed);
+            first.next.store(EMPTY, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the links of the free regions, one per block.
+    fn links(&self) -> &[Link] {
+        #[cfg(feature = "log")]
+        trace!("Allocator::links");
+
+        unsafe {
+            let end = (self as *const Self).add(1).cast::<u8>();
+            let start = end.add(end.align_offset(std::mem::align_of::<Link>()));
+            std::slice::from_raw_parts(start.cast(), self.size)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    #[must_use]
+    pub unsafe fn data(&self) -> &[Block] {
+        #[cfg(feature = "log")]
+        trace!("Allocator::data");
+
+        let end = self.links().as_ptr_range().end.cast::<u8>();
+        let start = end.add(end.align_offset(std::mem::align_of::<Block>()));
+        std::slice::from_raw_parts(start.cast(), self.size)
+    }
+
+    /// Returns the number of free blocks, including those of regions held by in progress
+    /// allocations.
+    #[must_use]
+    pub fn free(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free");
+
+        self.free.load(Ordering::Relaxed)
+    }
+
+    /// Allocates a given number of blocks.
+    pub fn allocate(&self, blocks: usize) -> Option<Wrapper> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        none_on_oom(self.try_allocate(blocks))
+    }
+
+    /// Allocates a given number of blocks.
+    ///
+    /// # Errors
+    ///
+    /// When no free region is large enough, which may be because free regions are held by
+    /// concurrent allocations or have not been coalesced, see [`Allocator::maintain`].
+    pub fn try_allocate(&self, blocks: usize) -> Result<Wrapper, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate");
+
+        if blocks == 0 {
+            return Ok(Wrapper {
+                allocator: self,
+                index: 0,
+                size: 0,
+            });
+        }
+        let links = self.links();
+        // The regions popped which were too small, linked through their links.
+        let mut skipped: Option<(usize, usize)> = None;
+        let mut largest_free = 0;
+        let found = loop {
+            let Some(index) = self.pop() else {
+                break None;
+            };
+            let size = links[index].size.load(Ordering::Relaxed) as usize;
+            if size >= blocks {
+                if size > blocks {
+                    let rest = index + blocks;
+                    links[rest]
+                        .size
+                        .store((size - blocks) as u32, Ordering::Relaxed);
+                    self.push(rest, rest);
+                }
+                break Some(index);
+            }
+            largest_free = largest_free.max(size);
+            links[index].next.store(
+                skipped.map_or(EMPTY, |(first, _)| link(first)),
+                Ordering::Relaxed,
+            );
+            skipped = Some((index, skipped.map_or(index, |(_, last)| last)));
+        };
+        if let Some((first, last)) = skipped {
+            self.push(first, last);
+        }
+        match found {
+            Some(index) => {
+                self.free.fetch_sub(blocks, Ordering::Relaxed);
+                Ok(Wrapper {
+                    allocator: self,
+                    index,
+                    size: blocks,
+                })
+            }
+            None => Err(AllocError::OutOfMemory {
+                requested: blocks,
+                largest_free,
+            }),
+        }
+    }
+
+    /// Coalesces adjacent free regions, returning the statistics of the free regions coalesced.
+    ///
+    /// The free regions are taken for the duration, so concurrent allocations may fail, and
+    /// regions freed or held by concurrent allocations meanwhile are neither coalesced nor
+    /// counted.
+    #[cfg(feature = "std")]
+    pub fn maintain(&self) -> Stats {
+        #[cfg(feature = "log")]
+        trace!("Allocator::maintain");
+
+        let links = self.links();
+        let mut head = self.head.load(Ordering::Acquire);
+        let mut next = loop {
+            let (tag, first) = unpack(head);
+            match self.head.compare_exchange_weak(
+                head,
+                pack(tag.wrapping_add(1), EMPTY),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break first,
+                Err(current) => head = current,
+            }
+        };
+        let mut regions = Vec::new();
+        while next != EMPTY {
+            let index = next as usize - 1;
+            regions.push((index, links[index].size.load(Ordering::Relaxed) as usize));
+            next = links[index].next.load(Ordering::Relaxed);
+        }
+        regions.sort_unstable();
+
+        let mut coalesced: Vec<(usize, usize)> = Vec::with_capacity(regions.len());
+        for (index, size) in regions {
+            match coalesced.last_mut() {
+                Some((last, last_size)) if *last + *last_size == index => *last_size += size,
+                _ => coalesced.push((index, size)),
+            }
+        }
+        for (i, &(index, size)) in coalesced.iter().enumerate() {
+            links[index].size.store(size as u32, Ordering::Relaxed);
+            let next = coalesced.get(i + 1).map_or(EMPTY, |&(next, _)| link(next));
+            links[index].next.store(next, Ordering::Relaxed);
+        }
+        // Pushing in address order keeps allocations towards the start of the blocks.
+        if let (Some(&(first, _)), Some(&(last, _))) = (coalesced.first(), coalesced.last()) {
+            self.push(first, last);
+        }
+
+        Stats {
+            total: self.size,
+            free: coalesced.iter().map(|&(_, size)| size).sum(),
+            largest_free: coalesced.iter().map(|&(_, size)| size).max().unwrap_or(0),
+            free_regions: coalesced.len(),
+            ..Stats::default()
+        }
+    }
+
+    /// Pushes the chain of regions from `first` to `last` linked through their links.
+    fn push(&self, first: usize, last: usize) {
+        let links = self.links();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let (tag, next) = unpack(head);
+            links[last].next.store(next, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                pack(tag.wrapping_add(1), link(first)),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Pops the first free region.
+    fn pop(&self) -> Option<usize> {
+        let links = self.links();
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let (tag, first) = unpack(head);
+            let index = first.checked_sub(1)? as usize;
+            // When the region was popped meanwhile this reads a stale link, but the tag changed
+            // so the swap fails.
+            let next = links[index].next.load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                pack(tag.wrapping_add(1), next),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(index),
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn deallocate(&self, index: usize, size: usize) {
+        self.links()[index]
+            .size
+            .store(size as u32, Ordering::Relaxed);
+        self.free.fetch_add(size, Ordering::Relaxed);
+        self.push(index, index);
+    }
+}
+
+impl fmt::Display for Allocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} blocks free ({} bytes per block)",
+            self.free(),
+            self.size,
+            size_of::<Block>()
+        )
+    }
+}
+
+/// A data block.
+#[derive(Debug, Default)]
+#[repr(C, align(8))]
+pub struct Block(UnsafeCell<[u8; 8]>);
+
+// A block is only accessed through the wrapper holding it.
+unsafe impl Sync for Block {}
+
+/// The size of the free region starting at a block and the link of the next free region.
+#[derive(Debug, Default)]
+#[repr(C)]
+struct Link {
+    size: AtomicU32,
+    next: AtomicU32,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Wrapper<'a> {
+    allocator: &'a Allocator,
+    index: usize,
+    size: usize,
+}
+
+impl<'a> Wrapper<'a> {
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index");
+
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::size");
+
+        self.size
+    }
+
+    /// Returns a pointer to the first byte of the allocation.
+    #[must_use]
+    pub fn as_ptr(&self) -> NonNull<u8> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::as_ptr");
+
+        NonNull::from(&self[..]).cast()
+    }
+}
+
+impl<'a> Deref for Wrapper<'a> {
+    type Target = [Block];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref");
+
+        unsafe { &self.allocator.data()[self.index..self.index + self.size] }
+    }
+}
+
+impl<'a> Drop for Wrapper<'a> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop");
+
+        if self.size == 0 {
+            return;
+        }
+        self.allocator.deallocate(self.index, self.size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn lock_free() {
+        let memory = ArrayAllocator::<8>::new();
+        let a = memory.allocate(2).unwrap();
+        let b = memory.allocate(3).unwrap();
+        let c = memory.allocate(3).unwrap();
+        assert_eq!((a.index(), b.index(), c.index()), (0, 2, 5));
+        assert_eq!(memory.free(), 0);
+        assert!(memory.allocate(1).is_none());
+        assert_eq!(memory.allocate(0).unwrap().size(), 0);
+
+        drop(a);
+        drop(b);
+        // Freed regions are not coalesced until maintained.
+        assert_eq!(
+            memory.try_allocate(4).unwrap_err(),
+            AllocError::OutOfMemory {
+                requested: 4,
+                largest_free: 3
+            }
+        );
+        // The skipped regions were pushed back.
+        assert_eq!(memory.free(), 5);
+        let stats = memory.maintain();
+        assert_eq!(
+            stats,
+            Stats {
+                total: 8,
+                free: 5,
+                largest_free: 5,
+                free_regions: 1,
+                ..Stats::default()
+            }
+        );
+        let d = memory.allocate(4).unwrap();
+        assert_eq!(d.index(), 0);
+        assert_eq!(memory.to_string(), "1/8 blocks free (8 bytes per block)");
+        drop(c);
+        drop(d);
+        assert_eq!(memory.maintain().largest_free, 8);
+    }
+
+    #[test]
+    fn lock_free_threads() {
+        let memory = ArrayAllocator::<64>::new();
+        std::thread::scope(|s| {
+            for i in 0..4u64 {
+                let memory = &memory;
+                s.spawn(move || {
+                    for _ in 0..1000 {
+                        if let Some(wrapper) = memory.allocate(1 + i as usize) {
+                            let ptr = wrapper.as_ptr().cast::<u64>();
+                            unsafe {
+                                ptr.write(i);
+                                std::thread::yield_now();
+                                assert_eq!(ptr.read(), i);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(memory.free(), 64);
+        assert_eq!(memory.maintain().largest_free, 64);
+    }
+}
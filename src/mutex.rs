@@ -1,9 +1,184 @@
 #![allow(clippy::module_name_repetitions)]
 
-#[derive(Debug)]
+#[cfg(not(any(feature = "pthread", feature = "critical-section", miri)))]
+compile_error!("one of the `pthread` or `critical-section` features must be enabled");
+
+#[cfg(all(feature = "pthread", not(feature = "critical-section"), not(miri)))]
+pub use nix::sys::pthread::{Mutex as RawMutex, MutexAttr};
+
+/// The error returned when locking or initializing the underlying lock fails.
+#[cfg(all(feature = "pthread", not(feature = "critical-section"), not(miri)))]
+pub type Error = nix::errno::Errno;
+
+#[cfg(all(feature = "critical-section", not(miri)))]
+pub use critical_section_backend::{Error, Mutex as RawMutex, MutexAttr};
+
+/// A lock backed by the [`critical_section`] crate.
+///
+/// Acquiring the lock enters a critical section (e.g. disabling interrupts on single core
+/// targets) which is left when the lock is released. Entering the critical section already waits
+/// for holders elsewhere, so the lock can only be found held when it is locked again from within
+/// the critical section holding it, which fails with [`Error::Reentrant`] rather than aliasing the
+/// data.
+#[cfg(all(feature = "critical-section", not(miri)))]
+mod critical_section_backend {
+    use std::cell::UnsafeCell;
+    use std::fmt;
+    use std::mem::MaybeUninit;
+
+    /// The error returned when locking a critical section lock fails.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Error {
+        /// The lock is already held by the current critical section.
+        Reentrant,
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Reentrant => {
+                    write!(f, "lock is already held by the current critical section")
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for Error {}
+
+    /// Attributes are not supported by the critical section backend, this exists so
+    /// constructors share a signature across backends.
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct MutexAttr;
+
+    #[repr(C)]
+    pub struct Mutex {
+        state: UnsafeCell<MaybeUninit<critical_section::RestoreState>>,
+        locked: UnsafeCell<bool>,
+    }
+
+    impl Mutex {
+        #[allow(clippy::needless_pass_by_value, clippy::unnecessary_wraps)]
+        pub fn new(_attr: Option<MutexAttr>) -> Result<Self, Error> {
+            Ok(Self {
+                state: UnsafeCell::new(MaybeUninit::uninit()),
+                locked: UnsafeCell::new(false),
+            })
+        }
+
+        /// Enters the critical section.
+        ///
+        /// # Errors
+        ///
+        /// When the current critical section already holds the lock.
+        pub fn lock(&self) -> Result<(), Error> {
+            let state = unsafe { critical_section::acquire() };
+            // Only the holder of the critical section can reach these accesses.
+            unsafe {
+                if *self.locked.get() {
+                    critical_section::release(state);
+                    return Err(Error::Reentrant);
+                }
+                *self.locked.get() = true;
+                (*self.state.get()).write(state);
+            }
+            Ok(())
+        }
+
+        /// Only fails to lock when the current critical section already holds the lock.
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn try_lock(&self) -> Result<bool, Error> {
+            Ok(self.lock().is_ok())
+        }
+
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn unlock(&self) -> Result<(), Error> {
+            unsafe {
+                *self.locked.get() = false;
+                critical_section::release((*self.state.get()).assume_init_read());
+            }
+            Ok(())
+        }
+    }
+
+    // Matches the output of the other backends so `Debug` output doesn't depend on the backend.
+    impl fmt::Debug for Mutex {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("Mutex").field(&UnsafeCell::new(())).finish()
+        }
+    }
+
+    unsafe impl Send for Mutex {}
+}
+
+#[cfg(miri)]
+pub use spin_backend::{Error, Mutex as RawMutex, MutexAttr};
+
+/// A spin lock used when running under Miri, which cannot model the FFI calls of the `pthread`
+/// backend.
+#[cfg(miri)]
+mod spin_backend {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Locking a spin lock cannot fail.
+    pub type Error = std::convert::Infallible;
+
+    /// Attributes are not supported by the spin lock backend, this exists so constructors share a
+    /// signature across backends.
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct MutexAttr;
+
+    #[repr(C)]
+    pub struct Mutex(AtomicBool);
+
+    impl Mutex {
+        #[allow(clippy::needless_pass_by_value, clippy::unnecessary_wraps)]
+        pub fn new(_attr: Option<MutexAttr>) -> Result<Self, Error> {
+            Ok(Self(AtomicBool::new(false)))
+        }
+
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn lock(&self) -> Result<(), Error> {
+            while self
+                .0
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                std::thread::yield_now();
+            }
+            Ok(())
+        }
+
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn try_lock(&self) -> Result<bool, Error> {
+            Ok(self
+                .0
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok())
+        }
+
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn unlock(&self) -> Result<(), Error> {
+            self.0.store(false, Ordering::Release);
+            Ok(())
+        }
+    }
+
+    // Matches the output of the other backends so `Debug` output doesn't depend on the backend.
+    impl std::fmt::Debug for Mutex {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_tuple("Mutex")
+                .field(&std::cell::UnsafeCell::new(()))
+                .finish()
+        }
+    }
+}
+
 #[repr(C)]
 pub struct Mutex<T> {
-    pub lock: nix::sys::pthread::Mutex,
+    pub lock: RawMutex,
+    /// The process holding the lock.
+    #[cfg(feature = "watchdog")]
+    pub(crate) owner: crate::watchdog::Owner,
     data: std::cell::UnsafeCell<T>,
 }
 
@@ -12,26 +187,76 @@ impl<T> Mutex<T> {
     ///
     /// # Panics
     ///
-    /// When [`nix::sys::pthread::Mutex::new`] errors.
-    pub fn new(data: T, attr: Option<nix::sys::pthread::MutexAttr>) -> Self {
+    /// When [`RawMutex::new`] errors.
+    pub fn new(data: T, attr: Option<MutexAttr>) -> Self {
         #[cfg(feature = "log")]
         log::trace!("Mutex::new");
 
         Self {
-            lock: nix::sys::pthread::Mutex::new(attr).unwrap(),
+            lock: RawMutex::new(attr).unwrap(),
+            #[cfg(feature = "watchdog")]
+            owner: crate::watchdog::Owner::new(),
             data: std::cell::UnsafeCell::new(data),
         }
     }
 
-    pub fn lock(&self) -> nix::Result<MutexGuard<T>> {
+    pub fn lock(&self) -> Result<MutexGuard<T>, Error> {
         #[cfg(feature = "log")]
         log::trace!("Mutex::lock");
 
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+
         self.lock.lock()?;
+        #[cfg(feature = "watchdog")]
+        self.owner.acquired();
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::lock_wait(start.elapsed());
 
         Ok(MutexGuard(self))
     }
 
+    /// Locks the mutex if it is not held, returning `None` rather than waiting when it is.
+    pub fn try_lock(&self) -> Result<Option<MutexGuard<T>>, Error> {
+        #[cfg(feature = "log")]
+        log::trace!("Mutex::try_lock");
+
+        #[cfg(all(feature = "pthread", not(feature = "critical-section"), not(miri)))]
+        let locked = match self.lock.try_lock() {
+            Ok(()) => true,
+            Err(nix::errno::Errno::EBUSY) => false,
+            Err(err) => return Err(err),
+        };
+        #[cfg(any(feature = "critical-section", miri))]
+        let locked = self.lock.try_lock()?;
+        #[cfg(feature = "watchdog")]
+        if locked {
+            self.owner.acquired();
+        }
+
+        // Constructed lazily as dropping a guard unlocks the mutex.
+        Ok(locked.then(|| MutexGuard(self)))
+    }
+
+    /// Releases the lock held by an exited process.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by a process which no longer exists.
+    #[cfg(feature = "watchdog")]
+    pub unsafe fn force_unlock(&self) -> Result<(), Error> {
+        #[cfg(feature = "log")]
+        log::trace!("Mutex::force_unlock");
+
+        self.owner.released();
+        self.lock.unlock()?;
+
+        #[cfg(feature = "async")]
+        crate::waiters::wake(self as *const Self as usize);
+        Ok(())
+    }
+
     /// Returns a pointer to the underlying data without locking.
     ///
     /// # Safety
@@ -45,8 +270,21 @@ impl<T> Mutex<T> {
     }
 }
 
+// The owner is omitted so output doesn't depend on the `watchdog` feature.
+#[allow(clippy::missing_fields_in_debug)]
+impl<T: std::fmt::Debug> std::fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mutex")
+            .field("lock", &self.lock)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
 pub struct MutexGuard<'a, T>(&'a Mutex<T>);
-unsafe impl<T> Sync for Mutex<T> {}
+// As with `std::sync::Mutex`, the lock provides the synchronization so only `T: Send` is required.
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
 impl<'a, T> std::ops::Deref for MutexGuard<'a, T> {
     type Target = T;
 
@@ -70,7 +308,13 @@ impl<'a, T> Drop for MutexGuard<'a, T> {
         #[cfg(feature = "log")]
         log::trace!("MutexGuard::drop");
 
+        #[cfg(feature = "watchdog")]
+        self.0.owner.released();
         self.0.lock.unlock().unwrap();
+
+        // Tasks waiting on the lock are keyed by the address of the mutex.
+        #[cfg(feature = "async")]
+        crate::waiters::wake(self.0 as *const Mutex<T> as usize);
     }
 }
 
@@ -88,10 +332,41 @@ mod tests {
         );
     }
 
+    #[cfg(all(feature = "critical-section", not(miri)))]
+    #[test]
+    fn mutex_reentrant() {
+        let mutex = Mutex::new((), None);
+        let guard = mutex.lock().unwrap();
+        assert_eq!(mutex.lock().err(), Some(Error::Reentrant));
+        assert!(mutex.try_lock().unwrap().is_none());
+        drop(guard);
+        assert!(mutex.try_lock().unwrap().is_some());
+    }
+
     #[test]
     fn mutex_get() {
         unsafe {
             Mutex::new((), None).get();
         }
     }
+
+    #[test]
+    fn mutex_lock() {
+        let mutex = Mutex::new(0u8, None);
+        *mutex.lock().unwrap() = 1;
+        assert_eq!(*mutex.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn mutex_try_lock() {
+        let mutex = Mutex::new(0u8, None);
+        let guard = mutex.try_lock().unwrap().unwrap();
+        #[cfg(not(feature = "critical-section"))]
+        assert!(std::thread::scope(|s| s
+            .spawn(|| mutex.try_lock().unwrap().is_none())
+            .join()
+            .unwrap()));
+        drop(guard);
+        assert!(mutex.try_lock().unwrap().is_some());
+    }
 }
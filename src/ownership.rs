@@ -0,0 +1,76 @@
+//! Attribution of the live allocations of a [`crate::linked_list::Allocator`] to the processes,
+//! and optionally the clients, which made them, see
+//! [`crate::linked_list::Allocator::allocations_by_owner`].
+//!
+//! The owner of each allocation is recorded in its audit header, see [`crate::audit`], so
+//! auditing must be enabled and any process sharing the allocator sees the owners of every
+//! allocation.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+
+/// The process, and client within it, which made an allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(C)]
+pub struct Owner {
+    pub pid: u32,
+    /// The client set with [`set_client`] on the allocating thread.
+    pub client: Option<u32>,
+}
+
+/// Live allocations made by an owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub owner: Owner,
+    /// The number of live allocations.
+    pub allocations: usize,
+    /// The total number of blocks in the live allocations, including headers and guards.
+    pub blocks: usize,
+}
+
+thread_local! {
+    static CLIENT: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Sets the client subsequent allocations on the current thread are attributed to, e.g. the peer
+/// a server is handling a request from.
+pub fn set_client(client: Option<u32>) {
+    #[cfg(feature = "log")]
+    log::trace!("ownership::set_client");
+
+    CLIENT.with(|cell| cell.set(client));
+}
+
+/// Returns the client allocations on the current thread are attributed to.
+#[must_use]
+pub fn client() -> Option<u32> {
+    #[cfg(feature = "log")]
+    log::trace!("ownership::client");
+
+    CLIENT.with(Cell::get)
+}
+
+/// Returns the owner of allocations made by the current thread.
+pub(crate) fn current() -> Owner {
+    Owner {
+        pid: std::process::id(),
+        client: client(),
+    }
+}
+
+/// Groups allocations of the given sizes by owner, ordered by the number of blocks descending.
+pub(crate) fn usage(allocations: impl IntoIterator<Item = (usize, Owner)>) -> Vec<Usage> {
+    let mut owners = HashMap::<Owner, Usage>::new();
+    for (size, owner) in allocations {
+        let entry = owners.entry(owner).or_insert(Usage {
+            owner,
+            allocations: 0,
+            blocks: 0,
+        });
+        entry.allocations += 1;
+        entry.blocks += size;
+    }
+    let mut usage = owners.into_values().collect::<Vec<_>>();
+    usage.sort_by(|a, b| b.blocks.cmp(&a.blocks).then(a.owner.cmp(&b.owner)));
+    usage
+}
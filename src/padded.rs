@@ -0,0 +1,137 @@
+//! Slices whose elements are spaced so each lands on its own cache line, see
+//! [`crate::linked_list::Allocator::allocate_slice_padded`].
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Index as IndexOp, IndexMut};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::Wrapper;
+use crate::Index;
+
+/// A slice of `T` with elements `stride` bytes apart, so per-thread elements do not false
+/// share.
+///
+/// Like [`crate::linked_list::Slice`] the elements are not dropped.
+pub struct PaddedSlice<'a, T, I: Index = usize> {
+    pub(crate) wrapper: Wrapper<'a, I>,
+    /// The offset in bytes of the first element from the start of the allocation.
+    pub(crate) offset: usize,
+    pub(crate) stride: usize,
+    pub(crate) len: usize,
+    pub(crate) __marker: PhantomData<T>,
+}
+
+impl<'a, T, I: Index> PaddedSlice<'a, T, I> {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The distance in bytes between elements.
+    #[must_use]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    #[must_use]
+    pub fn wrapper(&self) -> &Wrapper<'a, I> {
+        &self.wrapper
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        #[cfg(feature = "log")]
+        trace!("PaddedSlice::get");
+
+        (index < self.len).then(|| unsafe { &*self.element(index) })
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        #[cfg(feature = "log")]
+        trace!("PaddedSlice::get_mut");
+
+        (index < self.len).then(|| unsafe { &mut *self.element(index) })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len).map(|index| unsafe { &*self.element(index) })
+    }
+
+    /// # Safety
+    ///
+    /// `index < self.len`.
+    unsafe fn element(&self, index: usize) -> *mut T {
+        self.wrapper[..]
+            .as_ptr()
+            .cast::<u8>()
+            .add(self.offset + index * self.stride)
+            .cast::<T>()
+            .cast_mut()
+    }
+}
+
+impl<'a, T, I: Index> IndexOp<usize> for PaddedSlice<'a, T, I> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index)
+            .unwrap_or_else(|| panic!("index {index} out of range for {}", self.len))
+    }
+}
+
+impl<'a, T, I: Index> IndexMut<usize> for PaddedSlice<'a, T, I> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        let len = self.len;
+        self.get_mut(index)
+            .unwrap_or_else(|| panic!("index {index} out of range for {len}"))
+    }
+}
+
+impl<'a, T: fmt::Debug, I: Index> fmt::Debug for PaddedSlice<'a, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn padded_slice() {
+        let memory = ArrayAllocator::<32>::new(None);
+        let _offset = memory.allocate(1).unwrap();
+        let mut counters = memory.allocate_slice_padded::<u64>(4, 64).unwrap();
+        assert_eq!(counters.stride(), 64);
+        for i in 0..4 {
+            counters[i] = i as u64;
+        }
+        counters[3] += 1;
+        assert_eq!(format!("{counters:?}"), "[0, 1, 2, 4]");
+        let addresses = (0..4)
+            .map(|i| counters.get(i).unwrap() as *const u64 as usize)
+            .collect::<Vec<_>>();
+        assert!(addresses.iter().all(|address| address % 64 == 0));
+        assert!(addresses.windows(2).all(|w| w[1] - w[0] == 64));
+        assert!(counters.get(4).is_none());
+    }
+
+    #[test]
+    fn padded_slice_stride() {
+        let memory = ArrayAllocator::<32>::new(None);
+        let slice = memory.allocate_slice_padded::<[u8; 100]>(2, 64).unwrap();
+        assert_eq!(slice.stride(), 128);
+        assert!(memory.allocate_slice_padded::<u8>(1, 48).is_none());
+        assert_eq!(memory.allocate_slice_padded::<u8>(0, 64).unwrap().len(), 0);
+    }
+}
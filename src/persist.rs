@@ -0,0 +1,365 @@
+//! A versioned format for [`crate::linked_list::Allocator`]s persisted in e.g. memory mapped
+//! files or persistent memory, so long lived arenas can be attached to by later processes and
+//! survive crate upgrades.
+//!
+//! A region starts with a [`Header`] recording the format version and the layout of the
+//! allocator, followed by the allocator and its blocks. Attaching to a region written by an
+//! older version upgrades it to [`FORMAT_VERSION`] one version at a time with a [`Migration`]
+//! supplied by the caller, as the crate ships none.
+
+use std::fmt;
+use std::mem::{align_of, size_of};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Block};
+use crate::Index;
+
+/// Identifies a region holding an allocator.
+pub const MAGIC: [u8; 8] = *b"ARRALLOC";
+
+/// The version of the format written by this version of the crate.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The header at the start of a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Header {
+    pub magic: [u8; 8],
+    pub version: u32,
+    /// The size of a block in bytes.
+    pub block_size: u32,
+    /// The size of the index type in bytes.
+    pub index_size: u32,
+    /// The size of the allocator in bytes.
+    pub allocator_size: u32,
+    /// The number of blocks following the allocator.
+    pub blocks: u64,
+    /// The enabled features which change the layout of the allocator or its blocks, see
+    /// [`features`].
+    pub features: u64,
+}
+
+/// Upgrades a region from the format version `from_version` to `from_version + 1`.
+///
+/// The region includes the header, whose version is incremented by [`attach`] once the migration
+/// succeeds.
+pub type Migration = fn(from_version: u32, region: &mut [u8]) -> Result<(), AttachError>;
+
+/// The error returned when creating or attaching to a region fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachError {
+    /// The region is too small for the header, the allocator and its blocks.
+    TooSmall,
+    /// The region is not aligned for the allocator.
+    Misaligned,
+    /// The region does not start with [`MAGIC`].
+    NotAnArena,
+    /// The region was written by a newer version of the crate.
+    TooNew(u32),
+    /// The region was written by an older version of the crate which cannot be migrated.
+    Unsupported(u32),
+    /// The block, index or allocator size of the region, or the features it was written with,
+    /// differ from those of the allocator.
+    LayoutMismatch,
+}
+
+impl fmt::Display for AttachError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall => write!(f, "region is too small"),
+            Self::Misaligned => write!(f, "region is misaligned"),
+            Self::NotAnArena => write!(f, "region does not hold an allocator"),
+            Self::TooNew(version) => write!(
+                f,
+                "format version {version} is newer than the supported version {FORMAT_VERSION}"
+            ),
+            Self::Unsupported(version) => {
+                write!(f, "format version {version} cannot be migrated")
+            }
+            Self::LayoutMismatch => write!(f, "layout of the allocator differs"),
+        }
+    }
+}
+
+impl std::error::Error for AttachError {}
+
+/// Returns a bit for each enabled feature which changes the layout of an allocator or its
+/// blocks, so a region written by a build with different features is rejected rather than
+/// reinterpreted.
+#[must_use]
+pub fn features() -> u64 {
+    [
+        cfg!(feature = "pthread"),
+        cfg!(feature = "critical-section"),
+        cfg!(miri),
+        cfg!(feature = "watchdog"),
+        cfg!(feature = "sanitizer"),
+        cfg!(feature = "quarantine"),
+        cfg!(feature = "canaries"),
+        cfg!(feature = "audit"),
+        cfg!(feature = "ownership"),
+        cfg!(feature = "testing"),
+        cfg!(feature = "latency"),
+    ]
+    .into_iter()
+    .enumerate()
+    .fold(0, |features, (i, enabled)| {
+        features | (u64::from(enabled) << i)
+    })
+}
+
+/// The offset of the allocator from the start of a region.
+fn offset<I: Index>() -> usize {
+    size_of::<Header>().next_multiple_of(align_of::<Allocator<I>>())
+}
+
+/// Returns the number of blocks following the allocator in a region of `len` bytes.
+fn blocks<I: Index>(len: usize) -> Option<usize> {
+    let bytes = len.checked_sub(offset::<I>() + size_of::<Allocator<I>>())?;
+    Some(bytes / size_of::<Block<I>>())
+}
+
+// Sizes of blocks and indices fit in a `u32`.
+#[allow(clippy::cast_possible_truncation)]
+fn current<I: Index>(blocks: usize) -> Header {
+    Header {
+        magic: MAGIC,
+        version: FORMAT_VERSION,
+        block_size: size_of::<Block<I>>() as u32,
+        index_size: size_of::<I>() as u32,
+        allocator_size: size_of::<Allocator<I>>() as u32,
+        blocks: blocks as u64,
+        features: features(),
+    }
+}
+
+/// Writes a header and initializes an allocator with as many blocks as fit in `region`.
+///
+/// # Safety
+///
+/// `region` must not hold an allocator in use, e.g. by another process.
+///
+/// # Errors
+///
+/// When `region` is too small or misaligned.
+///
+/// # Panics
+///
+/// When failing to initialize the mutex of the allocator.
+// Alignment is checked before the allocator is written.
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe fn create<I: Index>(
+    region: &mut [u8],
+    attr: Option<crate::MutexAttr>,
+) -> Result<&Allocator<I>, AttachError> {
+    #[cfg(feature = "log")]
+    trace!("persist::create");
+
+    let n = blocks::<I>(region.len()).ok_or(AttachError::TooSmall)?;
+    let ptr = region.as_mut_ptr();
+    if !ptr.add(offset::<I>()).cast::<Allocator<I>>().is_aligned() {
+        return Err(AttachError::Misaligned);
+    }
+    ptr.cast::<Header>().write_unaligned(current::<I>(n));
+    let allocator = ptr.add(offset::<I>()).cast::<Allocator<I>>();
+    Allocator::init(allocator, attr, n);
+    Ok(&*allocator)
+}
+
+/// Attaches to the allocator in `region`, migrating it from older format versions with
+/// `migrate`.
+///
+/// The lock is reinitialized and hooks are cleared, as they belong to the process which set them.
+///
+/// # Safety
+///
+/// `region` must have been written by [`create`], or be a copy of such a region e.g. read from a
+/// file, and the allocator must not be in use, e.g. by another process.
+///
+/// # Errors
+///
+/// When `region` is too small, misaligned or doesn't hold an allocator, when its format version is
+/// newer than [`FORMAT_VERSION`], when it is older and `migrate` is `None` or fails, or when its
+/// layout differs from that of `Allocator<I>`.
+///
+/// # Panics
+///
+/// When failing to initialize the mutex of the allocator.
+// Alignment is checked before the allocator is written.
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe fn attach<I: Index>(
+    region: &mut [u8],
+    attr: Option<crate::MutexAttr>,
+    migrate: Option<Migration>,
+) -> Result<&Allocator<I>, AttachError> {
+    #[cfg(feature = "log")]
+    trace!("persist::attach");
+
+    if region.len() < size_of::<Header>() {
+        return Err(AttachError::TooSmall);
+    }
+    let read = |region: &[u8]| region.as_ptr().cast::<Header>().read_unaligned();
+    let mut header = read(region);
+    if header.magic != MAGIC {
+        return Err(AttachError::NotAnArena);
+    }
+    if header.version > FORMAT_VERSION {
+        return Err(AttachError::TooNew(header.version));
+    }
+    while header.version < FORMAT_VERSION {
+        let migrate = migrate.ok_or(AttachError::Unsupported(header.version))?;
+        migrate(header.version, region)?;
+        header = read(region);
+        header.version += 1;
+        region.as_mut_ptr().cast::<Header>().write_unaligned(header);
+    }
+
+    let expected = current::<I>(0);
+    let layout = |header: &Header| {
+        (
+            header.block_size,
+            header.index_size,
+            header.allocator_size,
+            header.features,
+        )
+    };
+    if layout(&header) != layout(&expected) {
+        return Err(AttachError::LayoutMismatch);
+    }
+    let fits = usize::try_from(header.blocks)
+        .ok()
+        .zip(blocks::<I>(region.len()))
+        .is_some_and(|(n, fit)| n <= fit);
+    if !fits {
+        return Err(AttachError::TooSmall);
+    }
+    let allocator = region
+        .as_mut_ptr()
+        .add(offset::<I>())
+        .cast::<Allocator<I>>();
+    if !allocator.is_aligned() {
+        return Err(AttachError::Misaligned);
+    }
+    Allocator::reattach(allocator, attr);
+    Ok(&*allocator)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    /// Returns the length of a region holding an allocator with `blocks` blocks.
+    fn len(blocks: usize) -> usize {
+        offset::<usize>() + size_of::<Allocator<usize>>() + blocks * size_of::<Block<usize>>()
+    }
+
+    /// Returns an aligned region of `len` bytes.
+    fn region(len: usize) -> Vec<u64> {
+        vec![0; len.div_ceil(8)]
+    }
+
+    fn bytes(region: &mut [u64]) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(region.as_mut_ptr().cast(), region.len() * 8) }
+    }
+
+    #[test]
+    fn persist() {
+        let mut memory = region(len(32));
+        let allocator = unsafe { create::<usize>(bytes(&mut memory), None) }.unwrap();
+        let total = allocator.stats().total;
+        let mut value = allocator.allocate_value::<u32>().unwrap();
+        *value = 7;
+        let offset = value.byte_offset();
+        std::mem::forget(value);
+
+        // A later process attaches to e.g. the contents of a file.
+        let mut copy = memory.clone();
+        let allocator = unsafe { attach::<usize>(bytes(&mut copy), None, None) }.unwrap();
+        assert_eq!(allocator.stats().total, total);
+        assert_eq!(allocator.stats().free, total - 1);
+        let value = unsafe { allocator.ptr_at(offset).unwrap().cast::<u32>().read() };
+        assert_eq!(value, 7);
+        drop(allocator.allocate(1).unwrap());
+    }
+
+    #[test]
+    fn persist_errors() {
+        let mut memory = region(len(32));
+        assert_eq!(
+            unsafe { attach::<usize>(bytes(&mut memory), None, None) }.unwrap_err(),
+            AttachError::NotAnArena
+        );
+        assert_eq!(
+            unsafe { create::<usize>(&mut bytes(&mut memory)[..8], None) }.unwrap_err(),
+            AttachError::TooSmall
+        );
+        unsafe { create::<usize>(bytes(&mut memory), None) }.unwrap();
+        assert_eq!(
+            unsafe { attach::<u16>(bytes(&mut memory), None, None) }.unwrap_err(),
+            AttachError::LayoutMismatch
+        );
+        assert_eq!(
+            unsafe { attach::<usize>(&mut bytes(&mut memory)[..len(16)], None, None) }.unwrap_err(),
+            AttachError::TooSmall
+        );
+
+        // A build with different features lays out the allocator differently.
+        let header = memory.as_mut_ptr().cast::<Header>();
+        unsafe { (*header).features ^= 1 << 10 };
+        assert_eq!(
+            unsafe { attach::<usize>(bytes(&mut memory), None, None) }.unwrap_err(),
+            AttachError::LayoutMismatch
+        );
+        unsafe { (*header).features ^= 1 << 10 };
+        unsafe { (*header).allocator_size += 8 };
+        assert_eq!(
+            unsafe { attach::<usize>(bytes(&mut memory), None, None) }.unwrap_err(),
+            AttachError::LayoutMismatch
+        );
+        unsafe { (*header).allocator_size -= 8 };
+
+        let set_version = |memory: &mut [u64], version| unsafe {
+            let header = memory.as_mut_ptr().cast::<Header>();
+            (*header).version = version;
+        };
+        set_version(&mut memory, FORMAT_VERSION + 1);
+        assert_eq!(
+            unsafe { attach::<usize>(bytes(&mut memory), None, None) }.unwrap_err(),
+            AttachError::TooNew(FORMAT_VERSION + 1)
+        );
+        set_version(&mut memory, FORMAT_VERSION - 1);
+        assert_eq!(
+            unsafe { attach::<usize>(bytes(&mut memory), None, None) }.unwrap_err(),
+            AttachError::Unsupported(FORMAT_VERSION - 1)
+        );
+    }
+
+    #[test]
+    fn persist_migrate() {
+        static FROM: std::sync::Mutex<Vec<u32>> = std::sync::Mutex::new(Vec::new());
+
+        let mut memory = region(len(32));
+        unsafe { create::<usize>(bytes(&mut memory), None) }.unwrap();
+        unsafe {
+            (*memory.as_mut_ptr().cast::<Header>()).version = FORMAT_VERSION - 1;
+        }
+        unsafe {
+            attach::<usize>(
+                bytes(&mut memory),
+                None,
+                Some(|from_version, _region| {
+                    FROM.lock().unwrap().push(from_version);
+                    Ok(())
+                }),
+            )
+        }
+        .unwrap();
+        assert_eq!(*FROM.lock().unwrap(), [FORMAT_VERSION - 1]);
+        let header = unsafe { memory.as_ptr().cast::<Header>().read() };
+        assert_eq!(header.version, FORMAT_VERSION);
+    }
+}
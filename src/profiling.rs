@@ -0,0 +1,117 @@
+//! Records the call site of every allocation so live allocations can be grouped by where they
+//! were made, see [`crate::linked_list::Allocator::dump_live_allocations`].
+//!
+//! Call sites are stored in a process local side table keyed by the address of the allocator, so
+//! when an allocator is shared between processes only allocations made by the current process
+//! are recorded.
+
+use std::collections::HashMap;
+use std::panic::Location;
+use std::sync::Mutex;
+
+/// Live allocations made at a call site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallSite {
+    pub location: &'static Location<'static>,
+    /// The number of live allocations.
+    pub allocations: usize,
+    /// The total number of blocks/slots in the live allocations.
+    pub blocks: usize,
+}
+
+type Allocations = HashMap<usize, (&'static Location<'static>, usize)>;
+
+/// Maps allocator addresses to their live allocations, keyed by index.
+static TABLE: Mutex<Option<HashMap<usize, Allocations>>> = Mutex::new(None);
+
+fn with_table<R>(f: impl FnOnce(&mut HashMap<usize, Allocations>) -> R) -> R {
+    let mut table = TABLE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    f(table.get_or_insert_with(HashMap::new))
+}
+
+/// Records an allocation of `size` blocks/slots at `index` within the allocator at `allocator`.
+pub(crate) fn record(
+    allocator: usize,
+    index: usize,
+    size: usize,
+    location: &'static Location<'static>,
+) {
+    with_table(|table| {
+        table
+            .entry(allocator)
+            .or_default()
+            .insert(index, (location, size));
+    });
+}
+
+/// Removes the allocation at `index` within the allocator at `allocator`.
+pub(crate) fn remove(allocator: usize, index: usize) {
+    with_table(|table| {
+        if let Some(allocations) = table.get_mut(&allocator) {
+            allocations.remove(&index);
+        }
+    });
+}
+
+/// Removes all allocations of the allocator at `allocator`, e.g. when it is initialized.
+pub(crate) fn clear(allocator: usize) {
+    with_table(|table| table.remove(&allocator));
+}
+
+/// Groups the live allocations of the allocator at `allocator` by call site, ordered by the
+/// number of blocks/slots descending.
+pub(crate) fn call_sites(allocator: usize) -> Vec<CallSite> {
+    let mut sites = HashMap::<&'static Location<'static>, CallSite>::new();
+    with_table(|table| {
+        for &(location, size) in table.get(&allocator).into_iter().flat_map(HashMap::values) {
+            let site = sites.entry(location).or_insert(CallSite {
+                location,
+                allocations: 0,
+                blocks: 0,
+            });
+            site.allocations += 1;
+            site.blocks += size;
+        }
+    });
+    let mut sites = sites.into_values().collect::<Vec<_>>();
+    sites.sort_by(|a, b| {
+        b.blocks
+            .cmp(&a.blocks)
+            .then_with(|| a.location.file().cmp(b.location.file()))
+            .then_with(|| a.location.line().cmp(&b.location.line()))
+    });
+    sites
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn call_sites_grouped() {
+        // Uses an address no real allocator can have.
+        let allocator = 1;
+        let (a, b) = (Location::caller(), Location::caller());
+        record(allocator, 0, 1, a);
+        record(allocator, 1, 2, a);
+        record(allocator, 3, 4, b);
+        let sites = call_sites(allocator);
+        assert_eq!(sites.len(), 2);
+        assert_eq!(
+            (sites[0].location, sites[0].allocations, sites[0].blocks),
+            (b, 1, 4)
+        );
+        assert_eq!(
+            (sites[1].location, sites[1].allocations, sites[1].blocks),
+            (a, 2, 3)
+        );
+        remove(allocator, 3);
+        assert_eq!(call_sites(allocator).len(), 1);
+        clear(allocator);
+        assert!(call_sites(allocator).is_empty());
+    }
+}
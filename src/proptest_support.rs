@@ -0,0 +1,236 @@
+//! [`proptest`] strategies and an interpreter for state machine tests of allocators.
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn free_list(ops in proptest::collection::vec(any::<AllocatorOp>(), 0..100)) {
+//!         let allocator = LinkedListArrayAllocator::<16>::new(None);
+//!         Interpreter::new(&*allocator).run(ops);
+//!     }
+//! }
+//! ```
+
+use proptest::prelude::*;
+
+use crate::raw::{RawAllocation, RawArrayAllocator};
+
+/// An operation applied to an allocator by an [`Interpreter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocatorOp {
+    /// Allocates a number of blocks/slots.
+    Allocate(usize),
+    /// Frees a live allocation, chosen by index modulo the number of live allocations.
+    Free(usize),
+    /// Moves a live allocation, chosen as for [`AllocatorOp::Free`], to a new allocation of a
+    /// number of blocks/slots.
+    Resize(usize, usize),
+    /// Checks the contents of every live allocation and the allocator statistics.
+    Iterate,
+}
+
+/// The parameters of the [`AllocatorOp`] strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocatorOpParams {
+    /// The largest number of blocks/slots allocated by a single operation.
+    pub max_blocks: usize,
+}
+
+impl Default for AllocatorOpParams {
+    fn default() -> Self {
+        Self { max_blocks: 4 }
+    }
+}
+
+impl Arbitrary for AllocatorOp {
+    type Parameters = AllocatorOpParams;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+        let max_blocks = params.max_blocks.max(1);
+        prop_oneof![
+            (1..=max_blocks).prop_map(AllocatorOp::Allocate),
+            any::<usize>().prop_map(AllocatorOp::Free),
+            (any::<usize>(), 1..=max_blocks).prop_map(|(i, blocks)| AllocatorOp::Resize(i, blocks)),
+            Just(AllocatorOp::Iterate),
+        ]
+        .boxed()
+    }
+}
+
+/// Applies [`AllocatorOp`]s to an allocator, panicking when an invariant is broken.
+///
+/// Every allocation is filled with a tag byte which is checked when it is freed, resized or
+/// iterated, so overlapping allocations are detected. Live allocations are freed on drop.
+#[derive(Debug)]
+pub struct Interpreter<'a, A: RawArrayAllocator + ?Sized> {
+    allocator: &'a A,
+    live: Vec<(RawAllocation, u8)>,
+    tag: u8,
+}
+
+impl<'a, A: RawArrayAllocator + ?Sized> Interpreter<'a, A> {
+    #[must_use]
+    pub fn new(allocator: &'a A) -> Self {
+        Self {
+            allocator,
+            live: Vec::new(),
+            tag: 0,
+        }
+    }
+
+    /// The live allocations.
+    pub fn live(&self) -> impl Iterator<Item = RawAllocation> + '_ {
+        self.live.iter().map(|(allocation, _)| *allocation)
+    }
+
+    /// Applies each of `ops` in turn, checking invariants after each.
+    ///
+    /// # Panics
+    ///
+    /// When an invariant is broken.
+    pub fn run(&mut self, ops: impl IntoIterator<Item = AllocatorOp>) {
+        for op in ops {
+            self.apply(op);
+            self.check();
+        }
+    }
+
+    /// Applies `op`.
+    ///
+    /// # Panics
+    ///
+    /// When an invariant is broken.
+    pub fn apply(&mut self, op: AllocatorOp) {
+        match op {
+            AllocatorOp::Allocate(blocks) => {
+                if let Some(allocation) = self.allocate(blocks) {
+                    let tag = self.next_tag();
+                    unsafe { self.bytes(allocation).fill(tag) };
+                    self.live.push((allocation, tag));
+                }
+            }
+            AllocatorOp::Free(i) => {
+                if self.live.is_empty() {
+                    return;
+                }
+                let (allocation, tag) = self.live.swap_remove(i % self.live.len());
+                self.check_allocation(allocation, tag);
+                unsafe { self.allocator.free(allocation) };
+            }
+            AllocatorOp::Resize(i, blocks) => {
+                if self.live.is_empty() {
+                    return;
+                }
+                let i = i % self.live.len();
+                let (old, tag) = self.live[i];
+                self.check_allocation(old, tag);
+                if let Some(new) = self.allocate(blocks) {
+                    unsafe {
+                        let (old_bytes, new_bytes) = (self.bytes(old), self.bytes(new));
+                        let n = std::cmp::min(old_bytes.len(), new_bytes.len());
+                        new_bytes[..n].copy_from_slice(&old_bytes[..n]);
+                        new_bytes[n..].fill(tag);
+                        self.allocator.free(old);
+                    }
+                    self.live[i] = (new, tag);
+                }
+            }
+            AllocatorOp::Iterate => self.check(),
+        }
+    }
+
+    /// Checks the contents of every live allocation and that the allocator statistics account for
+    /// every block/slot.
+    ///
+    /// # Panics
+    ///
+    /// When an invariant is broken.
+    pub fn check(&self) {
+        for &(allocation, tag) in &self.live {
+            self.check_allocation(allocation, tag);
+        }
+        let stats = self.allocator.stats();
+        let allocated = self.live().map(|allocation| allocation.size).sum::<usize>();
+        assert_eq!(
+            allocated + stats.free,
+            stats.total,
+            "allocated and free blocks do not sum to the total"
+        );
+        assert!(stats.largest_free <= stats.free);
+    }
+
+    fn allocate(&self, blocks: usize) -> Option<RawAllocation> {
+        self.allocator
+            .allocate_bytes(blocks * self.allocator.block_size())
+    }
+
+    fn next_tag(&mut self) -> u8 {
+        self.tag = self.tag.wrapping_add(1);
+        self.tag
+    }
+
+    fn check_allocation(&self, allocation: RawAllocation, tag: u8) {
+        let bytes = unsafe { self.bytes(allocation) };
+        assert!(
+            bytes.iter().all(|&byte| byte == tag),
+            "allocation {allocation:?} was overwritten"
+        );
+    }
+
+    /// # Safety
+    ///
+    /// `allocation` must be live and no other reference to its bytes may exist.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn bytes(&self, allocation: RawAllocation) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(
+            self.allocator.as_ptr(allocation).as_ptr(),
+            allocation.size * self.allocator.block_size(),
+        )
+    }
+}
+
+impl<'a, A: RawArrayAllocator + ?Sized> Drop for Interpreter<'a, A> {
+    fn drop(&mut self) {
+        for (allocation, _) in self.live.drain(..) {
+            unsafe { self.allocator.free(allocation) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::{linked_list, slab};
+
+    proptest! {
+        #[test]
+        fn linked_list_ops(ops in proptest::collection::vec(any::<AllocatorOp>(), 0..100)) {
+            let allocator = linked_list::ArrayAllocator::<16>::new(None);
+            Interpreter::new(&*allocator).run(ops);
+            prop_assert_eq!(allocator.stats().free, 16);
+        }
+
+        #[test]
+        fn slab_ops(ops in proptest::collection::vec(
+            any_with::<AllocatorOp>(AllocatorOpParams { max_blocks: 1 }),
+            0..100,
+        )) {
+            let allocator = slab::ArrayAllocator::<8, u64>::new(None);
+            Interpreter::new(&*allocator).run(ops);
+            prop_assert_eq!(allocator.stats().free, 8);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "was overwritten")]
+    fn interpreter_detects_overwrite() {
+        let allocator = linked_list::ArrayAllocator::<4>::new(None);
+        let mut interpreter = Interpreter::new(&*allocator);
+        interpreter.apply(AllocatorOp::Allocate(1));
+        let allocation = interpreter.live().next().unwrap();
+        unsafe { *allocator.as_ptr(allocation).as_ptr() = 0 };
+        interpreter.apply(AllocatorOp::Iterate);
+    }
+}
@@ -0,0 +1,124 @@
+//! A bounded queue of freed allocations which have not yet rejoined the free list, see
+//! [`crate::linked_list::Allocator::set_quarantine`].
+
+/// The largest number of freed allocations which can be quarantined.
+pub const QUARANTINE_CAPACITY: usize = 16;
+
+/// A ring buffer of the index and size of quarantined allocations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub(crate) struct Quarantine {
+    entries: [(usize, usize); QUARANTINE_CAPACITY],
+    /// The position of the oldest entry.
+    start: usize,
+    len: usize,
+    limit: usize,
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Self {
+            entries: [(0, 0); QUARANTINE_CAPACITY],
+            start: 0,
+            len: 0,
+            limit: 0,
+        }
+    }
+}
+
+impl Quarantine {
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The number of allocations held.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Sets the number of allocations held, entries beyond it should then be removed with
+    /// [`Quarantine::evict`].
+    ///
+    /// # Panics
+    ///
+    /// When `limit > QUARANTINE_CAPACITY`.
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        assert!(
+            limit <= QUARANTINE_CAPACITY,
+            "quarantine limit {limit} exceeds capacity {QUARANTINE_CAPACITY}"
+        );
+        self.limit = limit;
+    }
+
+    /// Adds an allocation, returning the oldest allocation if the quarantine was full or
+    /// `allocation` itself if quarantine is disabled.
+    pub(crate) fn push(&mut self, allocation: (usize, usize)) -> Option<(usize, usize)> {
+        if self.limit == 0 {
+            return Some(allocation);
+        }
+        let evicted = (self.len == self.limit).then(|| self.pop());
+        self.entries[(self.start + self.len) % QUARANTINE_CAPACITY] = allocation;
+        self.len += 1;
+        evicted
+    }
+
+    /// Removes the oldest allocation if more than the limit are held.
+    pub(crate) fn evict(&mut self) -> Option<(usize, usize)> {
+        (self.len > self.limit).then(|| self.pop())
+    }
+
+    /// Returns the size of the quarantined allocation starting at `index`, if any.
+    pub(crate) fn get(&self, index: usize) -> Option<usize> {
+        (0..self.len)
+            .map(|i| self.entries[(self.start + i) % QUARANTINE_CAPACITY])
+            .find_map(|(start, size)| (start == index).then_some(size))
+    }
+
+    fn pop(&mut self) -> (usize, usize) {
+        let allocation = self.entries[self.start];
+        self.start = (self.start + 1) % QUARANTINE_CAPACITY;
+        self.len -= 1;
+        allocation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn quarantine_fifo() {
+        let mut quarantine = Quarantine::default();
+        assert_eq!(quarantine.push((0, 1)), Some((0, 1)));
+        quarantine.set_limit(2);
+        assert_eq!(quarantine.push((1, 1)), None);
+        assert_eq!(quarantine.push((2, 2)), None);
+        assert_eq!(quarantine.push((4, 1)), Some((1, 1)));
+        quarantine.set_limit(1);
+        assert_eq!(quarantine.evict(), Some((2, 2)));
+        assert_eq!(quarantine.evict(), None);
+        quarantine.set_limit(0);
+        assert_eq!(quarantine.evict(), Some((4, 1)));
+        assert_eq!(quarantine.evict(), None);
+    }
+
+    #[test]
+    fn quarantine_wraps() {
+        let mut quarantine = Quarantine::default();
+        quarantine.set_limit(QUARANTINE_CAPACITY);
+        for i in 0..QUARANTINE_CAPACITY {
+            assert_eq!(quarantine.push((i, 1)), None);
+        }
+        for i in QUARANTINE_CAPACITY..3 * QUARANTINE_CAPACITY {
+            assert_eq!(quarantine.push((i, 1)), Some((i - QUARANTINE_CAPACITY, 1)));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds capacity")]
+    fn quarantine_limit() {
+        Quarantine::default().set_limit(QUARANTINE_CAPACITY + 1);
+    }
+}
@@ -0,0 +1,361 @@
+use std::ptr::NonNull;
+
+/// The byte allocations are filled with when the `debug-fill` feature is enabled.
+#[cfg(feature = "debug-fill")]
+pub const ALLOCATED_FILL: u8 = 0xCD;
+
+/// The byte freed allocations are filled with, before they rejoin the free list, when the
+/// `debug-fill` feature is enabled.
+#[cfg(feature = "debug-fill")]
+pub const FREED_FILL: u8 = 0xDD;
+
+/// Fills the bytes of `slice` with `byte`.
+#[cfg(feature = "debug-fill")]
+pub(crate) fn fill<T>(slice: &mut [T], byte: u8) {
+    unsafe { slice.as_mut_ptr().write_bytes(byte, slice.len()) };
+}
+
+/// Zeroes the bytes of `slice` with writes the compiler can't elide.
+#[cfg(feature = "zeroize")]
+pub(crate) fn zeroize<T>(slice: &mut [T]) {
+    use zeroize::Zeroize;
+
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            slice.as_mut_ptr().cast::<u8>(),
+            std::mem::size_of_val(slice),
+        )
+    }
+    .zeroize();
+}
+
+/// A region allocated through [`RawArrayAllocator::allocate_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawAllocation {
+    /// The index of the first block/slot.
+    pub index: usize,
+    /// The number of blocks/slots.
+    pub size: usize,
+}
+
+/// Usage statistics of an allocator, in blocks/slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// The total number of blocks/slots.
+    pub total: usize,
+    /// The number of free blocks/slots.
+    pub free: usize,
+    /// The largest number of contiguous free blocks/slots.
+    pub largest_free: usize,
+    /// The number of runs of contiguous free blocks/slots.
+    pub free_regions: usize,
+    /// The number of live allocations, counted by [`crate::linked_list::Allocator`], otherwise 0.
+    pub allocations: usize,
+    /// The greatest number of blocks/slots used at once since initialization, tracked by
+    /// [`crate::linked_list::Allocator`], otherwise 0.
+    pub peak_used: usize,
+}
+
+/// The priority of an allocation.
+///
+/// Once a reserve is set, e.g. with [`crate::linked_list::Allocator::set_reserve`], normal
+/// allocations fail rather than leave fewer free blocks/slots than the reserve, while critical
+/// allocations may use them, so e.g. control messages still get memory once data fills the
+/// allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl Stats {
+    /// Writes a one line summary, e.g. `3/8 blocks used (72/192 bytes), 2 free regions, largest
+    /// free region 4 blocks`.
+    pub(crate) fn summarize(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        unit: &str,
+        unit_size: usize,
+    ) -> std::fmt::Result {
+        let used = self.total - self.free;
+        write!(
+            f,
+            "{used}/{} {unit} used ({}/{} bytes), {} free regions, largest free region {} {unit}",
+            self.total,
+            used * unit_size,
+            self.total * unit_size,
+            self.free_regions,
+            self.largest_free,
+        )
+    }
+}
+
+/// A failed allocation, passed to an allocator's out of memory hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocRequest {
+    /// The number of blocks/slots requested.
+    pub requested: usize,
+    /// Usage statistics at the time of the failure.
+    pub stats: Stats,
+}
+
+/// A function called when an allocation fails through lack of memory.
+pub type OomHook = fn(&AllocRequest);
+
+/// A watermark crossed by the usage of an allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watermark {
+    /// The number of used blocks/slots rose to the high watermark.
+    High,
+    /// The number of used blocks/slots fell to the low watermark.
+    Low,
+}
+
+/// A watermark crossing, passed to an allocator's watermark hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatermarkEvent {
+    pub watermark: Watermark,
+    /// The number of used blocks/slots after the crossing.
+    pub used: usize,
+    /// The total number of blocks/slots.
+    pub total: usize,
+}
+
+/// A function called when the usage of an allocator crosses a watermark.
+pub type WatermarkHook = fn(&WatermarkEvent);
+
+/// High and low watermarks on the number of used blocks/slots, tracking usage so crossings can be
+/// detected without walking the free list.
+///
+/// Once the high watermark is reached the hook is not called again until usage falls to the low
+/// watermark, and vice versa, so usage hovering around a watermark does not call the hook on
+/// every allocation.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Watermarks {
+    low: usize,
+    high: usize,
+    hook: WatermarkHook,
+    used: usize,
+    total: usize,
+    above: bool,
+}
+
+// Function pointers cannot be meaningfully compared, so the hook is not compared.
+impl PartialEq for Watermarks {
+    fn eq(&self, other: &Self) -> bool {
+        (self.low, self.high, self.used, self.total, self.above)
+            == (other.low, other.high, other.used, other.total, other.above)
+    }
+}
+
+impl Eq for Watermarks {}
+
+impl Watermarks {
+    /// # Panics
+    ///
+    /// When `low >= high`.
+    pub(crate) fn new(low: usize, high: usize, hook: WatermarkHook, stats: Stats) -> Self {
+        assert!(
+            low < high,
+            "low watermark {low} must be below high watermark {high}"
+        );
+        let used = stats.total - stats.free;
+        Self {
+            low,
+            high,
+            hook,
+            used,
+            total: stats.total,
+            above: used >= high,
+        }
+    }
+
+    /// Records the allocation of `n` blocks/slots, returning the hook to call if the high
+    /// watermark was reached.
+    pub(crate) fn allocated(&mut self, n: usize) -> Option<(WatermarkHook, WatermarkEvent)> {
+        self.used += n;
+        (!self.above && self.used >= self.high).then(|| {
+            self.above = true;
+            self.event(Watermark::High)
+        })
+    }
+
+    /// Records the freeing of `n` blocks/slots, returning the hook to call if the low watermark
+    /// was reached.
+    pub(crate) fn freed(&mut self, n: usize) -> Option<(WatermarkHook, WatermarkEvent)> {
+        self.used -= n;
+        (self.above && self.used <= self.low).then(|| {
+            self.above = false;
+            self.event(Watermark::Low)
+        })
+    }
+
+    fn event(&self, watermark: Watermark) -> (WatermarkHook, WatermarkEvent) {
+        (
+            self.hook,
+            WatermarkEvent {
+                watermark,
+                used: self.used,
+                total: self.total,
+            },
+        )
+    }
+}
+
+/// An allocator which manages memory within an array of blocks/slots.
+pub trait RawArrayAllocator {
+    /// The size in bytes of a block/slot.
+    fn block_size(&self) -> usize;
+
+    /// The alignment in bytes guaranteed for allocations.
+    fn block_align(&self) -> usize;
+
+    /// Allocates space for at least `bytes` bytes.
+    ///
+    /// The memory is uninitialized.
+    fn allocate_bytes(&self, bytes: usize) -> Option<RawAllocation>;
+
+    /// Frees an allocation.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must have been returned by [`RawArrayAllocator::allocate_bytes`] on this
+    /// allocator and must not be freed again.
+    unsafe fn free(&self, allocation: RawAllocation);
+
+    /// Returns a pointer to the start of an allocation.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation from this allocator.
+    unsafe fn as_ptr(&self, allocation: RawAllocation) -> NonNull<u8>;
+
+    /// Returns usage statistics.
+    fn stats(&self) -> Stats;
+}
+
+impl<A: RawArrayAllocator + ?Sized> RawArrayAllocator for &A {
+    fn block_size(&self) -> usize {
+        (**self).block_size()
+    }
+
+    fn block_align(&self) -> usize {
+        (**self).block_align()
+    }
+
+    fn allocate_bytes(&self, bytes: usize) -> Option<RawAllocation> {
+        (**self).allocate_bytes(bytes)
+    }
+
+    unsafe fn free(&self, allocation: RawAllocation) {
+        (**self).free(allocation);
+    }
+
+    unsafe fn as_ptr(&self, allocation: RawAllocation) -> NonNull<u8> {
+        (**self).as_ptr(allocation)
+    }
+
+    fn stats(&self) -> Stats {
+        (**self).stats()
+    }
+}
+
+/// Renders a map of `total` blocks/slots with `#` for used and `.` for free, given the free
+/// regions as `(index, size)`.
+///
+/// Each line covers `width` blocks/slots and is prefixed with the index of its first.
+///
+/// # Panics
+///
+/// When `width == 0`.
+#[cfg(feature = "std")]
+pub(crate) fn render_map(
+    total: usize,
+    free: impl IntoIterator<Item = (usize, usize)>,
+    width: usize,
+) -> String {
+    use std::fmt::Write;
+
+    assert_ne!(width, 0, "width must be non-zero");
+
+    let mut map = vec![b'#'; total];
+    for (index, size) in free {
+        map[index..index + size].fill(b'.');
+    }
+
+    let digits = total.saturating_sub(1).to_string().len();
+    let mut out = String::new();
+    for (i, line) in map.chunks(width).enumerate() {
+        let line = std::str::from_utf8(line).unwrap();
+        writeln!(out, "{:>digits$} [{line}]", i * width).unwrap();
+    }
+    out
+}
+
+/// Renders the runs of used and free blocks/slots of `total` as a row of boxes labelled with
+/// their number, given the free regions as `(index, size)` ordered by index.
+///
+/// Adjacent free regions, e.g. free slab slots, are merged into one run.
+#[cfg(feature = "std")]
+pub(crate) fn render_regions(
+    total: usize,
+    free: impl IntoIterator<Item = (usize, usize)>,
+) -> String {
+    let mut runs: Vec<(bool, usize)> = Vec::new();
+    let mut push = |is_free: bool, len: usize| match runs.last_mut() {
+        _ if len == 0 => {}
+        Some((last_free, last_len)) if *last_free == is_free => *last_len += len,
+        _ => runs.push((is_free, len)),
+    };
+    let mut end = 0;
+    for (index, size) in free {
+        push(false, index - end);
+        push(true, size);
+        end = index + size;
+    }
+    push(false, total - end);
+
+    let labels = runs
+        .into_iter()
+        .map(|(is_free, len)| format!("{}:{len}", if is_free { "free" } else { "used" }))
+        .collect::<Vec<_>>();
+    let border = |left: char, middle: char, right: char| {
+        let lines = labels
+            .iter()
+            .map(|label| "─".repeat(label.len()))
+            .collect::<Vec<_>>();
+        format!("{left}{}{right}\n", lines.join(&middle.to_string()))
+    };
+    format!(
+        "{}│{}│\n{}",
+        border('┌', '┬', '┐'),
+        labels.join("│"),
+        border('└', '┴', '┘')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn render_map_lines() {
+        assert_eq!(
+            render_map(12, [(4, 4), (10, 2)], 8),
+            " 0 [####....]\n 8 [##..]\n"
+        );
+        assert_eq!(render_map(0, [], 8), "");
+    }
+
+    #[test]
+    fn render_regions_runs() {
+        assert_eq!(
+            render_regions(8, [(2, 1), (3, 1), (7, 1)]),
+            "┌──────┬──────┬──────┬──────┐\n│used:2│free:2│used:3│free:1│\n└──────┴──────┴──────┴──────┘\n"
+        );
+        assert_eq!(render_regions(0, []), "┌┐\n││\n└┘\n");
+    }
+}
@@ -0,0 +1,163 @@
+//! A registry of [`crate::slab::Allocator`]s, one per type, carved out of a single
+//! [`crate::linked_list::Allocator`], so programs with many object types needn't lay out an array
+//! per type and track their offsets by hand.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::mem::{align_of, size_of};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Block, Wrapper};
+use crate::slab::Allocator;
+use crate::Index;
+
+/// The slab of each type and the blocks holding it.
+type Slabs<'a, I> = HashMap<TypeId, (NonNull<u8>, Wrapper<'a, I>)>;
+
+/// Hands out the [`crate::slab::Allocator`] of each type, initializing it within the blocks of a
+/// [`crate::linked_list::Allocator`] when the type is first registered.
+///
+/// Slabs live until the registry is dropped, which frees their blocks.
+pub struct SlabRegistry<'a, I: Index = usize> {
+    allocator: &'a crate::linked_list::Allocator<I>,
+    slabs: Mutex<Slabs<'a, I>>,
+}
+
+// Only slabs of `Send` values are registered, which may be shared between threads.
+unsafe impl<'a, I: Index> Send for SlabRegistry<'a, I> {}
+unsafe impl<'a, I: Index> Sync for SlabRegistry<'a, I> {}
+
+impl<'a, I: Index> SlabRegistry<'a, I> {
+    /// Constructs an empty registry carving slabs out of `allocator`.
+    #[must_use]
+    pub fn new(allocator: &'a crate::linked_list::Allocator<I>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("SlabRegistry::new");
+
+        Self {
+            allocator,
+            slabs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the slab of `T`, creating it with `slots` slots if it doesn't exist.
+    ///
+    /// `slots` is ignored when the slab exists, so every caller gets the slab registered first.
+    /// Returns `None` when the backing allocator has no free region large enough for the slab.
+    ///
+    /// # Panics
+    ///
+    /// When locking the backing allocator's mutex or initializing the slab's mutex fails.
+    pub fn register<T: Send + 'static>(
+        &self,
+        slots: usize,
+        attr: Option<crate::MutexAttr>,
+    ) -> Option<&Allocator<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("SlabRegistry::register");
+
+        let mut slabs = self.slabs();
+        let ptr = if let Some((ptr, _)) = slabs.get(&TypeId::of::<T>()) {
+            *ptr
+        } else {
+            // The slots are padded from the end of the slab to their alignment.
+            let bytes = size_of::<Allocator<T, I>>()
+                + align_of::<crate::slab::Block<T, I>>()
+                + slots * size_of::<crate::slab::Block<T, I>>();
+            let mut wrapper = self.allocator.allocate_aligned(
+                bytes.div_ceil(size_of::<Block<I>>()),
+                align_of::<Allocator<T, I>>(),
+            )?;
+            let ptr = NonNull::new(wrapper.as_mut_ptr().cast::<u8>()).unwrap();
+            unsafe {
+                Allocator::<T, I>::init(ptr.as_ptr().cast(), attr, slots);
+            }
+            slabs.insert(TypeId::of::<T>(), (ptr, wrapper));
+            ptr
+        };
+        // Slabs are never moved or freed while the registry lives.
+        Some(unsafe { ptr.cast().as_ref() })
+    }
+
+    /// Returns the slab of `T`, or `None` if it hasn't been registered.
+    #[must_use]
+    pub fn get<T: Send + 'static>(&self) -> Option<&Allocator<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("SlabRegistry::get");
+
+        let ptr = self.slabs().get(&TypeId::of::<T>())?.0;
+        Some(unsafe { ptr.cast().as_ref() })
+    }
+
+    /// Returns the number of registered slabs.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("SlabRegistry::len");
+
+        self.slabs().len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("SlabRegistry::is_empty");
+
+        self.slabs().is_empty()
+    }
+
+    fn slabs(&self) -> std::sync::MutexGuard<'_, Slabs<'a, I>> {
+        self.slabs
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl<'a, I: Index> std::fmt::Debug for SlabRegistry<'a, I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlabRegistry")
+            .field("slabs", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn slab_registry() {
+        let memory = ArrayAllocator::<256>::new(None);
+        let registry = SlabRegistry::new(&memory);
+        assert!(registry.get::<u64>().is_none());
+
+        let numbers = registry.register::<u64>(4, None).unwrap();
+        let names = registry.register::<String>(2, None).unwrap();
+        assert_eq!(registry.len(), 2);
+        assert!(std::ptr::eq(registry.get::<u64>().unwrap(), numbers));
+        // The slab registered first is kept.
+        assert_eq!(
+            registry.register::<u64>(100, None).unwrap().stats().total,
+            4
+        );
+
+        let a = numbers.allocate(1).unwrap();
+        let b = names.allocate(String::from("b")).unwrap();
+        let c = names.allocate(String::from("c")).unwrap();
+        assert!(names.allocate(String::from("d")).is_none());
+        assert_eq!((*a, b.as_str(), c.as_str()), (1, "b", "c"));
+        assert!(registry.register::<[u8; 8192]>(1, None).is_none());
+        drop((a, b, c));
+
+        assert!(memory.stats().free < 256);
+        drop(registry);
+        assert_eq!(memory.stats().free, 256);
+    }
+}
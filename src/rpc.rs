@@ -0,0 +1,511 @@
+//! A request/response layer over a [`crate::slab::Allocator`], see [`rpc`].
+//!
+//! Each request is allocated in a slot of the slab and its index sent to the server over a
+//! [`crate::channel`]. The server writes the response into the same slot and marks the call
+//! complete, waking the caller waiting on the state of the call with `futex` on Linux.
+//!
+//! The queue is held in the allocator, so a client or server given up with [`Client::into_raw`]
+//! or [`Server::into_raw`] can be reconstructed by another process sharing the slab and the
+//! allocator.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::channel::{self, RecvError, TryRecvError};
+use crate::futex;
+use crate::linked_list::Allocator;
+use crate::slab::{self, Wrapper};
+use crate::Index;
+
+/// The request has not been taken by the server.
+const PENDING: u32 = 0;
+/// The server took the request and is producing the response.
+const SERVING: u32 = 1;
+/// The response is written.
+const COMPLETE: u32 = 2;
+/// The server was dropped before taking the request, which was dropped, or panicked while
+/// producing the response.
+const CANCELLED: u32 = 3;
+/// The caller took the response.
+const TAKEN: u32 = 4;
+
+#[repr(C)]
+union Payload<Req, Resp> {
+    request: ManuallyDrop<Req>,
+    response: ManuallyDrop<Resp>,
+}
+
+/// A slot of an RPC slab, holding the request and then the response of a call.
+#[repr(C)]
+pub struct Call<Req, Resp> {
+    state: AtomicU32,
+    payload: UnsafeCell<Payload<Req, Resp>>,
+}
+
+impl<Req, Resp> Drop for Call<Req, Resp> {
+    fn drop(&mut self) {
+        let payload = self.payload.get_mut();
+        match *self.state.get_mut() {
+            PENDING => unsafe { ManuallyDrop::drop(&mut payload.request) },
+            COMPLETE => unsafe { ManuallyDrop::drop(&mut payload.response) },
+            _ => {}
+        }
+    }
+}
+
+impl<Req, Resp> fmt::Debug for Call<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Call")
+            .field("state", &self.state.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// The slab RPC calls are allocated in.
+pub type CallAllocator<Req, Resp, I = usize> = slab::Allocator<Call<Req, Resp>, I>;
+
+/// The client and server returned by [`rpc`].
+pub type Endpoints<'a, Req, Resp, I = usize> = (Client<'a, Req, Resp, I>, Server<'a, Req, Resp, I>);
+
+/// Constructs a client and server of calls allocated within `slab`, queuing up to `capacity`
+/// calls in a buffer allocated within `allocator`.
+///
+/// Returns `None` when `capacity == 0` or when `allocator` has no free region large enough.
+///
+/// # Panics
+///
+/// When locking the mutex fails.
+pub fn rpc<'a, Req, Resp, I: Index>(
+    slab: &'a CallAllocator<Req, Resp, I>,
+    allocator: &'a Allocator<I>,
+    capacity: usize,
+) -> Option<Endpoints<'a, Req, Resp, I>> {
+    #[cfg(feature = "log")]
+    trace!("rpc");
+
+    let (sender, receiver) = channel::channel(allocator, capacity)?;
+    Some((
+        Client {
+            slab,
+            queue: sender,
+        },
+        Server {
+            slab,
+            queue: receiver,
+        },
+    ))
+}
+
+/// The error returned by [`Client::call`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallError {
+    /// The slab has no free slots.
+    OutOfMemory,
+    /// The server was dropped.
+    Disconnected,
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfMemory => write!(f, "no free slot for the request"),
+            Self::Disconnected => write!(f, "server was dropped"),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+/// The error returned by [`Pending::wait`] when the server was dropped before taking the request
+/// or panicked while serving it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "server was dropped or panicked before serving the request"
+        )
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// The calling half of an [`rpc`], which can be cloned to call from several threads.
+pub struct Client<'a, Req, Resp, I: Index = usize> {
+    slab: &'a CallAllocator<Req, Resp, I>,
+    queue: channel::Sender<'a, usize, I>,
+}
+
+impl<'a, Req, Resp, I: Index> Client<'a, Req, Resp, I> {
+    /// Allocates `request` and queues it for the server, waiting while the queue is full.
+    ///
+    /// # Errors
+    ///
+    /// When the slab has no free slots or the server was dropped.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn call(&self, request: Req) -> Result<Pending<'a, Req, Resp, I>, CallError> {
+        #[cfg(feature = "log")]
+        trace!("Client::call");
+
+        let wrapper = self
+            .slab
+            .allocate(Call {
+                state: AtomicU32::new(PENDING),
+                payload: UnsafeCell::new(Payload {
+                    request: ManuallyDrop::new(request),
+                }),
+            })
+            .ok_or(CallError::OutOfMemory)?;
+        self.queue
+            .send(wrapper.index())
+            .map_err(|_| CallError::Disconnected)?;
+        Ok(Pending { wrapper })
+    }
+
+    /// Gives up the client without dropping it, returning the index and number of the blocks of
+    /// its queue, e.g. so another process can reconstruct it with [`Client::from_raw`].
+    #[must_use]
+    pub fn into_raw(self) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("Client::into_raw");
+
+        self.queue.into_raw()
+    }
+
+    /// Reconstructs a client given up with [`Client::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `index` and `size` must have been returned by [`Client::into_raw`] for an RPC of calls
+    /// allocated within `slab` and queued within `allocator`, and the client must not have been
+    /// reconstructed already.
+    pub unsafe fn from_raw(
+        slab: &'a CallAllocator<Req, Resp, I>,
+        allocator: &'a Allocator<I>,
+        index: usize,
+        size: usize,
+    ) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Client::from_raw");
+
+        Self {
+            slab,
+            queue: channel::Sender::from_raw(allocator, index, size),
+        }
+    }
+}
+
+impl<'a, Req, Resp, I: Index> Clone for Client<'a, Req, Resp, I> {
+    fn clone(&self) -> Self {
+        Self {
+            slab: self.slab,
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<'a, Req, Resp, I: Index> fmt::Debug for Client<'a, Req, Resp, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("queue", &self.queue)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A call waiting on its response, see [`Client::call`].
+///
+/// Dropping a pending call waits for it to be served or cancelled, as until then the server may
+/// write to its slot.
+#[derive(Debug)]
+#[must_use = "dropping a pending call waits for its response"]
+pub struct Pending<'a, Req, Resp, I: Index = usize> {
+    wrapper: Wrapper<'a, Call<Req, Resp>, I>,
+}
+
+impl<'a, Req, Resp, I: Index> Pending<'a, Req, Resp, I> {
+    /// Returns whether the response is written or the call was cancelled, in which case
+    /// [`Pending::wait`] returns without waiting.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Pending::is_done");
+
+        self.wrapper.state.load(Ordering::Acquire) >= COMPLETE
+    }
+
+    /// Waits for the response.
+    ///
+    /// # Errors
+    ///
+    /// When the server was dropped before taking the request or panicked while serving it.
+    pub fn wait(self) -> Result<Resp, Cancelled> {
+        #[cfg(feature = "log")]
+        trace!("Pending::wait");
+
+        let call = &*self.wrapper;
+        loop {
+            match call.state.load(Ordering::Acquire) {
+                COMPLETE => {
+                    let response =
+                        unsafe { ManuallyDrop::take(&mut (*call.payload.get()).response) };
+                    call.state.store(TAKEN, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                CANCELLED => return Err(Cancelled),
+                state => futex::wait(&call.state, state),
+            }
+        }
+    }
+}
+
+impl<'a, Req, Resp, I: Index> Drop for Pending<'a, Req, Resp, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Pending::drop");
+
+        loop {
+            let state = self.wrapper.state.load(Ordering::Acquire);
+            if state >= COMPLETE {
+                break;
+            }
+            futex::wait(&self.wrapper.state, state);
+        }
+    }
+}
+
+/// The serving half of an [`rpc`].
+///
+/// Dropping the server cancels the calls queued for it.
+pub struct Server<'a, Req, Resp, I: Index = usize> {
+    slab: &'a CallAllocator<Req, Resp, I>,
+    queue: channel::Receiver<'a, usize, I>,
+}
+
+impl<'a, Req, Resp, I: Index> Server<'a, Req, Resp, I> {
+    /// Serves the next call with `f`, waiting while none are queued.
+    ///
+    /// # Errors
+    ///
+    /// When every client was dropped and no calls are queued.
+    pub fn serve(&self, f: impl FnOnce(Req) -> Resp) -> Result<(), RecvError> {
+        #[cfg(feature = "log")]
+        trace!("Server::serve");
+
+        let index = self.queue.recv()?;
+        unsafe { self.complete(index, f) };
+        Ok(())
+    }
+
+    /// Serves the next call with `f` without waiting.
+    ///
+    /// # Errors
+    ///
+    /// When no calls are queued.
+    pub fn try_serve(&self, f: impl FnOnce(Req) -> Resp) -> Result<(), TryRecvError> {
+        #[cfg(feature = "log")]
+        trace!("Server::try_serve");
+
+        let index = self.queue.try_recv()?;
+        unsafe { self.complete(index, f) };
+        Ok(())
+    }
+
+    /// Gives up the server without dropping it, see [`Client::into_raw`].
+    #[must_use]
+    pub fn into_raw(self) -> (usize, usize) {
+        #[cfg(feature = "log")]
+        trace!("Server::into_raw");
+
+        let this = ManuallyDrop::new(self);
+        unsafe { std::ptr::read(&this.queue) }.into_raw()
+    }
+
+    /// Reconstructs a server given up with [`Server::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `index` and `size` must have been returned by [`Server::into_raw`] for an RPC of calls
+    /// allocated within `slab` and queued within `allocator`, and the server must not have been
+    /// reconstructed already.
+    pub unsafe fn from_raw(
+        slab: &'a CallAllocator<Req, Resp, I>,
+        allocator: &'a Allocator<I>,
+        index: usize,
+        size: usize,
+    ) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Server::from_raw");
+
+        Self {
+            slab,
+            queue: channel::Receiver::from_raw(allocator, index, size),
+        }
+    }
+
+    /// Returns the call in the slot at `index`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold a call dequeued from the queue.
+    unsafe fn call(&self, index: usize) -> &Call<Req, Resp> {
+        let wrapper = ManuallyDrop::new(Wrapper::from_index(self.slab, index));
+        &*std::ptr::addr_of!(**wrapper)
+    }
+
+    /// Produces the response of the call in the slot at `index` with `f`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold a call dequeued from the queue.
+    unsafe fn complete(&self, index: usize, f: impl FnOnce(Req) -> Resp) {
+        let call = self.call(index);
+        let request = ManuallyDrop::take(&mut (*call.payload.get()).request);
+        call.state.store(SERVING, Ordering::Relaxed);
+        // Cancels the call if `f` panics, so the caller doesn't wait forever.
+        let guard = CancelOnDrop(call);
+        let response = f(request);
+        std::mem::forget(guard);
+        (*call.payload.get()).response = ManuallyDrop::new(response);
+        call.state.store(COMPLETE, Ordering::Release);
+        futex::wake(&call.state, u32::MAX);
+    }
+}
+
+/// Cancels a call taken by the server when dropped.
+struct CancelOnDrop<'c, Req, Resp>(&'c Call<Req, Resp>);
+
+impl<'c, Req, Resp> Drop for CancelOnDrop<'c, Req, Resp> {
+    fn drop(&mut self) {
+        self.0.state.store(CANCELLED, Ordering::Release);
+        futex::wake(&self.0.state, u32::MAX);
+    }
+}
+
+impl<'a, Req, Resp, I: Index> Drop for Server<'a, Req, Resp, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Server::drop");
+
+        // Disconnecting first stops further calls being queued, so the drain cancels every call.
+        self.queue.close();
+        while let Ok(index) = self.queue.try_recv() {
+            unsafe {
+                let call = self.call(index);
+                ManuallyDrop::drop(&mut (*call.payload.get()).request);
+                call.state.store(CANCELLED, Ordering::Release);
+                futex::wake(&call.state, u32::MAX);
+            }
+        }
+    }
+}
+
+impl<'a, Req, Resp, I: Index> fmt::Debug for Server<'a, Req, Resp, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("queue", &self.queue)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn rpc_call() {
+        let slab = slab::ArrayAllocator::<4, Call<u32, String>>::new(None);
+        let memory = ArrayAllocator::<8>::new(None);
+        let (client, server) = rpc(&slab, &memory, 4).unwrap();
+        let responses = std::thread::scope(|s| {
+            s.spawn(move || while server.serve(|x| x.to_string()).is_ok() {});
+            let responses = (0..10)
+                .map(|i| client.call(i).unwrap().wait().unwrap())
+                .collect::<Vec<_>>();
+            // Stops the server.
+            drop(client);
+            responses
+        });
+        assert_eq!(
+            responses,
+            (0..10).map(|i| i.to_string()).collect::<Vec<_>>()
+        );
+        assert_eq!(slab.stats().free, 4);
+    }
+
+    #[test]
+    fn rpc_raw() {
+        let slab = slab::ArrayAllocator::<2, Call<u32, u32>>::new(None);
+        let memory = ArrayAllocator::<8>::new(None);
+        let (client, server) = rpc(&slab, &memory, 2).unwrap();
+        let (index, size) = server.into_raw();
+        let (slab_ref, memory_ref) = (&*slab, &*memory);
+        std::thread::scope(|s| {
+            // E.g. another process sharing the slab and the allocator.
+            s.spawn(move || {
+                let server = unsafe { Server::from_raw(slab_ref, memory_ref, index, size) };
+                while server.serve(|x| x * 2).is_ok() {}
+            });
+            assert_eq!(client.call(3).unwrap().wait(), Ok(6));
+            let (index, size) = client.into_raw();
+            let client = unsafe { Client::<u32, u32>::from_raw(&slab, &memory, index, size) };
+            assert_eq!(client.call(4).unwrap().wait(), Ok(8));
+        });
+        assert_eq!(memory.stats().free, 8);
+    }
+
+    #[test]
+    fn rpc_try_serve() {
+        let slab = slab::ArrayAllocator::<1, Call<u8, u8>>::new(None);
+        let memory = ArrayAllocator::<4>::new(None);
+        let (client, server) = rpc(&slab, &memory, 2).unwrap();
+        assert_eq!(server.try_serve(|x| x), Err(TryRecvError::Empty));
+        let pending = client.call(1).unwrap();
+        assert!(!pending.is_done());
+        assert_eq!(client.call(2).err(), Some(CallError::OutOfMemory));
+        server.try_serve(|x| x + 1).unwrap();
+        assert!(pending.is_done());
+        assert_eq!(pending.wait(), Ok(2));
+    }
+
+    #[test]
+    fn rpc_cancelled() {
+        let slab = slab::ArrayAllocator::<2, Call<std::rc::Rc<()>, ()>>::new(None);
+        let memory = ArrayAllocator::<4>::new(None);
+        let rc = std::rc::Rc::new(());
+        let (client, server) = rpc(&slab, &memory, 2).unwrap();
+        let pending = client.call(rc.clone()).unwrap();
+        drop(server);
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+        assert_eq!(pending.wait(), Err(Cancelled));
+        assert_eq!(client.call(rc.clone()).err(), Some(CallError::Disconnected));
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+        assert_eq!(slab.stats().free, 2);
+    }
+
+    #[test]
+    fn rpc_panic() {
+        let slab = slab::ArrayAllocator::<2, Call<u32, u32>>::new(None);
+        let memory = ArrayAllocator::<4>::new(None);
+        let (client, server) = rpc(&slab, &memory, 2).unwrap();
+        let pending = client.call(1).unwrap();
+        let served = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            server.try_serve(|_| panic!("serving"))
+        }));
+        assert!(served.is_err());
+        assert_eq!(pending.wait(), Err(Cancelled));
+        assert_eq!(slab.stats().free, 2);
+    }
+}
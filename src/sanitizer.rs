@@ -0,0 +1,86 @@
+//! Marks free memory as inaccessible to the address sanitizer and Valgrind, see
+//! [`crate::linked_list::Allocator::set_poisoning`].
+//!
+//! The address sanitizer is used when the crate is built with `-Zsanitizer=address`. Valgrind
+//! client requests are issued on `x86_64` Linux and do nothing when not running under Valgrind.
+
+use std::mem::size_of_val;
+
+#[cfg(sanitize = "address")]
+extern "C" {
+    fn __asan_poison_memory_region(addr: *const u8, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const u8, size: usize);
+}
+
+/// `VG_USERREQ__MAKE_MEM_NOACCESS` from `memcheck.h`.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+const MAKE_MEM_NOACCESS: usize = 0x4d43_0000;
+/// `VG_USERREQ__MAKE_MEM_UNDEFINED` from `memcheck.h`.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+const MAKE_MEM_UNDEFINED: usize = 0x4d43_0001;
+
+/// Issues a Valgrind client request, see `VALGRIND_DO_CLIENT_REQUEST_EXPR` in `valgrind.h`.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn client_request(request: usize, addr: *const u8, len: usize) {
+    let args = [request, addr as usize, len, 0, 0, 0];
+    // The rotations of `rdi` sum to 128 bits so leave it unchanged, natively this sequence does
+    // nothing while Valgrind recognizes it and handles the request.
+    unsafe {
+        std::arch::asm!(
+            "rol rdi, 3",
+            "rol rdi, 13",
+            "rol rdi, 61",
+            "rol rdi, 51",
+            "xchg rbx, rbx",
+            in("rax") args.as_ptr(),
+            inout("rdx") 0usize => _,
+            out("rdi") _,
+            options(nostack),
+        );
+    }
+}
+
+#[allow(unused_variables)]
+fn set(addr: *const u8, len: usize, poisoned: bool) {
+    if len == 0 {
+        return;
+    }
+    #[cfg(sanitize = "address")]
+    unsafe {
+        if poisoned {
+            __asan_poison_memory_region(addr, len);
+        } else {
+            __asan_unpoison_memory_region(addr, len);
+        }
+    }
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    client_request(
+        if poisoned {
+            MAKE_MEM_NOACCESS
+        } else {
+            MAKE_MEM_UNDEFINED
+        },
+        addr,
+        len,
+    );
+}
+
+/// Marks `slice` as inaccessible.
+pub(crate) fn poison<T>(slice: &[T]) {
+    set(slice.as_ptr().cast(), size_of_val(slice), true);
+}
+
+/// Marks `slice` as accessible, its contents are considered uninitialized.
+pub(crate) fn unpoison<T>(slice: &[T]) {
+    set(slice.as_ptr().cast(), size_of_val(slice), false);
+}
+
+/// Marks all but the first `keep` bytes of `value` as inaccessible.
+pub(crate) fn poison_tail<T>(value: &T, keep: usize) {
+    let len = size_of_val(value).saturating_sub(keep);
+    set(
+        (value as *const T).cast::<u8>().wrapping_add(keep),
+        len,
+        true,
+    );
+}
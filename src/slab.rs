@@ -1,3 +1,4 @@
+use std::fmt;
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::ops::{Deref, DerefMut, Drop};
@@ -5,42 +6,78 @@ use std::ops::{Deref, DerefMut, Drop};
 #[cfg(feature = "log")]
 use log::trace;
 
+use crate::error::{none_on_oom, AllocError};
+use crate::raw::{
+    AllocRequest, OomHook, Priority, RawAllocation, RawArrayAllocator, Stats, WatermarkHook,
+    Watermarks,
+};
+use crate::Index;
+
 #[derive(Debug)]
 #[repr(C)]
-pub struct ArrayAllocator<const N: usize, T> {
-    allocator: Allocator<T>,
-    data: [Block<T>; N],
+pub struct ArrayAllocator<const N: usize, T, I: Index = usize> {
+    allocator: Allocator<T, I>,
+    data: [Block<T, I>; N],
 }
-impl<const N: usize, T> ArrayAllocator<N, T> {
-    pub fn allocator(&self) -> &Allocator<T> {
+impl<const N: usize, T, I: Index> ArrayAllocator<N, T, I> {
+    pub fn allocator(&self) -> &Allocator<T, I> {
         &self.allocator
     }
 
-    pub fn data(&self) -> &[Block<T>; N] {
+    /// The raw slots, whose contents can't be read safely, see [`Allocator::slot_state`] to
+    /// inspect them.
+    pub fn data(&self) -> &[Block<T, I>; N] {
         &self.data
     }
 
+    /// # Panics
+    ///
+    /// When failing to initialize the mutex, see [`ArrayAllocator::try_new`].
     #[must_use]
-    pub fn new(attr: Option<nix::sys::pthread::MutexAttr>) -> Self {
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
         #[cfg(feature = "log")]
         trace!("ArrayAllocator::new");
 
-        let mut this: Self = unsafe { std::mem::zeroed() };
+        Self::try_new(attr).unwrap()
+    }
+
+    /// # Errors
+    ///
+    /// When failing to initialize the mutex, e.g. when the process lacks permission for `attr`.
+    // Results with an infallible error aren't `must_use`.
+    #[cfg_attr(miri, must_use)]
+    pub fn try_new(attr: Option<crate::MutexAttr>) -> Result<Self, crate::mutex::Error> {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::try_new");
+
+        // Zeroing `Self` would zero the lock, which is not a valid value for every backend. The
+        // data is fully written by `Allocator::init`.
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
         unsafe {
-            Allocator::init(&mut this.allocator, attr, N);
+            Allocator::try_init(
+                std::ptr::addr_of_mut!((*this.as_mut_ptr()).allocator),
+                attr,
+                N,
+            )?;
+            Ok(this.assume_init())
         }
-        this
     }
 }
 
-impl<const N: usize, T> Deref for ArrayAllocator<N, T> {
-    type Target = Allocator<T>;
+impl<const N: usize, T, I: Index> fmt::Display for ArrayAllocator<N, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.allocator.fmt(f)
+    }
+}
+
+impl<const N: usize, T, I: Index> Deref for ArrayAllocator<N, T, I> {
+    type Target = Allocator<T, I>;
 
     fn deref(&self) -> &Self::Target {
         &self.allocator
     }
 }
-impl<const N: usize, T> DerefMut for ArrayAllocator<N, T> {
+impl<const N: usize, T, I: Index> DerefMut for ArrayAllocator<N, T, I> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.allocator
     }
@@ -48,9 +85,9 @@ impl<const N: usize, T> DerefMut for ArrayAllocator<N, T> {
 
 #[derive(Debug)]
 #[repr(C)]
-pub struct Allocator<T>(crate::mutex::Mutex<InnerAllocator<T>>);
+pub struct Allocator<T, I = usize>(crate::mutex::Mutex<InnerAllocator<T, I>>);
 
-impl<T> Allocator<T> {
+impl<T, I: Index> Allocator<T, I> {
     /// Initializes `Self` at `ptr`.
     ///
     /// # Safety
@@ -59,18 +96,48 @@ impl<T> Allocator<T> {
     ///
     /// # Panics
     ///
-    /// When failing to initialize the inner mutex.
-
-    pub unsafe fn init(ptr: *mut Self, attr: Option<nix::sys::pthread::MutexAttr>, size: usize) {
+    /// When failing to initialize the inner mutex or when `size` is greater than [`Index::MAX`].
+    pub unsafe fn init(ptr: *mut Self, attr: Option<crate::MutexAttr>, size: usize) {
         #[cfg(feature = "log")]
         trace!("Allocator::init");
 
-        (*ptr).0.lock = nix::sys::pthread::Mutex::new(attr).unwrap();
+        Self::try_init(ptr, attr, size).unwrap();
+    }
+
+    /// Initializes `Self` at `ptr`, see [`Allocator::init`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Errors
+    ///
+    /// When failing to initialize the inner mutex, e.g. with `EPERM` or `ENOMEM`, in which case
+    /// `ptr` is left uninitialized.
+    ///
+    /// # Panics
+    ///
+    /// When `size` is greater than [`Index::MAX`].
+    pub unsafe fn try_init(
+        ptr: *mut Self,
+        attr: Option<crate::MutexAttr>,
+        size: usize,
+    ) -> Result<(), crate::mutex::Error> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_init");
+
+        std::ptr::addr_of_mut!((*ptr).0.lock).write(crate::mutex::RawMutex::new(attr)?);
+        #[cfg(feature = "watchdog")]
+        std::ptr::addr_of_mut!((*ptr).0.owner).write(crate::watchdog::Owner::new());
 
         #[cfg(feature = "log")]
         trace!("Allocator::init 2");
 
-        <InnerAllocator<T>>::init((*ptr).0.get(), size);
+        <InnerAllocator<T, I>>::init((*ptr).0.get(), size);
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::clear(ptr as usize);
+        Ok(())
     }
 
     /// Allocates a given `x`.
@@ -78,326 +145,1922 @@ impl<T> Allocator<T> {
     /// # Panics
     ///
     /// When locking the mutex fails.
-    pub fn allocate(&self, x: T) -> Option<Wrapper<T>> {
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate(&self, x: T) -> Option<Wrapper<T, I>> {
         #[cfg(feature = "log")]
         trace!("Allocator::allocate");
-        let mut inner_allocator = self.0.lock().unwrap();
-        if let Some(head) = inner_allocator.head {
-            let index = head;
-            unsafe {
-                inner_allocator.head = inner_allocator.data().as_ref()[index].empty;
-                inner_allocator.data().as_mut()[index] = Block {
-                    full: ManuallyDrop::new(x),
-                };
-            }
-            Some(Wrapper {
-                allocator: self,
-                index,
-            })
-        } else {
-            None
-        }
+
+        none_on_oom(self.try_allocate(x))
     }
 
-    /// Returns wrappers for all non-free spaces.
+    /// Allocates a given `x`.
     ///
-    /// The intended usage is for one process `std::mem::forget`s all its wrappers then another
-    /// picks them up with this iterator.
+    /// # Errors
+    ///
+    /// When there are no free slots or when locking the mutex fails, in which case `x` is dropped.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn try_allocate(&self, x: T) -> Result<Wrapper<T, I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate");
+
+        let index = self.claim(true, Priority::Normal)?;
+        Ok(unsafe { self.fill(index, x) })
+    }
+
+    /// Allocates a given `x` with a given priority, see [`Allocator::set_reserve`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_with_priority(&self, x: T, priority: Priority) -> Option<Wrapper<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_with_priority");
+
+        let index = none_on_oom(self.claim(true, priority))?;
+        Some(unsafe { self.fill(index, x) })
+    }
+
+    /// Moves `x` into the slot at `index`.
     ///
     /// # Safety
     ///
-    /// Since this returns wrappers for all non-free spaces, dropping these may invalidate other
-    /// presently held wrappers.
+    /// The slot must have been claimed and not yet filled.
+    unsafe fn fill(&self, index: usize, x: T) -> Wrapper<T, I> {
+        // The slot is no longer in the free list so we don't need to lock to write to it.
+        (*self.0.get()).data().as_mut()[index] = Block {
+            full: ManuallyDrop::new(x),
+        };
+        Wrapper {
+            allocator: self,
+            index,
+        }
+    }
+
+    /// Allocates a given `x`, waiting for a slot to be freed while there are no free slots.
     ///
-    /// `drop(x.iter().collect::<Vec<_>>())` would free all memory, invalidating any wrappers
-    /// presently held.
+    /// The allocator is never locked while another thread holds the lock, instead the task waits
+    /// for it to be released so the executor thread is not blocked. Only frees and unlocks made by
+    /// the current process wake the waiting task, the future never resolves for an allocator
+    /// without slots.
+    #[cfg(feature = "async")]
+    pub fn allocate_async(&self, x: T) -> AllocateFuture<'_, T, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_async");
+
+        AllocateFuture {
+            allocator: self,
+            value: Some(x),
+        }
+    }
+
+    /// Removes the first free slot from the free list, returning its index.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    fn claim(&self, wait: bool, priority: Priority) -> Result<usize, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::claim");
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "slab::allocate",
+            head = tracing::field::Empty,
+            index = tracing::field::Empty,
+        )
+        .entered();
+
+        #[cfg(feature = "latency")]
+        let start = std::time::Instant::now();
+
+        let mut inner_allocator = if wait {
+            self.0.lock().map_err(AllocError::LockFailed)?
+        } else {
+            self.0
+                .try_lock()
+                .map_err(AllocError::LockFailed)?
+                .ok_or(AllocError::WouldBlock)?
+        };
+        #[cfg(feature = "latency")]
+        let locked = std::time::Instant::now();
+
+        #[cfg(feature = "tracing")]
+        span.record("head", tracing::field::debug(inner_allocator.head));
+
+        #[cfg(feature = "testing")]
+        let injected = inner_allocator
+            .failure
+            .inject(std::mem::size_of::<Block<T, I>>());
+        #[cfg(not(feature = "testing"))]
+        let injected = false;
+
+        // Normal allocations may not use the reserve.
+        let reserved = priority == Priority::Normal
+            && inner_allocator.reserve > 0
+            && inner_allocator.free <= inner_allocator.reserve;
+
+        let Some(index) = inner_allocator.head.filter(|_| !injected && !reserved) else {
+            let oom_hook = inner_allocator.oom_hook;
+            #[cfg(feature = "latency")]
+            inner_allocator.latency.record_allocate(start, locked);
+            drop(inner_allocator);
+            #[cfg(feature = "metrics")]
+            crate::instrument::failed("slab");
+            self.out_of_memory(oom_hook, 1);
+            return Err(AllocError::OutOfMemory {
+                requested: 1,
+                largest_free: 0,
+            });
+        };
+        inner_allocator.head = unsafe { inner_allocator.data().as_ref()[index].next_free() };
+        inner_allocator.free -= 1;
+
+        #[cfg(feature = "sanitizer")]
+        if inner_allocator.poisoning {
+            crate::sanitizer::unpoison(unsafe { &inner_allocator.data().as_ref()[index..=index] });
+        }
+
+        #[cfg(feature = "debug-fill")]
+        crate::raw::fill(
+            unsafe { &mut inner_allocator.data().as_mut()[index..=index] },
+            crate::raw::ALLOCATED_FILL,
+        );
+
+        let crossed = inner_allocator
+            .watermarks
+            .as_mut()
+            .and_then(|watermarks| watermarks.allocated(1));
+
+        #[cfg(feature = "latency")]
+        inner_allocator.latency.record_allocate(start, locked);
+        drop(inner_allocator);
+
+        if let Some((hook, event)) = crossed {
+            hook(&event);
+        }
+
+        #[cfg(feature = "tracing")]
+        span.record("index", index);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::allocated("slab", std::mem::size_of::<Block<T, I>>());
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::record(
+            self as *const Self as usize,
+            index,
+            1,
+            std::panic::Location::caller(),
+        );
+
+        Ok(index)
+    }
+
+    /// Claims a run of `n` adjacent free slots, filled in order through the returned reservation,
+    /// e.g. for a batch of messages which must occupy consecutive indices.
+    ///
+    /// Returns `None` when there is no run of `n` free slots. Slots which have not been filled are
+    /// freed when the reservation is dropped.
     ///
     /// # Panics
     ///
     /// When locking the mutex fails.
-    pub unsafe fn iter(&self) -> WrapperIterator<T> {
+    pub fn reserve_run(&self, n: usize) -> Option<RunReservation<T, I>> {
         #[cfg(feature = "log")]
-        trace!("Allocator::iter");
-        let head = self.0.lock().unwrap().head;
-        WrapperIterator {
+        trace!("Allocator::reserve_run");
+
+        let reservation = |start| RunReservation {
             allocator: self,
-            free: head,
-            used: 0,
+            start,
+            len: n,
+            filled: 0,
+        };
+        if n == 0 {
+            return Some(reservation(0));
         }
-    }
-}
 
-#[derive(Debug)]
-pub struct WrapperIterator<'a, T> {
-    allocator: &'a Allocator<T>,
-    free: Option<usize>,
-    used: usize,
-}
-impl<'a, T> WrapperIterator<'a, T> {
-    #[must_use]
-    pub fn allocator(&self) -> &'a Allocator<T> {
-        self.allocator
-    }
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        let Some(start) = inner_allocator.claim_run(n) else {
+            let oom_hook = inner_allocator.oom_hook;
+            drop(inner_allocator_guard);
+            #[cfg(feature = "metrics")]
+            crate::instrument::failed("slab");
+            self.out_of_memory(oom_hook, n);
+            return None;
+        };
+
+        #[cfg(feature = "sanitizer")]
+        if inner_allocator.poisoning {
+            crate::sanitizer::unpoison(unsafe {
+                &inner_allocator.data().as_ref()[start..start + n]
+            });
+        }
 
-    #[must_use]
-    pub fn free(&self) -> &Option<usize> {
-        &self.free
+        #[cfg(feature = "debug-fill")]
+        crate::raw::fill(
+            unsafe { &mut inner_allocator.data().as_mut()[start..start + n] },
+            crate::raw::ALLOCATED_FILL,
+        );
+
+        let crossed = inner_allocator
+            .watermarks
+            .as_mut()
+            .and_then(|watermarks| watermarks.allocated(n));
+        drop(inner_allocator_guard);
+
+        if let Some((hook, event)) = crossed {
+            hook(&event);
+        }
+
+        Some(reservation(start))
     }
 
-    #[must_use]
-    pub fn used(&self) -> &usize {
-        &self.used
+    /// Drops the value in the slot at `index` and frees it.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been allocated from this allocator and must not be freed again.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    unsafe fn deallocate(&self, index: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::deallocate");
+
+        ManuallyDrop::drop(&mut (*self.0.get()).data().as_mut()[index].full);
+        self.release(index);
     }
-}
-impl<'a, T> Iterator for WrapperIterator<'a, T> {
-    type Item = Wrapper<'a, T>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let inner_guard = self.allocator.0.lock().unwrap();
-        let inner = &*inner_guard;
-        loop {
-            let free = self.free.unwrap_or(inner.size);
-            println!("free: {free}");
-            println!("self.used: {}", self.used);
-            if self.used < free {
-                let temp = self.used;
-                self.used += 1;
-                break Some(Wrapper {
-                    allocator: self.allocator,
-                    index: temp,
-                });
-            }
-            println!("free: {free}");
-            println!("self.used: {}", self.used);
-            println!("inner.size: {}", inner.size);
-            if self.used == inner.size {
-                break None;
-            }
+    /// Returns the slot at `index` to the free list without dropping its contents.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been allocated from this allocator and must not be released again.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    unsafe fn release(&self, index: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::release");
 
-            debug_assert_eq!(self.used, free);
-            debug_assert!(
-                unsafe { inner.data().as_ref()[free].empty.unwrap_or(inner.size) } > free
-            );
-            // println!("inner.data().as_ref()[free].empty: {:?}",
-            // unsafe{inner.data().as_ref()[free].empty}); println!("inner.data().
-            // as_ref()[free+1].empty: {:?}", unsafe{inner.data().as_ref()[free+1].empty});
-            // println!("inner.data().as_ref()[free+2].empty: {:?}",
-            // unsafe{inner.data().as_ref()[free+2].empty});
-            self.free = unsafe { inner.data().as_ref()[free].empty };
-            self.used = free + 1;
-        }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("slab::free", index).entered();
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::freed("slab", std::mem::size_of::<Block<T, I>>());
+
+        #[cfg(feature = "debug-checks")]
+        self.check_free(index);
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::remove(self as *const Self as usize, index);
+
+        // The slot is still held, so its contents may be wiped without locking.
+        #[cfg(feature = "zeroize")]
+        crate::raw::zeroize(&mut (*self.0.get()).data().as_mut()[index..=index]);
+
+        #[cfg(feature = "quarantine")]
+        let Some(index) = self.quarantine(index) else {
+            return;
+        };
+
+        self.free_slot(index);
     }
-}
 
-#[derive(Debug, Eq, PartialEq)]
-#[repr(C)]
-pub struct InnerAllocator<T> {
-    head: Option<usize>,
-    size: usize,
-    _marker: PhantomData<T>,
-}
+    /// Checks the slot at `index` is within the allocator and not free.
+    ///
+    /// # Panics
+    ///
+    /// When the slot is outside the allocator, when it is free, e.g. on a double free, or when
+    /// locking the mutex fails.
+    #[cfg(feature = "debug-checks")]
+    fn check_free(&self, index: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::check_free");
 
-use std::ptr::NonNull;
+        let inner_allocator = self.0.lock().unwrap();
+        assert!(
+            index < inner_allocator.size,
+            "freeing slot {index} outside the allocator of {} slots",
+            inner_allocator.size
+        );
+        let mut current = inner_allocator.head;
+        let data = unsafe { inner_allocator.data().as_ref() };
+        // The free list is ordered by index so the walk stops after the slot.
+        while let Some(free) = current.filter(|&free| free <= index) {
+            assert_ne!(free, index, "freeing free slot {index}, e.g. a double free");
+            current = unsafe { data[free].next_free() };
+        }
+    }
 
-#[allow(clippy::needless_range_loop)]
-impl<T> InnerAllocator<T> {
+    /// Quarantines the slot at `index`, returning the slot which should rejoin the free list, if
+    /// any.
+    ///
     /// # Safety
     ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
+    /// The slot must have been allocated from this allocator and must not be released again.
     ///
     /// # Panics
     ///
-    /// When `&self == std::ptr::null()`.
-    #[must_use]
-    pub unsafe fn data(&self) -> NonNull<[Block<T>]> {
+    /// When locking the mutex fails.
+    #[cfg(feature = "quarantine")]
+    unsafe fn quarantine(&self, index: usize) -> Option<usize> {
         #[cfg(feature = "log")]
-        trace!("InnerAllocator::data");
+        trace!("Allocator::quarantine");
 
-        std::ptr::NonNull::slice_from_raw_parts(
-            NonNull::new((self as *const Self as *mut Self).add(1).cast()).unwrap(),
-            self.size,
-        )
+        let mut inner_allocator = self.0.lock().unwrap();
+        if inner_allocator.quarantine.limit() == 0 {
+            return Some(index);
+        }
+
+        #[cfg(feature = "debug-fill")]
+        crate::raw::fill(
+            &mut inner_allocator.data().as_mut()[index..=index],
+            crate::raw::FREED_FILL,
+        );
+
+        #[cfg(feature = "sanitizer")]
+        if inner_allocator.poisoning {
+            crate::sanitizer::poison(&inner_allocator.data().as_ref()[index..=index]);
+        }
+
+        inner_allocator
+            .quarantine
+            .push((index, 1))
+            .map(|(index, _)| index)
     }
 
-    unsafe fn init(ptr: *mut Self, size: usize) {
+    /// Returns the slot at `index` to the free list.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been allocated from this allocator and must not be released again.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    unsafe fn free_slot(&self, index: usize) {
         #[cfg(feature = "log")]
-        trace!("InnerAllocator::init");
+        trace!("Allocator::free_slot");
 
-        if size > 0 {
-            #[cfg(feature = "log")]
-            trace!("InnerAllocator::init non-empty");
+        #[cfg(feature = "latency")]
+        let start = std::time::Instant::now();
 
-            (*ptr).head = Some(0);
-            (*ptr).size = size;
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        #[cfg(feature = "latency")]
+        let locked = std::time::Instant::now();
+        // To avoid a massive number of mutex deref calls we deref here.
+        let inner_allocator = &mut *inner_allocator_guard;
+        let data = inner_allocator.data().as_mut();
 
-            #[cfg(feature = "log")]
-            trace!("InnerAllocator::init head written");
+        // The slot may have been poisoned in quarantine.
+        #[cfg(feature = "sanitizer")]
+        if inner_allocator.poisoning {
+            crate::sanitizer::unpoison(&data[index..=index]);
+        }
 
-            let data_ref = (*ptr).data().as_mut();
-            for i in 0..(size - 1) {
-                // println!("inner data: {:#?}",(*ptr).data().as_ref());
-                data_ref[i] = Block { empty: Some(i + 1) };
+        #[cfg(feature = "debug-fill")]
+        crate::raw::fill(&mut data[index..=index], crate::raw::FREED_FILL);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(head = ?inner_allocator.head);
+
+        if let Some(head) = inner_allocator.head {
+            debug_assert_ne!(head, index);
+            if head > index {
+                data[index].set_next_free(Some(head));
+                inner_allocator.head = Some(index);
+            } else {
+                debug_assert!(head < index);
+                let mut current = head;
+
+                loop {
+                    match data[current].next_free() {
+                        None => {
+                            data[index].set_next_free(None);
+                            data[current].set_next_free(Some(index));
+                            break;
+                        }
+                        Some(next) if next > index => {
+                            data[index].set_next_free(Some(next));
+                            data[current].set_next_free(Some(index));
+                            break;
+                        }
+                        Some(next) => {
+                            debug_assert!(next < index);
+                            current = next;
+                        }
+                    }
+                }
             }
-            data_ref[size - 1] = Block { empty: None };
         } else {
-            #[cfg(feature = "log")]
-            trace!("InnerAllocator::init empty");
+            inner_allocator.head = Some(index);
+            data[index].set_next_free(None);
+        }
+        inner_allocator.free += 1;
 
-            (*ptr).head = None;
-            (*ptr).size = size;
+        #[cfg(feature = "sanitizer")]
+        if inner_allocator.poisoning {
+            crate::sanitizer::poison_tail(&data[index], std::mem::size_of::<Option<I>>());
         }
-    }
-}
 
-#[repr(C)]
-pub union Block<T> {
-    empty: Option<usize>,
-    full: ManuallyDrop<T>,
-}
+        let crossed = inner_allocator
+            .watermarks
+            .as_mut()
+            .and_then(|watermarks| watermarks.freed(1));
 
-impl<T: std::fmt::Debug> std::fmt::Debug for Block<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        #[cfg(feature = "log")]
-        trace!("Block::fmt");
+        #[cfg(feature = "latency")]
+        inner_allocator.latency.record_free(start, locked);
 
-        f.debug_struct("Block")
-            .field("empty", unsafe { &self.empty })
-            .field("full", unsafe { &self.full })
-            .finish()
+        drop(inner_allocator_guard);
+
+        if let Some((hook, event)) = crossed {
+            hook(&event);
+        }
+
+        #[cfg(feature = "async")]
+        crate::waiters::wake(self as *const Self as usize);
     }
-}
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct Wrapper<'a, T> {
-    allocator: &'a Allocator<T>,
-    index: usize,
+    /// Enables or disables marking free slots as inaccessible to the address sanitizer and
+    /// Valgrind, so use after free of allocations is reported rather than silently reading stale
+    /// data.
+    ///
+    /// While enabled the allocator's memory must not be moved, copied or reused, e.g. an
+    /// [`ArrayAllocator`] on the stack must not be moved or dropped, as the sanitizer would report
+    /// the accesses. Disabling marks all of the allocator's memory as accessible.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "sanitizer")]
+    pub fn set_poisoning(&self, enabled: bool) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_poisoning");
+
+        let mut inner_allocator_guard = self.0.lock().unwrap();
+        let inner_allocator = &mut *inner_allocator_guard;
+        inner_allocator.poisoning = enabled;
+        if enabled {
+            inner_allocator.poison_free();
+        } else {
+            crate::sanitizer::unpoison(unsafe { inner_allocator.data().as_ref() });
+        }
+    }
+
+    /// Sets the number of freed allocations held in quarantine before their slots rejoin the free
+    /// list, so recently freed memory is not immediately reallocated and use after free is not
+    /// masked by the memory being reused. `0`, the default, disables quarantine and reducing the
+    /// limit releases the oldest quarantined allocations.
+    ///
+    /// Quarantined slots are filled and poisoned as if free but are counted as used, e.g. by
+    /// [`Allocator::stats`].
+    ///
+    /// # Panics
+    ///
+    /// When `limit >` [`QUARANTINE_CAPACITY`](crate::quarantine::QUARANTINE_CAPACITY) or when locking the mutex fails.
+    #[cfg(feature = "quarantine")]
+    pub fn set_quarantine(&self, limit: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_quarantine");
+
+        self.0.lock().unwrap().quarantine.set_limit(limit);
+        // Each slot is freed once the allocator is unlocked, as freeing locks it.
+        loop {
+            let evicted = self.0.lock().unwrap().quarantine.evict();
+            let Some((index, _)) = evicted else {
+                break;
+            };
+            unsafe { self.free_slot(index) };
+        }
+    }
+
+    /// Sets a function called whenever an allocation fails through lack of memory, replacing any
+    /// previous hook.
+    ///
+    /// The hook is called after the allocator is unlocked, so it may use the allocator. As a
+    /// function pointer it is only valid within the process which set it, so when the allocator is
+    /// shared between processes the hook should only be set in one of them.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn set_oom_hook(&self, hook: OomHook) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_oom_hook");
+
+        self.0.lock().unwrap().oom_hook = Some(hook);
+    }
+
+    /// Removes and returns the out of memory hook.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn take_oom_hook(&self) -> Option<OomHook> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::take_oom_hook");
+
+        self.0.lock().unwrap().oom_hook.take()
+    }
+
+    /// Sets a function called when the number of used slots rises to `high` or falls to `low`,
+    /// replacing any previous watermarks.
+    ///
+    /// Crossings are detected within allocate and free while the lock is held, so each is seen
+    /// exactly once, and the hook is called after the allocator is unlocked, so it may use the
+    /// allocator. After the high watermark is reached the hook is not called again until usage
+    /// falls to the low watermark. Like the out of memory hook, the hook is only valid within the
+    /// process which set it; to notify other processes it may write to e.g. an eventfd.
+    ///
+    /// # Panics
+    ///
+    /// When `low >= high` or when locking the mutex fails.
+    pub fn set_watermarks(&self, low: usize, high: usize, hook: WatermarkHook) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_watermarks");
+
+        let mut inner_allocator = self.0.lock().unwrap();
+        let stats = inner_allocator.stats();
+        inner_allocator.watermarks = Some(Watermarks::new(low, high, hook, stats));
+    }
+
+    /// Removes any watermarks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn clear_watermarks(&self) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::clear_watermarks");
+
+        self.0.lock().unwrap().watermarks = None;
+    }
+
+    /// Reserves `slots` slots for [`Priority::Critical`] allocations.
+    ///
+    /// Normal allocations fail with out of memory rather than leave fewer than `slots` slots free,
+    /// while critical allocations may use every free slot. A reserve of 0, the default, disables
+    /// the check.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn set_reserve(&self, slots: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_reserve");
+
+        self.0.lock().unwrap().reserve = slots;
+    }
+
+    /// Returns the number of slots reserved for [`Priority::Critical`] allocations.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn reserve(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::reserve");
+
+        self.0.lock().unwrap().reserve
+    }
+
+    /// Returns whether the lock is held, and whether it has been held for longer than `timeout`
+    /// by a process which no longer exists, in which case it will never be released.
+    ///
+    /// A supervisor may poll this and reclaim an abandoned allocator with
+    /// [`Allocator::force_unlock`] rather than deadlock on it.
+    #[cfg(feature = "watchdog")]
+    #[must_use]
+    pub fn check_lock_health(&self, timeout: std::time::Duration) -> crate::watchdog::LockHealth {
+        #[cfg(feature = "log")]
+        trace!("Allocator::check_lock_health");
+
+        self.0.owner.health(timeout)
+    }
+
+    /// Releases a lock held by a process which no longer exists.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by a process which no longer exists, e.g. as reported by
+    /// [`Allocator::check_lock_health`], and no other process may force unlock it concurrently.
+    /// The holder may have died part way through modifying the allocator, so the slot it was
+    /// allocating or freeing may be lost or still in use.
+    ///
+    /// # Panics
+    ///
+    /// When unlocking the mutex fails.
+    #[cfg(feature = "watchdog")]
+    pub unsafe fn force_unlock(&self) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::force_unlock");
+
+        self.0.force_unlock().unwrap();
+    }
+
+    /// Sets the failures to inject into allocations, replacing any previous configuration.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "testing")]
+    pub fn set_failure_injection(&self, failure: crate::testing::FailureInjection) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::set_failure_injection");
+
+        self.0.lock().unwrap().failure = failure;
+    }
+
+    /// Returns the current failure injection state.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "testing")]
+    #[must_use]
+    pub fn failure_injection(&self) -> crate::testing::FailureInjection {
+        #[cfg(feature = "log")]
+        trace!("Allocator::failure_injection");
+
+        self.0.lock().unwrap().failure
+    }
+
+    /// Calls the out of memory hook, if set.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    fn out_of_memory(&self, oom_hook: Option<OomHook>, requested: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::out_of_memory");
+
+        if let Some(hook) = oom_hook {
+            hook(&AllocRequest {
+                requested,
+                stats: self.stats(),
+            });
+        }
+    }
+
+    /// Renders the used (`#`) and free (`.`) slots, `width` per line with each line prefixed by the
+    /// index of its first slot, e.g.
+    ///
+    /// ```text
+    ///  0 [####....]
+    ///  8 [##..]
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// When `width == 0` or when locking the mutex fails.
+    #[must_use]
+    #[cfg(feature = "std")]
+    pub fn render_map(&self, width: usize) -> String {
+        #[cfg(feature = "log")]
+        trace!("Allocator::render_map");
+
+        let (total, free) = self.free_list();
+        crate::raw::render_map(total, free, width)
+    }
+
+    /// Renders the runs of used and free slots as a row of boxes labelled with their number of
+    /// slots, see [`crate::linked_list::Allocator::dump`].
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    #[cfg(feature = "std")]
+    pub fn dump(&self) -> String {
+        #[cfg(feature = "log")]
+        trace!("Allocator::dump");
+
+        let (total, free) = self.free_list();
+        crate::raw::render_regions(total, free)
+    }
+
+    /// Returns the number of slots and the free slots as `(index, 1)`.
+    #[cfg(feature = "std")]
+    fn free_list(&self) -> (usize, Vec<(usize, usize)>) {
+        let inner_allocator = self.0.lock().unwrap();
+        let data = unsafe { inner_allocator.data().as_ref() };
+
+        let mut free = Vec::new();
+        let mut next = inner_allocator.head;
+        while let Some(index) = next {
+            free.push((index, 1));
+            next = unsafe { data[index].next_free() };
+        }
+        (inner_allocator.size, free)
+    }
+
+    /// Groups the live allocations made by this process by call site, ordered by the number of
+    /// slots descending.
+    ///
+    /// Call sites are recorded through `#[track_caller]`, so allocations made through wrappers
+    /// which are not `#[track_caller]` are attributed to the wrapper.
+    #[cfg(feature = "profiling")]
+    #[must_use]
+    pub fn dump_live_allocations(&self) -> Vec<crate::profiling::CallSite> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::dump_live_allocations");
+
+        crate::profiling::call_sites(self as *const Self as usize)
+    }
+
+    /// Returns the latencies recorded since the allocator was initialized.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "latency")]
+    #[must_use]
+    pub fn latency_histogram(&self) -> crate::latency::Latency {
+        #[cfg(feature = "log")]
+        trace!("Allocator::latency_histogram");
+
+        self.0.lock().unwrap().latency
+    }
+
+    /// Returns usage statistics.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        #[cfg(feature = "log")]
+        trace!("Allocator::stats");
+
+        self.0.lock().unwrap().stats()
+    }
+
+    /// Walks the free list under the lock, checking its slots are ordered and lie within the
+    /// allocator, see [`crate::linked_list::Allocator::check_integrity`].
+    ///
+    /// # Errors
+    ///
+    /// When the free list is corrupt, returning the first corruption found.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn check_integrity(
+        &self,
+    ) -> Result<crate::integrity::IntegrityReport, crate::integrity::IntegrityError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::check_integrity");
+
+        let inner_allocator = self.0.lock().unwrap();
+        let data = unsafe { inner_allocator.data().as_ref() };
+        crate::integrity::check(inner_allocator.size, inner_allocator.head, |index| {
+            (1, unsafe { data[index].next_free() })
+        })
+    }
+
+    /// Returns the state of the slot at `index`, as of the call.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails or when `index` is out of range.
+    #[must_use]
+    pub fn slot_state(&self, index: usize) -> SlotState {
+        #[cfg(feature = "log")]
+        trace!("Allocator::slot_state");
+
+        let inner_allocator = self.0.lock().unwrap();
+        assert!(
+            index < inner_allocator.size,
+            "index {index} out of range for {} slots",
+            inner_allocator.size
+        );
+        inner_allocator.slot_state(index)
+    }
+
+    /// Returns the number of slots holding values, as of the call.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn occupied_count(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::occupied_count");
+
+        let inner_allocator = self.0.lock().unwrap();
+        let stats = inner_allocator.stats();
+        #[cfg(feature = "quarantine")]
+        let quarantined = inner_allocator.quarantine.len();
+        #[cfg(not(feature = "quarantine"))]
+        let quarantined = 0;
+        stats.total - stats.free - quarantined
+    }
+
+    /// Returns the indices of the free slots in ascending order, as of the call.
+    ///
+    /// Slots may be allocated or freed by others once this returns, so the indices are a snapshot
+    /// for diagnostics or planning rather than a claim on the slots.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg(feature = "std")]
+    pub fn free_slots(&self) -> impl Iterator<Item = usize> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free_slots");
+
+        let inner_allocator = self.0.lock().unwrap();
+        let data = unsafe { inner_allocator.data().as_ref() };
+
+        let mut free = Vec::new();
+        let mut next = inner_allocator.head;
+        while let Some(index) = next {
+            free.push(index);
+            next = unsafe { data[index].next_free() };
+        }
+        free.into_iter()
+    }
+
+    /// Returns the index of the slot holding `value`, or `None` if `value` is not an occupied slot
+    /// of this allocator, e.g. so callbacks given `&T` can recover its wrapper's index.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn key_of(&self, value: &T) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::key_of");
+
+        let inner_allocator = self.0.lock().unwrap();
+        let start = unsafe { inner_allocator.data() }
+            .as_ptr()
+            .cast::<Block<T, I>>() as usize;
+        let offset = (value as *const T as usize).checked_sub(start)?;
+        let index = offset / std::mem::size_of::<Block<T, I>>();
+        (offset % std::mem::size_of::<Block<T, I>>() == 0 && inner_allocator.is_occupied(index))
+            .then_some(index)
+    }
+
+    /// Returns a pointer to a `[U]` of one `U` per slot following the slots, aligned for `U`, in
+    /// which allocators layered over the slab keep per slot state, see [`crate::slab_gen`].
+    ///
+    /// # Safety
+    ///
+    /// The memory following the slots must be valid for the `[U]`.
+    pub(crate) unsafe fn trailer<U>(&self) -> NonNull<[U]> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::trailer");
+
+        let inner_allocator = &*self.0.get();
+        let end = inner_allocator
+            .data()
+            .as_ptr()
+            .cast::<Block<T, I>>()
+            .add(inner_allocator.size)
+            .cast::<u8>();
+        let start = end.add(end.align_offset(std::mem::align_of::<U>()));
+        NonNull::slice_from_raw_parts(NonNull::new_unchecked(start.cast()), inner_allocator.size)
+    }
+
+    /// Returns mutable references to the values in the slots at `indices`, or `None` if any slot
+    /// is free or any index is repeated.
+    ///
+    /// Wrappers borrow the allocator, so taking `&mut self` ensures none are live and the
+    /// references are unique, e.g. for values whose wrappers were forgotten.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn get_disjoint_mut<const K: usize>(&mut self, indices: [usize; K]) -> Option<[&mut T; K]> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::get_disjoint_mut");
+
+        let inner_allocator = self.0.lock().unwrap();
+        for (i, index) in indices.iter().enumerate() {
+            if indices[..i].contains(index) || !inner_allocator.is_occupied(*index) {
+                return None;
+            }
+        }
+        // The indices are distinct and occupied, so the references don't alias.
+        unsafe {
+            let data = inner_allocator.data().as_ptr().cast::<Block<T, I>>();
+            Some(indices.map(|index| &mut **std::ptr::addr_of_mut!((*data.add(index)).full)))
+        }
+    }
+
+    /// Returns wrappers for all non-free spaces.
+    ///
+    /// The intended usage is for one process `std::mem::forget`s all its wrappers then another
+    /// picks them up with this iterator.
+    ///
+    /// # Safety
+    ///
+    /// Since this returns wrappers for all non-free spaces, dropping these may invalidate other
+    /// presently held wrappers.
+    ///
+    /// `drop(x.iter().collect::<Vec<_>>())` would free all memory, invalidating any wrappers
+    /// presently held.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub unsafe fn iter(&self) -> WrapperIterator<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::iter");
+        let head = self.0.lock().unwrap().head;
+        WrapperIterator {
+            allocator: self,
+            free: head,
+            used: 0,
+        }
+    }
+}
+
+// Wrappers produced by the iterator may be dropped on another thread.
+unsafe impl<'a, T: Send, I> Send for WrapperIterator<'a, T, I> {}
+unsafe impl<'a, T: Send + Sync, I> Sync for WrapperIterator<'a, T, I> {}
+
+#[derive(Debug)]
+pub struct WrapperIterator<'a, T, I = usize> {
+    allocator: &'a Allocator<T, I>,
+    free: Option<usize>,
+    used: usize,
+}
+impl<'a, T, I> WrapperIterator<'a, T, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &'a Allocator<T, I> {
+        self.allocator
+    }
+
+    #[must_use]
+    pub fn free(&self) -> &Option<usize> {
+        &self.free
+    }
+
+    #[must_use]
+    pub fn used(&self) -> &usize {
+        &self.used
+    }
+}
+impl<'a, T, I: Index> Iterator for WrapperIterator<'a, T, I> {
+    type Item = Wrapper<'a, T, I>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let inner_guard = self.allocator.0.lock().unwrap();
+        let inner = &*inner_guard;
+        loop {
+            let free = self.free.unwrap_or(inner.size);
+            if self.used < free {
+                let temp = self.used;
+                self.used += 1;
+                break Some(Wrapper {
+                    allocator: self.allocator,
+                    index: temp,
+                });
+            }
+            if self.used == inner.size {
+                break None;
+            }
+
+            debug_assert_eq!(self.used, free);
+            debug_assert!(
+                unsafe {
+                    inner.data().as_ref()[free]
+                        .next_free()
+                        .unwrap_or(inner.size)
+                } > free
+            );
+            // println!("inner.data().as_ref()[free].empty: {:?}",
+            // unsafe{inner.data().as_ref()[free].empty}); println!("inner.data().
+            // as_ref()[free+1].empty: {:?}", unsafe{inner.data().as_ref()[free+1].empty});
+            // println!("inner.data().as_ref()[free+2].empty: {:?}",
+            // unsafe{inner.data().as_ref()[free+2].empty});
+            self.free = unsafe { inner.data().as_ref()[free].next_free() };
+            self.used = free + 1;
+        }
+    }
+}
+
+#[derive(Debug, Eq)]
+#[repr(C)]
+pub struct InnerAllocator<T, I = usize> {
+    head: Option<usize>,
+    size: usize,
+    oom_hook: Option<OomHook>,
+    watermarks: Option<Watermarks>,
+    /// The number of blocks/slots only critical allocations may use, see [`Priority`].
+    reserve: usize,
+    /// The number of slots in the free list, so the reserve can be checked without walking it.
+    free: usize,
+    #[cfg(feature = "sanitizer")]
+    poisoning: bool,
+    #[cfg(feature = "quarantine")]
+    quarantine: crate::quarantine::Quarantine,
+    #[cfg(feature = "testing")]
+    failure: crate::testing::FailureInjection,
+    #[cfg(feature = "latency")]
+    latency: crate::latency::Latency,
+    _marker: PhantomData<(T, I)>,
+}
+
+// Function pointers cannot be meaningfully compared, so only whether hooks are set is compared.
+impl<T, I> PartialEq for InnerAllocator<T, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.head == other.head
+            && self.size == other.size
+            && self.oom_hook.is_some() == other.oom_hook.is_some()
+            && self.watermarks == other.watermarks
+            && self.reserve == other.reserve
+    }
+}
+
+use std::ptr::NonNull;
+
+#[allow(clippy::needless_range_loop)]
+impl<T, I: Index> InnerAllocator<T, I> {
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    ///
+    /// # Panics
+    ///
+    /// When `&self == std::ptr::null()`.
+    #[must_use]
+    pub unsafe fn data(&self) -> NonNull<[Block<T, I>]> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::data");
+
+        // Slots with a greater alignment than `Self` are padded from the end of `Self`.
+        let end = (self as *const Self as *mut Self).add(1).cast::<u8>();
+        let start = end.add(end.align_offset(std::mem::align_of::<Block<T, I>>()));
+        std::ptr::NonNull::slice_from_raw_parts(NonNull::new(start.cast()).unwrap(), self.size)
+    }
+
+    /// Returns usage statistics.
+    fn stats(&self) -> Stats {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::stats");
+
+        let data = unsafe { self.data().as_ref() };
+
+        let mut stats = Stats {
+            total: self.size,
+            ..Stats::default()
+        };
+        // The free list is ordered by index so contiguous free slots are adjacent in it.
+        let mut run = 0;
+        let mut previous = None;
+        let mut next = self.head;
+        while let Some(index) = next {
+            stats.free += 1;
+            run = if previous == index.checked_sub(1) {
+                run + 1
+            } else {
+                1
+            };
+            if run == 1 {
+                stats.free_regions += 1;
+            }
+            stats.largest_free = std::cmp::max(stats.largest_free, run);
+            previous = Some(index);
+            next = unsafe { data[index].next_free() };
+        }
+        debug_assert_eq!(stats.free, self.free);
+        stats
+    }
+
+    /// Removes the first run of `n > 0` adjacent free slots from the free list, returning the
+    /// index of the first.
+    fn claim_run(&mut self, n: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::claim_run");
+
+        let data = unsafe { self.data().as_mut() };
+        // The free slot preceding the run.
+        let mut before = None;
+        let mut start = self.head?;
+        let mut last = start;
+        // The free list is ordered by index so runs are adjacent in it.
+        while last + 1 - start < n {
+            let next = unsafe { data[last].next_free() }?;
+            if next != last + 1 {
+                before = Some(last);
+                start = next;
+            }
+            last = next;
+        }
+        let after = unsafe { data[last].next_free() };
+        match before {
+            Some(before) => unsafe { data[before].set_next_free(after) },
+            None => self.head = after,
+        }
+        self.free -= n;
+        Some(start)
+    }
+
+    /// Returns whether the slot at `index` holds a value, i.e. it is neither free nor quarantined.
+    fn is_occupied(&self, index: usize) -> bool {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::is_occupied");
+
+        index < self.size && self.slot_state(index) == SlotState::Occupied
+    }
+
+    /// Returns the state of the slot at `index < self.size`.
+    fn slot_state(&self, index: usize) -> SlotState {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::slot_state");
+
+        #[cfg(feature = "quarantine")]
+        if self.quarantine.get(index).is_some() {
+            return SlotState::Quarantined;
+        }
+        let data = unsafe { self.data().as_ref() };
+        let mut next = self.head;
+        // The free list is ordered by index.
+        while let Some(free) = next.filter(|&free| free <= index) {
+            if free == index {
+                return SlotState::Free;
+            }
+            next = unsafe { data[free].next_free() };
+        }
+        SlotState::Occupied
+    }
+
+    /// Marks all but the free list link of every free slot as inaccessible.
+    #[cfg(feature = "sanitizer")]
+    fn poison_free(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::poison_free");
+
+        let data = unsafe { self.data().as_ref() };
+        let mut next = self.head;
+        while let Some(index) = next {
+            crate::sanitizer::poison_tail(&data[index], std::mem::size_of::<Option<I>>());
+            next = unsafe { data[index].next_free() };
+        }
+    }
+
+    unsafe fn init(ptr: *mut Self, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::init");
+
+        assert!(
+            size <= I::MAX,
+            "{size} slots cannot be indexed by {}",
+            I::MAX
+        );
+
+        if size > 0 {
+            #[cfg(feature = "log")]
+            trace!("InnerAllocator::init non-empty");
+
+            (*ptr).head = Some(0);
+            (*ptr).size = size;
+            (*ptr).oom_hook = None;
+            std::ptr::addr_of_mut!((*ptr).watermarks).write(None);
+            (*ptr).reserve = 0;
+            (*ptr).free = size;
+            #[cfg(feature = "sanitizer")]
+            std::ptr::addr_of_mut!((*ptr).poisoning).write(false);
+            #[cfg(feature = "quarantine")]
+            std::ptr::addr_of_mut!((*ptr).quarantine)
+                .write(crate::quarantine::Quarantine::default());
+            #[cfg(feature = "testing")]
+            std::ptr::addr_of_mut!((*ptr).failure)
+                .write(crate::testing::FailureInjection::default());
+            #[cfg(feature = "latency")]
+            std::ptr::addr_of_mut!((*ptr).latency).write(crate::latency::Latency::default());
+
+            #[cfg(feature = "log")]
+            trace!("InnerAllocator::init head written");
+
+            let data_ref = (*ptr).data().as_mut();
+            for i in 0..(size - 1) {
+                // println!("inner data: {:#?}",(*ptr).data().as_ref());
+                data_ref[i] = Block::free(Some(i + 1));
+            }
+            data_ref[size - 1] = Block::free(None);
+        } else {
+            #[cfg(feature = "log")]
+            trace!("InnerAllocator::init empty");
+
+            (*ptr).head = None;
+            (*ptr).size = size;
+            (*ptr).oom_hook = None;
+            std::ptr::addr_of_mut!((*ptr).watermarks).write(None);
+            (*ptr).reserve = 0;
+            (*ptr).free = size;
+            #[cfg(feature = "sanitizer")]
+            std::ptr::addr_of_mut!((*ptr).poisoning).write(false);
+            #[cfg(feature = "quarantine")]
+            std::ptr::addr_of_mut!((*ptr).quarantine)
+                .write(crate::quarantine::Quarantine::default());
+            #[cfg(feature = "testing")]
+            std::ptr::addr_of_mut!((*ptr).failure)
+                .write(crate::testing::FailureInjection::default());
+            #[cfg(feature = "latency")]
+            std::ptr::addr_of_mut!((*ptr).latency).write(crate::latency::Latency::default());
+        }
+    }
+}
+
+/// The state of a slot, see [`Allocator::slot_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotState {
+    /// The slot is in the free list.
+    Free,
+    /// The slot holds a value.
+    Occupied,
+    /// The slot was freed and is held in quarantine, only with the `quarantine` feature.
+    Quarantined,
+}
+
+#[repr(C)]
+pub union Block<T, I: Index = usize> {
+    empty: Option<I>,
+    full: ManuallyDrop<T>,
+}
+
+impl<T, I: Index> Block<T, I> {
+    /// Constructs a free slot pointing to the `next` free slot.
+    fn free(next: Option<usize>) -> Self {
+        Self {
+            empty: next.map(I::from_usize),
+        }
+    }
+
+    /// Sets the next free slot, only writing the free list link so the rest of the slot keeps its
+    /// fill pattern or may be inaccessible.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be free, or being freed with its value already dropped or moved out.
+    unsafe fn set_next_free(&mut self, next: Option<usize>) {
+        self.empty = next.map(I::from_usize);
+    }
+
+    /// Returns the next free slot.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be free.
+    unsafe fn next_free(&self) -> Option<usize> {
+        self.empty.map(Index::to_usize)
+    }
+}
+
+impl<T: std::fmt::Debug, I: Index> std::fmt::Debug for Block<T, I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        #[cfg(feature = "log")]
+        trace!("Block::fmt");
+
+        f.debug_struct("Block")
+            .field("empty", unsafe { &self.empty })
+            .field("full", unsafe { &self.full })
+            .finish()
+    }
+}
+
+/// The future returned by [`Allocator::allocate_async`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct AllocateFuture<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<T, I>,
+    /// The value to allocate, taken once allocated.
+    value: Option<T>,
+}
+
+// The value is never pinned, it is only moved into the allocator.
+#[cfg(feature = "async")]
+impl<'a, T, I: Index> Unpin for AllocateFuture<'a, T, I> {}
+
+#[cfg(feature = "async")]
+impl<'a, T, I: Index> std::future::Future for AllocateFuture<'a, T, I> {
+    type Output = Wrapper<'a, T, I>;
+
+    /// # Panics
+    ///
+    /// When polled after completing or when locking the mutex fails.
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        use std::task::Poll;
+
+        #[cfg(feature = "log")]
+        trace!("AllocateFuture::poll");
+
+        let this = self.get_mut();
+        let allocator = this.allocator;
+        let attempt = || match allocator.claim(false, Priority::Normal) {
+            Ok(index) => Some(index),
+            Err(AllocError::OutOfMemory { .. } | AllocError::WouldBlock) => None,
+            Err(err) => panic!("{err}"),
+        };
+        let claimed = attempt().or_else(|| {
+            crate::waiters::register(allocator as *const Allocator<T, I> as usize, cx.waker());
+            crate::waiters::register(&allocator.0 as *const _ as usize, cx.waker());
+            // Slots freed or the lock released before the waker was registered would not have
+            // woken it.
+            attempt()
+        });
+        match claimed {
+            Some(index) => {
+                let x = this.value.take().expect("polled after completion");
+                Poll::Ready(unsafe { allocator.fill(index, x) })
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A run of adjacent slots claimed by [`Allocator::reserve_run`], filled in order by
+/// [`RunReservation::push`].
+#[derive(Debug)]
+pub struct RunReservation<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<T, I>,
+    start: usize,
+    len: usize,
+    /// The number of slots filled, from `start`.
+    filled: usize,
+}
+
+impl<'a, T, I: Index> RunReservation<'a, T, I> {
+    /// The index of the first slot.
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The number of slots.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of slots filled.
+    #[must_use]
+    pub fn filled(&self) -> usize {
+        self.filled
+    }
+
+    /// Moves `x` into the next unfilled slot, returning `x` if every slot is filled.
+    ///
+    /// # Errors
+    ///
+    /// When every slot is filled.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn push(&mut self, x: T) -> Result<Wrapper<'a, T, I>, T> {
+        #[cfg(feature = "log")]
+        trace!("RunReservation::push");
+
+        if self.filled == self.len {
+            return Err(x);
+        }
+        let index = self.start + self.filled;
+        self.filled += 1;
+        // The slot is not in the free list so we don't need to lock to write to it.
+        unsafe {
+            (*self.allocator.0.get()).data().as_mut()[index] = Block {
+                full: ManuallyDrop::new(x),
+            };
+        }
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::allocated("slab", std::mem::size_of::<Block<T, I>>());
+
+        #[cfg(feature = "profiling")]
+        crate::profiling::record(
+            self.allocator as *const Allocator<T, I> as usize,
+            index,
+            1,
+            std::panic::Location::caller(),
+        );
+
+        Ok(Wrapper {
+            allocator: self.allocator,
+            index,
+        })
+    }
+}
+
+impl<'a, T, I: Index> Drop for RunReservation<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("RunReservation::drop");
+
+        for index in self.start + self.filled..self.start + self.len {
+            unsafe { self.allocator.free_slot(index) };
+        }
+    }
+}
+
+// Sending a `Wrapper` moves the `T` and sharing it shares `&T`. Since the allocator can be reached
+// through a shared `Wrapper`, and values moved into it, sharing also requires `T: Send`.
+unsafe impl<'a, T: Send, I: Index> Send for Wrapper<'a, T, I> {}
+unsafe impl<'a, T: Send + Sync, I: Index> Sync for Wrapper<'a, T, I> {}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Wrapper<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<T, I>,
+    index: usize,
+}
+
+impl<'a, T, I: Index> Wrapper<'a, T, I> {
+    /// Constructs a wrapper for the occupied slot at `index`, e.g. one given up with
+    /// [`Wrapper::into_index`].
+    ///
+    /// # Safety
+    ///
+    /// The slot at `index` must be occupied and not held by another wrapper.
+    pub unsafe fn from_index(allocator: &'a Allocator<T, I>, index: usize) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::from_index");
+
+        Self { allocator, index }
+    }
+
+    /// Gives up the wrapper without freeing its slot, returning the index of the slot.
+    #[must_use]
+    pub fn into_index(self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::into_index");
+
+        ManuallyDrop::new(self).index
+    }
+
+    /// Moves the value out of the slot, freeing the slot.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::into_inner");
+
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            let value =
+                ManuallyDrop::take(&mut (*this.allocator.0.get()).data().as_mut()[this.index].full);
+            this.allocator.release(this.index);
+            value
+        }
+    }
+
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator_mut");
+
+        &mut self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index");
+
+        self.index
+    }
+
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    pub unsafe fn index_mut(&mut self) -> &mut usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index_mut");
+
+        &mut self.index
+    }
+}
+
+impl<'a, T, I: Index> Drop for Wrapper<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop");
+
+        unsafe {
+            self.allocator.deallocate(self.index);
+        }
+    }
+}
+
+impl<'a, T, I: Index> Deref for Wrapper<'a, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &*self.allocator.0.get() };
+
+        unsafe { &inner_allocator.data().as_ref()[self.index].full }
+    }
+}
+impl<'a, T, I: Index> DerefMut for Wrapper<'a, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &mut *self.allocator.0.get() };
+
+        unsafe { &mut inner_allocator.data().as_mut()[self.index].full }
+    }
+}
+
+impl<T, I: Index> fmt::Display for Allocator<T, I> {
+    /// Summarizes usage in one line, e.g. `3/8 slots used (48/128 bytes), 2 free regions,
+    /// largest free region 4 slots`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.stats()
+            .summarize(f, "slots", std::mem::size_of::<Block<T, I>>())
+    }
+}
+
+impl<T, I: Index> RawArrayAllocator for Allocator<T, I> {
+    fn block_size(&self) -> usize {
+        std::mem::size_of::<Block<T, I>>()
+    }
+
+    fn block_align(&self) -> usize {
+        std::mem::align_of::<Block<T, I>>()
+    }
+
+    /// Allocates a slot when `bytes` fits within one.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    fn allocate_bytes(&self, bytes: usize) -> Option<RawAllocation> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_bytes");
+
+        if bytes == 0 {
+            return Some(RawAllocation { index: 0, size: 0 });
+        }
+        if bytes > self.block_size() {
+            return None;
+        }
+        none_on_oom(self.claim(true, Priority::Normal))
+            .map(|index| RawAllocation { index, size: 1 })
+    }
+
+    /// Frees a slot without dropping its contents.
+    unsafe fn free(&self, allocation: RawAllocation) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free");
+
+        if allocation.size != 0 {
+            self.release(allocation.index);
+        }
+    }
+
+    unsafe fn as_ptr(&self, allocation: RawAllocation) -> NonNull<u8> {
+        let data = (*self.0.get()).data();
+        NonNull::new_unchecked(
+            data.as_ptr()
+                .cast::<Block<T, I>>()
+                .add(allocation.index)
+                .cast(),
+        )
+    }
+
+    fn stats(&self) -> Stats {
+        Allocator::stats(self)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<T, I: Index> crate::testing::FreeRegions for Allocator<T, I> {
+    fn free_regions(&self) -> Vec<crate::testing::Region> {
+        let inner_allocator = self.0.lock().unwrap();
+        let data = unsafe { inner_allocator.data().as_ref() };
+
+        let mut regions = Vec::new();
+        let mut next = inner_allocator.head;
+        while let Some(index) = next {
+            regions.push(crate::testing::Region { index, size: 1 });
+            next = unsafe { data[index].next_free() };
+        }
+        regions
+    }
+}
+
+impl<T, I: Index> AsRef<Allocator<T, I>> for Allocator<T, I> {
+    fn as_ref(&self) -> &Allocator<T, I> {
+        self
+    }
+}
+impl<const N: usize, T, I: Index> AsRef<Allocator<T, I>> for ArrayAllocator<N, T, I> {
+    fn as_ref(&self) -> &Allocator<T, I> {
+        &self.allocator
+    }
+}
+
+/// A [`Wrapper`] which holds a handle `A` to its allocator rather than a reference, e.g.
+/// `Arc<ArrayAllocator<N, T>>`, so it can outlive any borrow of the allocator.
+#[derive(Debug)]
+pub struct OwnedWrapper<T, A, I: Index = usize>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+    allocator: A,
+    index: usize,
+    __marker: PhantomData<(T, I)>,
+}
+
+impl<T, A, I: Index> OwnedWrapper<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+    /// Allocates a given `x`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate(allocator: A, x: T) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::allocate");
+
+        let wrapper = (*allocator).as_ref().allocate(x)?;
+        let index = wrapper.index;
+        std::mem::forget(wrapper);
+        Some(Self {
+            allocator,
+            index,
+            __marker: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<T, I> {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::allocator");
+
+        (*self.allocator).as_ref()
+    }
+
+    #[must_use]
+    pub fn handle(&self) -> &A {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::handle");
+
+        &self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::index");
+
+        self.index
+    }
+}
+
+// See `Wrapper`, the handle must additionally be `Send`/`Sync`.
+unsafe impl<T: Send, A, I: Index> Send for OwnedWrapper<T, A, I>
+where
+    A: Deref + Send,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+}
+unsafe impl<T: Send + Sync, A, I: Index> Sync for OwnedWrapper<T, A, I>
+where
+    A: Deref + Sync,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+}
+
+impl<T, A, I: Index> Drop for OwnedWrapper<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::drop");
+
+        unsafe {
+            self.allocator().deallocate(self.index);
+        }
+    }
+}
+
+impl<T, A, I: Index> Deref for OwnedWrapper<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &*self.allocator().0.get() };
+
+        unsafe { &inner_allocator.data().as_ref()[self.index].full }
+    }
+}
+impl<T, A, I: Index> DerefMut for OwnedWrapper<T, A, I>
+where
+    A: Deref,
+    A::Target: AsRef<Allocator<T, I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("OwnedWrapper::deref_mut");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+
+        let inner_allocator = unsafe { &mut *self.allocator().0.get() };
+
+        unsafe { &mut inner_allocator.data().as_mut()[self.index].full }
+    }
+}
+
+/// A value paired with the number of [`Shared`] references to its slot.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Counted<T> {
+    count: std::sync::atomic::AtomicUsize,
+    value: T,
+}
+
+impl<T, I: Index> Allocator<Counted<T>, I> {
+    /// Allocates a given `x` with one reference.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[cfg_attr(feature = "profiling", track_caller)]
+    pub fn allocate_shared(&self, x: T) -> Option<Shared<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate_shared");
+
+        let wrapper = self.allocate(Counted {
+            count: std::sync::atomic::AtomicUsize::new(1),
+            value: x,
+        })?;
+        Some(Shared {
+            allocator: self,
+            index: wrapper.into_index(),
+        })
+    }
+}
+
+/// A reference counted value in a slot, freed when the last reference is dropped, e.g. for
+/// caches whose entries are held by several processes.
+///
+/// The count is kept in the slot, so references can be handed to another process mapping the same
+/// allocator with [`Shared::into_index`] and [`Shared::from_index`].
+#[derive(Debug)]
+pub struct Shared<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<Counted<T>, I>,
+    index: usize,
 }
 
-impl<'a, T> Wrapper<'a, T> {
+impl<'a, T, I: Index> Shared<'a, T, I> {
+    /// Constructs a reference to the slot at `index`, taking over a reference given up with
+    /// [`Shared::into_index`].
+    ///
+    /// # Safety
+    ///
+    /// The slot at `index` must hold a value with a reference given up and not yet taken over.
+    pub unsafe fn from_index(allocator: &'a Allocator<Counted<T>, I>, index: usize) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Shared::from_index");
+
+        Self { allocator, index }
+    }
+
+    /// Gives up the reference without decrementing the count, returning the index of the slot.
     #[must_use]
-    pub fn allocator(&self) -> &Allocator<T> {
+    pub fn into_index(self) -> usize {
         #[cfg(feature = "log")]
-        trace!("Wrapper::allocator");
+        trace!("Shared::into_index");
 
-        self.allocator
+        ManuallyDrop::new(self).index
     }
 
-    /// # Safety
-    ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn allocator_mut(&mut self) -> &mut &'a Allocator<T> {
+    #[must_use]
+    pub fn allocator(&self) -> &'a Allocator<Counted<T>, I> {
         #[cfg(feature = "log")]
-        trace!("Wrapper::allocator_mut");
+        trace!("Shared::allocator");
 
-        &mut self.allocator
+        self.allocator
     }
 
     #[must_use]
     pub fn index(&self) -> usize {
         #[cfg(feature = "log")]
-        trace!("Wrapper::index");
+        trace!("Shared::index");
 
         self.index
     }
 
-    /// # Safety
-    ///
-    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
-    /// memory of the allocator to which this belongs.
-    pub unsafe fn index_mut(&mut self) -> &mut usize {
+    /// Returns the number of references to the slot.
+    #[must_use]
+    pub fn count(&self) -> usize {
         #[cfg(feature = "log")]
-        trace!("Wrapper::index_mut");
+        trace!("Shared::count");
 
-        &mut self.index
+        self.counted()
+            .count
+            .load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    fn counted(&self) -> &Counted<T> {
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+        let inner_allocator = unsafe { &*self.allocator.0.get() };
+        unsafe { &inner_allocator.data().as_ref()[self.index].full }
     }
 }
 
-impl<'a, T> Drop for Wrapper<'a, T> {
-    fn drop(&mut self) {
-        #[cfg(feature = "log")]
-        trace!("Wrapper::drop");
+// As with `Arc`, references share the value across threads.
+unsafe impl<'a, T: Send + Sync, I: Index> Send for Shared<'a, T, I> {}
+unsafe impl<'a, T: Send + Sync, I: Index> Sync for Shared<'a, T, I> {}
 
-        let mut inner_allocator_guard = self.allocator.0.lock().unwrap();
-        // To avoid a massive number of mutex deref calls we deref here.
-        let inner_allocator = &mut *inner_allocator_guard;
-        let data = unsafe { inner_allocator.data().as_mut() };
+impl<'a, T, I: Index> Clone for Shared<'a, T, I> {
+    fn clone(&self) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Shared::clone");
+
+        self.counted()
+            .count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Self {
+            allocator: self.allocator,
+            index: self.index,
+        }
+    }
+}
 
-        if let Some(head) = inner_allocator.head {
-            debug_assert_ne!(head, self.index);
-            if head > self.index {
-                unsafe {
-                    ManuallyDrop::drop(&mut data[self.index].full);
-                }
-                data[self.index] = Block { empty: Some(head) };
-                inner_allocator.head = Some(self.index);
-            } else {
-                debug_assert!(head < self.index);
-                let mut current = head;
+impl<'a, T, I: Index> Drop for Shared<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Shared::drop");
 
-                loop {
-                    match unsafe { data[current].empty } {
-                        None => {
-                            unsafe {
-                                ManuallyDrop::drop(&mut data[self.index].full);
-                            }
-                            data[self.index] = Block { empty: None };
-                            data[current].empty = Some(self.index);
-                            break;
-                        }
-                        Some(next) if next > self.index => {
-                            unsafe {
-                                ManuallyDrop::drop(&mut data[self.index].full);
-                            }
-                            data[self.index] = Block { empty: Some(next) };
-                            data[current].empty = Some(self.index);
-                            break;
-                        }
-                        Some(next) => {
-                            debug_assert!(next < self.index);
-                            current = next;
-                        }
-                    }
-                }
-            }
-        } else {
+        if self
+            .counted()
+            .count
+            .fetch_sub(1, std::sync::atomic::Ordering::Release)
+            == 1
+        {
+            std::sync::atomic::fence(std::sync::atomic::Ordering::Acquire);
             unsafe {
-                ManuallyDrop::drop(&mut data[self.index].full);
+                self.allocator.deallocate(self.index);
             }
-            inner_allocator.head = Some(self.index);
-            data[self.index] = Block { empty: None };
         }
     }
 }
 
-impl<'a, T> Deref for Wrapper<'a, T> {
+impl<'a, T, I: Index> Deref for Shared<'a, T, I> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
         #[cfg(feature = "log")]
-        trace!("Wrapper::deref");
-
-        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
-        // memory.
-
-        let inner_allocator = unsafe { &*self.allocator.0.get() };
-
-        unsafe { &inner_allocator.data().as_ref()[self.index].full }
-    }
-}
-impl<'a, T> DerefMut for Wrapper<'a, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        #[cfg(feature = "log")]
-        trace!("Wrapper::deref_mut");
-
-        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
-        // memory.
-
-        let inner_allocator = unsafe { &mut *self.allocator.0.get() };
+        trace!("Shared::deref");
 
-        unsafe { &mut inner_allocator.data().as_mut()[self.index].full }
+        &self.counted().value
     }
 }
 
@@ -405,7 +2068,7 @@ impl<'a, T> DerefMut for Wrapper<'a, T> {
 mod tests {
     #![allow(clippy::pedantic)]
 
-    use std::mem::forget;
+    use std::mem::{forget, size_of};
     use std::time::{Duration, Instant};
 
     use rand::Rng;
@@ -431,6 +2094,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 10,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -456,6 +2131,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(1),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 9,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -481,6 +2168,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(2),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 8,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -506,6 +2205,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(3),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 7,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -531,6 +2242,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(4),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 6,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -556,6 +2279,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(5),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 5,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -581,6 +2316,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(6),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 4,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -606,6 +2353,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(7),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 3,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -631,6 +2390,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(8),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 2,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -656,6 +2427,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(9),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 1,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -681,6 +2464,18 @@ mod tests {
                 InnerAllocator {
                     head: None,
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 0,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -706,6 +2501,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(1),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 1,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -731,6 +2538,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(1),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 2,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -756,6 +2575,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(1),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 3,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -781,6 +2612,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(1),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 4,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -806,6 +2649,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 5,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -831,6 +2686,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 6,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -856,6 +2723,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 7,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -881,6 +2760,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 8,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -906,6 +2797,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 9,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -931,6 +2834,18 @@ mod tests {
                 InnerAllocator {
                     head: Some(0),
                     size: SIZE,
+                    oom_hook: None,
+                    watermarks: None,
+                    reserve: 0,
+                    free: 10,
+                    #[cfg(feature = "sanitizer")]
+                    poisoning: false,
+                    #[cfg(feature = "quarantine")]
+                    quarantine: crate::quarantine::Quarantine::default(),
+                    #[cfg(feature = "testing")]
+                    failure: crate::testing::FailureInjection::default(),
+                    #[cfg(feature = "latency")]
+                    latency: crate::latency::Latency::default(),
                     _marker: PhantomData
                 }
             );
@@ -949,6 +2864,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn slab_2() {
         const SIZE: usize = 100;
         const MAX: usize = 1_000_000;
@@ -1290,6 +3206,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn slab_small_index() {
+        assert_eq!(size_of::<Block<u8, u16>>(), 4);
+
+        let memory = ArrayAllocator::<3, u8, u16>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(2).unwrap();
+        let c = memory.allocate(3).unwrap();
+        assert!(memory.allocate(4).is_none());
+        drop(b);
+        let d = memory.allocate(5).unwrap();
+        assert_eq!(d.index(), 1);
+        assert_eq!((*a, *c, *d), (1, 3, 5));
+    }
+
     #[test]
     fn inner_allocator_debug() {
         assert_eq!(
@@ -1304,6 +3235,45 @@ mod tests {
         let _ = ArrayAllocator::<0, ()>::new(None);
     }
 
+    #[test]
+    fn check_integrity() {
+        use crate::integrity::{IntegrityError, IntegrityReport};
+
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        let a = allocator.allocate(1).unwrap();
+        let b = allocator.allocate(2).unwrap();
+        drop(a);
+        assert_eq!(
+            allocator.check_integrity(),
+            Ok(IntegrityReport {
+                free_regions: 3,
+                free: 3
+            })
+        );
+
+        // Corrupt the free list as a misbehaving process might.
+        let guard = allocator.0.lock().unwrap();
+        let data = unsafe { guard.data().as_mut() };
+        unsafe { data[3].set_next_free(Some(0)) };
+        drop(guard);
+        assert_eq!(
+            allocator.check_integrity(),
+            Err(IntegrityError::Unordered { prev: 3, index: 0 })
+        );
+        let guard = allocator.0.lock().unwrap();
+        let data = unsafe { guard.data().as_mut() };
+        unsafe { data[2].set_next_free(Some(7)) };
+        drop(guard);
+        assert_eq!(
+            allocator.check_integrity(),
+            Err(IntegrityError::OutOfBounds {
+                index: 7,
+                size: None
+            })
+        );
+        std::mem::forget(b);
+    }
+
     #[test]
     fn block_debug() {
         assert_eq!(
@@ -1323,6 +3293,513 @@ mod tests {
         assert_eq!(format!("{wrapper:?}"), expected);
     }
 
+    #[test]
+    fn owned_wrapper() {
+        let allocator = std::sync::Arc::new(ArrayAllocator::<2, u8>::new(None));
+        let mut a = OwnedWrapper::allocate(allocator.clone(), 1).unwrap();
+        let b = OwnedWrapper::allocate(allocator.clone(), 2).unwrap();
+        assert!(OwnedWrapper::allocate(allocator.clone(), 3).is_none());
+        *a = 4;
+        let a = std::thread::spawn(move || {
+            assert_eq!(*a, 4);
+            a
+        })
+        .join()
+        .unwrap();
+        assert_eq!((a.index(), b.index()), (0, 1));
+        drop(a);
+        assert_eq!(OwnedWrapper::allocate(allocator, 5).unwrap().index(), 0);
+    }
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send::<ArrayAllocator<1, u8>>();
+        assert_sync::<ArrayAllocator<1, u8>>();
+        assert_sync::<Allocator<std::cell::Cell<u8>>>();
+        assert_send::<Wrapper<u8>>();
+        assert_sync::<Wrapper<u8>>();
+        assert_send::<Wrapper<std::cell::Cell<u8>>>();
+        assert_send::<WrapperIterator<u8>>();
+        assert_send::<OwnedWrapper<u8, std::sync::Arc<ArrayAllocator<1, u8>>>>();
+        assert_sync::<OwnedWrapper<u8, std::sync::Arc<ArrayAllocator<1, u8>>>>();
+    }
+
+    #[test]
+    fn try_allocate() {
+        let allocator = ArrayAllocator::<1, u8>::new(None);
+        let a = allocator.try_allocate(1).unwrap();
+        assert_eq!(
+            allocator.try_allocate(2).unwrap_err(),
+            AllocError::OutOfMemory {
+                requested: 1,
+                largest_free: 0
+            }
+        );
+        drop(a);
+        assert!(allocator.try_allocate(2).is_ok());
+    }
+
+    #[test]
+    fn oom_hook() {
+        static CALLS: std::sync::Mutex<Vec<AllocRequest>> = std::sync::Mutex::new(Vec::new());
+
+        let allocator = ArrayAllocator::<1, u8>::new(None);
+        allocator.set_oom_hook(|request| CALLS.lock().unwrap().push(*request));
+        let _a = allocator.allocate(1).unwrap();
+        assert!(allocator.allocate(2).is_none());
+        assert_eq!(
+            *CALLS.lock().unwrap(),
+            [AllocRequest {
+                requested: 1,
+                stats: Stats {
+                    total: 1,
+                    free: 0,
+                    largest_free: 0,
+                    free_regions: 0,
+                    ..Stats::default()
+                }
+            }]
+        );
+        assert!(allocator.take_oom_hook().is_some());
+        assert!(allocator.allocate(3).is_none());
+        assert_eq!(CALLS.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reserve() {
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        allocator.set_reserve(2);
+        assert_eq!(allocator.reserve(), 2);
+        let a = allocator.allocate(0).unwrap();
+        let b = allocator.allocate(1).unwrap();
+        assert!(allocator.allocate(2).is_none());
+        let c = allocator
+            .allocate_with_priority(2, Priority::Critical)
+            .unwrap();
+        let d = allocator
+            .allocate_with_priority(3, Priority::Critical)
+            .unwrap();
+        assert_eq!(allocator.stats().free, 0);
+        drop(d);
+        drop(c);
+        drop(b);
+        assert_eq!(*allocator.allocate(1).unwrap(), 1);
+        drop(a);
+    }
+
+    #[test]
+    fn watermarks() {
+        use crate::raw::{Watermark, WatermarkEvent};
+
+        static EVENTS: std::sync::Mutex<Vec<WatermarkEvent>> = std::sync::Mutex::new(Vec::new());
+
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        allocator.set_watermarks(1, 3, |event| EVENTS.lock().unwrap().push(*event));
+        let a = allocator.allocate(0).unwrap();
+        let b = allocator.allocate(1).unwrap();
+        let c = allocator.allocate(2).unwrap();
+        drop(c);
+        let c = allocator.allocate(2).unwrap();
+        drop(c);
+        drop(b);
+        drop(a);
+        assert_eq!(
+            *EVENTS.lock().unwrap(),
+            [
+                WatermarkEvent {
+                    watermark: Watermark::High,
+                    used: 3,
+                    total: 4
+                },
+                WatermarkEvent {
+                    watermark: Watermark::Low,
+                    used: 1,
+                    total: 4
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn reserve_run() {
+        let memory = ArrayAllocator::<8, u32>::new(None);
+        let a = memory.allocate(0).unwrap();
+        let b = memory.allocate(1).unwrap();
+        let c = memory.allocate(2).unwrap();
+        drop(b);
+        assert!(memory.reserve_run(6).is_none());
+
+        let mut run = memory.reserve_run(3).unwrap();
+        assert_eq!((run.start(), run.len()), (3, 3));
+        assert_eq!(memory.stats().free, 3);
+        let d = run.push(3).unwrap();
+        let e = run.push(4).unwrap();
+        assert_eq!((d.index(), e.index(), *e), (3, 4, 4));
+        drop(run);
+        assert_eq!(memory.stats().free, 4);
+
+        let mut run = memory.reserve_run(1).unwrap();
+        assert_eq!(run.start(), 1);
+        let f = run.push(5).unwrap();
+        assert_eq!(run.push(6).unwrap_err(), 6);
+        assert_eq!(memory.reserve_run(0).unwrap().len(), 0);
+        drop((a, c, d, e, f));
+        assert_eq!(memory.stats().free, 8);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn allocate_async() {
+        use std::future::Future;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Counter(AtomicUsize);
+        impl Wake for Counter {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let memory = ArrayAllocator::<1, String>::new(None);
+        let a = memory.allocate(String::from("a")).unwrap();
+        let mut future = memory.allocate_async(String::from("b"));
+        assert!(std::pin::Pin::new(&mut future).poll(&mut cx).is_pending());
+        drop(a);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        let Poll::Ready(b) = std::pin::Pin::new(&mut future).poll(&mut cx) else {
+            panic!("allocation did not complete");
+        };
+        assert_eq!(*b, "b");
+    }
+
+    #[test]
+    fn free_slots() {
+        let memory = ArrayAllocator::<4, u32>::new(None);
+        let a = memory.allocate(0).unwrap();
+        let _b = memory.allocate(1).unwrap();
+        let _c = memory.allocate(2).unwrap();
+        drop(a);
+        let free = memory.free_slots();
+        let _d = memory.allocate(3).unwrap();
+        assert_eq!(free.collect::<Vec<_>>(), [0, 3]);
+        assert_eq!(memory.free_slots().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn slot_state() {
+        let memory = ArrayAllocator::<3, u32>::new(None);
+        let a = memory.allocate(0).unwrap();
+        let _b = memory.allocate(1).unwrap();
+        assert_eq!(memory.occupied_count(), 2);
+        drop(a);
+        assert_eq!(memory.slot_state(0), SlotState::Free);
+        assert_eq!(memory.slot_state(1), SlotState::Occupied);
+        assert_eq!(memory.slot_state(2), SlotState::Free);
+        assert_eq!(memory.occupied_count(), 1);
+    }
+
+    #[cfg(feature = "quarantine")]
+    #[test]
+    fn slot_state_quarantined() {
+        let memory = ArrayAllocator::<2, u32>::new(None);
+        memory.set_quarantine(1);
+        drop(memory.allocate(0).unwrap());
+        assert_eq!(memory.slot_state(0), SlotState::Quarantined);
+        assert_eq!(memory.occupied_count(), 0);
+    }
+
+    #[test]
+    fn key_of() {
+        let memory = ArrayAllocator::<4, u32>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let b = memory.allocate(2).unwrap();
+        assert_eq!(memory.key_of(&a), Some(a.index()));
+        assert_eq!(memory.key_of(&b), Some(b.index()));
+        assert_eq!(memory.key_of(&1), None);
+
+        let free = unsafe { &memory.data()[2].full };
+        assert_eq!(memory.key_of(free), None);
+
+        let memory = ArrayAllocator::<4, u8>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let within = unsafe { &*(&*a as *const u8).add(1) };
+        assert_eq!(memory.key_of(within), None);
+    }
+
+    #[test]
+    fn get_disjoint_mut() {
+        let mut memory = ArrayAllocator::<4, u32>::new(None);
+        let a_wrapper = memory.allocate(1).unwrap();
+        let b = memory.allocate(2).unwrap();
+        let c_wrapper = memory.allocate(3).unwrap();
+        let (a, c) = (a_wrapper.index(), c_wrapper.index());
+        drop(b);
+        // Forgetting the wrappers ends their borrows of the allocator.
+        std::mem::forget((a_wrapper, c_wrapper));
+        let [x, y] = memory.get_disjoint_mut([c, a]).unwrap();
+        std::mem::swap(x, y);
+        assert_eq!(
+            memory.get_disjoint_mut([a, c]).map(|[x, y]| (*x, *y)),
+            Some((3, 1))
+        );
+        assert!(memory.get_disjoint_mut([0, 0]).is_none());
+        assert!(memory.get_disjoint_mut([0, 1]).is_none());
+        assert!(memory.get_disjoint_mut([3]).is_none());
+        assert!(memory.get_disjoint_mut([4]).is_none());
+        assert!(memory.get_disjoint_mut([]).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "must be below")]
+    fn watermarks_inverted() {
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        allocator.set_watermarks(3, 3, |_| {});
+    }
+
+    #[test]
+    fn slab_aligned() {
+        #[repr(align(64))]
+        struct Aligned(u8);
+
+        let allocator = ArrayAllocator::<2, Aligned>::new(None);
+        let a = allocator.allocate(Aligned(1)).unwrap();
+        let b = allocator.allocate(Aligned(2)).unwrap();
+        assert_eq!(a.0, 1);
+        assert_eq!(b.0, 2);
+        assert_eq!(std::ptr::addr_of!(*a) as usize % 64, 0);
+        assert_eq!(
+            std::ptr::addr_of!(*a).cast::<u8>(),
+            std::ptr::addr_of!(allocator.data()[0]).cast::<u8>()
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn failure_injection() {
+        use crate::testing::FailureInjection;
+
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        allocator.set_failure_injection(FailureInjection::every(
+            std::num::NonZeroUsize::new(2).unwrap(),
+        ));
+        let _a = allocator.allocate(1).unwrap();
+        assert!(allocator.allocate(2).is_none());
+        let _b = allocator.allocate(3).unwrap();
+        assert_eq!(allocator.failure_injection().allocations(), 3);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn reference_model() {
+        use rand::Rng;
+
+        use crate::testing::{assert_equivalent, SlabModel};
+
+        let mut rng = rand::thread_rng();
+        let allocator = ArrayAllocator::<8, u32>::new(None);
+        let mut model = SlabModel::new(8);
+        let mut live = Vec::new();
+        for i in 0..1000 {
+            if live.is_empty() || rng.gen_bool(0.5) {
+                let wrapper = allocator.allocate(i);
+                assert_eq!(wrapper.as_ref().map(Wrapper::index), model.allocate());
+                live.extend(wrapper);
+            } else {
+                let wrapper = live.swap_remove(rng.gen_range(0..live.len()));
+                model.free(wrapper.index());
+                drop(wrapper);
+            }
+            assert_equivalent(&*allocator, &model);
+        }
+    }
+
+    #[cfg(feature = "sanitizer")]
+    #[test]
+    fn poisoning() {
+        let allocator = ArrayAllocator::<4, [u64; 4]>::new(None);
+        let a = allocator.allocate([1; 4]).unwrap();
+        allocator.set_poisoning(true);
+        let b = allocator.allocate([2; 4]).unwrap();
+        let c = allocator.allocate([3; 4]).unwrap();
+        drop(a);
+        drop(c);
+        let d = allocator.allocate([4; 4]).unwrap();
+        assert_eq!((*b, *d), ([2; 4], [4; 4]));
+        drop(b);
+        drop(d);
+        assert_eq!(allocator.stats().free, 4);
+        allocator.set_poisoning(false);
+    }
+
+    #[cfg(feature = "debug-fill")]
+    #[test]
+    fn debug_fill() {
+        use crate::raw::{RawArrayAllocator, ALLOCATED_FILL, FREED_FILL};
+
+        let allocator = ArrayAllocator::<2, [u8; 32]>::new(None);
+        let a = allocator.allocate_bytes(32).unwrap();
+        let ptr = unsafe { allocator.as_ptr(a) }.as_ptr();
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, 32) };
+        assert!(bytes.iter().all(|&byte| byte == ALLOCATED_FILL));
+        unsafe { allocator.free(a) };
+        // The start of the slot holds the free list link.
+        let link = std::mem::size_of::<Option<usize>>();
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, 32) };
+        assert!(bytes[link..].iter().all(|&byte| byte == FREED_FILL));
+    }
+
+    // Freed memory is then filled with `FREED_FILL`.
+    #[cfg(all(feature = "zeroize", not(feature = "debug-fill")))]
+    #[test]
+    fn zeroize() {
+        let allocator = ArrayAllocator::<2, [u8; 32]>::new(None);
+        let a = allocator.allocate([0xAB; 32]).unwrap();
+        let ptr = a.as_ptr();
+        drop(a);
+        // The start of the slot holds the free list link.
+        let link = std::mem::size_of::<Option<usize>>();
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, 32) };
+        assert!(bytes[link..].iter().all(|&byte| byte == 0));
+    }
+
+    #[cfg(feature = "debug-checks")]
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn double_free() {
+        use crate::raw::RawArrayAllocator;
+
+        let allocator = ArrayAllocator::<3, u64>::new(None);
+        let _a = allocator.allocate(1).unwrap();
+        let b = allocator.allocate_bytes(8).unwrap();
+        unsafe {
+            allocator.free(b);
+            allocator.free(b);
+        }
+    }
+
+    #[cfg(feature = "quarantine")]
+    #[test]
+    fn quarantine() {
+        let allocator = ArrayAllocator::<3, u8>::new(None);
+        allocator.set_quarantine(1);
+        let a = allocator.allocate(0).unwrap();
+        drop(a);
+        let b = allocator.allocate(1).unwrap();
+        assert_eq!(b.index(), 1);
+        // Evicts `a`.
+        drop(b);
+        assert_eq!(allocator.allocate(2).unwrap().index(), 0);
+        allocator.set_quarantine(0);
+        assert_eq!(allocator.stats().free, 3);
+    }
+
+    #[test]
+    fn display() {
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        let a = allocator.allocate(0).unwrap();
+        let _b = allocator.allocate(1).unwrap();
+        drop(a);
+        assert_eq!(
+            allocator.to_string(),
+            "1/4 slots used (16/64 bytes), 2 free regions, largest free region 2 slots"
+        );
+    }
+
+    #[test]
+    fn render_map() {
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        let _a = allocator.allocate(0).unwrap();
+        let b = allocator.allocate(1).unwrap();
+        let _c = allocator.allocate(2).unwrap();
+        drop(b);
+        assert_eq!(allocator.render_map(3), "0 [#.#]\n3 [.]\n");
+    }
+
+    #[test]
+    fn dump() {
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        let _a = allocator.allocate(0).unwrap();
+        let b = allocator.allocate(1).unwrap();
+        let _c = allocator.allocate(2).unwrap();
+        drop(b);
+        assert_eq!(
+            allocator.dump(),
+            "┌──────┬──────┬──────┬──────┐\n│used:1│free:1│used:1│free:1│\n└──────┴──────┴──────┴──────┘\n"
+        );
+    }
+
+    #[cfg(feature = "latency")]
+    #[test]
+    fn latency_histogram() {
+        let allocator = ArrayAllocator::<1, u8>::new(None);
+        let a = allocator.allocate(0).unwrap();
+        assert!(allocator.allocate(1).is_none());
+        drop(a);
+        let latency = allocator.latency_histogram();
+        assert_eq!(latency.allocate.count(), 2);
+        assert_eq!(latency.free.count(), 1);
+        assert_eq!(latency.lock_wait.count(), 3);
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn dump_live_allocations() {
+        let allocator = ArrayAllocator::<4, u8>::new(None);
+        let wrappers = (0..3)
+            .map(|x| (line!(), allocator.allocate(x).unwrap()))
+            .collect::<Vec<_>>();
+        let sites = allocator.dump_live_allocations();
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].location.line(), wrappers[0].0);
+        assert_eq!((sites[0].allocations, sites[0].blocks), (3, 3));
+        drop(wrappers);
+        assert!(allocator.dump_live_allocations().is_empty());
+    }
+
+    #[test]
+    fn allocator_stats() {
+        let memory = ArrayAllocator::<5, u8>::new(None);
+        let a = memory.allocate(0).unwrap();
+        let b = memory.allocate(1).unwrap();
+        let c = memory.allocate(2).unwrap();
+        drop(a);
+        drop(c);
+        assert_eq!(
+            memory.stats(),
+            Stats {
+                total: 5,
+                free: 4,
+                largest_free: 3,
+                free_regions: 2,
+                ..Stats::default()
+            }
+        );
+        drop(b);
+        assert_eq!(memory.stats().largest_free, 5);
+    }
+
+    #[test]
+    fn raw_array_allocator() {
+        let memory = ArrayAllocator::<1, u64>::new(None);
+        assert!(memory.allocate_bytes(memory.block_size() + 1).is_none());
+        let allocation = memory.allocate_bytes(8).unwrap();
+        assert_eq!(allocation, RawAllocation { index: 0, size: 1 });
+        assert!(memory.allocate_bytes(1).is_none());
+        unsafe {
+            memory.as_ptr(allocation).as_ptr().write_bytes(1, 8);
+            RawArrayAllocator::free(&*memory, allocation);
+        }
+        assert_eq!(memory.stats().free, 1);
+    }
+
     #[test]
     fn wrapper_allocator() {
         let allocator = ArrayAllocator::<1, ()>::new(None);
@@ -1384,7 +3861,7 @@ mod tests {
         let b = memory.allocate(()).unwrap();
         drop(a);
         drop(b);
-        drop(memory);
+        let _ = memory;
     }
     // `head < self.index` and `Some(next) = unsafe { inner_allocator.data[current].empty }`
     #[test]
@@ -1396,6 +3873,26 @@ mod tests {
         drop(a);
         drop(b);
         drop(c);
-        drop(memory);
+        let _ = memory;
+    }
+
+    #[test]
+    fn shared() {
+        let memory = ArrayAllocator::<2, Counted<std::rc::Rc<u32>>>::new(None);
+        let rc = std::rc::Rc::new(7);
+        let a = memory.allocate_shared(rc.clone()).unwrap();
+        let b = a.clone();
+        assert_eq!((a.count(), **b), (2, 7));
+        assert_eq!(memory.stats().free, 1);
+        drop(a);
+        assert_eq!(b.count(), 1);
+
+        // Handed to e.g. another process by index.
+        let index = b.into_index();
+        let c = unsafe { Shared::from_index(&memory, index) };
+        assert_eq!(c.count(), 1);
+        drop(c);
+        assert_eq!(memory.stats().free, 2);
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
     }
 }
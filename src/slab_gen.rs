@@ -0,0 +1,326 @@
+//! A [`crate::slab::Allocator`] whose allocations are addressed by [`Key`]s pairing a slot with a
+//! generation, so a key kept after its value is freed, e.g. by another process, is rejected rather
+//! than addressing a value later allocated in the same slot.
+//!
+//! Each slot has a generation counter following the slots, odd while the slot is occupied and
+//! even otherwise, incremented on every allocation and free. Counters wrap after 2<sup>31</sup>
+//! reuses of a slot.
+
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::collections::Key;
+use crate::error::{none_on_oom, AllocError};
+use crate::slab::Block;
+use crate::Index;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ArrayAllocator<const N: usize, T, I: Index = usize> {
+    allocator: Allocator<T, I>,
+    data: [Block<T, I>; N],
+    generations: [AtomicU32; N],
+}
+
+impl<const N: usize, T, I: Index> ArrayAllocator<N, T, I> {
+    #[must_use]
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::new");
+
+        // Zeroing `Self` would zero the lock, which is not a valid value for every backend. The
+        // data and generations are fully written by `Allocator::init`.
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        unsafe {
+            Allocator::init(
+                std::ptr::addr_of_mut!((*this.as_mut_ptr()).allocator),
+                attr,
+                N,
+            );
+            this.assume_init()
+        }
+    }
+}
+
+impl<const N: usize, T, I: Index> fmt::Display for ArrayAllocator<N, T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.allocator.fmt(f)
+    }
+}
+
+impl<const N: usize, T, I: Index> Deref for ArrayAllocator<N, T, I> {
+    type Target = Allocator<T, I>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+
+/// A slab allocator followed by its slots, then a generation counter per slot aligned for
+/// [`AtomicU32`].
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Allocator<T, I: Index = usize>(crate::slab::Allocator<T, I>);
+
+impl<T, I: Index> Allocator<T, I> {
+    /// Initializes `Self` at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for the allocator, its `size` slots and `size` generation counters.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the inner mutex or when `size` is greater than [`Index::MAX`].
+    pub unsafe fn init(ptr: *mut Self, attr: Option<crate::MutexAttr>, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::init");
+
+        crate::slab::Allocator::init(ptr.cast::<crate::slab::Allocator<T, I>>(), attr, size);
+        let generations = (*ptr).0.trailer::<AtomicU32>();
+        generations
+            .as_ptr()
+            .cast::<AtomicU32>()
+            .write_bytes(0, generations.len());
+    }
+
+    /// Returns the underlying slab allocator, e.g. for its statistics.
+    ///
+    /// Values allocated directly from it have no valid key.
+    #[must_use]
+    pub fn slab(&self) -> &crate::slab::Allocator<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::slab");
+
+        &self.0
+    }
+
+    fn generations(&self) -> &[AtomicU32] {
+        unsafe { self.0.trailer::<AtomicU32>().as_ref() }
+    }
+
+    /// Allocates a given `x`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate(&self, x: T) -> Option<Wrapper<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        none_on_oom(self.try_allocate(x))
+    }
+
+    /// Allocates a given `x`.
+    ///
+    /// # Errors
+    ///
+    /// When there are no free slots or when locking the mutex fails, in which case `x` is dropped.
+    pub fn try_allocate(&self, x: T) -> Result<Wrapper<T, I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate");
+
+        let inner = self.0.try_allocate(x)?;
+        self.generations()[inner.index()].fetch_add(1, Ordering::AcqRel);
+        Ok(Wrapper {
+            allocator: self,
+            inner: ManuallyDrop::new(inner),
+        })
+    }
+
+    /// Returns whether `key` addresses an occupied slot.
+    #[must_use]
+    pub fn contains_key(&self, key: Key) -> bool {
+        #[cfg(feature = "log")]
+        trace!("Allocator::contains_key");
+
+        key.generation % 2 == 1
+            && self
+                .generations()
+                .get(key.index)
+                .is_some_and(|generation| generation.load(Ordering::Acquire) == key.generation)
+    }
+
+    /// Returns the value of `key`, or `None` when the key is stale.
+    ///
+    /// # Safety
+    ///
+    /// The value must not be freed or written while the reference lives.
+    #[must_use]
+    pub unsafe fn get(&self, key: Key) -> Option<&T> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::get");
+
+        self.contains_key(key).then(|| {
+            let wrapper = ManuallyDrop::new(crate::slab::Wrapper::from_index(&self.0, key.index));
+            &*std::ptr::addr_of!(**wrapper)
+        })
+    }
+
+    /// Constructs a wrapper for the value of `key`, e.g. one given up with [`Wrapper::into_key`],
+    /// or returns `None` when the key is stale.
+    ///
+    /// # Safety
+    ///
+    /// The value must not be held by another wrapper.
+    #[must_use]
+    pub unsafe fn from_key(&self, key: Key) -> Option<Wrapper<T, I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::from_key");
+
+        self.contains_key(key).then(|| Wrapper {
+            allocator: self,
+            inner: ManuallyDrop::new(crate::slab::Wrapper::from_index(&self.0, key.index)),
+        })
+    }
+}
+
+impl<T, I: Index> fmt::Display for Allocator<T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<const N: usize, T, I: Index> AsRef<Allocator<T, I>> for ArrayAllocator<N, T, I> {
+    fn as_ref(&self) -> &Allocator<T, I> {
+        &self.allocator
+    }
+}
+
+/// A value in a slot of an [`Allocator`], freeing the slot and invalidating its key on drop.
+#[derive(Debug)]
+pub struct Wrapper<'a, T, I: Index = usize> {
+    allocator: &'a Allocator<T, I>,
+    inner: ManuallyDrop<crate::slab::Wrapper<'a, T, I>>,
+}
+
+impl<'a, T, I: Index> Wrapper<'a, T, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &'a Allocator<T, I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    /// Returns the key of the value.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::key");
+
+        let index = self.inner.index();
+        Key {
+            index,
+            generation: self.allocator.generations()[index].load(Ordering::Acquire),
+        }
+    }
+
+    /// Gives up the wrapper without freeing its slot, returning its key, see
+    /// [`Allocator::from_key`].
+    #[must_use]
+    pub fn into_key(self) -> Key {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::into_key");
+
+        let key = self.key();
+        // The inner wrapper is never dropped, so the slot stays occupied.
+        std::mem::forget(self);
+        key
+    }
+
+    /// Moves the value out of the slot, freeing the slot and invalidating its key.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::into_inner");
+
+        let mut this = ManuallyDrop::new(self);
+        this.invalidate();
+        unsafe { ManuallyDrop::take(&mut this.inner) }.into_inner()
+    }
+
+    fn invalidate(&self) {
+        self.allocator.generations()[self.inner.index()].fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+impl<'a, T, I: Index> Drop for Wrapper<'a, T, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop");
+
+        // The key is invalidated before the slot is freed so it never addresses a later value.
+        self.invalidate();
+        unsafe {
+            ManuallyDrop::drop(&mut self.inner);
+        }
+    }
+}
+
+impl<'a, T, I: Index> Deref for Wrapper<'a, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref");
+
+        &self.inner
+    }
+}
+
+impl<'a, T, I: Index> DerefMut for Wrapper<'a, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut");
+
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn slab_gen() {
+        let memory = ArrayAllocator::<2, u64>::new(None);
+        let a = memory.allocate(1).unwrap();
+        let key = a.key();
+        assert_eq!(key.generation, 1);
+        assert_eq!(unsafe { memory.get(key) }, Some(&1));
+        drop(a);
+        assert!(!memory.contains_key(key));
+        assert_eq!(unsafe { memory.get(key) }, None);
+
+        // The slot is reused with a new generation, so the stale key stays rejected.
+        let b = memory.allocate(2).unwrap();
+        assert_eq!(b.key().index, key.index);
+        assert_eq!(b.key().generation, 3);
+        assert_eq!(unsafe { memory.get(key) }, None);
+        assert!(unsafe { memory.from_key(key) }.is_none());
+
+        let key = b.into_key();
+        assert_eq!(memory.slab().stats().free, 1);
+        let b = unsafe { memory.from_key(key) }.unwrap();
+        assert_eq!(b.into_inner(), 2);
+        assert!(!memory.contains_key(key));
+        assert_eq!(memory.slab().stats().free, 2);
+
+        let c = memory.allocate(3).unwrap();
+        let d = memory.allocate(4).unwrap();
+        assert!(memory.allocate(5).is_none());
+        assert_ne!(c.key(), d.key());
+        assert!(!memory.contains_key(Key {
+            index: 2,
+            generation: 1
+        }));
+    }
+}
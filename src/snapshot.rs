@@ -0,0 +1,289 @@
+//! Point in time copies of [`crate::linked_list::Allocator`]s, see
+//! [`crate::linked_list::Allocator::snapshot_cow`].
+//!
+//! A snapshot holds a copy of the allocator and its blocks taken while the allocator was locked,
+//! so it is consistent, and is itself an allocator, so e.g. an analyzer can walk or allocate from
+//! it while producers keep allocating from the original.
+//!
+//! On Linux the copy is written to a `memfd` which the snapshot maps copy-on-write, so writes to
+//! the snapshot leave the file untouched. The file can be passed to an analyzer process, which
+//! maps its own copy-on-write view of the same point in time with [`Snapshot::from_fd`].
+
+use std::alloc::Layout;
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::ptr::NonNull;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::Allocator;
+use crate::Index;
+
+/// Where the memory of a [`Snapshot`] comes from.
+#[derive(Debug)]
+pub(crate) enum Backing {
+    /// Allocated by the global allocator.
+    Heap,
+    /// Mapped from a `memfd`.
+    #[cfg(target_os = "linux")]
+    Memfd(OwnedFd),
+}
+
+/// A copy of an allocator which owns its memory.
+///
+/// Allocations live at the same byte offsets as in the original, see
+/// [`crate::linked_list::Allocator::ptr_at`].
+#[derive(Debug)]
+pub struct Snapshot<I: Index = usize> {
+    ptr: NonNull<Allocator<I>>,
+    layout: Layout,
+    backing: Backing,
+}
+
+impl<I: Index> Snapshot<I> {
+    /// Allocates memory for a snapshot of `layout`, mapped shared from a new `memfd` on Linux and
+    /// otherwise, or when that fails, from the global allocator.
+    pub(crate) fn allocate(layout: Layout) -> (NonNull<u8>, Backing) {
+        #[cfg(feature = "log")]
+        trace!("Snapshot::allocate");
+
+        #[cfg(target_os = "linux")]
+        if let Some((ptr, fd)) = map_memfd(layout.size()) {
+            return (ptr, Backing::Memfd(fd));
+        }
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        let Some(ptr) = NonNull::new(ptr) else {
+            std::alloc::handle_alloc_error(layout);
+        };
+        (ptr, Backing::Heap)
+    }
+
+    /// Takes ownership of the snapshot written to `ptr`, remapping a `memfd` copy-on-write so the
+    /// file keeps the point in time copy.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be an initialized allocator in memory returned by [`Snapshot::allocate`] for
+    /// `layout` along with `backing`.
+    ///
+    /// # Panics
+    ///
+    /// When remapping the `memfd` fails.
+    pub(crate) unsafe fn new(ptr: NonNull<Allocator<I>>, layout: Layout, backing: Backing) -> Self {
+        #[cfg(feature = "log")]
+        trace!("Snapshot::new");
+
+        #[cfg(target_os = "linux")]
+        if let Backing::Memfd(fd) = &backing {
+            map_private(
+                fd.as_raw_fd(),
+                layout.size(),
+                libc::MAP_FIXED,
+                ptr.as_ptr().cast(),
+            )
+            .unwrap_or_else(|err| panic!("failed to map snapshot copy-on-write: {err}"));
+        }
+        Self {
+            ptr,
+            layout,
+            backing,
+        }
+    }
+
+    /// Maps a copy-on-write view of the snapshot in `fd`, e.g. passed from another process by
+    /// [`Snapshot::fd`].
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be the file of a [`Snapshot`] of an allocator of `I`.
+    ///
+    /// # Errors
+    ///
+    /// When reading the size of or mapping `fd` fails.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn from_fd(fd: OwnedFd) -> std::io::Result<Self> {
+        #[cfg(feature = "log")]
+        trace!("Snapshot::from_fd");
+
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+        if libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let invalid = |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+        let size =
+            usize::try_from(stat.assume_init().st_size).map_err(|err| invalid(err.to_string()))?;
+        let layout = Layout::from_size_align(size, std::mem::align_of::<Allocator<I>>())
+            .map_err(|err| invalid(err.to_string()))?;
+        let ptr = map_private(fd.as_raw_fd(), size, 0, std::ptr::null_mut())?;
+        Ok(Self {
+            ptr: ptr.cast(),
+            layout,
+            backing: Backing::Memfd(fd),
+        })
+    }
+
+    /// Returns the `memfd` holding the point in time copy, which another process can map with
+    /// [`Snapshot::from_fd`], or `None` when the snapshot was allocated on the heap.
+    ///
+    /// Writes through this snapshot, e.g. allocating from it, are not seen through the file.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        #[cfg(feature = "log")]
+        trace!("Snapshot::fd");
+
+        match &self.backing {
+            Backing::Memfd(fd) => Some(std::os::fd::AsFd::as_fd(fd)),
+            Backing::Heap => None,
+        }
+    }
+}
+
+impl<I: Index> std::ops::Deref for Snapshot<I> {
+    type Target = Allocator<I>;
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Snapshot::deref");
+
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<I: Index> Drop for Snapshot<I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Snapshot::drop");
+
+        match self.backing {
+            Backing::Heap => unsafe { std::alloc::dealloc(self.ptr.as_ptr().cast(), self.layout) },
+            #[cfg(target_os = "linux")]
+            Backing::Memfd(_) => unsafe {
+                libc::munmap(self.ptr.as_ptr().cast(), self.layout.size());
+            },
+        }
+    }
+}
+
+/// Creates a `memfd` of `len` bytes and maps it shared, returning `None` when that fails, e.g. as
+/// `memfd_create` is unavailable.
+#[cfg(target_os = "linux")]
+fn map_memfd(len: usize) -> Option<(NonNull<u8>, OwnedFd)> {
+    let fd =
+        unsafe { libc::memfd_create(c"array-allocators-snapshot".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return None;
+    }
+    let fd = unsafe { <OwnedFd as std::os::fd::FromRawFd>::from_raw_fd(fd) };
+    if unsafe { libc::ftruncate(fd.as_raw_fd(), libc::off_t::try_from(len).ok()?) } != 0 {
+        return None;
+    }
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return None;
+    }
+    Some((NonNull::new(ptr.cast())?, fd))
+}
+
+/// Maps the `len` bytes of `fd` copy-on-write at `addr`, or anywhere when `addr` is null.
+#[cfg(target_os = "linux")]
+fn map_private(
+    fd: std::os::fd::RawFd,
+    len: usize,
+    flags: libc::c_int,
+    addr: *mut libc::c_void,
+) -> std::io::Result<NonNull<u8>> {
+    let ptr = unsafe {
+        libc::mmap(
+            addr,
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | flags,
+            fd,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(NonNull::new(ptr.cast()).unwrap())
+}
+
+// As with `Allocator`, the lock provides the synchronization.
+unsafe impl<I: Index + Send> Send for Snapshot<I> {}
+unsafe impl<I: Index + Send> Sync for Snapshot<I> {}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use crate::linked_list::{ArrayAllocator, OutOfBandArrayAllocator};
+
+    #[test]
+    fn snapshot() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut value = memory.allocate_value::<u32>().unwrap();
+        *value = 1;
+        let offset = value.byte_offset();
+
+        let snapshot = memory.snapshot_cow();
+        assert_eq!(snapshot.stats(), memory.stats());
+        *value = 2;
+        let read = |ptr: std::ptr::NonNull<u8>| unsafe { ptr.cast::<u32>().read() };
+        assert_eq!(read(snapshot.ptr_at(offset).unwrap()), 1);
+        assert_eq!(read(memory.ptr_at(offset).unwrap()), 2);
+        drop(value);
+
+        // The snapshot is an independent allocator.
+        let free = snapshot.stats().free;
+        let other = snapshot.allocate(2).unwrap();
+        assert_eq!(snapshot.stats().free, free - 2);
+        assert_eq!(memory.stats().free, 8);
+        drop(other);
+    }
+
+    #[test]
+    fn snapshot_out_of_band() {
+        let memory = OutOfBandArrayAllocator::<4>::new(None);
+        let _a = memory.allocate(1).unwrap();
+        let snapshot = memory.snapshot_cow();
+        assert_eq!(snapshot.stats(), memory.stats());
+        let b = snapshot.allocate(3).unwrap();
+        assert_eq!(snapshot.stats().free, 0);
+        drop(b);
+        assert_eq!(snapshot.stats().free, 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn snapshot_cow_fd() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let mut value = memory.allocate_value::<u32>().unwrap();
+        *value = 1;
+        let offset = value.byte_offset();
+
+        let snapshot = memory.snapshot_cow();
+        let fd = snapshot.fd().unwrap().try_clone_to_owned().unwrap();
+        // Writes to the snapshot are not seen through its file.
+        let other = snapshot.allocate(7).unwrap();
+        unsafe { snapshot.ptr_at(offset).unwrap().cast::<u32>().write(2) };
+
+        let view = unsafe { super::Snapshot::<usize>::from_fd(fd) }.unwrap();
+        assert_eq!(view.stats(), memory.stats());
+        assert_eq!(
+            unsafe { view.ptr_at(offset).unwrap().cast::<u32>().read() },
+            1
+        );
+        drop(other);
+    }
+}
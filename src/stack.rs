@@ -0,0 +1,452 @@
+//! A double-ended stack allocator, which allocates from either end of the same array so e.g.
+//! short-lived data can be allocated from one end and long-lived data from the other without
+//! splitting the memory between two allocators.
+//!
+//! Each end is a stack, allocating bumps its top towards the other end and the space between the
+//! tops is free. Freeing the top allocation of an end pops it, while freeing an allocation below
+//! the top only marks it, so its blocks are reclaimed once every allocation above it is freed.
+//! Both ends of every allocation are tagged with its extent, stored apart from the data blocks,
+//! so popping runs of freed allocations takes O(1) per allocation.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut, Drop};
+use std::ptr::NonNull;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::error::{none_on_oom, AllocError};
+use crate::Index;
+
+/// An end of the array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    /// Allocates upwards from the first block.
+    Low,
+    /// Allocates downwards from the last block.
+    High,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ArrayAllocator<const N: usize, I = usize> {
+    allocator: Allocator<I>,
+    data: [Block<I>; N],
+    tags: [Tag<I>; N],
+}
+impl<const N: usize, I: Index> ArrayAllocator<N, I> {
+    #[must_use]
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::new");
+
+        // See `linked_list::ArrayAllocator::new`.
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        let ptr = this.as_mut_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(0, 1);
+            std::ptr::addr_of_mut!((*ptr).tags).write_bytes(0, 1);
+            Allocator::init(std::ptr::addr_of_mut!((*ptr).allocator), attr, N);
+            this.assume_init()
+        }
+    }
+}
+
+impl<const N: usize, I> Deref for ArrayAllocator<N, I> {
+    type Target = Allocator<I>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+impl<const N: usize, I> DerefMut for ArrayAllocator<N, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.allocator
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Allocator<I = usize>(crate::mutex::Mutex<InnerAllocator<I>>);
+
+impl<I: Index> Allocator<I> {
+    /// Initializes `Self` at `ptr`.
+    ///
+    /// `ptr` must be followed by `n` data blocks then `n` tags.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the inner mutex or when `n` is greater than [`Index::MAX`].
+    pub unsafe fn init(ptr: *mut Self, attr: Option<crate::MutexAttr>, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::init");
+
+        std::ptr::addr_of_mut!((*ptr).0.lock).write(crate::mutex::RawMutex::new(attr).unwrap());
+        #[cfg(feature = "watchdog")]
+        std::ptr::addr_of_mut!((*ptr).0.owner).write(crate::watchdog::Owner::new());
+        <InnerAllocator<I>>::init((*ptr).0.get(), n);
+    }
+
+    /// Allocates a given number of blocks from a given end.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate(&self, end: End, blocks: usize) -> Option<Wrapper<I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        none_on_oom(self.try_allocate(end, blocks))
+    }
+
+    /// Allocates a given number of blocks from a given end.
+    ///
+    /// # Errors
+    ///
+    /// When there are fewer than `blocks` blocks between the tops of the ends or when locking the
+    /// mutex fails.
+    pub fn try_allocate(&self, end: End, blocks: usize) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate");
+
+        if blocks == 0 {
+            return Ok(Wrapper {
+                allocator: self,
+                end,
+                index: 0,
+                size: 0,
+            });
+        }
+        let mut inner_allocator = self.0.lock().map_err(AllocError::LockFailed)?;
+        match inner_allocator.allocate(end, blocks) {
+            Some(index) => Ok(Wrapper {
+                allocator: self,
+                end,
+                index,
+                size: blocks,
+            }),
+            None => Err(AllocError::OutOfMemory {
+                requested: blocks,
+                largest_free: inner_allocator.free(),
+            }),
+        }
+    }
+
+    /// Returns the number of blocks between the tops of the ends.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn free(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free");
+
+        self.0.lock().unwrap().free()
+    }
+
+    /// Returns the number of blocks used by an end, including freed allocations not yet popped.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn used(&self, end: End) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::used");
+
+        let inner_allocator = self.0.lock().unwrap();
+        match end {
+            End::Low => inner_allocator.low,
+            End::High => inner_allocator.size - inner_allocator.high,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct InnerAllocator<I = usize> {
+    /// The top of the low end, the first block not allocated from it.
+    low: usize,
+    /// The top of the high end, the last block allocated from it.
+    high: usize,
+    size: usize,
+    _marker: PhantomData<I>,
+}
+
+impl<I: Index> InnerAllocator<I> {
+    /// Returns the data blocks.
+    ///
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    ///
+    /// # Panics
+    ///
+    /// When the pointer to the data blocks is null.
+    pub unsafe fn data(&mut self) -> NonNull<[Block<I>]> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::data");
+
+        NonNull::slice_from_raw_parts(
+            NonNull::new((self as *mut Self).add(1).cast::<Block<I>>()).unwrap(),
+            self.size,
+        )
+    }
+
+    /// Returns the tags, which follow the data blocks.
+    unsafe fn tags<'b>(&mut self) -> &'b mut [Tag<I>] {
+        let tags = self.data().as_ptr().cast::<Block<I>>().add(self.size);
+        std::slice::from_raw_parts_mut(tags.cast::<Tag<I>>(), self.size)
+    }
+
+    unsafe fn init(ptr: *mut Self, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::init");
+
+        assert!(n <= I::MAX, "{n} blocks cannot be indexed by {}", I::MAX);
+
+        (*ptr).low = 0;
+        (*ptr).high = n;
+        (*ptr).size = n;
+        std::ptr::addr_of_mut!((*ptr)._marker).write(PhantomData);
+    }
+
+    fn free(&self) -> usize {
+        self.high - self.low
+    }
+
+    /// Allocates `blocks` from the top of `end`, returning its index.
+    fn allocate(&mut self, end: End, blocks: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::allocate");
+
+        if blocks > self.free() {
+            return None;
+        }
+        let start = match end {
+            End::Low => {
+                self.low += blocks;
+                self.low - blocks
+            }
+            End::High => {
+                self.high -= blocks;
+                self.high
+            }
+        };
+        let tags = unsafe { self.tags() };
+        let tag = Tag {
+            start: I::from_usize(start),
+            size: I::from_usize(blocks),
+            freed: false,
+        };
+        tags[start] = tag;
+        tags[start + blocks - 1] = tag;
+        Some(start)
+    }
+
+    /// Frees the `size` blocks at `index` allocated from `end`, popping every freed allocation at
+    /// the top of `end`.
+    fn deallocate(&mut self, end: End, index: usize, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::deallocate");
+
+        let tags = unsafe { self.tags() };
+        tags[index].freed = true;
+        tags[index + size - 1].freed = true;
+        match end {
+            // The tag below the top of the low end is the last tag of its top allocation.
+            End::Low => {
+                while self.low > 0 && tags[self.low - 1].freed {
+                    self.low = tags[self.low - 1].start.to_usize();
+                }
+            }
+            // The tag at the top of the high end is the first tag of its top allocation.
+            End::High => {
+                while self.high < self.size && tags[self.high].freed {
+                    self.high += tags[self.high].size.to_usize();
+                }
+            }
+        }
+    }
+}
+
+/// A data block, sized and aligned as a [`crate::linked_list::Block`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Block<I = usize>([I; 2]);
+
+/// The tag at either end of an allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Tag<I = usize> {
+    start: I,
+    size: I,
+    /// Whether the allocation was freed but not yet popped.
+    freed: bool,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Wrapper<'a, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    end: End,
+    index: usize,
+    size: usize,
+}
+
+impl<'a, I: Index> Wrapper<'a, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    /// Returns the end the blocks were allocated from.
+    #[must_use]
+    pub fn end(&self) -> End {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::end");
+
+        self.end
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index");
+
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::size");
+
+        self.size
+    }
+}
+
+// See `linked_list::Wrapper`.
+unsafe impl<'a, I: Index> Send for Wrapper<'a, I> {}
+unsafe impl<'a, I: Index> Sync for Wrapper<'a, I> {}
+
+impl<'a, I: Index> Deref for Wrapper<'a, I> {
+    type Target = [Block<I>];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe { &inner_allocator.data().as_ref()[self.index..self.index + self.size] }
+    }
+}
+impl<'a, I: Index> DerefMut for Wrapper<'a, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut");
+
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe { &mut inner_allocator.data().as_mut()[self.index..self.index + self.size] }
+    }
+}
+
+impl<'a, I: Index> Drop for Wrapper<'a, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop");
+
+        if self.size == 0 {
+            return;
+        }
+        self.allocator
+            .0
+            .lock()
+            .unwrap()
+            .deallocate(self.end, self.index, self.size);
+    }
+}
+
+impl<I: Index> fmt::Display for Allocator<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner_allocator = self.0.lock().map_err(|_| fmt::Error)?;
+        write!(
+            f,
+            "{}/{} blocks free ({} bytes per block)",
+            inner_allocator.free(),
+            inner_allocator.size,
+            size_of::<Block<I>>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn stack_ends() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(End::Low, 2).unwrap();
+        let b = memory.allocate(End::High, 3).unwrap();
+        let c = memory.allocate(End::Low, 1).unwrap();
+        assert_eq!((a.index(), b.index(), c.index()), (0, 5, 2));
+        assert_eq!((memory.used(End::Low), memory.used(End::High)), (3, 3));
+        assert_eq!(memory.to_string(), "2/8 blocks free (16 bytes per block)");
+        assert_eq!(
+            memory.try_allocate(End::High, 3).unwrap_err(),
+            AllocError::OutOfMemory {
+                requested: 3,
+                largest_free: 2
+            }
+        );
+        let d = memory.allocate(End::High, 2).unwrap();
+        assert_eq!(d.index(), 3);
+        assert_eq!(memory.free(), 0);
+        drop(d);
+        drop(c);
+        drop(b);
+        assert_eq!(memory.free(), 6);
+        drop(a);
+        assert_eq!(memory.free(), 8);
+    }
+
+    #[test]
+    fn stack_out_of_order() {
+        let memory = ArrayAllocator::<8, u16>::new(None);
+        let a = memory.allocate(End::Low, 1).unwrap();
+        let b = memory.allocate(End::Low, 2).unwrap();
+        let c = memory.allocate(End::Low, 1).unwrap();
+        let x = memory.allocate(End::High, 1).unwrap();
+        let y = memory.allocate(End::High, 2).unwrap();
+
+        // Allocations below the top are reclaimed once those above are freed.
+        drop(b);
+        assert_eq!(memory.used(End::Low), 4);
+        drop(c);
+        assert_eq!(memory.used(End::Low), 1);
+        drop(x);
+        assert_eq!(memory.used(End::High), 3);
+        drop(y);
+        assert_eq!(memory.used(End::High), 0);
+        drop(a);
+        assert_eq!(memory.free(), 8);
+        assert_eq!(memory.allocate(End::High, 8).unwrap().index(), 0);
+        assert_eq!(memory.allocate(End::Low, 0).unwrap().size(), 0);
+    }
+}
@@ -0,0 +1,413 @@
+//! C and UTF-8 strings allocated within a [`crate::linked_list::Allocator`].
+
+use std::ffi::{c_char, CStr, FromBytesWithNulError};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::str::Utf8Error;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::linked_list::{Allocator, Slice, Vec};
+use crate::Index;
+
+/// A NUL-terminated string allocated within a [`crate::linked_list::Allocator`], e.g. to hand to
+/// C APIs.
+pub struct ArenaCStr<'a, I: Index = usize>(Slice<'a, u8, I>);
+
+impl<'a, I: Index> ArenaCStr<'a, I> {
+    /// Converts bytes already allocated within an allocator, which must end with their only NUL.
+    ///
+    /// # Errors
+    ///
+    /// When the bytes contain an interior NUL or do not end with a NUL.
+    pub fn from_bytes_with_nul(bytes: Slice<'a, u8, I>) -> Result<Self, FromBytesWithNulError> {
+        #[cfg(feature = "log")]
+        trace!("ArenaCStr::from_bytes_with_nul");
+
+        CStr::from_bytes_with_nul(&bytes)?;
+        Ok(Self(bytes))
+    }
+
+    /// Returns a pointer to the NUL-terminated string, valid for as long as `self`.
+    #[must_use]
+    pub fn as_c_ptr(&self) -> *const c_char {
+        self.0.as_ptr().cast()
+    }
+
+    #[must_use]
+    pub fn as_c_str(&self) -> &CStr {
+        unsafe { CStr::from_bytes_with_nul_unchecked(&self.0) }
+    }
+
+    /// Returns the underlying bytes, including the NUL.
+    #[must_use]
+    pub fn into_bytes_with_nul(self) -> Slice<'a, u8, I> {
+        self.0
+    }
+}
+
+impl<'a, I: Index> Deref for ArenaCStr<'a, I> {
+    type Target = CStr;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_c_str()
+    }
+}
+
+impl<'a, I: Index> AsRef<CStr> for ArenaCStr<'a, I> {
+    fn as_ref(&self) -> &CStr {
+        self.as_c_str()
+    }
+}
+
+impl<'a, I: Index> fmt::Debug for ArenaCStr<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_c_str(), f)
+    }
+}
+
+/// A UTF-8 string allocated within a [`crate::linked_list::Allocator`].
+pub struct ArenaStr<'a, I: Index = usize>(Slice<'a, u8, I>);
+
+impl<'a, I: Index> ArenaStr<'a, I> {
+    /// Converts bytes already allocated within an allocator, which must be valid UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// When the bytes are not valid UTF-8, returning them alongside the error.
+    pub fn from_utf8(bytes: Slice<'a, u8, I>) -> Result<Self, (Slice<'a, u8, I>, Utf8Error)> {
+        #[cfg(feature = "log")]
+        trace!("ArenaStr::from_utf8");
+
+        match std::str::from_utf8(&bytes) {
+            Ok(_) => Ok(Self(bytes)),
+            Err(err) => Err((bytes, err)),
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    #[must_use]
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { std::str::from_utf8_unchecked_mut(&mut self.0) }
+    }
+
+    /// Returns the underlying bytes.
+    #[must_use]
+    pub fn into_bytes(self) -> Slice<'a, u8, I> {
+        self.0
+    }
+}
+
+impl<'a, I: Index> Deref for ArenaStr<'a, I> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<'a, I: Index> DerefMut for ArenaStr<'a, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_str()
+    }
+}
+
+impl<'a, I: Index> AsRef<str> for ArenaStr<'a, I> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'a, I: Index> PartialEq<str> for ArenaStr<'a, I> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a, I: Index> PartialEq<&str> for ArenaStr<'a, I> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<'a, I: Index> fmt::Debug for ArenaStr<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<'a, I: Index> fmt::Display for ArenaStr<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+/// A growable UTF-8 string allocated within a [`crate::linked_list::Allocator`], resizing its
+/// bytes to twice their capacity when full.
+///
+/// `From<&str>` cannot be implemented as allocating requires an allocator, see
+/// [`ArenaString::from_str_in`].
+pub struct ArenaString<'a, I: Index = usize>(Vec<'a, u8, I>);
+
+impl<'a, I: Index> ArenaString<'a, I> {
+    /// Constructs an empty string within `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn new_in(allocator: &'a Allocator<I>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::new_in");
+
+        Self(Vec::new_in(allocator))
+    }
+
+    /// Allocates a copy of `s` within `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    #[must_use]
+    pub fn from_str_in(s: &str, allocator: &'a Allocator<I>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::from_str_in");
+
+        Self::try_from_str_in(s, allocator).expect("memory allocation failed")
+    }
+
+    /// Allocates a copy of `s` within `allocator`, returning `None` when out of memory.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn try_from_str_in(s: &str, allocator: &'a Allocator<I>) -> Option<Self> {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::try_from_str_in");
+
+        let mut string = Self(Vec::try_with_capacity_in(s.len(), allocator)?);
+        string.0.extend_from_slice(s.as_bytes());
+        Some(string)
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::capacity");
+
+        self.0.capacity()
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    #[must_use]
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { std::str::from_utf8_unchecked_mut(&mut self.0) }
+    }
+
+    /// Appends `s`, returning `None` when out of memory, in which case nothing is appended.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn try_push_str(&mut self, s: &str) -> Option<()> {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::try_push_str");
+
+        self.0.try_reserve(s.len())?;
+        self.0.extend_from_slice(s.as_bytes());
+        Some(())
+    }
+
+    /// Appends `s`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    pub fn push_str(&mut self, s: &str) {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::push_str");
+
+        self.try_push_str(s).expect("memory allocation failed");
+    }
+
+    /// Appends `c`.
+    ///
+    /// # Panics
+    ///
+    /// When out of memory or when locking the mutex fails.
+    pub fn push(&mut self, c: char) {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::push");
+
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Removes the last character and returns it, or `None` if empty.
+    pub fn pop(&mut self) -> Option<char> {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::pop");
+
+        let c = self.chars().next_back()?;
+        self.0.truncate(self.0.len() - c.len_utf8());
+        Some(c)
+    }
+
+    /// Removes all characters, keeping the capacity.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("ArenaString::clear");
+
+        self.0.clear();
+    }
+
+    /// Returns the underlying bytes.
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<'a, u8, I> {
+        self.0
+    }
+}
+
+impl<'a, I: Index> Deref for ArenaString<'a, I> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<'a, I: Index> DerefMut for ArenaString<'a, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_str()
+    }
+}
+
+impl<'a, I: Index> AsRef<str> for ArenaString<'a, I> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'a, I: Index> PartialEq<str> for ArenaString<'a, I> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a, I: Index> PartialEq<&str> for ArenaString<'a, I> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<'a, I: Index> fmt::Write for ArenaString<'a, I> {
+    /// Fails when out of memory.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).ok_or(fmt::Error)
+    }
+}
+
+impl<'a, I: Index> fmt::Debug for ArenaString<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<'a, I: Index> fmt::Display for ArenaString<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn allocate_cstr() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let s = memory.allocate_cstr("hello").unwrap();
+        assert_eq!(s.to_bytes_with_nul(), b"hello\0");
+        assert_eq!(unsafe { CStr::from_ptr(s.as_c_ptr()) }, c"hello");
+        assert_eq!(format!("{s:?}"), "\"hello\"");
+        assert!(memory.allocate_cstr("a\0b").is_none());
+        assert_eq!(memory.allocate_cstr("").unwrap().to_bytes(), b"");
+    }
+
+    #[test]
+    fn cstr_from_bytes_with_nul() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut bytes = memory.allocate_slice::<u8>(3).unwrap();
+        bytes.copy_from_slice(b"ab\0");
+        let s = ArenaCStr::from_bytes_with_nul(bytes).unwrap();
+        assert_eq!(s.as_c_str(), c"ab");
+        assert_eq!(s.into_bytes_with_nul().len(), 3);
+
+        let mut bytes = memory.allocate_slice::<u8>(3).unwrap();
+        bytes.copy_from_slice(b"a\0b");
+        assert!(ArenaCStr::from_bytes_with_nul(bytes).is_err());
+        assert_eq!(memory.stats().free, 4);
+    }
+
+    #[test]
+    fn allocate_str() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut s = memory.allocate_str("héllo").unwrap();
+        assert_eq!(s, "héllo");
+        s.make_ascii_uppercase();
+        assert_eq!(s.to_string(), "HéLLO");
+        assert_eq!(format!("{s:?}"), "\"HéLLO\"");
+        assert_eq!(memory.allocate_str("").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn str_from_utf8() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut bytes = memory.allocate_slice::<u8>(2).unwrap();
+        bytes.copy_from_slice(b"ok");
+        assert_eq!(ArenaStr::from_utf8(bytes).unwrap(), "ok");
+
+        let mut bytes = memory.allocate_slice::<u8>(2).unwrap();
+        bytes.copy_from_slice(&[b'a', 0xff]);
+        let (bytes, err) = ArenaStr::from_utf8(bytes).unwrap_err();
+        assert_eq!(err.valid_up_to(), 1);
+        assert_eq!(&bytes[..], &[b'a', 0xff]);
+    }
+
+    #[test]
+    fn string() {
+        use std::fmt::Write;
+
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut s = memory.allocate_string("hé").unwrap();
+        assert_eq!(s, "hé");
+        s.push_str("llo");
+        s.push('!');
+        write!(s, " {}", 1).unwrap();
+        assert_eq!(s.to_string(), "héllo! 1");
+        assert_eq!(format!("{s:?}"), "\"héllo! 1\"");
+        assert_eq!(s.pop(), Some('1'));
+        s.make_ascii_uppercase();
+        assert_eq!(s, "HéLLO! ");
+        assert!(s.try_push_str(&"x".repeat(1000)).is_none());
+        assert_eq!(s, "HéLLO! ");
+        s.clear();
+        assert_eq!(s.pop(), None);
+        drop(s);
+        assert_eq!(memory.stats().free, 4);
+
+        let s = crate::linked_list::String::new_in(&memory);
+        assert_eq!(s.capacity(), 0);
+    }
+}
@@ -0,0 +1,264 @@
+use std::num::NonZeroUsize;
+
+/// Configures allocators to artificially fail allocations, so out of memory handling can be
+/// exercised deterministically.
+///
+/// Injected failures are reported in the same way as real ones, e.g. as
+/// [`crate::AllocError::OutOfMemory`] and through the out of memory hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FailureInjection {
+    fail_every: Option<NonZeroUsize>,
+    byte_budget: Option<usize>,
+    allocations: usize,
+    allocated: usize,
+}
+
+impl FailureInjection {
+    /// Fails every `fail_every`th allocation and every allocation which would take the total
+    /// number of bytes allocated over `byte_budget`.
+    #[must_use]
+    pub fn new(fail_every: Option<NonZeroUsize>, byte_budget: Option<usize>) -> Self {
+        Self {
+            fail_every,
+            byte_budget,
+            allocations: 0,
+            allocated: 0,
+        }
+    }
+
+    /// Fails every `n`th allocation.
+    #[must_use]
+    pub fn every(n: NonZeroUsize) -> Self {
+        Self::new(Some(n), None)
+    }
+
+    /// Fails allocations once `bytes` bytes have been allocated.
+    #[must_use]
+    pub fn budget(bytes: usize) -> Self {
+        Self::new(None, Some(bytes))
+    }
+
+    /// The number of allocations attempted.
+    #[must_use]
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    /// The number of bytes allocated, excluding failed allocations.
+    #[must_use]
+    pub fn allocated(&self) -> usize {
+        self.allocated
+    }
+
+    /// Records an allocation of `bytes` bytes, returning whether it should fail.
+    pub(crate) fn inject(&mut self, bytes: usize) -> bool {
+        self.allocations += 1;
+        if let Some(n) = self.fail_every {
+            if self.allocations % n.get() == 0 {
+                return true;
+            }
+        }
+        let allocated = self.allocated.saturating_add(bytes);
+        if self.byte_budget.is_some_and(|budget| allocated > budget) {
+            return true;
+        }
+        self.allocated = allocated;
+        false
+    }
+}
+
+/// A free region of an allocator or model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+    /// The index of the first free block/slot.
+    pub index: usize,
+    /// The number of free blocks/slots.
+    pub size: usize,
+}
+
+/// Types which can list their free regions, used to compare allocators and models.
+pub trait FreeRegions {
+    /// Returns the free regions in the order the allocator searches them.
+    fn free_regions(&self) -> Vec<Region>;
+}
+
+/// Asserts an allocator and a model have the same free regions.
+///
+/// # Panics
+///
+/// When the free regions differ.
+#[track_caller]
+pub fn assert_equivalent<A: FreeRegions + ?Sized, M: FreeRegions + ?Sized>(
+    allocator: &A,
+    model: &M,
+) {
+    let (actual, expected) = (allocator.free_regions(), model.free_regions());
+    assert_eq!(
+        actual, expected,
+        "allocator free regions (left) differ from model free regions (right)"
+    );
+}
+
+/// A reference implementation of [`crate::linked_list::Allocator`].
+///
+/// Allocations are taken from the start of the first free region large enough.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedListModel {
+    allocated: Vec<bool>,
+}
+
+impl LinkedListModel {
+    /// Constructs a model of an allocator with `blocks` blocks.
+    #[must_use]
+    pub fn new(blocks: usize) -> Self {
+        Self {
+            allocated: vec![false; blocks],
+        }
+    }
+
+    /// Allocates `blocks` blocks, returning the index of the first.
+    ///
+    /// Zero blocks are never allocated from the array so return index 0.
+    pub fn allocate(&mut self, blocks: usize) -> Option<usize> {
+        if blocks == 0 {
+            return Some(0);
+        }
+        let region = self
+            .free_regions()
+            .into_iter()
+            .find(|region| region.size >= blocks)?;
+        self.allocated[region.index..region.index + blocks].fill(true);
+        Some(region.index)
+    }
+
+    /// Frees `size` blocks starting at `index`.
+    ///
+    /// # Panics
+    ///
+    /// When any of the blocks are not allocated.
+    pub fn free(&mut self, index: usize, size: usize) {
+        let blocks = &mut self.allocated[index..index + size];
+        assert!(blocks.iter().all(|&allocated| allocated), "double free");
+        blocks.fill(false);
+    }
+}
+
+impl FreeRegions for LinkedListModel {
+    fn free_regions(&self) -> Vec<Region> {
+        let mut regions = Vec::<Region>::new();
+        for (index, &allocated) in self.allocated.iter().enumerate() {
+            if allocated {
+                continue;
+            }
+            match regions.last_mut() {
+                Some(last) if last.index + last.size == index => last.size += 1,
+                _ => regions.push(Region { index, size: 1 }),
+            }
+        }
+        regions
+    }
+}
+
+/// A reference implementation of [`crate::slab::Allocator`].
+///
+/// Allocations take the free slot with the lowest index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlabModel {
+    allocated: Vec<bool>,
+}
+
+impl SlabModel {
+    /// Constructs a model of an allocator with `slots` slots.
+    #[must_use]
+    pub fn new(slots: usize) -> Self {
+        Self {
+            allocated: vec![false; slots],
+        }
+    }
+
+    /// Allocates a slot, returning its index.
+    pub fn allocate(&mut self) -> Option<usize> {
+        let index = self.allocated.iter().position(|&allocated| !allocated)?;
+        self.allocated[index] = true;
+        Some(index)
+    }
+
+    /// Frees the slot at `index`.
+    ///
+    /// # Panics
+    ///
+    /// When the slot is not allocated.
+    pub fn free(&mut self, index: usize) {
+        assert!(self.allocated[index], "double free");
+        self.allocated[index] = false;
+    }
+}
+
+impl FreeRegions for SlabModel {
+    fn free_regions(&self) -> Vec<Region> {
+        self.allocated
+            .iter()
+            .enumerate()
+            .filter(|(_, &allocated)| !allocated)
+            .map(|(index, _)| Region { index, size: 1 })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn linked_list_model() {
+        let mut model = LinkedListModel::new(4);
+        assert_eq!(model.allocate(1), Some(0));
+        assert_eq!(model.allocate(2), Some(1));
+        model.free(0, 1);
+        assert_eq!(model.allocate(2), None);
+        assert_eq!(
+            model.free_regions(),
+            [Region { index: 0, size: 1 }, Region { index: 3, size: 1 }]
+        );
+        model.free(1, 2);
+        assert_eq!(model.free_regions(), [Region { index: 0, size: 4 }]);
+    }
+
+    #[test]
+    fn slab_model() {
+        let mut model = SlabModel::new(3);
+        assert_eq!(model.allocate(), Some(0));
+        assert_eq!(model.allocate(), Some(1));
+        model.free(0);
+        assert_eq!(model.allocate(), Some(0));
+        assert_eq!(model.free_regions(), [Region { index: 2, size: 1 }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn slab_model_double_free() {
+        let mut model = SlabModel::new(1);
+        model.free(0);
+    }
+
+    #[test]
+    fn failure_injection_every() {
+        let mut failure = FailureInjection::every(NonZeroUsize::new(3).unwrap());
+        let failed = (0..6).map(|_| failure.inject(1)).collect::<Vec<_>>();
+        assert_eq!(failed, [false, false, true, false, false, true]);
+        assert_eq!(failure.allocations(), 6);
+        assert_eq!(failure.allocated(), 4);
+    }
+
+    #[test]
+    fn failure_injection_budget() {
+        let mut failure = FailureInjection::budget(10);
+        assert!(!failure.inject(6));
+        assert!(failure.inject(6));
+        assert!(!failure.inject(4));
+        assert!(failure.inject(1));
+        assert_eq!(failure.allocated(), 10);
+    }
+}
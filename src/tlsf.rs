@@ -0,0 +1,584 @@
+//! A two-level segregated fit allocator, which allocates and frees in O(1) for real-time users
+//! rather than walking a free list as [`crate::linked_list`] and [`crate::boundary_tag`] do.
+//!
+//! Free regions are kept in segregated lists, the first level splitting sizes by powers of two
+//! and the second splitting each power of two into [`SL`] ranges. Bitmaps of the non-empty lists
+//! let allocation find a list whose regions are all large enough with a couple of bit scans.
+//! Requests are rounded up to the start of the next range, so a request may fail while a free
+//! region of exactly its size exists in the same range. As in [`crate::boundary_tag`], both ends
+//! of every region are tagged so frees coalesce with their neighbours in O(1), and the tags are
+//! stored apart from the data blocks.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut, Drop};
+use std::ptr::NonNull;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+use crate::error::{none_on_oom, AllocError};
+use crate::Index;
+
+/// The log2 of the number of second level lists per first level list.
+const SL_LOG2: usize = 4;
+/// The number of second level lists per first level list.
+pub const SL: usize = 1 << SL_LOG2;
+/// The number of first level lists, one per power of two.
+pub const FL: usize = usize::BITS as usize;
+
+/// Returns the lists holding free regions of `size` blocks.
+fn mapping(size: usize) -> (usize, usize) {
+    debug_assert!(size > 0);
+    let fl = size.ilog2() as usize;
+    let sl = if fl >= SL_LOG2 {
+        (size >> (fl - SL_LOG2)) - SL
+    } else {
+        (size << (SL_LOG2 - fl)) - SL
+    };
+    (fl, sl)
+}
+
+/// Returns the first lists whose free regions are all at least `size` blocks.
+fn search(size: usize) -> Option<(usize, usize)> {
+    let (fl, _) = mapping(size);
+    let size = if fl >= SL_LOG2 {
+        size.checked_add((1 << (fl - SL_LOG2)) - 1)?
+    } else {
+        size
+    };
+    Some(mapping(size))
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ArrayAllocator<const N: usize, I = usize> {
+    allocator: Allocator<I>,
+    data: [Block<I>; N],
+    tags: [Tag<I>; N],
+}
+impl<const N: usize, I: Index> ArrayAllocator<N, I> {
+    #[must_use]
+    pub fn new(attr: Option<crate::MutexAttr>) -> Self {
+        #[cfg(feature = "log")]
+        trace!("ArrayAllocator::new");
+
+        // See `linked_list::ArrayAllocator::new`.
+        let mut this = std::mem::MaybeUninit::<Self>::uninit();
+        let ptr = this.as_mut_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(0, 1);
+            std::ptr::addr_of_mut!((*ptr).tags).write_bytes(0, 1);
+            Allocator::init(std::ptr::addr_of_mut!((*ptr).allocator), attr, N);
+            this.assume_init()
+        }
+    }
+}
+
+impl<const N: usize, I> Deref for ArrayAllocator<N, I> {
+    type Target = Allocator<I>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+impl<const N: usize, I> DerefMut for ArrayAllocator<N, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.allocator
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Allocator<I = usize>(crate::mutex::Mutex<InnerAllocator<I>>);
+
+impl<I: Index> Allocator<I> {
+    /// Initializes `Self` at `ptr`.
+    ///
+    /// `ptr` must be followed by `n` data blocks then `n` tags.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid.
+    ///
+    /// # Panics
+    ///
+    /// When failing to initialize the inner mutex or when `n` is greater than [`Index::MAX`].
+    pub unsafe fn init(ptr: *mut Self, attr: Option<crate::MutexAttr>, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("Allocator::init");
+
+        std::ptr::addr_of_mut!((*ptr).0.lock).write(crate::mutex::RawMutex::new(attr).unwrap());
+        #[cfg(feature = "watchdog")]
+        std::ptr::addr_of_mut!((*ptr).0.owner).write(crate::watchdog::Owner::new());
+        <InnerAllocator<I>>::init((*ptr).0.get(), n);
+    }
+
+    /// Allocates a given number of blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    pub fn allocate(&self, blocks: usize) -> Option<Wrapper<I>> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::allocate");
+
+        none_on_oom(self.try_allocate(blocks))
+    }
+
+    /// Allocates a given number of blocks.
+    ///
+    /// # Errors
+    ///
+    /// When there is no free region in a list whose regions are all large enough or when locking
+    /// the mutex fails.
+    pub fn try_allocate(&self, blocks: usize) -> Result<Wrapper<I>, AllocError> {
+        #[cfg(feature = "log")]
+        trace!("Allocator::try_allocate");
+
+        if blocks == 0 {
+            return Ok(Wrapper {
+                allocator: self,
+                index: 0,
+                size: 0,
+            });
+        }
+        let mut inner_allocator = self.0.lock().map_err(AllocError::LockFailed)?;
+        match inner_allocator.allocate(blocks) {
+            Some(index) => Ok(Wrapper {
+                allocator: self,
+                index,
+                size: blocks,
+            }),
+            None => Err(AllocError::OutOfMemory {
+                requested: blocks,
+                largest_free: inner_allocator.largest_free(),
+            }),
+        }
+    }
+
+    /// Returns the number of free blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn free(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free");
+
+        self.0
+            .lock()
+            .unwrap()
+            .free_regions()
+            .map(|(_, size)| size)
+            .sum()
+    }
+
+    /// Returns the number of free regions, as frees coalesce with free neighbours this is the
+    /// number of runs of adjacent free blocks.
+    ///
+    /// # Panics
+    ///
+    /// When locking the mutex fails.
+    #[must_use]
+    pub fn free_regions(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Allocator::free_regions");
+
+        self.0.lock().unwrap().free_regions().count()
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct InnerAllocator<I = usize> {
+    /// Bit `fl` is set when any list in `heads[fl]` is non-empty.
+    fl_bitmap: usize,
+    /// Bit `sl` of `sl_bitmaps[fl]` is set when `heads[fl][sl]` is non-empty.
+    sl_bitmaps: [u16; FL],
+    /// The first region in each free list.
+    heads: [[Option<I>; SL]; FL],
+    size: usize,
+    _marker: PhantomData<I>,
+}
+
+impl<I: Index> InnerAllocator<I> {
+    /// Returns the data blocks.
+    ///
+    /// # Safety
+    ///
+    /// You almost definitely should not use this, it is extremely unsafe and can invalidate all
+    /// memory of the allocator to which this belongs.
+    ///
+    /// # Panics
+    ///
+    /// When the pointer to the data blocks is null.
+    pub unsafe fn data(&mut self) -> NonNull<[Block<I>]> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::data");
+
+        NonNull::slice_from_raw_parts(
+            NonNull::new((self as *mut Self).add(1).cast::<Block<I>>()).unwrap(),
+            self.size,
+        )
+    }
+
+    /// Returns the tags, which follow the data blocks.
+    ///
+    /// The tags are not borrowed from `self` so the free lists can be updated alongside them.
+    unsafe fn tags<'b>(&mut self) -> &'b mut [Tag<I>] {
+        let tags = self.data().as_ptr().cast::<Block<I>>().add(self.size);
+        std::slice::from_raw_parts_mut(tags.cast::<Tag<I>>(), self.size)
+    }
+
+    unsafe fn init(ptr: *mut Self, n: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::init");
+
+        assert!(n <= I::MAX, "{n} blocks cannot be indexed by {}", I::MAX);
+
+        (*ptr).fl_bitmap = 0;
+        std::ptr::addr_of_mut!((*ptr).sl_bitmaps).write([0; FL]);
+        std::ptr::addr_of_mut!((*ptr).heads).write([[None; SL]; FL]);
+        (*ptr).size = n;
+        std::ptr::addr_of_mut!((*ptr)._marker).write(PhantomData);
+        if n > 0 {
+            (*ptr).tag(0, n, true);
+            (*ptr).insert(0);
+        }
+    }
+
+    /// Writes the tags at both ends of the region of `size` blocks at `start`, clearing its links.
+    fn tag(&mut self, start: usize, size: usize, free: bool) {
+        let tags = unsafe { self.tags() };
+        let tag = Tag {
+            size: I::from_usize(size),
+            free,
+            prev: None,
+            next: None,
+        };
+        tags[start + size - 1] = tag;
+        tags[start] = tag;
+    }
+
+    /// Pushes the tagged free region at `start` to the front of the list for its size.
+    fn insert(&mut self, start: usize) {
+        let tags = unsafe { self.tags() };
+        let (fl, sl) = mapping(tags[start].size());
+        let head = self.heads[fl][sl];
+        tags[start].prev = None;
+        tags[start].next = head;
+        if let Some(head) = head {
+            tags[head.to_usize()].prev = Some(I::from_usize(start));
+        }
+        self.heads[fl][sl] = Some(I::from_usize(start));
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+    }
+
+    /// Removes the free region at `start` from the list for its size.
+    fn remove(&mut self, start: usize) {
+        let tags = unsafe { self.tags() };
+        let (fl, sl) = mapping(tags[start].size());
+        let (prev, next) = (tags[start].prev, tags[start].next);
+        match prev {
+            Some(prev) => tags[prev.to_usize()].next = next,
+            None => self.heads[fl][sl] = next,
+        }
+        if let Some(next) = next {
+            tags[next.to_usize()].prev = prev;
+        }
+        if self.heads[fl][sl].is_none() {
+            self.sl_bitmaps[fl] &= !(1 << sl);
+            if self.sl_bitmaps[fl] == 0 {
+                self.fl_bitmap &= !(1 << fl);
+            }
+        }
+    }
+
+    /// Returns the first non-empty lists at or after `(fl, sl)`.
+    fn find(&self, fl: usize, sl: usize) -> Option<(usize, usize)> {
+        let sl_map = self.sl_bitmaps[fl] & (u16::MAX << sl);
+        if sl_map != 0 {
+            return Some((fl, sl_map.trailing_zeros() as usize));
+        }
+        let fl_map = self.fl_bitmap & usize::MAX.checked_shl(fl as u32 + 1).unwrap_or(0);
+        if fl_map == 0 {
+            return None;
+        }
+        let fl = fl_map.trailing_zeros() as usize;
+        Some((fl, self.sl_bitmaps[fl].trailing_zeros() as usize))
+    }
+
+    /// Allocates `blocks` from the front of the first non-empty list whose regions are all large
+    /// enough, returning its index.
+    fn allocate(&mut self, blocks: usize) -> Option<usize> {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::allocate");
+
+        let (fl, sl) = search(blocks)?;
+        let (fl, sl) = self.find(fl, sl)?;
+        let start = self.heads[fl][sl]?.to_usize();
+        let size = unsafe { self.tags() }[start].size();
+        self.remove(start);
+        if size > blocks {
+            self.tag(start + blocks, size - blocks, true);
+            self.insert(start + blocks);
+        }
+        self.tag(start, blocks, false);
+        Some(start)
+    }
+
+    /// Frees the `size` blocks at `index`, coalescing with the free regions either side.
+    fn deallocate(&mut self, index: usize, size: usize) {
+        #[cfg(feature = "log")]
+        trace!("InnerAllocator::deallocate");
+
+        let (mut start, mut end) = (index, index + size);
+        let tags = unsafe { self.tags() };
+        // The tag before the region ends the preceding region and the tag after starts the
+        // following region.
+        let before = (start > 0 && tags[start - 1].free).then(|| tags[start - 1].size());
+        let after = (end < tags.len() && tags[end].free).then(|| tags[end].size());
+        if let Some(before) = before {
+            start -= before;
+            self.remove(start);
+        }
+        if let Some(after) = after {
+            self.remove(end);
+            end += after;
+        }
+        self.tag(start, end - start, true);
+        self.insert(start);
+    }
+
+    /// Returns the start and size of each free region, by list.
+    fn free_regions(&mut self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let tags: &[Tag<I>] = unsafe { self.tags() };
+        self.heads
+            .iter()
+            .flatten()
+            .flat_map(move |&head| {
+                std::iter::successors(head.map(Index::to_usize), |&start| {
+                    tags[start].next.map(Index::to_usize)
+                })
+            })
+            .map(|start| (start, tags[start].size()))
+    }
+
+    fn largest_free(&mut self) -> usize {
+        self.free_regions().map(|(_, size)| size).max().unwrap_or(0)
+    }
+}
+
+/// A data block, sized and aligned as a [`crate::linked_list::Block`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Block<I = usize>([I; 2]);
+
+/// The tag at either end of a region of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Tag<I = usize> {
+    size: I,
+    free: bool,
+    /// The previous free region in its list, only meaningful in the first tag of a free region.
+    prev: Option<I>,
+    /// The next free region in its list, only meaningful in the first tag of a free region.
+    next: Option<I>,
+}
+
+impl<I: Index> Tag<I> {
+    fn size(&self) -> usize {
+        self.size.to_usize()
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Wrapper<'a, I: Index = usize> {
+    allocator: &'a Allocator<I>,
+    index: usize,
+    size: usize,
+}
+
+impl<'a, I: Index> Wrapper<'a, I> {
+    #[must_use]
+    pub fn allocator(&self) -> &Allocator<I> {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::allocator");
+
+        self.allocator
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::index");
+
+        self.index
+    }
+
+    #[must_use]
+    pub fn size(&self) -> usize {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::size");
+
+        self.size
+    }
+}
+
+// See `linked_list::Wrapper`.
+unsafe impl<'a, I: Index> Send for Wrapper<'a, I> {}
+unsafe impl<'a, I: Index> Sync for Wrapper<'a, I> {}
+
+impl<'a, I: Index> Deref for Wrapper<'a, I> {
+    type Target = [Block<I>];
+
+    fn deref(&self) -> &Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref");
+
+        // We circumvent acquiring a guard as we don't need to lock to safely dereference allocated
+        // memory.
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe { &inner_allocator.data().as_ref()[self.index..self.index + self.size] }
+    }
+}
+impl<'a, I: Index> DerefMut for Wrapper<'a, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::deref_mut");
+
+        let inner_allocator = unsafe { &mut *(self.allocator.0.get()) };
+        unsafe { &mut inner_allocator.data().as_mut()[self.index..self.index + self.size] }
+    }
+}
+
+impl<'a, I: Index> Drop for Wrapper<'a, I> {
+    fn drop(&mut self) {
+        #[cfg(feature = "log")]
+        trace!("Wrapper::drop");
+
+        if self.size == 0 {
+            return;
+        }
+        self.allocator
+            .0
+            .lock()
+            .unwrap()
+            .deallocate(self.index, self.size);
+    }
+}
+
+impl<I: Index> fmt::Display for Allocator<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut inner_allocator = self.0.lock().map_err(|_| fmt::Error)?;
+        let size = inner_allocator.size;
+        let free = inner_allocator
+            .free_regions()
+            .map(|(_, size)| size)
+            .sum::<usize>();
+        write!(
+            f,
+            "{free}/{size} blocks free ({} bytes per block)",
+            size_of::<Block<I>>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn tlsf_mapping() {
+        assert_eq!(mapping(1), (0, 0));
+        assert_eq!(mapping(3), (1, 8));
+        assert_eq!(mapping(16), (4, 0));
+        assert_eq!(mapping(31), (4, 15));
+        assert_eq!(mapping(101), (6, 9));
+        // 101 lies in [100, 104) so requests are rounded up to the next range.
+        assert_eq!(search(101), Some((6, 10)));
+        assert_eq!(search(100), Some((6, 9)));
+        assert_eq!(search(usize::MAX), None);
+    }
+
+    #[test]
+    fn tlsf_coalesce() {
+        let memory = ArrayAllocator::<8>::new(None);
+        let a = memory.allocate(2).unwrap();
+        let b = memory.allocate(3).unwrap();
+        let c = memory.allocate(3).unwrap();
+        assert_eq!((a.index(), b.index(), c.index()), (0, 2, 5));
+        assert_eq!(memory.free(), 0);
+        assert!(memory.allocate(1).is_none());
+
+        drop(b);
+        assert_eq!(memory.free_regions(), 1);
+        drop(a);
+        assert_eq!(memory.free_regions(), 1);
+        assert_eq!(memory.free(), 5);
+        drop(c);
+        assert_eq!(memory.free_regions(), 1);
+        assert_eq!(memory.allocate(8).unwrap().size(), 8);
+        assert_eq!(memory.to_string(), "8/8 blocks free (16 bytes per block)");
+    }
+
+    #[test]
+    fn tlsf_segregated() {
+        let memory = ArrayAllocator::<64, u16>::new(None);
+        let a = memory.allocate(20).unwrap();
+        let b = memory.allocate(1).unwrap();
+        let c = memory.allocate(4).unwrap();
+        let d = memory.allocate(1).unwrap();
+        drop(a);
+        drop(c);
+        assert_eq!(memory.free_regions(), 3);
+        // Small requests take a region from the smallest list large enough rather than the first.
+        let e = memory.allocate(3).unwrap();
+        assert_eq!(e.index(), 21);
+        drop(e);
+        let e = memory.allocate(17).unwrap();
+        assert_eq!(e.index(), 0);
+        assert_eq!(
+            memory.try_allocate(39).unwrap_err(),
+            AllocError::OutOfMemory {
+                requested: 39,
+                largest_free: 38
+            }
+        );
+        drop((b, d, e));
+        assert_eq!(memory.free_regions(), 1);
+        assert_eq!(memory.free(), 64);
+    }
+
+    #[test]
+    fn tlsf_random() {
+        use rand::Rng;
+
+        let memory = ArrayAllocator::<256>::new(None);
+        let mut rng = rand::thread_rng();
+        let mut wrappers = Vec::new();
+        for _ in 0..2000 {
+            if rng.gen_bool(0.6) {
+                if let Some(mut wrapper) = memory.allocate(rng.gen_range(1..20)) {
+                    let index = wrapper.index();
+                    wrapper.fill(Block([index, index]));
+                    wrappers.push(wrapper);
+                }
+            } else if !wrappers.is_empty() {
+                let wrapper = wrappers.swap_remove(rng.gen_range(0..wrappers.len()));
+                assert!(wrapper.iter().all(|block| block.0 == [wrapper.index(); 2]));
+            }
+            let used = wrappers.iter().map(|w| w.size()).sum::<usize>();
+            assert_eq!(memory.free(), 256 - used);
+        }
+        drop(wrappers);
+        assert_eq!(memory.free_regions(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+//! Volatile access to memory which may be written by devices or non-Rust peers, so the compiler
+//! must not cache or reorder accesses.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+/// A view of a slice where every access is volatile, see
+/// [`crate::linked_list::Slice::as_volatile`].
+pub struct VolatileSlice<'a, T> {
+    ptr: NonNull<T>,
+    len: usize,
+    __marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T: Copy> VolatileSlice<'a, T> {
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads and writes of `len` elements for `'a`, and not otherwise
+    /// accessed through references for `'a`.
+    #[must_use]
+    pub unsafe fn from_raw_parts(ptr: NonNull<T>, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            __marker: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn as_ptr(&self) -> NonNull<T> {
+        self.ptr
+    }
+
+    /// Reads the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    #[must_use]
+    pub fn read(&self, index: usize) -> T {
+        #[cfg(feature = "log")]
+        trace!("VolatileSlice::read");
+
+        assert!(
+            index < self.len,
+            "index {index} out of range for {}",
+            self.len
+        );
+        unsafe { self.ptr.as_ptr().add(index).read_volatile() }
+    }
+
+    /// Writes the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// When `index >= self.len()`.
+    pub fn write(&mut self, index: usize, value: T) {
+        #[cfg(feature = "log")]
+        trace!("VolatileSlice::write");
+
+        assert!(
+            index < self.len,
+            "index {index} out of range for {}",
+            self.len
+        );
+        unsafe { self.ptr.as_ptr().add(index).write_volatile(value) };
+    }
+
+    /// Reads every element into `dst`, in order.
+    ///
+    /// # Panics
+    ///
+    /// When `dst.len() != self.len()`.
+    pub fn copy_to_slice(&self, dst: &mut [T]) {
+        #[cfg(feature = "log")]
+        trace!("VolatileSlice::copy_to_slice");
+
+        assert_eq!(dst.len(), self.len, "destination length mismatch");
+        for (i, element) in dst.iter_mut().enumerate() {
+            *element = unsafe { self.ptr.as_ptr().add(i).read_volatile() };
+        }
+    }
+
+    /// Writes every element from `src`, in order.
+    ///
+    /// # Panics
+    ///
+    /// When `src.len() != self.len()`.
+    pub fn copy_from_slice(&mut self, src: &[T]) {
+        #[cfg(feature = "log")]
+        trace!("VolatileSlice::copy_from_slice");
+
+        assert_eq!(src.len(), self.len, "source length mismatch");
+        for (i, &element) in src.iter().enumerate() {
+            unsafe { self.ptr.as_ptr().add(i).write_volatile(element) };
+        }
+    }
+
+    /// Returns a view of the elements in `start..end`.
+    ///
+    /// # Panics
+    ///
+    /// When `start > end` or `end > self.len()`.
+    #[must_use]
+    pub fn subslice(&mut self, start: usize, end: usize) -> VolatileSlice<'_, T> {
+        assert!(
+            start <= end && end <= self.len,
+            "range {start}..{end} out of range for {}",
+            self.len
+        );
+        unsafe {
+            VolatileSlice::from_raw_parts(
+                NonNull::new_unchecked(self.ptr.as_ptr().add(start)),
+                end - start,
+            )
+        }
+    }
+}
+
+impl<'a, T> fmt::Debug for VolatileSlice<'a, T> {
+    /// Does not read the elements, as reads may have side effects.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VolatileSlice")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+// A `VolatileSlice` is a unique borrow of its elements.
+unsafe impl<'a, T: Send> Send for VolatileSlice<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for VolatileSlice<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use crate::linked_list::ArrayAllocator;
+
+    #[test]
+    fn volatile_slice() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut slice = memory.allocate_slice::<u32>(4).unwrap();
+        let mut volatile = slice.as_volatile();
+        volatile.copy_from_slice(&[1, 2, 3, 4]);
+        volatile.write(0, 5);
+        assert_eq!(volatile.read(0), 5);
+        let mut sub = volatile.subslice(1, 3);
+        assert_eq!(sub.len(), 2);
+        sub.write(1, 6);
+        let mut out = [0; 4];
+        volatile.copy_to_slice(&mut out);
+        assert_eq!(out, [5, 2, 6, 4]);
+        assert_eq!(&slice[..], &[5, 2, 6, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn volatile_slice_out_of_range() {
+        let memory = ArrayAllocator::<4>::new(None);
+        let mut slice = memory.allocate_slice::<u32>(2).unwrap();
+        let _ = slice.as_volatile().read(2);
+    }
+}
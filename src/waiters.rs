@@ -0,0 +1,69 @@
+//! Wakers of tasks awaiting free memory or the release of a lock, see
+//! [`crate::linked_list::Allocator::allocate_async`].
+//!
+//! Wakers are stored in a process local side table keyed by the address of the allocator or of
+//! its mutex, so when an allocator is shared between processes only frees and unlocks made by the
+//! current process wake waiting tasks.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::task::Waker;
+
+/// Maps allocator addresses to the wakers of tasks awaiting free memory.
+static TABLE: Mutex<Option<HashMap<usize, Vec<Waker>>>> = Mutex::new(None);
+
+fn with_table<R>(f: impl FnOnce(&mut HashMap<usize, Vec<Waker>>) -> R) -> R {
+    let mut table = TABLE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    f(table.get_or_insert_with(HashMap::new))
+}
+
+/// Registers `waker` to be woken when memory is freed within the allocator at `allocator`.
+pub(crate) fn register(allocator: usize, waker: &Waker) {
+    with_table(|table| {
+        let wakers = table.entry(allocator).or_default();
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    });
+}
+
+/// Wakes every task awaiting free memory within the allocator at `allocator`.
+pub(crate) fn wake(allocator: usize) {
+    let wakers = with_table(|table| table.remove(&allocator));
+    for waker in wakers.into_iter().flatten() {
+        waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use super::*;
+
+    struct Counter(AtomicUsize);
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn waiters_wake() {
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        // A unique key as tests share the table.
+        let key = &counter as *const _ as usize;
+        register(key, &waker);
+        register(key, &waker);
+        wake(key);
+        wake(key);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+}
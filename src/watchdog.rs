@@ -0,0 +1,113 @@
+//! Detection of allocator locks held by processes which no longer exist, so a supervisor can
+//! reclaim a shared allocator rather than deadlock forever, see
+//! [`crate::linked_list::Allocator::check_lock_health`].
+//!
+//! Each lock records the pid of the process holding it and when it was locked. As the record lies
+//! within the memory of the allocator it can be read by any process sharing it.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The health of an allocator lock, as of the check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockHealth {
+    /// The lock is not held.
+    Unlocked,
+    /// The lock is held by a process which exists or has held it for less than the timeout.
+    Held { pid: u32, held_for: Duration },
+    /// The lock has been held for longer than the timeout by a process which no longer exists, it
+    /// will never be released without e.g. [`crate::linked_list::Allocator::force_unlock`].
+    Abandoned { pid: u32, held_for: Duration },
+}
+
+/// The process holding a lock.
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct Owner {
+    /// The pid of the holder, 0 when unlocked.
+    pid: AtomicU32,
+    /// When the lock was acquired, in milliseconds since the Unix epoch, as the clock is shared
+    /// across processes.
+    since: AtomicU64,
+}
+
+impl Owner {
+    pub(crate) const fn new() -> Self {
+        Self {
+            pid: AtomicU32::new(0),
+            since: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the current process as the holder, called once the lock is acquired.
+    pub(crate) fn acquired(&self) {
+        self.since.store(now(), Ordering::Relaxed);
+        self.pid.store(std::process::id(), Ordering::Release);
+    }
+
+    /// Clears the holder, called before the lock is released.
+    pub(crate) fn released(&self) {
+        self.pid.store(0, Ordering::Release);
+    }
+
+    pub(crate) fn health(&self, timeout: Duration) -> LockHealth {
+        let pid = self.pid.load(Ordering::Acquire);
+        if pid == 0 {
+            return LockHealth::Unlocked;
+        }
+        let held_for =
+            Duration::from_millis(now().saturating_sub(self.since.load(Ordering::Relaxed)));
+        if held_for > timeout && !exists(pid) {
+            LockHealth::Abandoned { pid, held_for }
+        } else {
+            LockHealth::Held { pid, held_for }
+        }
+    }
+}
+
+// Milliseconds since the epoch fit in a `u64` for millions of years.
+#[allow(clippy::cast_possible_truncation)]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Returns whether the process `pid` exists. Where this cannot be determined every process is
+/// assumed to exist, so locks are never reported abandoned.
+fn exists(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    return std::path::Path::new(&format!("/proc/{pid}")).exists();
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::pedantic)]
+
+    use super::*;
+
+    #[test]
+    fn owner() {
+        let owner = Owner::new();
+        assert_eq!(owner.health(Duration::ZERO), LockHealth::Unlocked);
+        owner.acquired();
+        assert!(matches!(
+            owner.health(Duration::ZERO),
+            LockHealth::Held { pid, .. } if pid == std::process::id()
+        ));
+        // No process has this pid as it exceeds the maximum pid on Linux.
+        owner.pid.store(u32::MAX, Ordering::Release);
+        owner.since.store(0, Ordering::Relaxed);
+        assert!(matches!(
+            owner.health(Duration::from_secs(1)),
+            LockHealth::Abandoned { pid: u32::MAX, .. }
+        ));
+        owner.released();
+        assert_eq!(owner.health(Duration::ZERO), LockHealth::Unlocked);
+    }
+}